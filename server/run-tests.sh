@@ -32,6 +32,7 @@ echo "*********** RUN 1 ***********"
     ${TEST_COMMAND} "$@"
     if [[ -z "$@" ]]; then
         ${TEST_COMMAND} -- --ignored redis
+        ${TEST_COMMAND} -- --ignored minio
     fi
 )
 
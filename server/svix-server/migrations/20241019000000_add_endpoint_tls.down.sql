@@ -0,0 +1,2 @@
+-- Remove tls column from endpoint table
+ALTER TABLE endpoint DROP COLUMN tls;
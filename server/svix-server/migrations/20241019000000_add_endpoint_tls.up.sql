@@ -0,0 +1,2 @@
+-- Add the client certificate / CA bundle configuration of endpoints
+ALTER TABLE endpoint ADD COLUMN tls jsonb;
@@ -0,0 +1,2 @@
+-- Remove oauth2 column from endpoint table
+ALTER TABLE endpoint DROP COLUMN oauth2;
@@ -0,0 +1,2 @@
+-- Add the OAuth2 client credentials configuration of endpoints
+ALTER TABLE endpoint ADD COLUMN oauth2 jsonb;
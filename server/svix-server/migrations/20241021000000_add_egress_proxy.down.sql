@@ -0,0 +1,3 @@
+-- Remove egress_proxy column from application and endpoint tables
+ALTER TABLE endpoint DROP COLUMN egress_proxy;
+ALTER TABLE application DROP COLUMN egress_proxy;
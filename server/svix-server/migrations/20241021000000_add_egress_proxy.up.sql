@@ -0,0 +1,3 @@
+-- Add the named egress proxy applications and endpoints send their webhooks through
+ALTER TABLE application ADD COLUMN egress_proxy TEXT;
+ALTER TABLE endpoint ADD COLUMN egress_proxy TEXT;
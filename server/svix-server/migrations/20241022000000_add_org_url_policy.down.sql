@@ -0,0 +1,2 @@
+-- Remove per-organization URL policies
+DROP TABLE orgurlpolicy;
@@ -0,0 +1,10 @@
+-- Add per-organization restrictions on the hosts, IPs and ports endpoints may point to
+CREATE TABLE orgurlpolicy (
+    org_id character varying NOT NULL COLLATE pg_catalog."C",
+    created_at timestamp with time zone NOT NULL,
+    updated_at timestamp with time zone NOT NULL,
+    policy jsonb NOT NULL
+);
+
+ALTER TABLE ONLY orgurlpolicy
+    ADD CONSTRAINT pk_orgurlpolicy PRIMARY KEY (org_id);
@@ -0,0 +1,2 @@
+-- Remove sink column from endpoint table
+ALTER TABLE endpoint DROP COLUMN sink;
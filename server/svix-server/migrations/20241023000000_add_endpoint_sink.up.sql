@@ -0,0 +1,2 @@
+-- Add the (encrypted) non-HTTP sink messages are pushed to instead of the endpoint's URL
+ALTER TABLE endpoint ADD COLUMN sink JSONB;
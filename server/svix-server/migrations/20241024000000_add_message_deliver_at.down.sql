@@ -0,0 +1,3 @@
+-- Remove deliver_at and cancelled_at columns from message table
+ALTER TABLE message DROP COLUMN cancelled_at;
+ALTER TABLE message DROP COLUMN deliver_at;
@@ -0,0 +1,3 @@
+-- Add the time scheduled messages are delivered at, and when their delivery was cancelled
+ALTER TABLE message ADD COLUMN deliver_at TIMESTAMPTZ;
+ALTER TABLE message ADD COLUMN cancelled_at TIMESTAMPTZ;
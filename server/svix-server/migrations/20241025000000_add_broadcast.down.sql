@@ -0,0 +1,4 @@
+-- Remove broadcasts
+DROP INDEX ix_message_per_broadcast;
+ALTER TABLE message DROP COLUMN broadcast_id;
+DROP TABLE broadcast;
@@ -0,0 +1,21 @@
+-- Add broadcasts of a message to many applications, and link the messages they create to them
+CREATE TABLE broadcast (
+    id character varying NOT NULL,
+    created_at timestamp with time zone NOT NULL,
+    updated_at timestamp with time zone NOT NULL,
+    org_id character varying NOT NULL COLLATE pg_catalog."C",
+    status smallint NOT NULL,
+    selector jsonb NOT NULL,
+    message_in text NOT NULL,
+    cursor character varying COLLATE pg_catalog."C",
+    num_messages integer NOT NULL
+);
+
+ALTER TABLE ONLY broadcast
+    ADD CONSTRAINT pk_broadcast PRIMARY KEY (id);
+
+CREATE INDEX ix_broadcast_per_org ON broadcast USING btree (org_id, id DESC);
+
+ALTER TABLE message ADD COLUMN broadcast_id character varying COLLATE pg_catalog."C";
+
+CREATE INDEX ix_message_per_broadcast ON message USING btree (broadcast_id, app_id) WHERE broadcast_id IS NOT NULL;
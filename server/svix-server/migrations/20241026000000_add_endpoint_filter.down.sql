@@ -0,0 +1,2 @@
+-- Remove endpoint payload filters
+ALTER TABLE endpoint DROP COLUMN filter;
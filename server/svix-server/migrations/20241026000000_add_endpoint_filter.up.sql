@@ -0,0 +1,2 @@
+-- Add filters on the payload of the messages sent to endpoints
+ALTER TABLE endpoint ADD COLUMN filter JSONB;
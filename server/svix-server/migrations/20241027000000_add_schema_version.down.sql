@@ -0,0 +1,3 @@
+-- Remove the schema versions of messages and endpoints
+ALTER TABLE endpoint DROP COLUMN schema_version;
+ALTER TABLE message DROP COLUMN schema_version;
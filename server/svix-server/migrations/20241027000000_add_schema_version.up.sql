@@ -0,0 +1,3 @@
+-- Add the schema version messages conform to, and the one endpoints expect
+ALTER TABLE message ADD COLUMN schema_version TEXT;
+ALTER TABLE endpoint ADD COLUMN schema_version TEXT;
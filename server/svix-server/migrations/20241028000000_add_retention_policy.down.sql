@@ -0,0 +1,3 @@
+-- Remove retention periods
+DROP TABLE orgretentionpolicy;
+ALTER TABLE application DROP COLUMN retention_period;
@@ -0,0 +1,12 @@
+-- Add retention periods after which messages, their destinations and attempts are deleted
+ALTER TABLE application ADD COLUMN retention_period INTEGER;
+
+CREATE TABLE orgretentionpolicy (
+    org_id character varying NOT NULL COLLATE pg_catalog."C",
+    created_at timestamp with time zone NOT NULL,
+    updated_at timestamp with time zone NOT NULL,
+    retention_period integer NOT NULL
+);
+
+ALTER TABLE ONLY orgretentionpolicy
+    ADD CONSTRAINT pk_orgretentionpolicy PRIMARY KEY (org_id);
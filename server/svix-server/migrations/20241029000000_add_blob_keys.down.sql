@@ -0,0 +1,3 @@
+-- Remove the keys of payloads and responses offloaded to the blob store
+ALTER TABLE messageattempt DROP COLUMN response_blob_key;
+ALTER TABLE messagecontent DROP COLUMN blob_key;
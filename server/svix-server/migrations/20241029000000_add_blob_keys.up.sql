@@ -0,0 +1,3 @@
+-- Add the keys of payloads and responses offloaded to the blob store
+ALTER TABLE messagecontent ADD COLUMN blob_key TEXT;
+ALTER TABLE messageattempt ADD COLUMN response_blob_key TEXT;
@@ -0,0 +1,2 @@
+-- Lets the destination reaper find the destinations still waiting on their next attempt
+CREATE INDEX ix_messagedestination_awaiting_attempt ON messagedestination USING btree (next_attempt) WHERE status IN (1, 3);
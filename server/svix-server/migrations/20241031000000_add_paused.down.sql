@@ -0,0 +1,2 @@
+ALTER TABLE endpoint DROP COLUMN paused;
+ALTER TABLE application DROP COLUMN paused;
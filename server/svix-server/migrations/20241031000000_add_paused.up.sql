@@ -0,0 +1,3 @@
+-- Deliveries to paused applications and endpoints are held until they're resumed
+ALTER TABLE application ADD COLUMN paused BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE endpoint ADD COLUMN paused BOOLEAN NOT NULL DEFAULT FALSE;
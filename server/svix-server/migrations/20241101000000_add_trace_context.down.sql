@@ -0,0 +1,2 @@
+ALTER TABLE endpoint DROP COLUMN propagate_trace_context;
+ALTER TABLE message DROP COLUMN trace_context;
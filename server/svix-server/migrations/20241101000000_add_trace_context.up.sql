@@ -0,0 +1,3 @@
+-- The W3C trace context of the request which created the message, for linking its attempts
+ALTER TABLE message ADD COLUMN trace_context JSONB;
+ALTER TABLE endpoint ADD COLUMN propagate_trace_context BOOLEAN NOT NULL DEFAULT FALSE;
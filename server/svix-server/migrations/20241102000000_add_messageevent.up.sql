@@ -0,0 +1,18 @@
+-- A log of the state changes of each application's messages, read as a feed by the events API
+CREATE TABLE messageevent (
+    id bigserial NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    app_id character varying NOT NULL COLLATE pg_catalog."C",
+    msg_id character varying NOT NULL COLLATE pg_catalog."C",
+    event_type smallint NOT NULL,
+    endp_id character varying COLLATE pg_catalog."C",
+    attempt_id character varying COLLATE pg_catalog."C",
+    status smallint
+);
+
+ALTER TABLE ONLY messageevent
+    ADD CONSTRAINT pk_messageevent PRIMARY KEY (id);
+
+CREATE INDEX ix_messageevent_per_app ON messageevent USING btree (app_id, id);
+
+CREATE INDEX ix_messageevent_created_at ON messageevent USING btree (created_at);
@@ -0,0 +1,20 @@
+-- An append-only log of the changes made to the configuration of each organization through the API
+CREATE TABLE auditlog (
+    id bigserial NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    org_id character varying NOT NULL COLLATE pg_catalog."C",
+    app_id character varying COLLATE pg_catalog."C",
+    actor character varying NOT NULL,
+    token_id character varying,
+    action character varying NOT NULL,
+    resource character varying NOT NULL,
+    before jsonb,
+    after jsonb
+);
+
+ALTER TABLE ONLY auditlog
+    ADD CONSTRAINT pk_auditlog PRIMARY KEY (id);
+
+CREATE INDEX ix_auditlog_per_org ON auditlog USING btree (org_id, id);
+
+CREATE INDEX ix_auditlog_created_at ON auditlog USING btree (created_at);
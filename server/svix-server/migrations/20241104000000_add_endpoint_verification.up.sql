@@ -0,0 +1,2 @@
+-- Whether the endpoint echoed the challenge sent to its URL, see `EndpointVerificationStatus`
+ALTER TABLE endpoint ADD COLUMN verification_status SMALLINT NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+DROP INDEX ix_messagedestination_held;
@@ -0,0 +1,3 @@
+-- Lets held destinations be released once their endpoint is resumed, and the destination reaper
+-- find those left held
+CREATE INDEX ix_messagedestination_held ON messagedestination USING btree (endp_id, id) WHERE status = 5;
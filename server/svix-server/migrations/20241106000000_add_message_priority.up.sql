@@ -0,0 +1,2 @@
+-- The priority of the queue the message's tasks are sent to, see `MessagePriority`
+ALTER TABLE message ADD COLUMN priority SMALLINT NOT NULL DEFAULT 1;
@@ -0,0 +1,3 @@
+CREATE INDEX ix_messagedestination_awaiting_attempt ON messagedestination USING btree (next_attempt) WHERE status IN (1, 3);
+DROP INDEX ix_messagedestination_leased_until;
+ALTER TABLE messagedestination DROP COLUMN leased_until;
@@ -0,0 +1,5 @@
+-- Set while a worker processes the destination's task, so the destination reaper only queues again
+-- the tasks lost with their worker
+ALTER TABLE messagedestination ADD COLUMN leased_until TIMESTAMPTZ;
+CREATE INDEX ix_messagedestination_leased_until ON messagedestination USING btree (leased_until) WHERE leased_until IS NOT NULL;
+DROP INDEX ix_messagedestination_awaiting_attempt;
@@ -0,0 +1,5 @@
+DROP INDEX ix_messageevent_unsequenced;
+DROP INDEX ix_messageevent_per_app;
+CREATE INDEX ix_messageevent_per_app ON messageevent USING btree (app_id, id);
+ALTER TABLE messageevent DROP COLUMN seq;
+DROP SEQUENCE messageevent_seq;
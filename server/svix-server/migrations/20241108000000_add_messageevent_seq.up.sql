@@ -0,0 +1,10 @@
+-- Events are read in the order of a number given to them once they're committed, rather than of
+-- their ID, which is taken before, so readers can't move past events still being committed. It's 0
+-- until then. Existing events keep their ID as their number.
+CREATE SEQUENCE messageevent_seq;
+ALTER TABLE messageevent ADD COLUMN seq BIGINT NOT NULL DEFAULT 0;
+UPDATE messageevent SET seq = id;
+SELECT setval('messageevent_seq', (SELECT COALESCE(MAX(id), 0) + 1 FROM messageevent), false);
+DROP INDEX ix_messageevent_per_app;
+CREATE INDEX ix_messageevent_per_app ON messageevent USING btree (app_id, seq);
+CREATE INDEX ix_messageevent_unsequenced ON messageevent USING btree (id) WHERE seq = 0;
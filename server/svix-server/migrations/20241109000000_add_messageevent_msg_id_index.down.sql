@@ -0,0 +1 @@
+DROP INDEX ix_messageevent_msg_id;
@@ -0,0 +1,2 @@
+-- The retention cleaner deletes the events of the messages it deletes
+CREATE INDEX ix_messageevent_msg_id ON messageevent USING btree (msg_id);
@@ -15,7 +15,11 @@ use url::Url;
 use validator::{Validate, ValidationError};
 
 use crate::{
-    core::{cryptography::Encryption, security::JwtSigningConfig},
+    core::{
+        cryptography::Encryption,
+        security::JwtSigningConfig,
+        types::{MessagePriority, OrganizationId},
+    },
     error::Result,
     v1::utils::validation_error,
 };
@@ -76,6 +80,46 @@ fn default_redis_pending_duration_secs() -> u64 {
     45
 }
 
+fn default_graceful_shutdown_timeout_secs() -> u64 {
+    20
+}
+
+fn default_destination_reaper_threshold_secs() -> u64 {
+    60 * 60
+}
+
+fn default_message_event_retention_secs() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_audit_log_retention_secs() -> u64 {
+    90 * 24 * 60 * 60
+}
+
+fn default_worker_max_queued_per_org() -> u16 {
+    100
+}
+
+fn default_worker_pool_idle_timeout() -> u64 {
+    90
+}
+
+fn default_max_message_delivery_delay() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_blob_payload_threshold() -> usize {
+    64 * 1024
+}
+
+fn default_blob_response_max_size() -> usize {
+    1024 * 1024
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_owned()
+}
+
 fn validate_operational_webhook_url(url: &str) -> Result<(), ValidationError> {
     match Url::parse(url) {
         Ok(url) => {
@@ -149,6 +193,13 @@ pub struct ConfigurationInner {
     pub opentelemetry_sample_ratio: Option<f64>,
     /// The service name to use for OpenTelemetry. If not provided, it defaults to "svix_server".
     pub opentelemetry_service_name: String,
+    /// Whether to expose metrics in the Prometheus format at `/metrics`. The same metrics are
+    /// still pushed to the OpenTelemetry address if given.
+    #[serde(default)]
+    pub prometheus_metrics_enabled: bool,
+    /// The address to serve Prometheus metrics on. They're never served on the API's
+    /// `listen_address`, so they aren't exposed publicly along with it.
+    pub prometheus_listen_address: SocketAddr,
     /// Whether to enable the logging of the databases at the configured log level. This may be
     /// useful for analyzing their response times.
     pub db_tracing: bool,
@@ -193,6 +244,22 @@ pub struct ConfigurationInner {
     /// redis)
     pub cache_dsn: Option<String>,
 
+    /// Where large message payloads and full attempt responses are offloaded to. Supported: none,
+    /// filesystem (must have blob_store_path configured), s3 (must have the s3_* fields configured).
+    #[serde(default)]
+    pub blob_store_type: BlobStoreType,
+    /// The directory blobs are stored in when using the filesystem blob store.
+    pub blob_store_path: Option<String>,
+    #[serde(flatten, default)]
+    pub blob_store_s3: Option<S3Config>,
+    /// Payloads larger than this (in bytes) are offloaded to the blob store, if one is configured.
+    #[serde(default = "default_blob_payload_threshold")]
+    pub blob_payload_threshold: usize,
+    /// Endpoint responses are read and offloaded to the blob store up to this size (in bytes), and
+    /// cut off after it.
+    #[serde(default = "default_blob_response_max_size")]
+    pub blob_response_max_size: usize,
+
     /// If true, headers are prefixed with `Webhook-`, otherwise with `Svix-` (default).
     pub whitelabel_headers: bool,
 
@@ -208,6 +275,10 @@ pub struct ConfigurationInner {
     #[serde(deserialize_with = "deserialize_hours")]
     pub endpoint_failure_disable_after: Duration,
 
+    /// How far in the future messages can be scheduled for delivery with `deliverAt` (in seconds)
+    #[serde(default = "default_max_message_delivery_delay")]
+    pub max_message_delivery_delay: u64,
+
     // Execution mode
     /// Should this instance run the API
     pub api_enabled: bool,
@@ -222,9 +293,91 @@ pub struct ConfigurationInner {
     /// Maximum number of concurrent worker tasks to spawn (0 is unlimited)
     pub worker_max_tasks: u16,
 
+    /// Maximum number of deliveries to a single organization's endpoints each worker makes at
+    /// once, so one tenant can't take up every slot (0 is unlimited). Deliveries over the limit
+    /// wait in the organization's queue in the worker while other tenants' go ahead.
+    #[serde(default)]
+    pub worker_max_tasks_per_org: u16,
+
+    /// Per-organization overrides of `worker_max_tasks_per_org`, e.g. to give larger tenants a
+    /// bigger share of the workers (0 is unlimited)
+    #[serde(default)]
+    pub worker_max_tasks_per_org_overrides: HashMap<OrganizationId, u16>,
+
+    /// Maximum number of deliveries to a single endpoint each worker makes at once (0 is
+    /// unlimited)
+    #[serde(default)]
+    pub worker_max_tasks_per_endpoint: u16,
+
+    /// How many tasks each worker takes from an organization's queue per turn, relative to other
+    /// organizations (1 without a weight)
+    #[serde(default)]
+    pub worker_org_weights: HashMap<OrganizationId, u16>,
+
+    /// Maximum number of tasks of a single organization each worker holds waiting for their turn
+    /// (0 is unlimited). Tasks beyond it are put back on the queue for a few seconds, letting
+    /// other tenants' tasks behind them through.
+    #[serde(default = "default_worker_max_queued_per_org")]
+    pub worker_max_queued_per_org: u16,
+
+    /// How `worker_max_tasks` is split between the message priorities when priority lanes are
+    /// enabled
+    #[serde(default)]
+    pub worker_priority_shares: PriorityShares,
+
+    /// Whether to offer HTTP/2 through ALPN when connecting to endpoints over TLS
+    #[serde(default)]
+    pub worker_http2_alpn: bool,
+    /// Hosts known to support HTTP/2, which are sent webhooks over HTTP/2 without negotiating it
+    /// first (also over plain HTTP)
+    #[serde(default)]
+    pub worker_http2_prior_knowledge_hosts: Vec<String>,
+    /// Maximum number of idle connections to keep open per host (unlimited if unset)
+    pub worker_pool_max_idle_per_host: Option<usize>,
+    /// How long to keep idle connections open for (in seconds)
+    #[serde(default = "default_worker_pool_idle_timeout")]
+    pub worker_pool_idle_timeout: u64,
+
+    /// Whether messages are queued on a separate queue per priority, each consumed with its own
+    /// share of the worker's tasks
+    #[serde(default)]
+    pub queue_priority_lanes_enabled: bool,
+
     /// Maximum seconds of a queue long-poll
     pub queue_max_poll_secs: u16,
 
+    /// How long to wait on shutdown for in-flight deliveries and API requests to complete.
+    /// Deliveries still running after it are cut off and put back on the queue.
+    #[serde(default = "default_graceful_shutdown_timeout_secs")]
+    pub graceful_shutdown_timeout_secs: u64,
+
+    /// How long a worker may take processing the task of a message destination. Past it, the task
+    /// is considered lost with the worker, e.g. because it crashed, and queued again.
+    #[serde(default = "default_destination_reaper_threshold_secs")]
+    pub destination_reaper_threshold_secs: u64,
+
+    /// How long the events of messages, as read through the events API, are kept for.
+    #[serde(default = "default_message_event_retention_secs")]
+    pub message_event_retention_secs: u64,
+
+    /// How long the entries of the audit log are kept for.
+    #[serde(default = "default_audit_log_retention_secs")]
+    pub audit_log_retention_secs: u64,
+
+    /// Whether to send each entry of the audit log as an operational webhook too.
+    #[serde(default)]
+    pub audit_log_operational_webhooks: bool,
+
+    /// Whether endpoints must echo a challenge sent to their URL, on creation and whenever it
+    /// changes, before any messages are sent to them.
+    #[serde(default)]
+    pub endpoint_verification_enabled: bool,
+
+    /// How long to wait before sending the verification challenge again after it failed, in
+    /// seconds. The endpoint fails verification once the challenge failed after the last one.
+    #[serde(deserialize_with = "deserialize_retry_schedule")]
+    pub endpoint_verification_retry_schedule: Vec<Duration>,
+
     /// The address of the rabbitmq exchange
     pub rabbit_dsn: Option<Arc<String>>,
     pub rabbit_consumer_prefetch_size: Option<u16>,
@@ -238,6 +391,16 @@ pub struct ConfigurationInner {
     #[serde(flatten)]
     pub proxy_config: Option<ProxyConfig>,
 
+    /// Named proxies which applications and endpoints can opt in to sending their webhooks
+    /// through instead of the default route, e.g. proxies with static egress IPs.
+    #[serde(default)]
+    pub egress_proxies: HashMap<String, EgressProxyConfig>,
+
+    /// The IPs webhooks not going through one of the `egress_proxies` are sent from, as published
+    /// through the API. Purely informational.
+    #[serde(default)]
+    pub egress_ips: Vec<IpNet>,
+
     #[serde(default = "default_redis_pending_duration_secs")]
     pub redis_pending_duration_secs: u64,
 
@@ -245,7 +408,7 @@ pub struct ConfigurationInner {
     pub internal: InternalConfig,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
 pub struct ProxyConfig {
     /// Proxy address.
     ///
@@ -258,7 +421,7 @@ pub struct ProxyConfig {
     pub addr: ProxyAddr,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ProxyAddr {
     /// A SOCKS5 proxy.
     Socks5(http::Uri),
@@ -290,6 +453,32 @@ impl<'de> Deserialize<'de> for ProxyAddr {
     }
 }
 
+/// An S3-compatible bucket, e.g. on AWS or a self-hosted MinIO.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct S3Config {
+    /// The endpoint of the service, e.g. `https://s3.us-east-1.amazonaws.com` or
+    /// `http://localhost:9000`. Buckets are addressed path-style.
+    #[serde(rename = "s3_endpoint")]
+    pub endpoint: Url,
+    #[serde(rename = "s3_bucket")]
+    pub bucket: String,
+    #[serde(rename = "s3_region", default = "default_s3_region")]
+    pub region: String,
+    #[serde(rename = "s3_access_key_id")]
+    pub access_key_id: String,
+    #[serde(rename = "s3_secret_access_key")]
+    pub secret_access_key: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EgressProxyConfig {
+    #[serde(flatten)]
+    pub proxy: ProxyConfig,
+    /// The IPs webhooks sent through this proxy originate from, as published through the API.
+    #[serde(default)]
+    pub egress_ips: Vec<IpNet>,
+}
+
 fn validate_config_complete(config: &ConfigurationInner) -> Result<(), ValidationError> {
     match config.cache_type {
         CacheType::None | CacheType::Memory => {}
@@ -374,6 +563,32 @@ fn validate_config_complete(config: &ConfigurationInner) -> Result<(), Validatio
         }
     }
 
+    match config.blob_store_type {
+        BlobStoreType::None => {}
+        BlobStoreType::Filesystem => {
+            if config.blob_store_path.is_none() {
+                return Err(ValidationError {
+                    code: Cow::from("missing field"),
+                    message: Some(Cow::from(
+                        "The blob_store_path field must be set if the blob_store_type is `filesystem`",
+                    )),
+                    params: HashMap::new(),
+                });
+            }
+        }
+        BlobStoreType::S3 => {
+            if config.blob_store_s3.is_none() {
+                return Err(ValidationError {
+                    code: Cow::from("missing field"),
+                    message: Some(Cow::from(
+                        "The s3_endpoint, s3_bucket, s3_access_key_id and s3_secret_access_key fields must be set if the blob_store_type is `s3`",
+                    )),
+                    params: HashMap::new(),
+                });
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -386,6 +601,15 @@ impl ConfigurationInner {
         self.cache_dsn.as_deref().or(self.redis_dsn.as_deref())
     }
 
+    /// The egress IPs of webhooks sent through the given named proxy, or through the default route
+    /// if `None`.
+    pub fn egress_ips(&self, egress_proxy: Option<&str>) -> &[IpNet] {
+        match egress_proxy.and_then(|name| self.egress_proxies.get(name)) {
+            Some(proxy) => &proxy.egress_ips,
+            None => &self.egress_ips,
+        }
+    }
+
     /// Fetches the configured backend information for the queue. May panic is the configuration has
     /// not been validated
     pub fn queue_backend(&self) -> QueueBackend<'_> {
@@ -419,6 +643,20 @@ impl ConfigurationInner {
             ),
         }
     }
+
+    /// Fetches the configured backend information for the blob store. May panic is the
+    /// configuration has not been validated
+    pub fn blob_store_backend(&self) -> BlobStoreBackend<'_> {
+        let err = "Called [`blob_store_backend`] before validating configuration";
+
+        match self.blob_store_type {
+            BlobStoreType::None => BlobStoreBackend::None,
+            BlobStoreType::Filesystem => {
+                BlobStoreBackend::Filesystem(self.blob_store_path.as_deref().expect(err))
+            }
+            BlobStoreType::S3 => BlobStoreBackend::S3(self.blob_store_s3.as_ref().expect(err)),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -458,6 +696,13 @@ pub enum CacheBackend<'a> {
     RedisSentinel(&'a str, &'a SentinelConfig),
 }
 
+#[derive(Debug, Eq, PartialEq)]
+pub enum BlobStoreBackend<'a> {
+    None,
+    Filesystem(&'a str),
+    S3(&'a S3Config),
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LogLevel {
@@ -493,6 +738,15 @@ pub enum CacheType {
     None,
 }
 
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlobStoreType {
+    #[default]
+    None,
+    Filesystem,
+    S3,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DefaultSignatureType {
@@ -533,6 +787,47 @@ impl fmt::Display for LogLevel {
     }
 }
 
+/// The share of the worker's tasks of each message priority, relative to the others.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct PriorityShares {
+    pub high: u16,
+    pub normal: u16,
+    pub low: u16,
+}
+
+impl Default for PriorityShares {
+    fn default() -> Self {
+        Self {
+            high: 2,
+            normal: 2,
+            low: 1,
+        }
+    }
+}
+
+impl PriorityShares {
+    /// The number of concurrent tasks of the priority, out of `max_tasks` (0 being unlimited).
+    /// Every priority gets at least one task, so none of them is starved.
+    pub fn max_tasks(&self, priority: MessagePriority, max_tasks: u16) -> u16 {
+        if max_tasks == 0 {
+            return 0;
+        }
+
+        let share = match priority {
+            MessagePriority::High => self.high,
+            MessagePriority::Normal => self.normal,
+            MessagePriority::Low => self.low,
+        };
+        let total = u32::from(self.high) + u32::from(self.normal) + u32::from(self.low);
+        if total == 0 {
+            return max_tasks;
+        }
+
+        (u32::from(max_tasks) * u32::from(share) / total).max(1) as u16
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
 pub struct SentinelConfig {
     #[serde(rename = "sentinel_service_name")]
@@ -611,8 +906,14 @@ mod tests {
         Figment,
     };
 
-    use super::{load, try_extract, CacheBackend, CacheType, QueueBackend, QueueType};
-    use crate::core::security::{JWTAlgorithm, JwtSigningConfig};
+    use super::{
+        load, try_extract, CacheBackend, CacheType, PriorityShares, ProxyAddr, QueueBackend,
+        QueueType, DEFAULTS,
+    };
+    use crate::core::{
+        security::{JWTAlgorithm, JwtSigningConfig},
+        types::MessagePriority,
+    };
 
     #[test]
     fn test_cache_or_queue_dsn_priority() {
@@ -672,4 +973,59 @@ jwt_algorithm = "HS512"
             JwtSigningConfig::Advanced(JWTAlgorithm::HS512(_))
         ));
     }
+
+    #[test]
+    fn test_egress_proxies() {
+        let raw_config = r#"
+jwt_secret = "not_actually_a_secret"
+egress_ips = ["203.0.113.10/32"]
+
+[egress_proxies.static-ip]
+proxy_addr = "socks5://egress-proxy:1080"
+egress_ips = ["203.0.113.20/32", "203.0.113.21/32"]
+        "#;
+
+        let cfg = try_extract(
+            Figment::new()
+                .merge(Toml::string(DEFAULTS))
+                .merge(Toml::string(raw_config)),
+        )
+        .unwrap();
+
+        let proxy = &cfg.egress_proxies["static-ip"];
+        assert!(matches!(proxy.proxy.addr, ProxyAddr::Socks5(_)));
+
+        let ips = |proxy| {
+            cfg.egress_ips(proxy)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ips(None), ["203.0.113.10/32"]);
+        assert_eq!(
+            ips(Some("static-ip")),
+            ["203.0.113.20/32", "203.0.113.21/32"]
+        );
+        // Unknown proxies are rejected when set, but fall back to the default IPs here
+        assert_eq!(ips(Some("unknown")), ["203.0.113.10/32"]);
+    }
+
+    #[test]
+    fn test_priority_shares() {
+        let shares = PriorityShares::default();
+        assert_eq!(shares.max_tasks(MessagePriority::High, 500), 200);
+        assert_eq!(shares.max_tasks(MessagePriority::Normal, 500), 200);
+        assert_eq!(shares.max_tasks(MessagePriority::Low, 500), 100);
+        // Unlimited stays unlimited, and no priority is left without a task
+        assert_eq!(shares.max_tasks(MessagePriority::Low, 0), 0);
+        assert_eq!(shares.max_tasks(MessagePriority::Low, 2), 1);
+
+        let shares = PriorityShares {
+            high: 1,
+            normal: 0,
+            low: 0,
+        };
+        assert_eq!(shares.max_tasks(MessagePriority::High, 10), 10);
+        assert_eq!(shares.max_tasks(MessagePriority::Normal, 10), 1);
+    }
 }
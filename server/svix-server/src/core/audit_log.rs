@@ -0,0 +1,464 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! The audit log of the changes to an organization's configuration.
+//!
+//! The changes to applications, endpoints and their settings, event types and the organization's
+//! policies are recorded in the append-only audit log. Each entry holds who made the change, what
+//! it was, and the fields of the resource it changed, before and after. The operations acting on
+//! messages and issuing access, such as resending, recovering or expunging content, are recorded
+//! too, though only that they happened.
+//!
+//! The handlers of the audited routes take an [`Auditor`], and record their change with it in the
+//! transaction making it, so a change is never made without being recorded, nor recorded without
+//! being made. The state of the resource is taken from the models the handler read and wrote, in
+//! the representation the API returns, and its secrets are redacted before it's stored.
+
+use aide::OperationInput;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, Method},
+};
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ConnectionTrait};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    operational_webhooks::{AuditLogEvent, OperationalWebhook, OperationalWebhookSender},
+    security::permissions_from_bearer,
+    types::{ApplicationId, OrganizationId},
+};
+use crate::{
+    db::models::auditlog,
+    error::{Error, Result},
+    AppState,
+};
+
+const API_PREFIX: &str = "/api/v1";
+
+const REDACTED: &str = "[REDACTED]";
+
+/// How an audited route changes its resource, which decides the resource its entries are about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ChangeKind {
+    /// Creates a resource under the route, identified by the `id` or `name` of its new state
+    Create,
+    /// Changes the resource at the route, creating it if need be
+    Update,
+    /// Deletes the resource at the route
+    Delete,
+    /// Acts on the resource at the route's parent
+    Action,
+    /// Acts on messages or issues access, so only that it happened is recorded
+    Operation,
+}
+
+struct AuditedRoute {
+    method: Method,
+    /// The route, relative to the API root, with its parameters prefixed with `:`
+    route: &'static str,
+    action: &'static str,
+    kind: ChangeKind,
+}
+
+macro_rules! audited_routes {
+    ($(($method:ident, $route:literal, $action:literal, $kind:ident)),* $(,)?) => {
+        &[$(AuditedRoute {
+            method: Method::$method,
+            route: $route,
+            action: $action,
+            kind: ChangeKind::$kind,
+        }),*]
+    };
+}
+
+/// The routes changing configuration, and the operations acting on messages and issuing access.
+/// Those creating messages, and those not changing anything, are left out.
+#[rustfmt::skip]
+static AUDITED_ROUTES: &[AuditedRoute] = audited_routes![
+    (POST, "/app", "application.created", Create),
+    (PUT, "/app/:app_id", "application.updated", Update),
+    (PATCH, "/app/:app_id", "application.updated", Update),
+    (DELETE, "/app/:app_id", "application.deleted", Delete),
+    (POST, "/app/:app_id/pause", "application.paused", Action),
+    (POST, "/app/:app_id/resume", "application.resumed", Action),
+    (PUT, "/app/:app_id/egress", "application.egress.updated", Update),
+    (POST, "/app/:app_id/endpoint", "endpoint.created", Create),
+    (PUT, "/app/:app_id/endpoint/:endpoint_id", "endpoint.updated", Update),
+    (PATCH, "/app/:app_id/endpoint/:endpoint_id", "endpoint.updated", Update),
+    (DELETE, "/app/:app_id/endpoint/:endpoint_id", "endpoint.deleted", Delete),
+    (POST, "/app/:app_id/endpoint/:endpoint_id/secret/rotate", "endpoint.secret.rotated", Action),
+    (POST, "/app/:app_id/endpoint/:endpoint_id/pause", "endpoint.paused", Action),
+    (POST, "/app/:app_id/endpoint/:endpoint_id/resume", "endpoint.resumed", Action),
+    (POST, "/app/:app_id/endpoint/:endpoint_id/verify", "endpoint.verification.requested", Action),
+    (POST, "/app/:app_id/endpoint/:endpoint_id/recover", "endpoint.recovered", Operation),
+    (PUT, "/app/:app_id/endpoint/:endpoint_id/headers", "endpoint.headers.updated", Update),
+    (PATCH, "/app/:app_id/endpoint/:endpoint_id/headers", "endpoint.headers.updated", Update),
+    (PUT, "/app/:app_id/endpoint/:endpoint_id/tls", "endpoint.tls.updated", Update),
+    (DELETE, "/app/:app_id/endpoint/:endpoint_id/tls", "endpoint.tls.deleted", Delete),
+    (PUT, "/app/:app_id/endpoint/:endpoint_id/oauth2", "endpoint.oauth2.updated", Update),
+    (DELETE, "/app/:app_id/endpoint/:endpoint_id/oauth2", "endpoint.oauth2.deleted", Delete),
+    (PUT, "/app/:app_id/endpoint/:endpoint_id/egress", "endpoint.egress.updated", Update),
+    (PUT, "/app/:app_id/endpoint/:endpoint_id/sink", "endpoint.sink.updated", Update),
+    (DELETE, "/app/:app_id/endpoint/:endpoint_id/sink", "endpoint.sink.deleted", Delete),
+    (POST, "/event-type", "event_type.created", Create),
+    (PUT, "/event-type/:event_type_name", "event_type.updated", Update),
+    (PATCH, "/event-type/:event_type_name", "event_type.updated", Update),
+    (DELETE, "/event-type/:event_type_name", "event_type.deleted", Delete),
+    (PUT, "/url-policy", "url_policy.updated", Update),
+    (DELETE, "/url-policy", "url_policy.deleted", Delete),
+    (PUT, "/retention-policy", "retention_policy.updated", Update),
+    (DELETE, "/retention-policy", "retention_policy.deleted", Delete),
+    (POST, "/app/:app_id/msg/:msg_id/cancel", "message.cancelled", Operation),
+    (DELETE, "/app/:app_id/msg/:msg_id/content", "message.content.expunged", Operation),
+    (POST, "/app/:app_id/msg/:msg_id/endpoint/:endpoint_id/resend", "message.resent", Operation),
+    (DELETE, "/app/:app_id/msg/:msg_id/attempt/:attempt_id/content", "message_attempt.content.expunged", Operation),
+    (POST, "/broadcast", "broadcast.created", Operation),
+    (POST, "/auth/dashboard-access/:app_id", "auth.dashboard_access.created", Operation),
+    (POST, "/auth/app-portal-access/:app_id", "auth.app_portal_access.created", Operation),
+    (POST, "/auth/logout", "auth.logged_out", Operation),
+    (POST, "/admin/redrive-dlq", "admin.dlq.redriven", Operation),
+];
+
+fn route_matches(route: &str, path: &str) -> bool {
+    let mut route = route.split('/');
+    let mut path = path.split('/');
+    loop {
+        match (route.next(), path.next()) {
+            (None, None) => return true,
+            (Some(r), Some(p)) if r.starts_with(':') && !p.is_empty() => {}
+            (Some(r), Some(p)) if r == p => {}
+            _ => return false,
+        }
+    }
+}
+
+/// The audited route a request to `path`, relative to the API root, is to, if any.
+fn audited_route(method: &Method, path: &str) -> Option<&'static AuditedRoute> {
+    AUDITED_ROUTES
+        .iter()
+        .find(|r| r.method == method && route_matches(r.route, path))
+}
+
+/// Whether the values of the field `key` are secret. Covers the secrets of endpoints, the private
+/// keys and credentials of their TLS, OAuth2 and sink configurations, and sensitive headers.
+fn is_secret_field(key: &str) -> bool {
+    const SECRET_WORDS: &[&str] = &[
+        "secret",
+        "password",
+        "token",
+        "credential",
+        "privatekey",
+        "clientkey",
+        "accesskey",
+        "apikey",
+        "authorization",
+        "authenticate",
+    ];
+
+    let key = key.replace(['-', '_'], "").to_lowercase();
+    key == "key" || SECRET_WORDS.iter().any(|w| key.contains(w))
+}
+
+/// Replaces the values of the secret fields, however deeply nested. Booleans are kept as they only
+/// tell whether a secret is set.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(obj) => {
+            for (key, value) in obj.iter_mut() {
+                if is_secret_field(key) && !matches!(value, Value::Null | Value::Bool(_)) {
+                    *value = Value::String(REDACTED.to_owned());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Narrows the states of a resource down to the fields that changed. Fields only present on one
+/// side are kept on that side.
+fn diff(before: Option<Value>, after: Option<Value>) -> (Option<Value>, Option<Value>) {
+    match (before, after) {
+        (Some(Value::Object(mut before)), Some(Value::Object(mut after))) => {
+            let unchanged: Vec<String> = before
+                .iter()
+                .filter(|(k, v)| after.get(k.as_str()) == Some(v))
+                .map(|(k, _)| k.clone())
+                .collect();
+            for key in unchanged {
+                before.remove(&key);
+                after.remove(&key);
+            }
+            (Some(Value::Object(before)), Some(Value::Object(after)))
+        }
+        (before, after) => (before, after),
+    }
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or(path, |(parent, _)| parent)
+}
+
+/// The state of a resource as recorded in the audit log, from its API representation.
+pub fn state(resource: impl Serialize) -> Option<Value> {
+    serde_json::to_value(resource).ok()
+}
+
+/// Records the change an audited route's request makes. Taken by the route's handler, which
+/// calls [`Auditor::record`] in the transaction making the change and [`Auditor::notify`] once
+/// it's committed.
+pub struct Auditor {
+    org_id: OrganizationId,
+    actor: String,
+    token_id: Option<String>,
+    route: &'static AuditedRoute,
+    /// The request's path, relative to the API root
+    path: String,
+    op_webhooks: OperationalWebhookSender,
+    send_webhooks: bool,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Auditor {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self> {
+        let permissions = permissions_from_bearer(parts, state).await?;
+
+        let path = parts.uri.path();
+        let path = path.strip_prefix(API_PREFIX).unwrap_or(path).to_owned();
+        let route = audited_route(&parts.method, &path).ok_or_else(|| {
+            Error::generic(format!("{} {path} isn't an audited route", parts.method))
+        })?;
+
+        Ok(Self {
+            org_id: permissions.org_id(),
+            actor: permissions.subject(),
+            token_id: permissions.token_id,
+            route,
+            path,
+            op_webhooks: state.op_webhooks.clone(),
+            send_webhooks: state.cfg.audit_log_operational_webhooks,
+        })
+    }
+}
+
+impl OperationInput for Auditor {}
+
+impl Auditor {
+    /// Records the change, given the states of the resource before and after it, through `db`.
+    /// Which should be the transaction making the change, for it to fail if it can't be recorded.
+    pub async fn record(
+        &self,
+        db: &impl ConnectionTrait,
+        app_id: Option<ApplicationId>,
+        before: Option<Value>,
+        after: Option<Value>,
+    ) -> Result<auditlog::Model> {
+        let resource = match self.route.kind {
+            ChangeKind::Create => {
+                let id = after
+                    .as_ref()
+                    .and_then(|v| v.get("id").or_else(|| v.get("name")))
+                    .and_then(Value::as_str);
+                match id {
+                    Some(id) => format!("{}/{id}", self.path),
+                    None => self.path.clone(),
+                }
+            }
+            ChangeKind::Action => parent(&self.path).to_owned(),
+            _ => self.path.clone(),
+        };
+
+        let (mut before, mut after) = diff(before, after);
+        before.iter_mut().for_each(redact);
+        after.iter_mut().for_each(redact);
+
+        let entry = auditlog::ActiveModel {
+            created_at: Set(Utc::now().into()),
+            org_id: Set(self.org_id.clone()),
+            app_id: Set(app_id),
+            actor: Set(self.actor.clone()),
+            token_id: Set(self.token_id.clone()),
+            action: Set(self.route.action.to_owned()),
+            resource: Set(resource),
+            before: Set(before),
+            after: Set(after),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        Ok(entry)
+    }
+
+    /// Sends the operational webhook of a recorded entry, if enabled. Only once the change is
+    /// committed, as it's done by then, a failure to send it is logged rather than returned.
+    pub async fn notify(&self, entry: auditlog::Model) {
+        if !self.send_webhooks {
+            return;
+        }
+
+        let org_id = entry.org_id.clone();
+        if let Err(e) = self
+            .op_webhooks
+            .send_operational_webhook(
+                &org_id,
+                OperationalWebhook::AuditLogEntryCreated(AuditLogEvent::from(entry)),
+            )
+            .await
+        {
+            tracing::error!("Failed to send the audit log entry's operational webhook: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aide::{axum::ApiRouter, openapi::ReferenceOr};
+    use http::Method;
+    use serde_json::json;
+
+    use super::{audited_route, diff, redact, AUDITED_ROUTES};
+
+    #[test]
+    fn test_audited_route() {
+        let action =
+            |method: Method, path: &str| audited_route(&method, path).map(|route| route.action);
+
+        assert_eq!(action(Method::POST, "/app"), Some("application.created"));
+        assert_eq!(
+            action(Method::PATCH, "/app/my-app/endpoint/ep_123"),
+            Some("endpoint.updated")
+        );
+        assert_eq!(
+            action(Method::POST, "/app/app_123/endpoint/ep_123/secret/rotate"),
+            Some("endpoint.secret.rotated")
+        );
+        assert_eq!(
+            action(Method::DELETE, "/event-type/user.signup"),
+            Some("event_type.deleted")
+        );
+        assert_eq!(
+            action(Method::POST, "/app/app_123/endpoint/ep_123/recover"),
+            Some("endpoint.recovered")
+        );
+        assert_eq!(
+            action(Method::DELETE, "/app/app_123/msg/msg_123/content"),
+            Some("message.content.expunged")
+        );
+
+        // Reads and the routes creating messages aren't audited
+        assert_eq!(action(Method::GET, "/app/app_123"), None);
+        assert_eq!(action(Method::POST, "/app/app_123/msg"), None);
+        assert_eq!(
+            action(Method::POST, "/event-type/schema/generate-example"),
+            None
+        );
+        assert_eq!(
+            action(Method::POST, "/app/app_123/endpoint/ep_123/recover/extra"),
+            None
+        );
+    }
+
+    /// Every route of the API which may change something is either audited or deliberately left out.
+    #[test]
+    fn test_all_changes_audited() {
+        // Routes creating messages, which are data rather than configuration, and those which
+        // only compute something
+        const NOT_AUDITED: &[(Method, &str)] = &[
+            (Method::POST, "/app/:app_id/msg"),
+            (Method::POST, "/app/:app_id/msg/bulk"),
+            (Method::POST, "/msg/bulk"),
+            (
+                Method::POST,
+                "/app/:app_id/endpoint/:endpoint_id/send-example",
+            ),
+            (Method::POST, "/event-type/schema/generate-example"),
+        ];
+
+        let mut openapi = crate::openapi::initialize_openapi();
+        let _ = ApiRouter::new()
+            .nest("/api/v1", crate::v1::router())
+            .finish_api(&mut openapi);
+
+        let paths = openapi.paths.expect("The API has routes");
+        for (path, item) in &paths.paths {
+            let ReferenceOr::Item(item) = item else {
+                panic!("Unexpected reference for {path}");
+            };
+            let route = path
+                .strip_prefix("/api/v1")
+                .unwrap()
+                .replace('{', ":")
+                .replace('}', "");
+
+            for (method, op) in [
+                (Method::POST, &item.post),
+                (Method::PUT, &item.put),
+                (Method::PATCH, &item.patch),
+                (Method::DELETE, &item.delete),
+            ] {
+                if op.is_none() || NOT_AUDITED.contains(&(method.clone(), route.as_str())) {
+                    continue;
+                }
+                assert!(
+                    AUDITED_ROUTES
+                        .iter()
+                        .any(|r| r.method == method && r.route == route),
+                    "{method} {route} isn't audited"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_redact() {
+        let mut value = json!({
+            "key": "whsec_C2FVsBQIhrscChlQIMV+b5sSYspob7oD",
+            "url": "https://example.com",
+            "hasClientKey": true,
+            "clientSecret": null,
+            "headers": { "Authorization": "Bearer 123", "X-Example": "123" },
+            "config": [{ "password": "hunter2", "user": "svix" }],
+        });
+        redact(&mut value);
+
+        assert_eq!(
+            value,
+            json!({
+                "key": "[REDACTED]",
+                "url": "https://example.com",
+                "hasClientKey": true,
+                "clientSecret": null,
+                "headers": { "Authorization": "[REDACTED]", "X-Example": "123" },
+                "config": [{ "password": "[REDACTED]", "user": "svix" }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let (before, after) = diff(
+            Some(json!({ "url": "https://a.example.com", "version": 1, "old": true })),
+            Some(json!({ "url": "https://b.example.com", "version": 1, "new": true })),
+        );
+        assert_eq!(
+            before,
+            Some(json!({ "url": "https://a.example.com", "old": true }))
+        );
+        assert_eq!(
+            after,
+            Some(json!({ "url": "https://b.example.com", "new": true }))
+        );
+
+        let (before, after) = diff(Some(json!({ "name": "app" })), None);
+        assert_eq!(before, Some(json!({ "name": "app" })));
+        assert_eq!(after, None);
+    }
+}
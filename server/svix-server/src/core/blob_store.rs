@@ -0,0 +1,303 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Offloads large message payloads and full attempt responses from the database to a blob store,
+//! either a local directory or an S3-compatible bucket.
+//!
+//! Offloaded rows keep the key of their blob (`messagecontent.blob_key` and
+//! `messageattempt.response_blob_key`). Payloads are resolved transparently when read, full
+//! responses only when a single attempt is fetched (the database keeps a truncated copy for lists).
+
+use std::{io::ErrorKind, path::PathBuf, sync::Arc, time::Duration};
+
+use aws_sdk_s3::{
+    config::{timeout::TimeoutConfig, BehaviorVersion, Credentials, Region},
+    error::DisplayErrorContext,
+};
+
+use super::types::{MessageAttemptId, MessageId};
+use crate::{
+    cfg::{BlobStoreBackend, ConfigurationInner, S3Config},
+    db::models::{messageattempt, messagecontent},
+    error::{Error, Result},
+};
+
+const S3_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where blobs are stored, or nowhere if no blob store is configured (the default) in which case
+/// everything stays in the database.
+#[derive(Clone, Default)]
+pub struct BlobStore {
+    backend: Option<Arc<Backend>>,
+    payload_threshold: usize,
+    response_max_size: usize,
+}
+
+enum Backend {
+    Filesystem(PathBuf),
+    S3(S3Bucket),
+}
+
+impl BlobStore {
+    pub fn new(cfg: &ConfigurationInner) -> Result<Self> {
+        let backend = match cfg.blob_store_backend() {
+            BlobStoreBackend::None => None,
+            BlobStoreBackend::Filesystem(path) => Some(Backend::Filesystem(path.into())),
+            BlobStoreBackend::S3(s3) => Some(Backend::S3(S3Bucket::new(s3))),
+        };
+
+        Ok(Self {
+            backend: backend.map(Arc::new),
+            payload_threshold: cfg.blob_payload_threshold,
+            response_max_size: cfg.blob_response_max_size,
+        })
+    }
+
+    /// A blob store keeping blobs in the given directory.
+    pub fn filesystem(
+        path: impl Into<PathBuf>,
+        payload_threshold: usize,
+        response_max_size: usize,
+    ) -> Self {
+        Self {
+            backend: Some(Arc::new(Backend::Filesystem(path.into()))),
+            payload_threshold,
+            response_max_size,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    /// Whether a payload of this size is offloaded rather than stored in the database.
+    pub fn should_offload_payload(&self, size: usize) -> bool {
+        self.is_enabled() && size > self.payload_threshold
+    }
+
+    /// How much of an endpoint's response is read: all that's offloaded if there's a blob store,
+    /// otherwise only what's kept in the database.
+    pub fn response_read_limit(&self, db_max_size: usize) -> usize {
+        if self.is_enabled() {
+            self.response_max_size.max(db_max_size)
+        } else {
+            db_max_size
+        }
+    }
+
+    pub fn payload_key(msg_id: &MessageId) -> String {
+        format!("payload/{msg_id}")
+    }
+
+    pub fn response_key(attempt_id: &MessageAttemptId) -> String {
+        format!("response/{attempt_id}")
+    }
+
+    fn backend(&self) -> Result<&Backend> {
+        self.backend
+            .as_deref()
+            .ok_or_else(|| Error::generic("Blob referenced but no blob store is configured"))
+    }
+
+    pub async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        match self.backend()? {
+            Backend::Filesystem(root) => {
+                let path = root.join(key);
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent)
+                        .await
+                        .map_err(Error::generic)?;
+                }
+                // Written next to its final path and then moved, so it's never read half-written
+                let tmp_path = path.with_extension("tmp");
+                tokio::fs::write(&tmp_path, data)
+                    .await
+                    .map_err(Error::generic)?;
+                tokio::fs::rename(&tmp_path, &path)
+                    .await
+                    .map_err(Error::generic)
+            }
+            Backend::S3(bucket) => bucket.put(key, data).await,
+        }
+    }
+
+    /// Fetches a blob, or `None` if it doesn't exist (anymore).
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.backend()? {
+            Backend::Filesystem(root) => match tokio::fs::read(root.join(key)).await {
+                Ok(data) => Ok(Some(data)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(Error::generic(e)),
+            },
+            Backend::S3(bucket) => bucket.get(key).await,
+        }
+    }
+
+    /// Deletes a blob. Deleting a blob which doesn't exist isn't an error.
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        match self.backend()? {
+            Backend::Filesystem(root) => match tokio::fs::remove_file(root.join(key)).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(Error::generic(e)),
+                _ => Ok(()),
+            },
+            Backend::S3(bucket) => bucket.delete(key).await,
+        }
+    }
+
+    /// Deletes the blobs of rows which were just deleted. Failures are only logged, as the rows
+    /// are gone either way.
+    pub async fn delete_all(&self, keys: impl IntoIterator<Item = String>) {
+        for key in keys {
+            if let Err(e) = self.delete(&key).await {
+                tracing::error!("Failed deleting blob {key}: {e}");
+            }
+        }
+    }
+
+    /// The payload of a message, fetched from the blob store if it was offloaded. `None` if the
+    /// blob is gone, just like expired payloads.
+    pub async fn resolve_payload(&self, content: messagecontent::Model) -> Result<Option<Vec<u8>>> {
+        match content.blob_key {
+            Some(key) => self.get(&key).await,
+            None => Ok(Some(content.payload)),
+        }
+    }
+
+    /// Replaces the truncated response of an attempt with the full one, if it was offloaded.
+    pub async fn resolve_response(
+        &self,
+        mut attempt: messageattempt::Model,
+    ) -> Result<messageattempt::Model> {
+        if let Some(key) = &attempt.response_blob_key {
+            if let Some(response) = self.get(key).await? {
+                attempt.response = String::from_utf8(response).map_err(Error::generic)?;
+            }
+        }
+        Ok(attempt)
+    }
+}
+
+/// A bucket of an S3-compatible service.
+struct S3Bucket {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+}
+
+impl S3Bucket {
+    fn new(cfg: &S3Config) -> Self {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url(cfg.endpoint.as_str().trim_end_matches('/'))
+            .force_path_style(true)
+            .region(Region::new(cfg.region.clone()))
+            .credentials_provider(Credentials::new(
+                cfg.access_key_id.clone(),
+                cfg.secret_access_key.clone(),
+                None,
+                None,
+                "svix-server",
+            ))
+            .timeout_config(
+                TimeoutConfig::builder()
+                    .operation_timeout(S3_REQUEST_TIMEOUT)
+                    .build(),
+            )
+            .build();
+
+        Self {
+            client: aws_sdk_s3::Client::from_conf(config),
+            bucket: cfg.bucket.clone(),
+        }
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(data.into())
+            .send()
+            .await
+            .map_err(|e| {
+                Error::generic(format!(
+                    "Failed storing blob {key}: {}",
+                    DisplayErrorContext(e)
+                ))
+            })?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let res = match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(res) => res,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(Error::generic(format!(
+                    "Failed fetching blob {key}: {}",
+                    DisplayErrorContext(e)
+                )));
+            }
+        };
+
+        let data = res.body.collect().await.map_err(Error::generic)?;
+        Ok(Some(data.into_bytes().to_vec()))
+    }
+
+    /// Deleting an object which doesn't exist succeeds with S3.
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| {
+                Error::generic(format!(
+                    "Failed deleting blob {key}: {}",
+                    DisplayErrorContext(e)
+                ))
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlobStore;
+    use crate::core::types::{BaseId, MessageId};
+
+    #[tokio::test]
+    async fn test_filesystem_blob_store() {
+        let dir = std::env::temp_dir().join(format!("svix-blobs-{}", MessageId::new(None, None)));
+        let store = BlobStore::filesystem(&dir, 10, 100);
+        assert!(!store.should_offload_payload(10));
+        assert!(store.should_offload_payload(11));
+        assert_eq!(store.response_read_limit(20), 100);
+        assert_eq!(store.response_read_limit(200), 200);
+
+        let key = BlobStore::payload_key(&MessageId::new(None, None));
+        assert_eq!(store.get(&key).await.unwrap(), None);
+
+        store.put(&key, b"payload".to_vec()).await.unwrap();
+        assert_eq!(store.get(&key).await.unwrap(), Some(b"payload".to_vec()));
+
+        store.delete(&key).await.unwrap();
+        assert_eq!(store.get(&key).await.unwrap(), None);
+        // Deleting it again is fine
+        store.delete(&key).await.unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert!(!BlobStore::default().should_offload_payload(usize::MAX));
+        assert_eq!(BlobStore::default().response_read_limit(20), 20);
+    }
+}
@@ -0,0 +1,102 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Broadcasts send the same message to many of an organization's applications, picked by their
+//! metadata.
+
+use schemars::JsonSchema;
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{extension::postgres::PgBinOper, Expr},
+    Condition, QuerySelect, QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationErrors};
+
+use super::types::metadata::Metadata;
+use crate::{
+    db::models::{application, applicationmetadata},
+    json_wrapper,
+    v1::utils::validation_error,
+};
+
+/// Which of the organization's applications a broadcast is sent to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum BroadcastSelector {
+    /// All of the organization's applications.
+    All,
+    /// The applications whose metadata contains all of the given keys, with the same values.
+    Metadata { metadata: Metadata },
+}
+
+json_wrapper!(BroadcastSelector);
+
+impl Validate for BroadcastSelector {
+    fn validate(&self) -> std::result::Result<(), ValidationErrors> {
+        match self {
+            Self::Metadata { metadata } if metadata.is_empty() => {
+                let mut errs = ValidationErrors::new();
+                errs.add(
+                    "metadata",
+                    validation_error(
+                        Some("length"),
+                        Some("Can't be empty, use the `all` selector to target all applications."),
+                    ),
+                );
+                Err(errs)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl BroadcastSelector {
+    /// The condition on `application` rows matching the selector.
+    pub fn condition(&self) -> Condition {
+        match self {
+            Self::All => Condition::all(),
+            Self::Metadata { metadata } => {
+                let matching = applicationmetadata::Entity::find()
+                    .select_only()
+                    .column(applicationmetadata::Column::Id)
+                    .filter(
+                        Expr::col((
+                            applicationmetadata::Entity,
+                            applicationmetadata::Column::Data,
+                        ))
+                        .binary(PgBinOper::Contains, Expr::val(metadata.clone())),
+                    )
+                    .into_query();
+                Condition::all().add(application::Column::Id.in_subquery(matching))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use validator::Validate;
+
+    use super::BroadcastSelector;
+
+    #[test]
+    fn test_broadcast_selector() {
+        let selector: BroadcastSelector = serde_json::from_value(json!({ "type": "all" })).unwrap();
+        assert_eq!(selector, BroadcastSelector::All);
+
+        let selector: BroadcastSelector = serde_json::from_value(json!({
+            "type": "metadata",
+            "metadata": { "tier": "enterprise" },
+        }))
+        .unwrap();
+        assert!(selector.validate().is_ok());
+
+        let selector: BroadcastSelector =
+            serde_json::from_value(json!({ "type": "metadata", "metadata": {} })).unwrap();
+        assert!(selector.validate().is_err());
+
+        assert!(serde_json::from_value::<BroadcastSelector>(json!({})).is_err());
+    }
+}
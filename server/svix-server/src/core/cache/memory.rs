@@ -11,6 +11,7 @@ use tokio::{
 };
 
 use super::{Cache, CacheBehavior, CacheKey, Result};
+use crate::metrics::CacheMetrics;
 
 #[derive(Debug)]
 struct ValueWrapper {
@@ -46,12 +47,17 @@ pub fn new() -> Cache {
         }
     });
 
-    MemoryCache { map: shared_state }.into()
+    MemoryCache {
+        map: shared_state,
+        metrics: CacheMetrics::new(&opentelemetry::global::meter("svix.com")),
+    }
+    .into()
 }
 
 #[derive(Clone)]
 pub struct MemoryCache {
     map: SharedState,
+    metrics: CacheMetrics,
 }
 
 #[async_trait]
@@ -60,6 +66,10 @@ impl CacheBehavior for MemoryCache {
         false
     }
 
+    fn metrics(&self) -> Option<&CacheMetrics> {
+        Some(&self.metrics)
+    }
+
     async fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         Ok(self
             .map
@@ -8,7 +8,7 @@ use axum::async_trait;
 use enum_dispatch::enum_dispatch;
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::core::retry::run_with_retries;
+use crate::{core::retry::run_with_retries, metrics::CacheMetrics};
 
 pub mod memory;
 pub mod none;
@@ -37,6 +37,12 @@ pub enum Error {
 }
 type Result<T> = std::result::Result<T, Error>;
 
+fn record_lookup<T>(metrics: Option<&CacheMetrics>, res: &Result<Option<T>>) {
+    if let (Some(metrics), Ok(value)) = (metrics, res) {
+        metrics.record_lookup(value.is_some());
+    }
+}
+
 /// A valid key value for the cache -- usually just a wrapper around a [`String`]
 pub trait CacheKey: AsRef<str> + Send + Sync {}
 
@@ -140,8 +146,14 @@ const RETRY_SCHEDULE: &[Duration] = &[
 #[enum_dispatch(Cache)]
 pub trait CacheBehavior: Sync + Send {
     fn should_retry(&self, e: &Error) -> bool;
+
+    /// The metrics lookups are recorded to, if any.
+    fn metrics(&self) -> Option<&CacheMetrics> {
+        None
+    }
+
     async fn get<T: CacheValue>(&self, key: &T::Key) -> Result<Option<T>> {
-        run_with_retries(
+        let res = run_with_retries(
             || async move {
                 self.get_raw(key.as_ref().as_bytes())
                     .await?
@@ -155,13 +167,15 @@ pub trait CacheBehavior: Sync + Send {
             |e| self.should_retry(e),
             RETRY_SCHEDULE,
         )
-        .await
+        .await;
+        record_lookup(self.metrics(), &res);
+        res
     }
 
     async fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
 
     async fn get_string<T: StringCacheKey>(&self, key: &T) -> Result<Option<String>> {
-        run_with_retries(
+        let res = run_with_retries(
             || async move {
                 self.get_raw(key.as_ref().as_bytes())
                     .await?
@@ -171,7 +185,9 @@ pub trait CacheBehavior: Sync + Send {
             |e| self.should_retry(e),
             RETRY_SCHEDULE,
         )
-        .await
+        .await;
+        record_lookup(self.metrics(), &res);
+        res
     }
 
     async fn set<T: CacheValue>(&self, key: &T::Key, value: &T, ttl: Duration) -> Result<()> {
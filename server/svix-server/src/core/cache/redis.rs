@@ -7,15 +7,20 @@ use axum::async_trait;
 use redis::AsyncCommands as _;
 
 use super::{Cache, CacheBehavior, CacheKey, Error, Result};
-use crate::redis::RedisManager;
+use crate::{metrics::CacheMetrics, redis::RedisManager};
 
 pub fn new(redis: RedisManager) -> Cache {
-    RedisCache { redis }.into()
+    RedisCache {
+        redis,
+        metrics: CacheMetrics::new(&opentelemetry::global::meter("svix.com")),
+    }
+    .into()
 }
 
 #[derive(Clone)]
 pub struct RedisCache {
     redis: RedisManager,
+    metrics: CacheMetrics,
 }
 
 #[async_trait]
@@ -24,6 +29,10 @@ impl CacheBehavior for RedisCache {
         matches!(e, Error::Pool(_) | Error::Database(_))
     }
 
+    fn metrics(&self) -> Option<&CacheMetrics> {
+        Some(&self.metrics)
+    }
+
     async fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let mut pool = self.redis.get().await?;
 
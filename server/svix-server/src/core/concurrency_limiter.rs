@@ -0,0 +1,113 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Caps how many tasks run at once per key, e.g. per organization, so no single key can take up
+//! all of the capacity. Limits are local to the process.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+pub struct ConcurrencyLimiter<K: Hash + Eq + Clone> {
+    /// The limit of keys without an override, 0 being unlimited
+    limit: u16,
+    overrides: HashMap<K, u16>,
+    in_flight: Arc<Mutex<HashMap<K, u16>>>,
+}
+
+impl<K: Hash + Eq + Clone> ConcurrencyLimiter<K> {
+    pub fn new(limit: u16, overrides: HashMap<K, u16>) -> Self {
+        Self {
+            limit,
+            overrides,
+            in_flight: Default::default(),
+        }
+    }
+
+    fn limit_for(&self, key: &K) -> u16 {
+        self.overrides.get(key).copied().unwrap_or(self.limit)
+    }
+
+    /// Takes one of the key's slots until the permit is dropped, or returns None when they're all
+    /// taken.
+    pub fn try_acquire(&self, key: &K) -> Option<ConcurrencyPermit<K>> {
+        let limit = self.limit_for(key);
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(key.clone()).or_default();
+        if limit > 0 && *count >= limit {
+            return None;
+        }
+        *count += 1;
+
+        Some(ConcurrencyPermit {
+            key: key.clone(),
+            in_flight: self.in_flight.clone(),
+        })
+    }
+
+    /// How many slots of the key are taken.
+    pub fn in_flight(&self, key: &K) -> u16 {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .get(key)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+pub struct ConcurrencyPermit<K: Hash + Eq + Clone> {
+    key: K,
+    in_flight: Arc<Mutex<HashMap<K, u16>>>,
+}
+
+impl<K: Hash + Eq + Clone> Drop for ConcurrencyPermit<K> {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.key) {
+            *count -= 1;
+            // Keeps the map from growing with every key ever seen
+            if *count == 0 {
+                in_flight.remove(&self.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::ConcurrencyLimiter;
+
+    #[test]
+    fn test_concurrency_limiter() {
+        let limiter = ConcurrencyLimiter::new(2, HashMap::from([("big", 3)]));
+
+        let a1 = limiter.try_acquire(&"a").unwrap();
+        let _a2 = limiter.try_acquire(&"a").unwrap();
+        assert!(limiter.try_acquire(&"a").is_none());
+        // Other keys aren't affected
+        let _b1 = limiter.try_acquire(&"b").unwrap();
+        assert_eq!(limiter.in_flight(&"a"), 2);
+
+        drop(a1);
+        assert_eq!(limiter.in_flight(&"a"), 1);
+        let _a3 = limiter.try_acquire(&"a").unwrap();
+
+        let _big: Vec<_> = (0..3)
+            .map(|_| limiter.try_acquire(&"big").unwrap())
+            .collect();
+        assert!(limiter.try_acquire(&"big").is_none());
+
+        let unlimited = ConcurrencyLimiter::new(0, HashMap::new());
+        let permits: Vec<_> = (0..100)
+            .map(|_| unlimited.try_acquire(&"a").unwrap())
+            .collect();
+        drop(permits);
+        assert_eq!(unlimited.in_flight(&"a"), 0);
+        assert!(unlimited.in_flight.lock().unwrap().is_empty());
+    }
+}
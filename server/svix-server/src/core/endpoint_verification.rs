@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Verifying that endpoints own their URL. When enabled, endpoints are pending verification when
+//! created or when their URL changes, and messages to them are held until the worker has sent a
+//! signed challenge to the URL and the response echoed it back.
+
+use rand::Rng;
+use sea_orm::{sea_query::Expr, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+
+use super::types::{EndpointId, EndpointVerificationStatus};
+use crate::{db::models::endpoint, error::Result};
+
+pub const VERIFICATION_EVENT_TYPE: &str = "endpoint.verification";
+
+/// The body of the request sent to the URL of endpoints pending verification.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationChallenge {
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub challenge: String,
+}
+
+impl VerificationChallenge {
+    pub fn new() -> Self {
+        Self {
+            typ: VERIFICATION_EVENT_TYPE.to_owned(),
+            challenge: hex::encode(rand::thread_rng().gen::<[u8; 24]>()),
+        }
+    }
+
+    /// Whether the body of a response echoes the challenge, either as is or as the `challenge`
+    /// field of a JSON object. Responses repeating the whole request don't count, so servers
+    /// echoing any request back can't pass as the owner of the URL.
+    pub fn is_echoed_by(&self, body: &[u8]) -> bool {
+        #[derive(Deserialize)]
+        struct ChallengeResponse {
+            #[serde(rename = "type")]
+            typ: Option<String>,
+            challenge: String,
+        }
+
+        if let Ok(res) = serde_json::from_slice::<ChallengeResponse>(body) {
+            return res.typ.is_none() && res.challenge == self.challenge;
+        }
+
+        std::str::from_utf8(body).is_ok_and(|body| body.trim() == self.challenge)
+    }
+}
+
+impl Default for VerificationChallenge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sets the result of the verification of the endpoint, unless it has been deleted, its URL
+/// changed or its verification restarted in the meantime. Returns whether it was set.
+pub async fn set_verification_result(
+    db: &DatabaseConnection,
+    endp_id: &EndpointId,
+    url: &str,
+    verified: bool,
+) -> Result<bool> {
+    let status = if verified {
+        EndpointVerificationStatus::Verified
+    } else {
+        EndpointVerificationStatus::Failed
+    };
+
+    let res = endpoint::Entity::update_many()
+        .col_expr(endpoint::Column::VerificationStatus, Expr::value(status))
+        .filter(endpoint::Column::Id.eq(endp_id.clone()))
+        .filter(endpoint::Column::Url.eq(url))
+        .filter(endpoint::Column::Deleted.eq(false))
+        .filter(
+            endpoint::Column::VerificationStatus
+                .eq(EndpointVerificationStatus::PendingVerification),
+        )
+        .exec(db)
+        .await?;
+    Ok(res.rows_affected > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VerificationChallenge;
+
+    #[test]
+    fn test_challenge_serialization() {
+        let challenge = VerificationChallenge::new();
+        let json = serde_json::to_value(&challenge).unwrap();
+        assert_eq!(json["type"], "endpoint.verification");
+        assert_eq!(json["challenge"], challenge.challenge.as_str());
+        assert_ne!(challenge.challenge, VerificationChallenge::new().challenge);
+    }
+
+    #[test]
+    fn test_is_echoed_by() {
+        let challenge = VerificationChallenge::new();
+        let c = challenge.challenge.as_str();
+
+        assert!(challenge.is_echoed_by(c.as_bytes()));
+        assert!(challenge.is_echoed_by(format!("{c}\n").as_bytes()));
+        assert!(challenge.is_echoed_by(format!(r#"{{"challenge":"{c}"}}"#).as_bytes()));
+        assert!(challenge.is_echoed_by(format!(r#"{{"challenge":"{c}","x":1}}"#).as_bytes()));
+
+        assert!(!challenge.is_echoed_by(b""));
+        assert!(!challenge.is_echoed_by(b"ok"));
+        assert!(!challenge.is_echoed_by(&c.as_bytes()[1..]));
+        assert!(!challenge.is_echoed_by(br#"{"challenge":"other"}"#));
+        assert!(!challenge.is_echoed_by(format!(r#"{{"echo":"{c}"}}"#).as_bytes()));
+        // Repeating the whole request doesn't count
+        assert!(!challenge.is_echoed_by(&serde_json::to_vec(&challenge).unwrap()));
+    }
+}
@@ -0,0 +1,238 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Holds tasks in a queue per key, e.g. per organization, and hands them out in weighted round
+//! robin order, so a key with a large backlog only gets its share of the turns rather than having
+//! every other key's tasks wait behind its own.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    sync::Mutex,
+};
+
+use tokio::sync::Notify;
+
+pub struct FairQueue<K: Hash + Eq + Clone, T> {
+    /// How many tasks in a row a key gets per turn, 1 for keys without a weight
+    weights: HashMap<K, u16>,
+    /// How many tasks a single key can have waiting, 0 being unlimited
+    max_per_key: usize,
+    inner: Mutex<Inner<K, T>>,
+    notify: Notify,
+}
+
+struct Inner<K, T> {
+    queues: HashMap<K, KeyQueue<T>>,
+    /// The keys with waiting tasks, in the order they get their turns
+    turns: VecDeque<K>,
+    len: usize,
+}
+
+struct KeyQueue<T> {
+    tasks: VecDeque<T>,
+    /// What's left of the key's current turn, 0 if it isn't its turn
+    turns_left: u16,
+}
+
+impl<K: Hash + Eq + Clone, T> FairQueue<K, T> {
+    pub fn new(weights: HashMap<K, u16>, max_per_key: usize) -> Self {
+        Self {
+            weights,
+            max_per_key,
+            inner: Mutex::new(Inner {
+                queues: HashMap::new(),
+                turns: VecDeque::new(),
+                len: 0,
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    fn weight(&self, key: &K) -> u16 {
+        self.weights.get(key).copied().unwrap_or(1).max(1)
+    }
+
+    /// How many tasks are waiting, of all keys.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds a task at the back of its key's queue, or gives it back when the key already has as
+    /// many tasks waiting as it may.
+    pub fn push(&self, key: K, task: T) -> Result<(), T> {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            let Inner { queues, turns, len } = &mut *inner;
+
+            let queue = queues.entry(key.clone()).or_insert_with(|| {
+                turns.push_back(key);
+                KeyQueue {
+                    tasks: VecDeque::new(),
+                    turns_left: 0,
+                }
+            });
+            if self.max_per_key > 0 && queue.tasks.len() >= self.max_per_key {
+                return Err(task);
+            }
+            queue.tasks.push_back(task);
+            *len += 1;
+        }
+
+        self.wake();
+        Ok(())
+    }
+
+    /// Takes the first task of the key whose turn it is which `try_start` lets start, e.g.
+    /// because it's under its concurrency limit, along with what `try_start` returned. Keys
+    /// without any task able to start lose their turn.
+    pub fn try_pop<P>(&self, mut try_start: impl FnMut(&K, &T) -> Option<P>) -> Option<(T, P)> {
+        let mut inner = self.inner.lock().unwrap();
+        let Inner { queues, turns, len } = &mut *inner;
+
+        for _ in 0..turns.len() {
+            let key = turns.front()?.clone();
+            let queue = queues
+                .get_mut(&key)
+                .expect("Every key with a turn has a queue");
+
+            let Some((index, started)) =
+                queue.tasks.iter().enumerate().find_map(|(index, task)| {
+                    try_start(&key, task).map(|started| (index, started))
+                })
+            else {
+                queue.turns_left = 0;
+                turns.rotate_left(1);
+                continue;
+            };
+
+            let task = queue
+                .tasks
+                .remove(index)
+                .expect("Index of an existing task");
+            *len -= 1;
+            if queue.turns_left == 0 {
+                queue.turns_left = self.weight(&key);
+            }
+            queue.turns_left -= 1;
+
+            if queue.tasks.is_empty() {
+                queues.remove(&key);
+                turns.pop_front();
+            } else if queue.turns_left == 0 {
+                turns.rotate_left(1);
+            }
+
+            return Some((task, started));
+        }
+
+        None
+    }
+
+    /// Like [`Self::try_pop`], but waits for a task able to start. Wakes up to check again when a
+    /// task is pushed or [`Self::wake`] is called, so there should only be a single caller at once.
+    pub async fn pop<P>(&self, mut try_start: impl FnMut(&K, &T) -> Option<P>) -> (T, P) {
+        loop {
+            if let Some(popped) = self.try_pop(&mut try_start) {
+                return popped;
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Has [`Self::pop`] check for a task able to start again, e.g. after a concurrency slot was
+    /// freed.
+    pub fn wake(&self) {
+        self.notify.notify_one();
+    }
+
+    /// Removes and returns the tasks matching the predicate, of all keys.
+    pub fn take_where(&self, mut predicate: impl FnMut(&T) -> bool) -> Vec<T> {
+        let mut inner = self.inner.lock().unwrap();
+        let Inner { queues, turns, len } = &mut *inner;
+
+        let mut taken = Vec::new();
+        for queue in queues.values_mut() {
+            let (matching, rest): (VecDeque<T>, VecDeque<T>) =
+                queue.tasks.drain(..).partition(|task| predicate(task));
+            queue.tasks = rest;
+            taken.extend(matching);
+        }
+        queues.retain(|_, queue| !queue.tasks.is_empty());
+        turns.retain(|key| queues.contains_key(key));
+        *len -= taken.len();
+
+        taken
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::FairQueue;
+
+    fn pop_all(queue: &FairQueue<&'static str, u32>) -> Vec<(&'static str, u32)> {
+        std::iter::from_fn(|| queue.try_pop(|key, _| Some(*key)))
+            .map(|(task, key)| (key, task))
+            .collect()
+    }
+
+    #[test]
+    fn test_fair_queue() {
+        let queue = FairQueue::new(HashMap::from([("big", 2)]), 3);
+
+        for task in 0..3 {
+            queue.push("noisy", task).unwrap();
+        }
+        // Full
+        assert_eq!(queue.push("noisy", 3), Err(3));
+        queue.push("quiet", 0).unwrap();
+        queue.push("big", 0).unwrap();
+        queue.push("big", 1).unwrap();
+        queue.push("big", 2).unwrap();
+        assert_eq!(queue.len(), 7);
+
+        // Keys take turns in the order they came in, the big one two tasks at a time
+        assert_eq!(
+            pop_all(&queue),
+            vec![
+                ("noisy", 0),
+                ("quiet", 0),
+                ("big", 0),
+                ("big", 1),
+                ("noisy", 1),
+                ("big", 2),
+                ("noisy", 2),
+            ]
+        );
+        assert!(queue.is_empty());
+
+        // Keys whose tasks can't start are skipped, and lose their turn
+        queue.push("noisy", 0).unwrap();
+        queue.push("noisy", 1).unwrap();
+        queue.push("quiet", 0).unwrap();
+        assert_eq!(
+            queue.try_pop(|key, _| (*key != "noisy").then_some(())),
+            Some((0, ()))
+        );
+        assert_eq!(
+            queue.try_pop(|key, _| (*key != "noisy").then_some(())),
+            None
+        );
+        // Only tasks which can start are taken, keeping the others' order
+        assert_eq!(
+            queue.try_pop(|_, task| (*task == 1).then_some(())),
+            Some((1, ()))
+        );
+
+        queue.push("quiet", 1).unwrap();
+        assert_eq!(queue.take_where(|task| *task == 0), vec![0]);
+        assert_eq!(pop_all(&queue), vec![("quiet", 1)]);
+        assert!(queue.is_empty());
+    }
+}
@@ -8,10 +8,13 @@ use super::types::EventTypeName;
 use crate::{
     core::{
         cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
+        payload_filter::EndpointFilter,
+        sink::EndpointSink,
         types::{
-            ApplicationId, ApplicationUid, EndpointHeaders, EndpointId, EndpointSecretInternal,
-            EventChannelSet, EventTypeNameSet, ExpiringSigningKeys, MessageAttemptTriggerType,
-            OrganizationId,
+            ApplicationId, ApplicationUid, EndpointHeaders, EndpointId, EndpointOAuth2Config,
+            EndpointSecretInternal, EndpointTlsConfig, EndpointVerificationStatus, EventChannelSet,
+            EventTypeNameSet, ExpiringSigningKeys, MessageAttemptTriggerType, OrganizationId,
+            SchemaVersion,
         },
     },
     db::models::{application, endpoint},
@@ -26,6 +29,8 @@ pub struct CreateMessageApp {
     pub uid: Option<ApplicationUid>,
     pub org_id: OrganizationId,
     pub rate_limit: Option<u16>,
+    pub egress_proxy: Option<String>,
+    pub paused: bool,
     endpoints: Vec<CreateMessageEndpoint>,
     deleted: bool,
 }
@@ -53,6 +58,8 @@ impl CreateMessageApp {
                 .map(|v| v.try_into())
                 .transpose()
                 .map_err(|_| Error::validation("Application rate limit out of bounds"))?,
+            egress_proxy: app.egress_proxy,
+            paused: app.paused,
             endpoints,
             deleted: app.deleted,
         })
@@ -121,11 +128,11 @@ impl CreateMessageApp {
                     // Manual attempt types go through regardless
                     && (trigger_type == MessageAttemptTriggerType::Manual
                         || (
-                            // If an endpoint has event types and it matches ours, or has no event types
+                            // If an endpoint has event types (or patterns) matching ours, or has no event types
                             endpoint
                                 .event_types_ids
                                 .as_ref()
-                                .map(|x| x.0.contains(event_type))
+                                .map(|x| x.matches(event_type))
                                 .unwrap_or(true)
                             // If an endpoint has no channels accept all messages, otherwise only if their channels overlap.
                             // A message with no channels doesn't match an endpoint with channels.
@@ -157,8 +164,17 @@ pub struct CreateMessageEndpoint {
     // Same type as the `DateTimeWithTimeZone from SeaORM used in the endpoint model
     pub first_failure_at: Option<DateTime<FixedOffset>>,
     pub headers: Option<EndpointHeaders>,
+    pub tls: Option<EndpointTlsConfig>,
+    pub oauth2: Option<EndpointOAuth2Config>,
+    pub egress_proxy: Option<String>,
+    pub sink: Option<EndpointSink>,
+    pub filter: Option<EndpointFilter>,
+    pub schema_version: Option<SchemaVersion>,
     pub disabled: bool,
     pub deleted: bool,
+    pub paused: bool,
+    pub propagate_trace_context: bool,
+    pub verification_status: EndpointVerificationStatus,
     // outside of this module, valid_signing_keys should be used instead
     old_signing_keys: Option<ExpiringSigningKeys>,
 }
@@ -198,8 +214,17 @@ impl TryFrom<endpoint::Model> for CreateMessageEndpoint {
                 .map_err(|_| Error::validation("Endpoint rate limit out of bounds"))?,
             first_failure_at: m.first_failure_at,
             headers: m.headers,
+            tls: m.tls,
+            oauth2: m.oauth2,
+            egress_proxy: m.egress_proxy,
+            sink: m.sink,
+            filter: m.filter,
+            schema_version: m.schema_version,
             disabled: m.disabled,
             deleted: m.deleted,
+            paused: m.paused,
+            propagate_trace_context: m.propagate_trace_context,
+            verification_status: m.verification_status,
         })
     }
 }
@@ -209,7 +234,7 @@ impl AppEndpointKey {
     // FIXME: Rewrite doc comment when AppEndpointValue members are known
     /// Returns a key for fetching all cached endpoints for a given organization and application.
     pub fn new(org: &OrganizationId, app: &ApplicationId) -> AppEndpointKey {
-        AppEndpointKey(format!("SVIX_CACHE_APP_v3_{org}_{app}"))
+        AppEndpointKey(format!("SVIX_CACHE_APP_v12_{org}_{app}"))
     }
 }
 
@@ -259,8 +284,17 @@ mod tests {
             rate_limit: None,
             first_failure_at: None,
             headers: None,
+            tls: None,
+            oauth2: None,
+            egress_proxy: None,
+            sink: None,
+            filter: None,
+            schema_version: None,
             disabled: false,
             deleted: false,
+            paused: false,
+            propagate_trace_context: false,
+            verification_status: EndpointVerificationStatus::Verified,
         };
 
         let keys = cme.valid_signing_keys();
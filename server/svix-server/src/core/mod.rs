@@ -1,16 +1,28 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
+pub mod audit_log;
+pub mod blob_store;
+pub mod broadcast;
 pub mod cache;
+pub mod concurrency_limiter;
 pub mod cryptography;
+pub mod endpoint_verification;
+pub mod fair_queue;
 pub mod idempotency;
 pub mod message_app;
+pub mod oauth2;
 pub mod operational_webhooks;
 pub mod otel_spans;
+pub mod pause;
+pub mod payload_filter;
 pub mod permissions;
 pub mod retry;
+pub mod schema_compat;
 pub mod security;
+pub mod sink;
 pub mod types;
+pub mod url_policy;
 pub mod webhook_http_client;
 
 #[cfg(test)]
@@ -0,0 +1,308 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Access tokens for endpoints authenticating webhooks with the OAuth2 client credentials grant.
+
+use std::time::Duration;
+
+use http::{HeaderValue, Method, Version};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
+    cryptography::Encryption,
+    types::{EndpointId, EndpointOAuth2Config},
+    url_policy::UrlPolicy,
+    webhook_http_client::{
+        read_body_bounded, CaseSensitiveHeaderMap, RequestBuilder, WebhookClient,
+    },
+};
+use crate::{
+    cfg::ProxyConfig,
+    error::{Error, Result},
+};
+
+/// How long to cache tokens whose response doesn't include `expires_in`
+const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(300);
+
+/// Tokens are dropped from the cache this long before they actually expire, so that a token never
+/// expires while a webhook using it is in flight.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+const TOKEN_REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Token responses larger than this are rejected, rather than read into memory
+const TOKEN_RESPONSE_MAX_SIZE: usize = 64 * 1024;
+
+/// Only this much of an erroring token response is included in the error message
+const ERROR_RESPONSE_MAX_SIZE: usize = 1000;
+
+#[derive(Deserialize, Serialize)]
+pub struct OAuth2AccessToken {
+    pub access_token: String,
+}
+
+kv_def!(OAuth2TokenCacheKey, OAuth2AccessToken);
+
+impl OAuth2TokenCacheKey {
+    pub fn new(endp_id: &EndpointId) -> OAuth2TokenCacheKey {
+        OAuth2TokenCacheKey(format!("SVIX_OAUTH2_TOKEN_v1_{endp_id}"))
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// Returns an access token for the given endpoint, either from the cache or freshly requested from
+/// the configured token URL. The token is requested through the same proxy, and under the same
+/// URL policy, as the webhooks it's used for.
+pub async fn get_access_token(
+    cache: &Cache,
+    client: &WebhookClient,
+    encryption: &Encryption,
+    endp_id: &EndpointId,
+    config: &EndpointOAuth2Config,
+    proxy: Option<&ProxyConfig>,
+    url_policy: Option<&UrlPolicy>,
+) -> Result<String> {
+    let key = OAuth2TokenCacheKey::new(endp_id);
+
+    if let Ok(Some(token)) = cache.get::<OAuth2AccessToken>(&key).await {
+        return Ok(token.access_token);
+    }
+
+    let (token, ttl) = fetch_access_token(client, encryption, config, proxy, url_policy).await?;
+    if let Some(ttl) = ttl.checked_sub(TOKEN_EXPIRY_MARGIN) {
+        if let Err(e) = cache.set(&key, &token, ttl).await {
+            tracing::warn!("Failed caching OAuth2 access token: {e}");
+        }
+    }
+
+    Ok(token.access_token)
+}
+
+/// Drops the cached access token of the endpoint, so the next call to [`get_access_token`]
+/// requests a new one.
+pub async fn invalidate_access_token(cache: &Cache, endp_id: &EndpointId) -> Result<()> {
+    cache
+        .delete(&OAuth2TokenCacheKey::new(endp_id))
+        .await
+        .map_err(Error::cache)
+}
+
+/// Requests a new access token using the client credentials grant (RFC 6749, section 4.4),
+/// returning it along with how long it's valid for.
+async fn fetch_access_token(
+    client: &WebhookClient,
+    encryption: &Encryption,
+    config: &EndpointOAuth2Config,
+    proxy: Option<&ProxyConfig>,
+    url_policy: Option<&UrlPolicy>,
+) -> Result<(OAuth2AccessToken, Duration)> {
+    let client_secret = config.client_secret(encryption)?;
+
+    let mut body = url::form_urlencoded::Serializer::new(String::new());
+    body.append_pair("grant_type", "client_credentials");
+    if let Some(scopes) = config.scopes.as_ref().filter(|s| !s.is_empty()) {
+        body.append_pair("scope", &scopes.join(" "));
+    }
+    let body = body.finish();
+
+    // Client credentials are form-encoded before being used for basic auth, per section 2.3.1
+    let credentials = format!(
+        "{}:{}",
+        url::form_urlencoded::byte_serialize(config.client_id.as_bytes()).collect::<String>(),
+        url::form_urlencoded::byte_serialize(client_secret.as_bytes()).collect::<String>(),
+    );
+    let mut headers = CaseSensitiveHeaderMap::new();
+    headers.insert(
+        "authorization".to_owned(),
+        format!("Basic {}", base64::encode(credentials))
+            .parse()
+            .map_err(|_| Error::validation("Invalid OAuth2 client credentials"))?,
+    );
+    headers.insert(
+        "accept".to_owned(),
+        HeaderValue::from_static("application/json"),
+    );
+
+    let mut req = RequestBuilder::new()
+        .method(Method::POST)
+        .uri_str(&config.token_url)
+        .map_err(|e| Error::validation(format!("OAuth2 token URL is invalid: {e:?}")))?
+        .headers(headers)
+        .body(
+            body.into_bytes(),
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        )
+        .version(Version::HTTP_11)
+        .timeout(TOKEN_REQUEST_TIMEOUT);
+    if let Some(proxy) = proxy {
+        req = req.proxy(proxy.clone());
+    }
+    if let Some(url_policy) = url_policy {
+        req = req.url_policy(url_policy.clone());
+    }
+    let req = req.build().map_err(Error::generic)?;
+
+    let res = client
+        .execute(req)
+        .await
+        .map_err(|e| Error::generic(format!("OAuth2 token request failed: {e}")))?;
+    let status = res.status();
+    // One byte past the limit is read to tell whether the response exceeds it
+    let body = tokio::time::timeout(
+        TOKEN_REQUEST_TIMEOUT,
+        read_body_bounded(res.into_body(), TOKEN_RESPONSE_MAX_SIZE + 1),
+    )
+    .await
+    .map_err(|_| Error::generic("Timed out reading OAuth2 token response"))?
+    .map_err(|e| Error::generic(format!("Failed reading OAuth2 token response: {e}")))?;
+
+    if !status.is_success() {
+        let body = String::from_utf8_lossy(&body[..body.len().min(ERROR_RESPONSE_MAX_SIZE)]);
+        return Err(Error::generic(format!(
+            "OAuth2 token request failed with status {status}: {body}"
+        )));
+    }
+
+    if body.len() > TOKEN_RESPONSE_MAX_SIZE {
+        return Err(Error::generic(format!(
+            "OAuth2 token response is larger than {TOKEN_RESPONSE_MAX_SIZE} bytes"
+        )));
+    }
+
+    let token: TokenResponse = serde_json::from_slice(&body)
+        .map_err(|e| Error::generic(format!("Invalid OAuth2 token response: {e}")))?;
+
+    if let Some(token_type) = &token.token_type {
+        if !token_type.eq_ignore_ascii_case("bearer") {
+            return Err(Error::generic(format!(
+                "Unsupported OAuth2 token type: {token_type}"
+            )));
+        }
+    }
+
+    let ttl = token
+        .expires_in
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TOKEN_TTL);
+
+    Ok((
+        OAuth2AccessToken {
+            access_token: token.access_token,
+        },
+        ttl,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, sync::Arc};
+
+    use axum::{extract::Form, http::HeaderMap, routing, Json, Router};
+    use ipnet::IpNet;
+    use serde_json::json;
+
+    use super::fetch_access_token;
+    use crate::core::{
+        cryptography::Encryption, types::EndpointOAuth2Config, url_policy::UrlPolicy,
+        webhook_http_client::WebhookClient,
+    };
+
+    #[tokio::test]
+    async fn test_fetch_access_token() {
+        async fn token(
+            headers: HeaderMap,
+            Form(form): Form<std::collections::HashMap<String, String>>,
+        ) -> Result<Json<serde_json::Value>, http::StatusCode> {
+            // "client:s3cret&" with the `&` form-encoded
+            if headers.get("authorization").unwrap() != "Basic Y2xpZW50OnMzY3JldCUyNg==" {
+                return Err(http::StatusCode::UNAUTHORIZED);
+            }
+            assert_eq!(form["grant_type"], "client_credentials");
+            assert_eq!(form["scope"], "a b");
+            Ok(Json(json!({
+                "access_token": "token",
+                "token_type": "Bearer",
+                "expires_in": 60,
+            })))
+        }
+
+        async fn large_token() -> Json<serde_json::Value> {
+            Json(json!({ "access_token": "a".repeat(100 * 1024) }))
+        }
+
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/token", tcp.local_addr().unwrap());
+        let large_url = format!("http://{}/large-token", tcp.local_addr().unwrap());
+        let app = Router::new()
+            .route("/token", routing::post(token))
+            .route("/large-token", routing::post(large_token));
+        let _jh = tokio::spawn(async {
+            axum::Server::from_tcp(tcp)
+                .unwrap()
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        });
+
+        let whitelist = Arc::new(vec![IpNet::new("127.0.0.1".parse().unwrap(), 0).unwrap()]);
+        let client = WebhookClient::new(Some(whitelist), None, false, None);
+        let encryption = Encryption::new_noop();
+
+        let config = |secret| {
+            EndpointOAuth2Config::new(
+                &encryption,
+                url.clone(),
+                "client".to_owned(),
+                secret,
+                Some(vec!["a".to_owned(), "b".to_owned()]),
+            )
+            .unwrap()
+        };
+
+        let (token, ttl) = fetch_access_token(&client, &encryption, &config("s3cret&"), None, None)
+            .await
+            .unwrap();
+        assert_eq!(token.access_token, "token");
+        assert_eq!(ttl.as_secs(), 60);
+
+        assert!(
+            fetch_access_token(&client, &encryption, &config("wrong"), None, None)
+                .await
+                .is_err()
+        );
+
+        // Responses too large to be a token aren't read in full
+        let large_config =
+            EndpointOAuth2Config::new(&encryption, large_url, "client".to_owned(), "s3cret&", None)
+                .unwrap();
+        assert!(
+            fetch_access_token(&client, &encryption, &large_config, None, None)
+                .await
+                .is_err()
+        );
+
+        // The token URL is subject to the URL policy, like the endpoint's
+        let url_policy = UrlPolicy {
+            allowed_ports: vec![443],
+            ..Default::default()
+        };
+        assert!(fetch_access_token(
+            &client,
+            &encryption,
+            &config("s3cret&"),
+            None,
+            Some(&url_policy)
+        )
+        .await
+        .is_err());
+    }
+}
@@ -3,7 +3,7 @@
 
 //! Module defining an interface for sending webhook events about the service.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use chrono::{DateTime, Utc};
 use http::StatusCode;
@@ -20,7 +20,7 @@ use super::{
 };
 use crate::{
     core::security::JwtSigningConfig,
-    db::models::{endpoint, messageattempt},
+    db::models::{auditlog, endpoint, messageattempt},
     error::{Error, HttpError, Result},
 };
 
@@ -35,7 +35,7 @@ pub struct EndpointDisabledEventData {
     pub fail_since: DateTime<Utc>,
 }
 
-/// Sent when an endpoint is created, updated, or deleted
+/// Sent when an endpoint is created, updated, deleted, paused or resumed
 #[derive(Debug, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EndpointEvent {
@@ -88,6 +88,35 @@ pub struct MessageAttemptEvent {
     pub last_attempt: MessageAttempetLast,
 }
 
+/// Sent when a change to the configuration is recorded in the audit log, when enabled.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogEvent {
+    pub app_id: Option<ApplicationId>,
+    pub actor: String,
+    pub token_id: Option<String>,
+    pub action: String,
+    pub resource: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<auditlog::Model> for AuditLogEvent {
+    fn from(entry: auditlog::Model) -> Self {
+        Self {
+            app_id: entry.app_id,
+            actor: entry.actor,
+            token_id: entry.token_id,
+            action: entry.action,
+            resource: entry.resource,
+            before: entry.before,
+            after: entry.after,
+            timestamp: entry.created_at.into(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(tag = "type", content = "data")]
 pub enum OperationalWebhook {
@@ -99,12 +128,18 @@ pub enum OperationalWebhook {
     EndpointUpdated(EndpointEvent),
     #[serde(rename = "endpoint.deleted")]
     EndpointDeleted(EndpointEvent),
+    #[serde(rename = "endpoint.paused")]
+    EndpointPaused(EndpointEvent),
+    #[serde(rename = "endpoint.resumed")]
+    EndpointResumed(EndpointEvent),
     #[serde(rename = "message.attempt.exhausted")]
     MessageAttemptExhausted(MessageAttemptEvent),
     #[serde(rename = "message.attempt.failing")]
     MessageAttemptFailing(MessageAttemptEvent),
     #[serde(rename = "message.attempt.recovered")]
     MessageAttemptRecovered(MessageAttemptEvent),
+    #[serde(rename = "audit_log.entry.created")]
+    AuditLogEntryCreated(AuditLogEvent),
 }
 
 pub type OperationalWebhookSender = Arc<OperationalWebhookSenderInner>;
@@ -130,6 +165,24 @@ impl OperationalWebhookSenderInner {
         })
     }
 
+    pub fn is_enabled(&self) -> bool {
+        self.url.is_some()
+    }
+
+    /// Checks that the server operational webhooks are sent through is up.
+    pub async fn health_check(&self) -> Result<()> {
+        let Some(url) = &self.url else { return Ok(()) };
+
+        reqwest::Client::new()
+            .get(format!("{url}/api/v1/health/ping/"))
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(Error::generic)?;
+        Ok(())
+    }
+
     pub async fn send_operational_webhook(
         &self,
         recipient_org_id: &OrganizationId,
@@ -3,11 +3,19 @@
 
 //! Module defining utilities for crating `tracing` spans compatible with OpenTelemetry's
 //! conventions.
-use std::net::SocketAddr;
+use std::{collections::HashMap, convert::Infallible, net::SocketAddr};
 
-use axum::extract::{ConnectInfo, MatchedPath};
-use http::header;
-use opentelemetry::trace::TraceContextExt;
+use aide::OperationInput;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, MatchedPath},
+};
+use http::{header, request::Parts, HeaderMap};
+use opentelemetry::{
+    propagation::TextMapPropagator,
+    trace::{SpanContext, TraceContextExt},
+};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
 use svix_ksuid::{KsuidLike, KsuidMs};
 use tower_http::{
     classify::ServerErrorsFailureClass,
@@ -16,6 +24,8 @@ use tower_http::{
 use tracing::field::{debug, Empty};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+use super::types::TraceContext;
+
 /// An implementor of [`MakeSpan`] which creates `tracing` spans populated with information about
 /// the request received by an `axum` web server.
 #[derive(Clone, Copy)]
@@ -138,3 +148,80 @@ impl OnFailure<ServerErrorsFailureClass> for AxumOtelOnFailure {
         }
     }
 }
+
+/// The W3C trace context of the request, to store with the messages it creates. Taken from the
+/// request's span when traces are exported, and from its `traceparent` and `tracestate` headers
+/// otherwise, so receivers can still join the caller's trace.
+pub struct RequestTraceContext(pub Option<TraceContext>);
+
+impl RequestTraceContext {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let propagator = TraceContextPropagator::new();
+
+        let mut context = tracing::Span::current().context();
+        if !context.span().span_context().is_valid() {
+            context = propagator.extract(&opentelemetry_http::HeaderExtractor(headers));
+        }
+        if !context.span().span_context().is_valid() {
+            return Self(None);
+        }
+
+        let mut carrier = HashMap::new();
+        propagator.inject_context(&context, &mut carrier);
+        Self(Some(TraceContext(carrier)))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestTraceContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+impl OperationInput for RequestTraceContext {}
+
+/// The span context stored in the trace context, for linking the spans of a message's attempts to
+/// the request which created it. Invalid if the trace context is.
+pub fn stored_span_context(trace_context: &TraceContext) -> SpanContext {
+    TraceContextPropagator::new()
+        .extract(&trace_context.0)
+        .span()
+        .span_context()
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderMap;
+
+    use super::{stored_span_context, RequestTraceContext};
+
+    #[test]
+    fn test_request_trace_context() {
+        const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+        assert!(RequestTraceContext::from_headers(&HeaderMap::new())
+            .0
+            .is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("traceparent", "not-a-trace".parse().unwrap());
+        assert!(RequestTraceContext::from_headers(&headers).0.is_none());
+
+        headers.insert("traceparent", TRACEPARENT.parse().unwrap());
+        headers.insert("tracestate", "vendor=value".parse().unwrap());
+        let trace_context = RequestTraceContext::from_headers(&headers).0.unwrap();
+        assert_eq!(trace_context.0["traceparent"], TRACEPARENT);
+        assert_eq!(trace_context.0["tracestate"], "vendor=value");
+
+        let span_context = stored_span_context(&trace_context);
+        assert!(span_context.is_valid());
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+    }
+}
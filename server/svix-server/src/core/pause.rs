@@ -0,0 +1,195 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Pausing the delivery of messages to an endpoint or a whole application. The destinations of
+//! messages sent while paused are held with the [`MessageStatus::Paused`] status rather than
+//! attempted, and queued again in the order they were created once resumed.
+
+use chrono::Utc;
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, sea_query::Expr, ColumnTrait, DatabaseConnection, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect,
+};
+
+use super::{
+    cache::{Cache, CacheBehavior},
+    message_app::AppEndpointKey,
+    operational_webhooks::{EndpointEvent, OperationalWebhook, OperationalWebhookSender},
+    types::{
+        ApplicationId, EndpointId, MessageAttemptTriggerType, MessageEndpointId, MessageStatus,
+    },
+};
+use crate::{
+    db::models::{application, endpoint, message, messagedestination},
+    error::Result,
+    queue::{MessageTask, ReleaseHeldDestinationsTask, TaskQueueProducer},
+};
+
+/// Makes the worker see the application or endpoint was paused or resumed right away, rather
+/// than once its cached copy expires.
+pub async fn invalidate_cached_app(cache: &Cache, app: &application::Model) {
+    if let Err(e) = cache
+        .delete(&AppEndpointKey::new(&app.org_id, &app.id))
+        .await
+    {
+        tracing::warn!("Failed to invalidate cached application {}: {}", app.id, e);
+    }
+}
+
+/// Sends an `endpoint.paused` or `endpoint.resumed` operational webhook for each of the endpoints.
+pub async fn send_pause_webhooks(
+    op_webhooks: &OperationalWebhookSender,
+    app: &application::Model,
+    endpoints: &[endpoint::Model],
+    paused: bool,
+) -> Result<()> {
+    for endp in endpoints {
+        let event = EndpointEvent::new(app.uid.as_ref(), endp);
+        let payload = if paused {
+            OperationalWebhook::EndpointPaused(event)
+        } else {
+            OperationalWebhook::EndpointResumed(event)
+        };
+        op_webhooks
+            .send_operational_webhook(&app.org_id, payload)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Queues again the destinations held for the endpoints while they were paused, oldest first.
+/// Returns how many were queued.
+///
+/// Only the destinations still held when they're marked as sending are queued, so releasing the
+/// same endpoints concurrently (e.g. by the reaper) doesn't queue any of them twice.
+pub async fn release_held_destinations(
+    db: &DatabaseConnection,
+    queue_tx: &TaskQueueProducer,
+    app_id: &ApplicationId,
+    endp_ids: Vec<EndpointId>,
+) -> Result<u64> {
+    const BATCH_SIZE: u64 = 1_000;
+    let mut iterator: Option<MessageEndpointId> = None;
+    let mut total = 0;
+
+    if endp_ids.is_empty() {
+        return Ok(0);
+    }
+
+    loop {
+        let mut query = messagedestination::Entity::find()
+            .select_only()
+            .column(messagedestination::Column::Id)
+            .filter(messagedestination::Column::EndpId.is_in(endp_ids.clone()))
+            .filter(messagedestination::Column::Status.eq(MessageStatus::Paused))
+            .order_by_asc(messagedestination::Column::Id)
+            .limit(BATCH_SIZE);
+        if let Some(iterator) = iterator {
+            query = query.filter(messagedestination::Column::Id.gt(iterator));
+        }
+
+        let ids: Vec<MessageEndpointId> = query.into_tuple().all(db).await?;
+        let count = ids.len() as u64;
+        iterator = ids.last().cloned();
+
+        // Marked as sending first, so the worker doesn't skip them
+        let mut released = messagedestination::Entity::update_many()
+            .col_expr(
+                messagedestination::Column::Status,
+                Expr::value(MessageStatus::Sending),
+            )
+            .col_expr(
+                messagedestination::Column::NextAttempt,
+                Expr::value(Utc::now()),
+            )
+            .filter(messagedestination::Column::Id.is_in(ids))
+            .filter(messagedestination::Column::Status.eq(MessageStatus::Paused))
+            .exec_with_returning(db)
+            .await?;
+        released.sort_by(|a, b| a.id.0.cmp(&b.id.0));
+
+        let priorities =
+            message::Entity::priorities(db, released.iter().map(|x| x.msg_id.clone())).await?;
+        for (i, msg_dest) in released.iter().enumerate() {
+            let priority = priorities
+                .get(&msg_dest.msg_id)
+                .copied()
+                .unwrap_or_default();
+            let task = MessageTask::new_task(
+                msg_dest.msg_id.clone(),
+                app_id.clone(),
+                msg_dest.endp_id.clone(),
+                MessageAttemptTriggerType::Scheduled,
+                priority,
+            );
+            if let Err(e) = queue_tx.send(&task, None).await {
+                // Held again, so they're released with the next try rather than left sending
+                // without a task
+                hold_again(db, released[i..].iter().map(|x| x.id.clone())).await?;
+                return Err(e);
+            }
+            total += 1;
+        }
+
+        if count < BATCH_SIZE {
+            break;
+        }
+    }
+
+    Ok(total)
+}
+
+/// Puts destinations released without being queued back on hold.
+async fn hold_again(
+    db: &DatabaseConnection,
+    ids: impl IntoIterator<Item = MessageEndpointId>,
+) -> Result<()> {
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::Status,
+            Expr::value(MessageStatus::Paused),
+        )
+        .col_expr(
+            messagedestination::Column::NextAttempt,
+            Expr::value(Option::<DateTimeWithTimeZone>::None),
+        )
+        .filter(messagedestination::Column::Id.is_in(ids))
+        .filter(messagedestination::Column::Status.eq(MessageStatus::Sending))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Releases the destinations held for the task's endpoints, leaving out those that were deleted
+/// or paused again since, or whose application was.
+pub async fn process_release_task(
+    db: &DatabaseConnection,
+    queue_tx: &TaskQueueProducer,
+    task: ReleaseHeldDestinationsTask,
+) -> Result<()> {
+    let Some(app) = application::Entity::find_by_id(task.app_id)
+        .filter(application::Column::Deleted.eq(false))
+        .one(db)
+        .await?
+    else {
+        return Ok(());
+    };
+    if app.paused {
+        return Ok(());
+    }
+
+    let endp_ids: Vec<EndpointId> = endpoint::Entity::secure_find(app.id.clone())
+        .select_only()
+        .column(endpoint::Column::Id)
+        .filter(endpoint::Column::Id.is_in(task.endpoint_ids))
+        .filter(endpoint::Column::Paused.eq(false))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let count = release_held_destinations(db, queue_tx, &app.id, endp_ids).await?;
+    tracing::debug!("Queued {count} held destinations again");
+
+    Ok(())
+}
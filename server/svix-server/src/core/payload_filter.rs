@@ -0,0 +1,261 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Filters on the payload of messages, so endpoints only receive the messages they care about.
+//!
+//! Filters are written in a subset of [JsonLogic](https://jsonlogic.com/), e.g.
+//! `{"and": [{">": [{"var": "amount"}, 1000]}, {"==": [{"var": "currency"}, "EUR"]}]}`.
+//! The supported operations are `var`, `==`, `!=`, `===`, `!==`, `<`, `<=`, `>`, `>=`, `!`, `!!`,
+//! `and`, `or` and `in`.
+
+use std::cmp::Ordering;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::json_wrapper;
+
+/// Max size of a serialized filter
+const MAX_FILTER_SIZE: usize = 4096;
+
+/// Max nesting of operations in a filter
+const MAX_FILTER_DEPTH: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema)]
+#[schemars(transparent)]
+pub struct EndpointFilter(Value);
+
+json_wrapper!(EndpointFilter);
+
+impl<'de> Deserialize<'de> for EndpointFilter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+
+        let size = serde_json::to_string(&value)
+            .map(|blob| blob.len())
+            .map_err(|_| serde::de::Error::custom("filter is not valid json"))?;
+        if size > MAX_FILTER_SIZE {
+            return Err(serde::de::Error::custom(format!(
+                "filter must be less than or equal to {MAX_FILTER_SIZE} bytes"
+            )));
+        }
+
+        if !value.is_object() {
+            return Err(serde::de::Error::custom("filter must be an operation"));
+        }
+        check(&value, 0).map_err(serde::de::Error::custom)?;
+
+        Ok(Self(value))
+    }
+}
+
+impl EndpointFilter {
+    /// Whether a message with the given payload passes the filter.
+    pub fn matches(&self, payload: &Value) -> bool {
+        truthy(&eval(&self.0, payload))
+    }
+}
+
+/// The arguments of an operation. A single argument doesn't have to be wrapped in an array.
+fn args(value: &Value) -> &[Value] {
+    match value {
+        Value::Array(args) => args,
+        arg => std::slice::from_ref(arg),
+    }
+}
+
+fn check(value: &Value, depth: usize) -> Result<(), String> {
+    if depth > MAX_FILTER_DEPTH {
+        return Err(format!(
+            "filter operations can be nested at most {MAX_FILTER_DEPTH} levels deep"
+        ));
+    }
+
+    match value {
+        Value::Array(items) => items.iter().try_for_each(|item| check(item, depth + 1)),
+        Value::Object(map) => {
+            let mut ops = map.iter();
+            let (Some((op, operands)), None) = (ops.next(), ops.next()) else {
+                return Err("filter operations must have exactly one operator".to_owned());
+            };
+            let args = args(operands);
+
+            let valid_arity = match op.as_str() {
+                "var" => {
+                    if !matches!(args.first(), Some(Value::String(_) | Value::Number(_))) {
+                        return Err("`var` takes a path to a field of the payload".to_owned());
+                    }
+                    (1..=2).contains(&args.len())
+                }
+                "==" | "!=" | "===" | "!==" | "<" | "<=" | ">" | ">=" | "in" => args.len() == 2,
+                "!" | "!!" => args.len() == 1,
+                "and" | "or" => !args.is_empty(),
+                _ => return Err(format!("unsupported filter operator `{op}`")),
+            };
+            if !valid_arity {
+                return Err(format!("wrong number of arguments to `{op}`"));
+            }
+
+            args.iter().try_for_each(|arg| check(arg, depth + 1))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn eval(value: &Value, payload: &Value) -> Value {
+    let Value::Object(map) = value else {
+        return match value {
+            Value::Array(items) => items.iter().map(|item| eval(item, payload)).collect(),
+            literal => literal.clone(),
+        };
+    };
+    let Some((op, operands)) = map.iter().next() else {
+        return Value::Null;
+    };
+    let args = args(operands);
+    let arg = |i: usize| args.get(i).map_or(Value::Null, |arg| eval(arg, payload));
+
+    let result = match op.as_str() {
+        "var" => {
+            return lookup(payload, &arg(0)).cloned().unwrap_or_else(|| arg(1));
+        }
+        "==" | "===" => loose_eq(&arg(0), &arg(1)),
+        "!=" | "!==" => !loose_eq(&arg(0), &arg(1)),
+        "<" => compare(&arg(0), &arg(1)) == Some(Ordering::Less),
+        "<=" => matches!(
+            compare(&arg(0), &arg(1)),
+            Some(Ordering::Less | Ordering::Equal)
+        ),
+        ">" => compare(&arg(0), &arg(1)) == Some(Ordering::Greater),
+        ">=" => matches!(
+            compare(&arg(0), &arg(1)),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        "!" => !truthy(&arg(0)),
+        "!!" => truthy(&arg(0)),
+        "and" => args.iter().all(|arg| truthy(&eval(arg, payload))),
+        "or" => args.iter().any(|arg| truthy(&eval(arg, payload))),
+        "in" => match (arg(0), arg(1)) {
+            (needle, Value::Array(haystack)) => haystack.iter().any(|x| loose_eq(&needle, x)),
+            (Value::String(needle), Value::String(haystack)) => haystack.contains(&needle),
+            _ => false,
+        },
+        _ => false,
+    };
+
+    Value::Bool(result)
+}
+
+/// Looks up a dot-separated path (e.g. `customer.address.city` or `items.0.price`) in the payload.
+fn lookup<'a>(payload: &'a Value, path: &Value) -> Option<&'a Value> {
+    let path = match path {
+        Value::String(path) if path.is_empty() => return Some(payload),
+        Value::String(path) => path.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => return None,
+    };
+
+    path.split('.').try_fold(payload, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+fn loose_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => a == b,
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::EndpointFilter;
+
+    fn filter(value: serde_json::Value) -> EndpointFilter {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let invoice = filter(json!({
+            "and": [
+                { ">": [{ "var": "amount" }, 1000] },
+                { "==": [{ "var": "currency" }, "EUR"] },
+            ]
+        }));
+        assert!(invoice.matches(&json!({ "amount": 1500, "currency": "EUR" })));
+        assert!(invoice.matches(&json!({ "amount": 1000.5, "currency": "EUR" })));
+        assert!(!invoice.matches(&json!({ "amount": 1000, "currency": "EUR" })));
+        assert!(!invoice.matches(&json!({ "amount": 1500, "currency": "USD" })));
+        assert!(!invoice.matches(&json!({ "currency": "EUR" })));
+        assert!(!invoice.matches(&json!([])));
+
+        let nested = filter(json!({ "in": [{ "var": "customer.tags.0" }, ["vip", "beta"]] }));
+        assert!(nested.matches(&json!({ "customer": { "tags": ["vip"] } })));
+        assert!(!nested.matches(&json!({ "customer": { "tags": ["free"] } })));
+        assert!(!nested.matches(&json!({ "customer": {} })));
+
+        let negated = filter(json!({ "!": { "var": "test" } }));
+        assert!(negated.matches(&json!({ "test": false })));
+        assert!(negated.matches(&json!({})));
+        assert!(!negated.matches(&json!({ "test": true })));
+
+        let default = filter(json!({ "==": [{ "var": ["region", "eu"] }, "eu"] }));
+        assert!(default.matches(&json!({})));
+        assert!(!default.matches(&json!({ "region": "us" })));
+    }
+
+    #[test]
+    fn test_filter_validation() {
+        for invalid in [
+            json!(true),
+            json!("amount"),
+            json!({}),
+            json!({ "unknown": [1, 2] }),
+            json!({ ">": [1] }),
+            json!({ "!": [1, 2] }),
+            json!({ "and": [] }),
+            json!({ "var": { "var": "a" } }),
+            json!({ "==": [1, 1], "!=": [1, 2] }),
+            json!({ "==": [{ "var": "a" }, "a".repeat(5000)] }),
+        ] {
+            assert!(
+                serde_json::from_value::<EndpointFilter>(invalid.clone()).is_err(),
+                "{invalid}"
+            );
+        }
+
+        let mut deep = json!({ "var": "a" });
+        for _ in 0..20 {
+            deep = json!({ "!": deep });
+        }
+        assert!(serde_json::from_value::<EndpointFilter>(deep).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Backward compatibility checks between versions of an event type's JSON schema.
+//!
+//! A new version is backward compatible if every payload valid against the previous version is
+//! still understood by consumers of the previous version. The checks are deliberately simple,
+//! they catch the common breaking changes: required properties being removed, and the allowed
+//! types or values of a property being narrowed.
+
+use std::collections::BTreeSet;
+
+use serde_json::Value;
+
+/// Lists the changes from `previous` to `next` which break backward compatibility, in a human
+/// readable form.
+pub fn breaking_changes(previous: &Value, next: &Value) -> Vec<String> {
+    let mut changes = vec![];
+    check(&mut changes, "", previous, next);
+    changes
+}
+
+fn describe(path: &str) -> String {
+    if path.is_empty() {
+        "the payload".to_owned()
+    } else {
+        format!("`{path}`")
+    }
+}
+
+fn check(changes: &mut Vec<String>, path: &str, previous: &Value, next: &Value) {
+    let (Value::Object(previous), Value::Object(next)) = (previous, next) else {
+        return;
+    };
+
+    if let Some(next_types) = types(next.get("type")) {
+        match types(previous.get("type")) {
+            None => changes.push(format!(
+                "{} is now restricted to {}",
+                describe(path),
+                next_types.into_iter().collect::<Vec<_>>().join(", ")
+            )),
+            Some(previous_types) => {
+                for ty in previous_types {
+                    let accepted = next_types.contains(ty)
+                        || (ty == "integer" && next_types.contains("number"));
+                    if !accepted {
+                        changes.push(format!("{} no longer accepts type {ty}", describe(path)));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(Value::Array(next_values)) = next.get("enum") {
+        match previous.get("enum") {
+            Some(Value::Array(previous_values)) => {
+                for value in previous_values {
+                    if !next_values.contains(value) {
+                        changes.push(format!("{} no longer accepts {value}", describe(path)));
+                    }
+                }
+            }
+            _ => changes.push(format!(
+                "{} is now restricted to a set of values",
+                describe(path)
+            )),
+        }
+    }
+
+    let next_required = required(next.get("required"));
+    for field in required(previous.get("required")) {
+        if !next_required.contains(field) {
+            changes.push(format!(
+                "required property `{}` was removed",
+                join(path, field)
+            ));
+        }
+    }
+
+    if let (Some(Value::Object(previous_props)), Some(Value::Object(next_props))) =
+        (previous.get("properties"), next.get("properties"))
+    {
+        for (name, previous_prop) in previous_props {
+            if let Some(next_prop) = next_props.get(name) {
+                check(changes, &join(path, name), previous_prop, next_prop);
+            }
+        }
+    }
+
+    if let (Some(previous_items), Some(next_items)) = (previous.get("items"), next.get("items")) {
+        check(changes, &format!("{path}[]"), previous_items, next_items);
+    }
+}
+
+fn join(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_owned()
+    } else {
+        format!("{path}.{field}")
+    }
+}
+
+/// The types allowed by a `type` keyword, `None` meaning any type.
+fn types(ty: Option<&Value>) -> Option<BTreeSet<&str>> {
+    match ty? {
+        Value::String(ty) => Some(BTreeSet::from([ty.as_str()])),
+        Value::Array(tys) => Some(tys.iter().filter_map(Value::as_str).collect()),
+        _ => None,
+    }
+}
+
+fn required(required: Option<&Value>) -> BTreeSet<&str> {
+    match required {
+        Some(Value::Array(fields)) => fields.iter().filter_map(Value::as_str).collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::breaking_changes;
+
+    #[test]
+    fn test_compatible_changes() {
+        let previous = json!({
+            "type": "object",
+            "properties": {
+                "invoiceId": { "type": "string" },
+                "amount": { "type": "integer" },
+                "status": { "enum": ["paid", "refunded"] },
+            },
+            "required": ["invoiceId"],
+        });
+        let next = json!({
+            "type": "object",
+            "properties": {
+                "invoiceId": { "type": "string" },
+                "amount": { "type": ["number", "null"] },
+                "status": { "enum": ["paid", "refunded", "disputed"] },
+                "userId": { "type": "string" },
+            },
+            "required": ["invoiceId", "userId"],
+        });
+        assert!(breaking_changes(&previous, &next).is_empty());
+        assert!(breaking_changes(&previous, &previous).is_empty());
+    }
+
+    #[test]
+    fn test_breaking_changes() {
+        let previous = json!({
+            "type": "object",
+            "properties": {
+                "invoiceId": { "type": "string" },
+                "amount": { "type": "number" },
+                "status": { "enum": ["paid", "refunded"] },
+                "items": {
+                    "type": "array",
+                    "items": { "type": "object", "required": ["sku"] },
+                },
+                "note": {},
+            },
+            "required": ["invoiceId", "amount"],
+        });
+        let next = json!({
+            "type": "object",
+            "properties": {
+                "invoiceId": { "type": "string" },
+                "amount": { "type": "integer" },
+                "status": { "enum": ["paid"] },
+                "items": {
+                    "type": "array",
+                    "items": { "type": "object" },
+                },
+                "note": { "type": "string" },
+            },
+            "required": ["invoiceId"],
+        });
+
+        let mut changes = breaking_changes(&previous, &next);
+        changes.sort();
+        assert_eq!(
+            changes,
+            vec![
+                "`amount` no longer accepts type number",
+                "`note` is now restricted to string",
+                r#"`status` no longer accepts "refunded""#,
+                "required property `amount` was removed",
+                "required property `items[].sku` was removed",
+            ]
+        );
+    }
+}
@@ -36,6 +36,8 @@ pub enum AccessLevel {
 pub struct Permissions {
     pub access_level: AccessLevel,
     pub feature_flags: FeatureFlagSet,
+    /// The `jti` of the token, if it has one
+    pub token_id: Option<String>,
 }
 
 impl Permissions {
@@ -52,6 +54,15 @@ impl Permissions {
             AccessLevel::Application(_, app_id) => Some(app_id.clone()),
         }
     }
+
+    /// The subject of the token: the application for application tokens, and the organization
+    /// otherwise.
+    pub fn subject(&self) -> String {
+        match &self.access_level {
+            AccessLevel::Organization(org_id) => org_id.to_string(),
+            AccessLevel::Application(_, app_id) => app_id.to_string(),
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -129,6 +140,7 @@ pub fn permissions_from_jwt(claims: JWTClaims<CustomClaim>) -> Result<Permission
             Ok(Permissions {
                 access_level: AccessLevel::Application(org_id, app_id),
                 feature_flags: claims.custom.feature_flags,
+                token_id: claims.jwt_id,
             })
         } else {
             Err(
@@ -149,6 +161,7 @@ pub fn permissions_from_jwt(claims: JWTClaims<CustomClaim>) -> Result<Permission
         Ok(Permissions {
             access_level: AccessLevel::Organization(org_id),
             feature_flags: claims.custom.feature_flags,
+            token_id: claims.jwt_id,
         })
     } else {
         Err(
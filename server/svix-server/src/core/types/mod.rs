@@ -47,6 +47,13 @@ macro_rules! enum_wrapper {
             }
         }
 
+        enum_sea_orm_wrapper!($name_id);
+    };
+}
+
+/// Stores an `i16`-backed enum as a `SMALLINT`, regardless of how it's serialized.
+macro_rules! enum_sea_orm_wrapper {
+    ($name_id:ty) => {
         impl From<$name_id> for sea_orm::entity::prelude::Value {
             fn from(v: $name_id) -> Self {
                 Self::SmallInt(Some(v.into()))
@@ -563,6 +570,7 @@ create_id_type!(
 create_id_type!(MessageEndpointId, "msgep_");
 create_id_type!(EventTypeId, "evtype_");
 create_id_type!(QueueBackgroundTaskId, "qtask_");
+create_id_type!(BroadcastId, "brdcst_");
 
 create_all_id_types!(ApplicationId, ApplicationUid, ApplicationIdOrUid, "app_");
 create_all_id_types!(EndpointId, EndpointUid, EndpointIdOrUid, "ep_");
@@ -586,6 +594,63 @@ impl Validate for EventTypeName {
     }
 }
 
+impl EventTypeName {
+    /// Whether this is a pattern matching many event types (e.g. `invoice.*`) rather than a
+    /// single event type. Patterns are only valid in endpoint subscriptions.
+    pub fn is_pattern(&self) -> bool {
+        self.0.split('.').any(|segment| segment == "*")
+    }
+
+    /// Validates the name as an event type, or as a pattern using `*` as whole segments.
+    pub fn validate_pattern(&self) -> Result<(), ValidationErrors> {
+        if !self.is_pattern() {
+            return self.validate();
+        }
+
+        static SEGMENT_RE: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9\-_]+$").unwrap());
+        if self.0.len() <= 256
+            && self
+                .0
+                .split('.')
+                .all(|segment| segment == "*" || SEGMENT_RE.is_match(segment))
+        {
+            Ok(())
+        } else {
+            let mut errors = ValidationErrors::new();
+            errors.add(
+                ALL_ERROR,
+                validation_error(
+                    Some("illegal_string_pattern"),
+                    Some(
+                        "Patterns must match [a-zA-Z0-9\\-_.*], with `*` only as a whole segment.",
+                    ),
+                ),
+            );
+            Err(errors)
+        }
+    }
+
+    /// Whether the event type is matched by this pattern, or is this event type if it isn't a
+    /// pattern.
+    ///
+    /// `*` matches any single segment, and when it's the last segment any number of trailing
+    /// segments: `invoice.*` matches `invoice.paid` and `invoice.line.added`, while `*.created`
+    /// matches `user.created` but not `user.profile.created`.
+    pub fn matches(&self, event_type: &EventTypeName) -> bool {
+        let mut pattern = self.0.split('.').peekable();
+        let mut name = event_type.0.split('.');
+        loop {
+            match (pattern.next(), name.next()) {
+                (Some("*"), Some(_)) if pattern.peek().is_none() => return true,
+                (Some(p), Some(n)) if p == "*" || p == n => {}
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+}
+
 string_wrapper!(
     EventChannel,
     crate::core::types::StringSchema {
@@ -632,6 +697,17 @@ impl Validate for EventTypeNameSet {
     }
 }
 
+impl EventTypeNameSet {
+    /// Whether any of the event types or patterns matches the event type.
+    pub fn matches(&self, event_type: &EventTypeName) -> bool {
+        self.0.contains(event_type)
+            || self
+                .0
+                .iter()
+                .any(|pattern| pattern.is_pattern() && pattern.matches(event_type))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ExpiringSigningKeys(pub Vec<ExpiringSigningKey>);
 json_wrapper!(ExpiringSigningKeys);
@@ -1232,6 +1308,155 @@ impl Validate for EndpointHeadersPatch {
     }
 }
 
+/// A string value that is encrypted at rest with the main secret (if set), e.g. private keys or
+/// credentials that svix-server needs to read back but must never return over the API.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedString {
+    encrypted: bool,
+    value: Vec<u8>,
+}
+
+impl EncryptedString {
+    pub fn new(encryption: &Encryption, value: &str) -> crate::error::Result<Self> {
+        Ok(Self {
+            encrypted: encryption.enabled(),
+            value: encryption.encrypt(value.as_bytes())?,
+        })
+    }
+
+    pub fn decrypt(&self, encryption: &Encryption) -> crate::error::Result<String> {
+        let value = if self.encrypted {
+            if encryption.enabled() {
+                encryption.decrypt(&self.value)?
+            } else {
+                return Err(crate::error::Error::generic(
+                    "main_secret unset, can't decrypt value",
+                ));
+            }
+        } else {
+            self.value.clone()
+        };
+        String::from_utf8(value).map_err(|_| crate::error::Error::generic("invalid UTF-8 value"))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedStringRepr {
+    encrypted: bool,
+    value: String,
+}
+
+impl Serialize for EncryptedString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        EncryptedStringRepr {
+            encrypted: self.encrypted,
+            value: base64::encode(&self.value),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EncryptedString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let repr = EncryptedStringRepr::deserialize(deserializer)?;
+        Ok(Self {
+            encrypted: repr.encrypted,
+            value: base64::decode(repr.value).map_err(|err| Error::custom(err.to_string()))?,
+        })
+    }
+}
+
+/// The TLS settings of an endpoint: an optional client certificate (and its private key) which is
+/// presented to the receiver for mutual TLS, and an optional CA bundle used to verify the
+/// receiver's certificate in addition to the system roots. All values are PEM encoded.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointTlsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client_key: Option<EncryptedString>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<String>,
+}
+json_wrapper!(EndpointTlsConfig);
+
+impl EndpointTlsConfig {
+    pub fn new(
+        encryption: &Encryption,
+        client_cert: Option<String>,
+        client_key: Option<&str>,
+        ca_bundle: Option<String>,
+    ) -> crate::error::Result<Self> {
+        Ok(Self {
+            client_cert,
+            client_key: client_key
+                .map(|key| EncryptedString::new(encryption, key))
+                .transpose()?,
+            ca_bundle,
+        })
+    }
+
+    pub fn client_key(&self, encryption: &Encryption) -> crate::error::Result<Option<String>> {
+        self.client_key
+            .as_ref()
+            .map(|key| key.decrypt(encryption))
+            .transpose()
+    }
+
+    pub fn has_client_key(&self) -> bool {
+        self.client_key.is_some()
+    }
+}
+
+/// The OAuth2 client credentials used to obtain an access token which is sent as a bearer token
+/// with every webhook to the endpoint.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointOAuth2Config {
+    pub token_url: String,
+    pub client_id: String,
+    client_secret: EncryptedString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+}
+json_wrapper!(EndpointOAuth2Config);
+
+impl EndpointOAuth2Config {
+    pub fn new(
+        encryption: &Encryption,
+        token_url: String,
+        client_id: String,
+        client_secret: &str,
+        scopes: Option<Vec<String>>,
+    ) -> crate::error::Result<Self> {
+        Ok(Self {
+            token_url,
+            client_id,
+            client_secret: EncryptedString::new(encryption, client_secret)?,
+            scopes,
+        })
+    }
+
+    pub fn client_secret(&self, encryption: &Encryption) -> crate::error::Result<String> {
+        self.client_secret.decrypt(encryption)
+    }
+}
+
+/// A W3C trace context, as the `traceparent` and (optional) `tracestate` header values keyed by
+/// their names.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceContext(pub HashMap<String, String>);
+json_wrapper!(TraceContext);
+
 /// A macro to which you pass the list of variants of an enum using `repr(N)`
 /// and it returns a `Vec<(N, String)>`, where each element is `(value, "VariantStringified")`
 macro_rules! repr_enum {
@@ -1325,13 +1550,15 @@ pub enum MessageStatus {
     Pending = 1,
     Fail = 2,
     Sending = 3,
+    Skipped = 4,
+    Paused = 5,
 }
 
 jsonschema_for_repr_enum! {
     MessageStatus,
     i16,
-    "The sending status of the message:\n- Success = 0\n- Pending = 1\n- Fail = 2\n- Sending = 3",
-    Success, Pending, Fail, Sending
+    "The sending status of the message:\n- Success = 0\n- Pending = 1\n- Fail = 2\n- Sending = 3\n- Skipped = 4\n- Paused = 5",
+    Success, Pending, Fail, Sending, Skipped, Paused
 }
 
 #[repr(i16)]
@@ -1352,9 +1579,96 @@ jsonschema_for_repr_enum! {
     CodeNone, Code1xx, Code2xx, Code3xx, Code4xx, Code5xx
 }
 
+#[repr(i16)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+pub enum BroadcastStatus {
+    Running = 0,
+    Finished = 1,
+    Failed = 2,
+}
+
+jsonschema_for_repr_enum! {
+    BroadcastStatus,
+    i16,
+    "The status of a broadcast:\n- Running = 0\n- Finished = 1\n- Failed = 2",
+    Running, Finished, Failed
+}
+
+#[repr(i16)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+pub enum MessageEventType {
+    Created = 0,
+    Attempted = 1,
+}
+
+jsonschema_for_repr_enum! {
+    MessageEventType,
+    i16,
+    "The type of a message event:\n- Created = 0\n- Attempted = 1",
+    Created, Attempted
+}
+
+#[repr(i16)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+pub enum EndpointVerificationStatus {
+    Verified = 0,
+    PendingVerification = 1,
+    Failed = 2,
+}
+
+jsonschema_for_repr_enum! {
+    EndpointVerificationStatus,
+    i16,
+    "Whether the endpoint echoed the verification challenge sent to its URL:\n- Verified = 0\n- PendingVerification = 1\n- Failed = 2",
+    Verified, PendingVerification, Failed
+}
+
+/// How urgently a message should be delivered. Messages are only delivered ahead of those of a
+/// lower priority when the server has priority lanes enabled.
+#[repr(i16)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    IntoPrimitive,
+    TryFromPrimitive,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MessagePriority {
+    High = 0,
+    #[default]
+    Normal = 1,
+    Low = 2,
+}
+
+impl MessagePriority {
+    pub fn is_normal(&self) -> bool {
+        *self == MessagePriority::Normal
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessagePriority::High => "high",
+            MessagePriority::Normal => "normal",
+            MessagePriority::Low => "low",
+        }
+    }
+}
+
 enum_wrapper!(MessageAttemptTriggerType);
 enum_wrapper!(MessageStatus);
 enum_wrapper!(StatusCodeClass);
+enum_wrapper!(BroadcastStatus);
+enum_wrapper!(MessageEventType);
+enum_wrapper!(EndpointVerificationStatus);
+enum_sea_orm_wrapper!(MessagePriority);
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize)]
 pub struct FeatureFlag(pub String);
@@ -1387,6 +1701,36 @@ impl<'de> Deserialize<'de> for FeatureFlag {
 
 pub type FeatureFlagSet = HashSet<FeatureFlag>;
 
+/// A version of an event type's schema, i.e. one of the keys of its `schemas`.
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize)]
+pub struct SchemaVersion(pub String);
+
+common_jsonschema_impl!(
+    SchemaVersion,
+    crate::core::types::StringSchema {
+        string_validation: Some(schemars::schema::StringValidation {
+            min_length: None,
+            max_length: Some(256),
+            pattern: Some(r"^[a-zA-Z0-9\-_.]+$".to_string()),
+        }),
+        example: Some("1".to_string()),
+    }
+);
+
+string_wrapper_impl!(SchemaVersion);
+
+impl<'de> Deserialize<'de> for SchemaVersion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).and_then(|s| {
+            validate_limited_str(&s).map_err(serde::de::Error::custom)?;
+            Ok(SchemaVersion(s))
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -1394,10 +1738,11 @@ mod tests {
     use validator::Validate;
 
     use super::{
-        validate_header_map, ApplicationId, ApplicationUid, EndpointHeaders, EndpointHeadersPatch,
-        EndpointSecret, EventChannel, EventTypeName,
+        validate_header_map, ApplicationId, ApplicationUid, EncryptedString, EndpointHeaders,
+        EndpointHeadersPatch, EndpointOAuth2Config, EndpointSecret, EndpointTlsConfig,
+        EventChannel, EventTypeName, EventTypeNameSet,
     };
-    use crate::core::cryptography::AsymmetricKey;
+    use crate::core::cryptography::{AsymmetricKey, Encryption};
 
     #[test]
     fn test_id_validation() {
@@ -1606,4 +1951,111 @@ mod tests {
             panic!("Shouldn't get here");
         }
     }
+
+    #[test]
+    fn test_encrypted_string() {
+        let encryption = Encryption::new([1; 32]);
+        let value = EncryptedString::new(&encryption, "secret").unwrap();
+        assert_eq!(value.decrypt(&encryption).unwrap(), "secret");
+
+        let serialized = serde_json::to_string(&value).unwrap();
+        assert!(!serialized.contains("secret"));
+        let deserialized: EncryptedString = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.decrypt(&encryption).unwrap(), "secret");
+
+        // Can't decrypt without the main secret
+        assert!(value.decrypt(&Encryption::new_noop()).is_err());
+
+        // But plaintext values stay readable if the main secret is set later
+        let value = EncryptedString::new(&Encryption::new_noop(), "secret").unwrap();
+        assert_eq!(value.decrypt(&encryption).unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_endpoint_tls_config() {
+        let encryption = Encryption::new([1; 32]);
+        let tls = EndpointTlsConfig::new(
+            &encryption,
+            Some("cert".to_owned()),
+            Some("key"),
+            Some("ca".to_owned()),
+        )
+        .unwrap();
+        assert!(tls.has_client_key());
+        assert_eq!(tls.client_key(&encryption).unwrap().as_deref(), Some("key"));
+
+        let roundtrip: EndpointTlsConfig =
+            serde_json::from_value(serde_json::to_value(&tls).unwrap()).unwrap();
+        assert_eq!(roundtrip, tls);
+
+        let tls = EndpointTlsConfig::new(&encryption, None, None, Some("ca".to_owned())).unwrap();
+        assert!(!tls.has_client_key());
+        assert_eq!(tls.client_key(&encryption).unwrap(), None);
+    }
+
+    #[test]
+    fn test_endpoint_oauth2_config() {
+        let encryption = Encryption::new([1; 32]);
+        let oauth2 = EndpointOAuth2Config::new(
+            &encryption,
+            "https://auth.example.com/token".to_owned(),
+            "client".to_owned(),
+            "secret",
+            Some(vec!["webhooks:write".to_owned()]),
+        )
+        .unwrap();
+        assert_eq!(oauth2.client_secret(&encryption).unwrap(), "secret");
+
+        let serialized = serde_json::to_value(&oauth2).unwrap();
+        assert!(!serialized.to_string().contains("\"secret\""));
+        let deserialized: EndpointOAuth2Config = serde_json::from_value(serialized).unwrap();
+        assert_eq!(deserialized, oauth2);
+    }
+
+    #[test]
+    fn test_event_type_patterns() {
+        let name = |s: &str| EventTypeName(s.to_owned());
+
+        for (pattern, event_type, matches) in [
+            ("invoice.paid", "invoice.paid", true),
+            ("invoice.paid", "invoice.voided", false),
+            ("invoice.*", "invoice.paid", true),
+            ("invoice.*", "invoice.line.added", true),
+            ("invoice.*", "invoice", false),
+            ("invoice.*", "customer.created", false),
+            ("*.created", "user.created", true),
+            ("*.created", "user.profile.created", false),
+            ("*.created", "user.deleted", false),
+            ("user.*.updated", "user.profile.updated", true),
+            ("user.*.updated", "user.profile.email.updated", false),
+            ("*", "anything.at.all", true),
+        ] {
+            assert_eq!(
+                name(pattern).matches(&name(event_type)),
+                matches,
+                "{pattern} {event_type}"
+            );
+        }
+
+        assert!(name("invoice.*").is_pattern());
+        assert!(!name("invoice.paid").is_pattern());
+        name("invoice.*").validate_pattern().unwrap();
+        name("*.created").validate_pattern().unwrap();
+        name("invoice.paid").validate_pattern().unwrap();
+        assert!(name("invoice*").validate_pattern().is_err());
+        assert!(name("in*.paid").validate_pattern().is_err());
+        assert!(name("invoice.*.&&").validate_pattern().is_err());
+        // Segments can't be empty
+        assert!(name("invoice..*").validate_pattern().is_err());
+        assert!(name(".*").validate_pattern().is_err());
+        assert!(name("*.").validate_pattern().is_err());
+        assert!(name("a.*.").validate_pattern().is_err());
+        // Patterns aren't valid event types
+        assert!(name("invoice.*").validate().is_err());
+
+        let set = EventTypeNameSet([name("invoice.*"), name("user.created")].into());
+        assert!(set.matches(&name("invoice.paid")));
+        assert!(set.matches(&name("user.created")));
+        assert!(!set.matches(&name("user.deleted")));
+    }
 }
@@ -0,0 +1,349 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Per-organization restrictions on the hosts, IPs and ports endpoints may point to.
+//!
+//! Policies can only narrow down what is reachable: private IPs stay blocked unless they're
+//! whitelisted in the server configuration, whatever an organization's policy says.
+//!
+//! Policies are enforced both when endpoints are created or updated, and when webhooks are sent.
+//! The latter is done by the DNS resolver of the [`WebhookClient`], so a name which resolved to an
+//! allowed IP when the endpoint was created can't later be rebound to a blocked one.
+//!
+//! [`WebhookClient`]: super::webhook_http_client::WebhookClient
+
+use std::{net::IpAddr, time::Duration};
+
+use ipnet::IpNet;
+use schemars::JsonSchema;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::{Host, Url};
+use validator::{Validate, ValidationError};
+
+use super::{
+    cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
+    types::OrganizationId,
+};
+use crate::{
+    db::models::orgurlpolicy,
+    error::{Error, Result},
+    json_wrapper,
+    v1::utils::validation_error,
+};
+
+/// How long policies are cached for by the workers. Updates through the API invalidate the cache
+/// right away, this only bounds the staleness if that fails.
+const URL_POLICY_CACHE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, Validate, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlPolicy {
+    /// If not empty, endpoints may only use these hostnames. `*.example.com` matches every
+    /// subdomain of `example.com`, but not `example.com` itself.
+    #[serde(default)]
+    #[validate(custom = "validate_hostname_patterns")]
+    pub allowed_hostnames: Vec<String>,
+    /// Hostnames endpoints may never use, in the same format as `allowedHostnames`.
+    #[serde(default)]
+    #[validate(custom = "validate_hostname_patterns")]
+    pub blocked_hostnames: Vec<String>,
+    /// If not empty, webhooks may only be sent to IPs in these networks.
+    #[serde(default)]
+    #[schemars(with = "Vec<String>", example = "example_cidrs")]
+    pub allowed_cidrs: Vec<IpNet>,
+    /// Networks webhooks may never be sent to.
+    #[serde(default)]
+    #[schemars(with = "Vec<String>", example = "example_cidrs")]
+    pub blocked_cidrs: Vec<IpNet>,
+    /// If not empty, endpoints may only use these ports.
+    #[serde(default)]
+    #[validate(custom = "validate_ports")]
+    pub allowed_ports: Vec<u16>,
+}
+json_wrapper!(UrlPolicy);
+
+fn example_cidrs() -> Vec<&'static str> {
+    vec!["203.0.113.0/24"]
+}
+
+fn validate_hostname_patterns(patterns: &[String]) -> std::result::Result<(), ValidationError> {
+    let is_valid = |pattern: &str| {
+        let name = pattern.strip_prefix("*.").unwrap_or(pattern);
+        !name.is_empty()
+            && name.len() <= 253
+            && name
+                .split('.')
+                .all(|label| !label.is_empty() && label.len() <= 63)
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    };
+
+    if patterns.iter().all(|p| is_valid(p)) {
+        Ok(())
+    } else {
+        Err(validation_error(
+            Some("hostname"),
+            Some("Hostnames must be valid DNS names, optionally prefixed with `*.`."),
+        ))
+    }
+}
+
+fn validate_ports(ports: &[u16]) -> std::result::Result<(), ValidationError> {
+    if ports.contains(&0) {
+        Err(validation_error(
+            Some("port"),
+            Some("Ports must be non-zero."),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Why a URL was rejected by a [`UrlPolicy`]
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum UrlPolicyViolation {
+    #[error("the hostname {0} is not permitted by the organization's URL policy")]
+    Hostname(String),
+    #[error("the IP address {0} is not permitted by the organization's URL policy")]
+    Ip(IpAddr),
+    #[error("the port {0} is not permitted by the organization's URL policy")]
+    Port(u16),
+    #[error("URLs without a host are not permitted")]
+    MissingHost,
+}
+
+fn hostname_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+impl UrlPolicy {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn host_allowed(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let matches = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|p| hostname_matches(&p.to_ascii_lowercase(), &host))
+        };
+
+        !matches(&self.blocked_hostnames)
+            && (self.allowed_hostnames.is_empty() || matches(&self.allowed_hostnames))
+    }
+
+    pub fn ip_allowed(&self, ip: IpAddr) -> bool {
+        !self.blocked_cidrs.iter().any(|net| net.contains(&ip))
+            && (self.allowed_cidrs.is_empty()
+                || self.allowed_cidrs.iter().any(|net| net.contains(&ip)))
+    }
+
+    pub fn port_allowed(&self, port: u16) -> bool {
+        self.allowed_ports.is_empty() || self.allowed_ports.contains(&port)
+    }
+
+    /// Checks everything that can be checked without resolving the URL's host. The IPs names
+    /// resolve to are checked when connecting.
+    pub fn check_url(&self, url: &Url) -> std::result::Result<(), UrlPolicyViolation> {
+        let host = url.host().ok_or(UrlPolicyViolation::MissingHost)?;
+
+        let host_str = match &host {
+            Host::Domain(domain) => domain.to_string(),
+            Host::Ipv4(ip) => ip.to_string(),
+            Host::Ipv6(ip) => ip.to_string(),
+        };
+        if !self.host_allowed(&host_str) {
+            return Err(UrlPolicyViolation::Hostname(host_str));
+        }
+
+        let ip = match host {
+            Host::Domain(_) => None,
+            Host::Ipv4(ip) => Some(IpAddr::V4(ip)),
+            Host::Ipv6(ip) => Some(IpAddr::V6(ip)),
+        };
+        if let Some(ip) = ip {
+            if !self.ip_allowed(ip) {
+                return Err(UrlPolicyViolation::Ip(ip));
+            }
+        }
+
+        if let Some(port) = url.port_or_known_default() {
+            if !self.port_allowed(port) {
+                return Err(UrlPolicyViolation::Port(port));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CachedUrlPolicy {
+    policy: Option<UrlPolicy>,
+}
+
+kv_def!(UrlPolicyCacheKey, CachedUrlPolicy);
+
+impl UrlPolicyCacheKey {
+    pub fn new(org_id: &OrganizationId) -> UrlPolicyCacheKey {
+        UrlPolicyCacheKey(format!("SVIX_URL_POLICY_v1_{org_id}"))
+    }
+}
+
+/// Fetches the URL policy of the organization, if it has one.
+pub async fn fetch_url_policy(
+    db: &DatabaseConnection,
+    org_id: &OrganizationId,
+) -> Result<Option<UrlPolicy>> {
+    Ok(orgurlpolicy::Entity::find_by_id(org_id.clone())
+        .one(db)
+        .await?
+        .map(|m| m.policy))
+}
+
+/// Like [`fetch_url_policy`], but goes through the cache first. Used when dispatching, where the
+/// policy is needed for every single attempt.
+pub async fn cached_url_policy(
+    cache: &Cache,
+    db: &DatabaseConnection,
+    org_id: &OrganizationId,
+) -> Result<Option<UrlPolicy>> {
+    let key = UrlPolicyCacheKey::new(org_id);
+
+    if let Ok(Some(cached)) = cache.get::<CachedUrlPolicy>(&key).await {
+        return Ok(cached.policy);
+    }
+
+    let policy = fetch_url_policy(db, org_id).await?;
+    let cached = CachedUrlPolicy { policy };
+    if let Err(e) = cache.set(&key, &cached, URL_POLICY_CACHE_TTL).await {
+        tracing::warn!("Failed caching URL policy: {e}");
+    }
+
+    Ok(cached.policy)
+}
+
+pub async fn invalidate_url_policy(cache: &Cache, org_id: &OrganizationId) -> Result<()> {
+    cache
+        .delete(&UrlPolicyCacheKey::new(org_id))
+        .await
+        .map_err(Error::cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use url::Url;
+    use validator::Validate;
+
+    use super::{UrlPolicy, UrlPolicyViolation};
+
+    fn check(policy: &UrlPolicy, url: &str) -> Result<(), UrlPolicyViolation> {
+        policy.check_url(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_empty_policy_allows_everything() {
+        let policy = UrlPolicy::default();
+        assert!(policy.is_empty());
+        assert!(check(&policy, "https://example.com/webhook").is_ok());
+        assert!(check(&policy, "http://203.0.113.1:8080/").is_ok());
+    }
+
+    #[test]
+    fn test_hostnames() {
+        let policy = UrlPolicy {
+            allowed_hostnames: vec!["*.example.com".to_owned(), "example.org".to_owned()],
+            blocked_hostnames: vec!["internal.example.com".to_owned()],
+            ..Default::default()
+        };
+
+        assert!(check(&policy, "https://hooks.example.com/").is_ok());
+        assert!(check(&policy, "https://a.b.EXAMPLE.com./").is_ok());
+        assert!(check(&policy, "https://example.org/").is_ok());
+
+        for url in [
+            "https://example.com/",
+            "https://badexample.com/",
+            "https://sub.example.org/",
+            "https://internal.example.com/",
+            "https://203.0.113.1/",
+        ] {
+            assert!(
+                matches!(check(&policy, url), Err(UrlPolicyViolation::Hostname(_))),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_cidrs() {
+        let policy = UrlPolicy {
+            allowed_cidrs: vec!["203.0.113.0/24".parse().unwrap()],
+            blocked_cidrs: vec!["203.0.113.128/25".parse().unwrap()],
+            ..Default::default()
+        };
+
+        assert!(check(&policy, "https://203.0.113.1/").is_ok());
+        assert_eq!(
+            check(&policy, "https://203.0.113.200/"),
+            Err(UrlPolicyViolation::Ip("203.0.113.200".parse().unwrap()))
+        );
+        assert!(check(&policy, "https://[2001:db8::1]/").is_err());
+        // Names are only checked against CIDRs once resolved
+        assert!(check(&policy, "https://example.com/").is_ok());
+
+        assert!(!policy.ip_allowed("198.51.100.1".parse::<IpAddr>().unwrap()));
+    }
+
+    #[test]
+    fn test_ports() {
+        let policy = UrlPolicy {
+            allowed_ports: vec![443, 8443],
+            ..Default::default()
+        };
+
+        assert!(check(&policy, "https://example.com/").is_ok());
+        assert!(check(&policy, "https://example.com:8443/").is_ok());
+        assert_eq!(
+            check(&policy, "http://example.com/"),
+            Err(UrlPolicyViolation::Port(80))
+        );
+    }
+
+    #[test]
+    fn test_validation() {
+        let valid = UrlPolicy {
+            allowed_hostnames: vec!["*.example.com".to_owned(), "example-1.org".to_owned()],
+            allowed_ports: vec![443],
+            ..Default::default()
+        };
+        assert!(valid.validate().is_ok());
+
+        for hostname in ["", "*", "a.*.example.com", "example..com", "exa mple.com"] {
+            let policy = UrlPolicy {
+                blocked_hostnames: vec![hostname.to_owned()],
+                ..Default::default()
+            };
+            assert!(policy.validate().is_err(), "{hostname:?}");
+        }
+
+        let policy = UrlPolicy {
+            allowed_ports: vec![0],
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+    }
+}
@@ -4,20 +4,24 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     pin::Pin,
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     task::Poll,
     time::{Duration, Instant},
 };
 
 use axum::headers::{authorization::Credentials, Authorization};
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures::{future::BoxFuture, FutureExt};
 use hickory_resolver::{
     error::ResolveError, lookup_ip::LookupIpIntoIter, AsyncResolver, TokioAsyncResolver,
 };
 use http::{header::HeaderName, HeaderMap, HeaderValue, Method, Response, StatusCode, Version};
 use hyper::{
-    client::connect::{dns::Name, HttpConnector},
+    body::HttpBody as _,
+    client::connect::{dns::Name, Connected, Connection, HttpConnector},
     ext::HeaderCaseMap,
     Body, Client, Uri,
 };
@@ -25,13 +29,26 @@ use hyper_openssl::{HttpsConnector, MaybeHttpsStream};
 use hyper_proxy::{Intercept, Proxy, ProxyConnector as HttpProxyConnector, ProxyStream};
 use hyper_socks2::SocksConnector;
 use ipnet::IpNet;
-use openssl::ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslVerifyMode};
+use openssl::{
+    error::ErrorStack,
+    pkey::PKey,
+    ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslVerifyMode},
+    x509::X509,
+};
 use serde::Serialize;
 use thiserror::Error;
-use tokio::{net::TcpStream, sync::Mutex};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+    sync::Mutex,
+};
 use tower::Service;
 
-use crate::cfg::{ProxyAddr, ProxyConfig};
+use super::url_policy::{UrlPolicy, UrlPolicyViolation};
+use crate::{
+    cfg::{ProxyAddr, ProxyConfig},
+    metrics::WebhookClientMetrics,
+};
 
 pub type CaseSensitiveHeaderMap = HashMap<String, HeaderValue>;
 
@@ -42,22 +59,187 @@ pub enum Error {
 
     #[error("requests to this IP range are blocked (see the server configuration)")]
     BlockedIp,
+    #[error("{0}")]
+    UrlPolicy(#[from] UrlPolicyViolation),
     #[error("error resolving name: {0}")]
     Resolve(#[from] ResolveError),
 
     #[error("request timed out")]
     TimedOut,
 
+    #[error("invalid TLS configuration: {0}")]
+    InvalidTlsConfig(String),
+    #[error("invalid proxy configuration: {0}")]
+    InvalidProxyConfig(String),
+
     #[error("error forming request: {0}")]
     InvalidHttpRequest(http::Error),
     #[error("error making request: {0}")]
     FailedRequest(hyper::Error),
 }
 
+/// Maximum number of distinct per-endpoint client configurations to keep clients (and thus
+/// connection pools) around for. When exceeded, all of them are dropped and rebuilt on demand.
+const MAX_CUSTOM_CLIENTS: usize = 1000;
+
+type HttpClient = Client<SvixHttpsConnector, Body>;
+
+/// Client-side TLS settings for a single request, e.g. for endpoints requiring mutual TLS.
+///
+/// All values are PEM encoded.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ClientTlsConfig {
+    /// The client certificate, optionally followed by its intermediate certificates
+    pub client_cert: Option<Vec<u8>>,
+    /// The private key belonging to `client_cert`
+    pub client_key: Option<Vec<u8>>,
+    /// Additional CA certificates to trust when verifying the server
+    pub ca_bundle: Option<Vec<u8>>,
+}
+
+impl ClientTlsConfig {
+    fn apply(&self, ssl: &mut SslConnectorBuilder) -> Result<(), Error> {
+        fn certs(pem: &[u8], what: &str) -> Result<Vec<X509>, Error> {
+            let certs = X509::stack_from_pem(pem).map_err(|e| tls_err(what, e))?;
+            if certs.is_empty() {
+                return Err(Error::InvalidTlsConfig(format!(
+                    "{what}: no PEM encoded certificate found"
+                )));
+            }
+            Ok(certs)
+        }
+
+        fn tls_err(what: &str, e: ErrorStack) -> Error {
+            Error::InvalidTlsConfig(format!("{what}: {e}"))
+        }
+
+        if let Some(client_cert) = &self.client_cert {
+            let mut chain = certs(client_cert, "client certificate")?.into_iter();
+            if let Some(leaf) = chain.next() {
+                ssl.set_certificate(&leaf)
+                    .map_err(|e| tls_err("client certificate", e))?;
+            }
+            for intermediate in chain {
+                ssl.add_extra_chain_cert(intermediate)
+                    .map_err(|e| tls_err("client certificate", e))?;
+            }
+        }
+
+        if let Some(client_key) = &self.client_key {
+            let key =
+                PKey::private_key_from_pem(client_key).map_err(|e| tls_err("client key", e))?;
+            ssl.set_private_key(&key)
+                .map_err(|e| tls_err("client key", e))?;
+            ssl.check_private_key()
+                .map_err(|e| tls_err("client key", e))?;
+        }
+
+        if let Some(ca_bundle) = &self.ca_bundle {
+            for ca in certs(ca_bundle, "CA bundle")? {
+                ssl.cert_store_mut()
+                    .add_cert(ca)
+                    .map_err(|e| tls_err("CA bundle", e))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the certificates and key can be parsed and that the key matches the client
+    /// certificate.
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut ssl = SslConnector::builder(SslMethod::tls())
+            .map_err(|e| Error::InvalidTlsConfig(e.to_string()))?;
+        self.apply(&mut ssl)
+    }
+}
+
+/// HTTP version and connection pooling settings of a [`WebhookClient`].
+#[derive(Clone, Debug, Default)]
+pub struct HttpClientConfig {
+    /// Offer HTTP/2 through ALPN when connecting over TLS
+    pub http2_alpn: bool,
+    /// Hosts which are sent requests over HTTP/2 without negotiating it first
+    pub http2_prior_knowledge_hosts: Vec<String>,
+    /// Maximum number of idle connections to keep open per host (unlimited if unset)
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long to keep idle connections open for (hyper's default if unset)
+    pub pool_idle_timeout: Option<Duration>,
+}
+
+/// The per-request settings that need a dedicated [`HttpClient`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+struct ClientKey {
+    tls: Option<ClientTlsConfig>,
+    proxy: Option<ProxyConfig>,
+    url_policy: Option<UrlPolicy>,
+    http2_prior_knowledge: bool,
+}
+
+/// Everything needed to (re)build a [`SvixHttpsConnector`].
+struct ConnectorConfig {
+    dns_resolver: NonLocalDnsResolver,
+    dangerous_disable_tls_verification: bool,
+    proxy_config: Option<ProxyConfig>,
+    http: HttpClientConfig,
+}
+
+impl ConnectorConfig {
+    fn build_client(&self, key: &ClientKey) -> Result<HttpClient, Error> {
+        let proxy_config = key.proxy.as_ref().or(self.proxy_config.as_ref());
+
+        let mut dns_resolver = self.dns_resolver.clone();
+        // With a proxy, the resolver only ever sees the proxy's name. The proxy resolves the
+        // target itself, so only the checks done on the URL apply.
+        if proxy_config.is_none() {
+            dns_resolver.url_policy = key.url_policy.clone().map(Arc::new);
+        }
+
+        let mut http = HttpConnector::new_with_resolver(dns_resolver);
+        http.enforce_http(false);
+
+        // Openssl is required here -- in practice, rustls does not support many
+        // ciphers that we encounter on a regular basis:
+        let mut ssl = SslConnector::builder(SslMethod::tls()).expect("SslConnector build failed");
+        if self.dangerous_disable_tls_verification {
+            ssl.set_verify(SslVerifyMode::NONE);
+        }
+        if let Some(tls) = &key.tls {
+            tls.apply(&mut ssl)?;
+        }
+        if self.http.http2_alpn {
+            ssl.set_alpn_protos(b"\x02h2\x08http/1.1")
+                .expect("Setting ALPN protocols failed");
+        }
+
+        let https = SvixHttpsConnector::new(http, proxy_config, ssl)
+            .map_err(|e| Error::InvalidProxyConfig(e.to_string()))?;
+
+        let mut builder = Client::builder();
+        builder
+            .http1_ignore_invalid_headers_in_responses(true)
+            .http1_title_case_headers(true)
+            .http2_only(key.http2_prior_knowledge);
+        if let Some(max_idle) = self.http.pool_max_idle_per_host {
+            builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(idle_timeout) = self.http.pool_idle_timeout {
+            builder.pool_idle_timeout(idle_timeout);
+        }
+
+        Ok(builder.build(https))
+    }
+}
+
 #[derive(Clone)]
 pub struct WebhookClient {
-    client: Client<SvixHttpsConnector, Body>,
+    client: HttpClient,
+    /// Clients for requests with a custom [`ClientTlsConfig`], proxy or [`UrlPolicy`], keyed by
+    /// those settings
+    custom_clients: Arc<std::sync::Mutex<HashMap<ClientKey, HttpClient>>>,
+    connector_cfg: Arc<ConnectorConfig>,
     whitelist_nets: Arc<Vec<IpNet>>,
+    metrics: WebhookClientMetrics,
 }
 
 impl WebhookClient {
@@ -66,34 +248,75 @@ impl WebhookClient {
         whitelist_names: Option<Arc<Vec<String>>>,
         dangerous_disable_tls_verification: bool,
         proxy_config: Option<&ProxyConfig>,
+    ) -> Self {
+        Self::with_http_config(
+            whitelist_nets,
+            whitelist_names,
+            dangerous_disable_tls_verification,
+            proxy_config,
+            HttpClientConfig::default(),
+        )
+    }
+
+    pub fn with_http_config(
+        whitelist_nets: Option<Arc<Vec<IpNet>>>,
+        whitelist_names: Option<Arc<Vec<String>>>,
+        dangerous_disable_tls_verification: bool,
+        proxy_config: Option<&ProxyConfig>,
+        http: HttpClientConfig,
     ) -> Self {
         let whitelist_nets = whitelist_nets.unwrap_or_else(|| Arc::new(Vec::new()));
         let whitelist_names = whitelist_names.unwrap_or_else(|| Arc::new(Vec::new()));
 
-        let dns_resolver = NonLocalDnsResolver::new(whitelist_nets.clone(), whitelist_names);
-        let mut http = HttpConnector::new_with_resolver(dns_resolver);
-        http.enforce_http(false);
-
-        // Openssl is required here -- in practice, rustls does not support many
-        // ciphers that we encounter on a regular basis:
-        let mut ssl = SslConnector::builder(SslMethod::tls()).expect("SslConnector build failed");
         if dangerous_disable_tls_verification {
             tracing::warn!("TLS certificate verification has been disabled by the configuration.");
-            ssl.set_verify(SslVerifyMode::NONE);
         }
 
-        let https = SvixHttpsConnector::new(http, proxy_config, ssl)
-            .expect("SvixHttpsConnector build failed");
+        let connector_cfg = ConnectorConfig {
+            dns_resolver: NonLocalDnsResolver::new(whitelist_nets.clone(), whitelist_names),
+            dangerous_disable_tls_verification,
+            proxy_config: proxy_config.cloned(),
+            http,
+        };
 
-        let client: Client<_, hyper::Body> = Client::builder()
-            .http1_ignore_invalid_headers_in_responses(true)
-            .http1_title_case_headers(true)
-            .build(https);
+        let client = connector_cfg
+            .build_client(&ClientKey::default())
+            .expect("SvixHttpsConnector build failed");
 
         Self {
             client,
+            custom_clients: Default::default(),
+            connector_cfg: Arc::new(connector_cfg),
             whitelist_nets,
+            metrics: WebhookClientMetrics::new(&opentelemetry::global::meter("svix.com")),
+        }
+    }
+
+    fn client_for(&self, key: ClientKey) -> Result<HttpClient, Error> {
+        if key == ClientKey::default() {
+            return Ok(self.client.clone());
+        }
+
+        let mut custom_clients = self.custom_clients.lock().unwrap();
+        if let Some(client) = custom_clients.get(&key) {
+            return Ok(client.clone());
         }
+
+        if custom_clients.len() >= MAX_CUSTOM_CLIENTS {
+            custom_clients.clear();
+        }
+
+        let client = self.connector_cfg.build_client(&key)?;
+        custom_clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// A resolver for the hosts of connections made by other clients, such as sinks', applying the
+    /// same checks as to webhook URLs.
+    pub fn broker_resolver(&self, url_policy: Option<&UrlPolicy>) -> BrokerResolver {
+        let mut dns_resolver = self.connector_cfg.dns_resolver.clone();
+        dns_resolver.url_policy = url_policy.cloned().map(Arc::new);
+        BrokerResolver { dns_resolver }
     }
 
     pub async fn execute(&self, request: Request) -> Result<Response<Body>, Error> {
@@ -107,6 +330,26 @@ impl WebhookClient {
     ) -> BoxFuture<'_, Result<Response<Body>, Error>> {
         async move {
             let org_req = request.clone();
+            if let Some(url_policy) = &request.url_policy {
+                let url = url::Url::parse(&request.uri.to_string())
+                    .map_err(|_| Error::UrlPolicy(UrlPolicyViolation::MissingHost))?;
+                url_policy.check_url(&url)?;
+            }
+
+            let http2_prior_knowledge = request.uri.host().is_some_and(|host| {
+                self.connector_cfg
+                    .http
+                    .http2_prior_knowledge_hosts
+                    .iter()
+                    .any(|h| h.eq_ignore_ascii_case(host))
+            });
+
+            let client = self.client_for(ClientKey {
+                tls: request.tls.clone(),
+                proxy: request.proxy.clone(),
+                url_policy: request.url_policy.clone(),
+                http2_prior_knowledge,
+            })?;
             if let Some(auth) = request.uri.authority() {
                 if let Ok(ip) = auth.host().parse::<IpAddr>() {
                     if !is_allowed(ip)
@@ -144,31 +387,23 @@ impl WebhookClient {
 
             let start = Instant::now();
             let res = if let Some(timeout) = request.timeout {
-                match tokio::time::timeout(timeout, self.client.request(req)).await {
+                match tokio::time::timeout(timeout, client.request(req)).await {
                     Ok(Ok(resp)) => Ok(resp),
-                    Ok(Err(e)) => Err({
-                        if e.to_string()
-                            .contains("requests to this IP range are blocked")
-                        {
-                            Error::BlockedIp
-                        } else {
-                            Error::FailedRequest(e)
-                        }
-                    }),
+                    Ok(Err(e)) => Err(request_error(e)),
                     Err(_to) => Err(Error::TimedOut),
                 }
             } else {
-                self.client.request(req).await.map_err(|e| {
-                    if e.to_string()
-                        .contains("requests to this IP range are blocked")
-                    {
-                        Error::BlockedIp
-                    } else {
-                        Error::FailedRequest(e)
-                    }
-                })
+                client.request(req).await.map_err(request_error)
             };
 
+            if let Ok(res) = &res {
+                let reused = res
+                    .extensions()
+                    .get::<ConnectionUsage>()
+                    .is_some_and(ConnectionUsage::mark_used);
+                self.metrics.record_request(res.version(), reused);
+            }
+
             if !retry {
                 return res;
             }
@@ -188,6 +423,42 @@ impl WebhookClient {
     }
 }
 
+/// Recovers the errors of the [`NonLocalDnsResolver`] from the connection errors they caused.
+fn request_error(e: hyper::Error) -> Error {
+    let mut source = std::error::Error::source(&e);
+    while let Some(err) = source {
+        if let Some(Error::UrlPolicy(violation)) = err.downcast_ref::<Error>() {
+            return Error::UrlPolicy(violation.clone());
+        }
+        source = err.source();
+    }
+
+    if e.to_string()
+        .contains("requests to this IP range are blocked")
+    {
+        Error::BlockedIp
+    } else {
+        Error::FailedRequest(e)
+    }
+}
+
+#[tracing::instrument(skip_all)]
+/// Reads a response body up to `limit` bytes, dropping the rest unread so a huge response is
+/// never held in memory.
+pub async fn read_body_bounded(mut body: Body, limit: usize) -> hyper::Result<Bytes> {
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        let remaining = limit - buf.len();
+        if chunk.len() >= remaining {
+            buf.extend_from_slice(&chunk[..remaining]);
+            break;
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf.freeze())
+}
+
 #[derive(Clone)]
 pub struct Request {
     method: Method,
@@ -197,6 +468,9 @@ pub struct Request {
     body: Option<Vec<u8>>,
     timeout: Option<Duration>,
     version: Version,
+    tls: Option<ClientTlsConfig>,
+    proxy: Option<ProxyConfig>,
+    url_policy: Option<UrlPolicy>,
 }
 
 pub struct RequestBuilder {
@@ -210,6 +484,9 @@ pub struct RequestBuilder {
     version: Option<Version>,
     timeout: Option<Duration>,
     basic_auth: Option<Vec<u8>>,
+    tls: Option<ClientTlsConfig>,
+    proxy: Option<ProxyConfig>,
+    url_policy: Option<UrlPolicy>,
 
     // Derived from body
     content_type: Option<HeaderValue>,
@@ -262,6 +539,9 @@ impl RequestBuilder {
             timeout: None,
             content_type: None,
             basic_auth: None,
+            tls: None,
+            proxy: None,
+            url_policy: None,
         }
     }
 
@@ -350,6 +630,24 @@ impl RequestBuilder {
         self.user_agent = Some(user_agent);
         self
     }
+
+    pub fn client_tls(mut self, tls: ClientTlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Sends the request through the given proxy instead of the client's default one
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Only lets the request through if both its URL and the IPs its host resolves to are
+    /// permitted by the policy
+    pub fn url_policy(mut self, url_policy: UrlPolicy) -> Self {
+        self.url_policy = Some(url_policy);
+        self
+    }
 }
 
 impl Default for RequestBuilder {
@@ -425,6 +723,9 @@ impl RequestBuilder {
             body: self.body,
             timeout: self.timeout,
             version: self.version.unwrap(),
+            tls: self.tls,
+            proxy: self.proxy,
+            url_policy: self.url_policy,
         })
     }
 }
@@ -457,12 +758,13 @@ impl SvixHttpsConnector {
                     let socks_https = HttpsConnector::with_connector(socks, ssl)?;
                     Ok(Self::Socks5Proxy(socks_https))
                 }
-                // In the HTTP proxy case, TLS is handled by the proxy connector
+                // In the HTTP proxy case, TLS is handled by the proxy connector, so it's given the
+                // same TLS settings (verification mode, client certificate, etc.)
                 ProxyAddr::Http(proxy_addr) => {
                     let proxy = Proxy::new(Intercept::All, proxy_addr);
-                    Ok(Self::HttpProxy(HttpProxyConnector::from_proxy(
-                        inner, proxy,
-                    )?))
+                    let mut connector = HttpProxyConnector::from_proxy(inner, proxy)?;
+                    connector.set_tls(Some(ssl.build()));
+                    Ok(Self::HttpProxy(connector))
                 }
             },
             None => {
@@ -474,7 +776,7 @@ impl SvixHttpsConnector {
 }
 
 impl Service<Uri> for SvixHttpsConnector {
-    type Response = ProxyStream<TcpStream>;
+    type Response = SvixConnection;
     type Error = Box<dyn std::error::Error + Send + Sync>;
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
 
@@ -500,17 +802,169 @@ impl Service<Uri> for SvixHttpsConnector {
         match self {
             Self::Regular(inner) => {
                 let fut = inner.call(req);
-                Box::pin(async move { Ok(convert_stream(fut.await?)) })
+                Box::pin(async move { Ok(convert_stream(fut.await?).into()) })
             }
             Self::Socks5Proxy(inner) => {
                 let fut = inner.call(req);
-                Box::pin(async move { Ok(convert_stream(fut.await?)) })
+                Box::pin(async move { Ok(convert_stream(fut.await?).into()) })
             }
             Self::HttpProxy(inner) => {
                 let fut = inner.call(req);
-                Box::pin(async move { fut.await.map_err(Into::into) })
+                Box::pin(async move { Ok(fut.await?.into()) })
+            }
+        }
+    }
+}
+
+/// Attached to every response, to tell whether it was received on a newly opened connection.
+#[derive(Clone, Debug, Default)]
+struct ConnectionUsage(Arc<AtomicBool>);
+
+impl ConnectionUsage {
+    /// Marks the connection as used, returning whether it already was
+    fn mark_used(&self) -> bool {
+        self.0.swap(true, Ordering::Relaxed)
+    }
+}
+
+/// A connection opened by [`SvixHttpsConnector`].
+pub struct SvixConnection {
+    inner: ProxyStream<TcpStream>,
+    usage: ConnectionUsage,
+}
+
+impl From<ProxyStream<TcpStream>> for SvixConnection {
+    fn from(inner: ProxyStream<TcpStream>) -> Self {
+        Self {
+            inner,
+            usage: ConnectionUsage::default(),
+        }
+    }
+}
+
+impl Connection for SvixConnection {
+    fn connected(&self) -> Connected {
+        let connected = self.inner.connected();
+        let connected = match &self.inner {
+            ProxyStream::Secured(stream)
+                if stream.ssl().selected_alpn_protocol() == Some(b"h2") =>
+            {
+                connected.negotiated_h2()
+            }
+            _ => connected,
+        };
+        connected.extra(self.usage.clone())
+    }
+}
+
+impl AsyncRead for SvixConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for SvixConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Resolves the hosts of connections made by other clients than the [`WebhookClient`], such as
+/// sinks', with the same checks as the hosts of webhook URLs: the organization's URL policy, and no
+/// private IPs unless whitelisted.
+///
+/// Connections must be made to the addresses it returns rather than to the host, as resolving it
+/// again may well give another IP.
+#[derive(Clone, Debug)]
+pub struct BrokerResolver {
+    dns_resolver: NonLocalDnsResolver,
+}
+
+impl BrokerResolver {
+    pub fn url_policy(&self) -> Option<&UrlPolicy> {
+        self.dns_resolver.url_policy.as_deref()
+    }
+
+    /// The address to connect to for a URL, which must have a port.
+    pub async fn resolve(&self, url: &url::Url) -> Result<SocketAddr, Error> {
+        if let Some(url_policy) = &self.dns_resolver.url_policy {
+            url_policy.check_url(url)?;
+        }
+
+        let host = match url.host() {
+            Some(url::Host::Domain(domain)) => domain.to_owned(),
+            Some(url::Host::Ipv4(ip)) => ip.to_string(),
+            Some(url::Host::Ipv6(ip)) => ip.to_string(),
+            None => return Err(UrlPolicyViolation::MissingHost.into()),
+        };
+        let port = url
+            .port_or_known_default()
+            .ok_or(UrlPolicyViolation::MissingHost)?;
+        let name = Name::from_str(&host).map_err(|_| UrlPolicyViolation::MissingHost)?;
+
+        let mut addrs = self.dns_resolver.clone().call(name).await?;
+        let addr = addrs.next().ok_or(Error::BlockedIp)?;
+        Ok(SocketAddr::new(addr.ip(), port))
+    }
+
+    /// Checks a host and port against the URL policy, for clients which resolve some hosts
+    /// themselves. The IPs the host resolves to must then be checked with [`Self::ip_allowed`].
+    pub fn check_host(&self, host: &str, port: u16) -> Result<(), Error> {
+        if let Some(url_policy) = &self.dns_resolver.url_policy {
+            if !url_policy.host_allowed(host) {
+                return Err(UrlPolicyViolation::Hostname(host.to_owned()).into());
+            }
+            if !url_policy.port_allowed(port) {
+                return Err(UrlPolicyViolation::Port(port).into());
             }
         }
+        Ok(())
+    }
+
+    /// Whether connections may be made to an IP the host resolved to.
+    pub fn ip_allowed(&self, host: &str, ip: IpAddr) -> bool {
+        let resolver = &self.dns_resolver;
+        (is_allowed(ip)
+            || resolver.whitelist_nets.iter().any(|net| net.contains(&ip))
+            || resolver.whitelist_names.iter().any(|name| name == host))
+            && resolver
+                .url_policy
+                .as_ref()
+                .map_or(true, |policy| policy.ip_allowed(ip))
     }
 }
 
@@ -521,12 +975,14 @@ type NonLocalHttpConnector = HttpConnector<NonLocalDnsResolver>;
 
 /// A DNS resolver that produces an error for names that resolve to private IPs.
 ///
-/// Specific private subnets or domain names may be whitelisted.
+/// Specific private subnets or domain names may be whitelisted. An organization's [`UrlPolicy`]
+/// may further restrict the IPs names can resolve to.
 #[derive(Clone, Debug)]
 struct NonLocalDnsResolver {
     state: Arc<Mutex<DnsState>>,
     whitelist_nets: Arc<Vec<IpNet>>,
     whitelist_names: Arc<Vec<String>>,
+    url_policy: Option<Arc<UrlPolicy>>,
 }
 
 #[derive(Clone, Debug)]
@@ -541,6 +997,7 @@ impl NonLocalDnsResolver {
             state: Arc::new(Mutex::new(DnsState::Init)),
             whitelist_nets,
             whitelist_names,
+            url_policy: None,
         }
     }
 }
@@ -558,6 +1015,7 @@ impl Service<Name> for NonLocalDnsResolver {
         let resolver = self.clone();
         let whitelist_nets = self.whitelist_nets.clone();
         let whitelist_names = self.whitelist_names.clone();
+        let url_policy = self.url_policy.clone();
 
         Box::pin(async move {
             let mut lock = resolver.state.lock().await;
@@ -580,19 +1038,30 @@ impl Service<Name> for NonLocalDnsResolver {
 
             let lookup = resolver.lookup_ip(name.as_str()).await?;
 
-            if lookup.iter().all(|ip| {
-                !is_allowed(ip)
-                    && !whitelist_nets.iter().any(|subnet| subnet.contains(&ip))
-                    && !whitelisted_name
-            }) {
-                Err(Error::BlockedIp)
-            } else {
-                Ok(SocketAddrs {
-                    iter: lookup.into_iter(),
-                    whitelist_nets,
-                    whitelisted_name,
-                })
+            let globally_allowed = |ip: &IpAddr| {
+                is_allowed(*ip)
+                    || whitelist_nets.iter().any(|subnet| subnet.contains(ip))
+                    || whitelisted_name
+            };
+
+            let Some(ip) = lookup.iter().find(globally_allowed) else {
+                return Err(Error::BlockedIp);
+            };
+            if let Some(url_policy) = &url_policy {
+                if !lookup
+                    .iter()
+                    .any(|ip| globally_allowed(&ip) && url_policy.ip_allowed(ip))
+                {
+                    return Err(UrlPolicyViolation::Ip(ip).into());
+                }
             }
+
+            Ok(SocketAddrs {
+                iter: lookup.into_iter(),
+                whitelist_nets,
+                whitelisted_name,
+                url_policy,
+            })
         })
     }
 }
@@ -601,6 +1070,7 @@ pub struct SocketAddrs {
     iter: LookupIpIntoIter,
     whitelist_nets: Arc<Vec<IpNet>>,
     whitelisted_name: bool,
+    url_policy: Option<Arc<UrlPolicy>>,
 }
 
 impl Iterator for SocketAddrs {
@@ -610,12 +1080,16 @@ impl Iterator for SocketAddrs {
         loop {
             match self.iter.next() {
                 Some(ip_addr) => {
-                    if is_allowed(ip_addr)
+                    if (is_allowed(ip_addr)
                         || self
                             .whitelist_nets
                             .iter()
                             .any(|subnet| subnet.contains(&ip_addr))
-                        || self.whitelisted_name
+                        || self.whitelisted_name)
+                        && self
+                            .url_policy
+                            .as_ref()
+                            .map_or(true, |policy| policy.ip_allowed(ip_addr))
                     {
                         return Some(SocketAddr::from((ip_addr, 0)));
                     }
@@ -699,7 +1173,17 @@ mod tests {
     use http::{HeaderValue, Method, Version};
     use ipnet::IpNet;
 
-    use super::{is_allowed, CaseSensitiveHeaderMap, RequestBuilder, WebhookClient};
+    use super::{
+        is_allowed, CaseSensitiveHeaderMap, ClientTlsConfig, ConnectionUsage, Error,
+        HttpClientConfig, RequestBuilder, WebhookClient,
+    };
+    use crate::core::url_policy::{UrlPolicy, UrlPolicyViolation};
+
+    fn static_dir() -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", "static"]
+            .iter()
+            .collect()
+    }
 
     #[test]
     fn is_allowed_test() {
@@ -907,4 +1391,261 @@ mod tests {
         let whc_without_validation = WebhookClient::new(Some(whitelist), None, true, None);
         assert!(whc_without_validation.execute(request).await.is_ok());
     }
+
+    #[test]
+    fn test_client_tls_config_validation() {
+        let dir = static_dir();
+        let cert = std::fs::read(dir.join("ex_cert.pem")).unwrap();
+        let key = std::fs::read(dir.join("ex_key.pem")).unwrap();
+
+        ClientTlsConfig::default().validate().unwrap();
+
+        ClientTlsConfig {
+            client_cert: Some(cert.clone()),
+            client_key: Some(key.clone()),
+            ca_bundle: Some(cert.clone()),
+        }
+        .validate()
+        .unwrap();
+
+        assert!(ClientTlsConfig {
+            client_cert: Some(b"not a certificate".to_vec()),
+            client_key: Some(key),
+            ca_bundle: None,
+        }
+        .validate()
+        .is_err());
+
+        assert!(ClientTlsConfig {
+            client_cert: Some(cert.clone()),
+            client_key: Some(cert),
+            ca_bundle: None,
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate() {
+        use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+
+        // The same self-signed certificate is used by both sides: as the server certificate, and
+        // as the client certificate which the server trusts as its own CA.
+        let dir = static_dir();
+        let mut tls_builder = SslAcceptor::mozilla_modern_v5(SslMethod::tls()).unwrap();
+        tls_builder
+            .set_certificate_file(dir.join("ex_cert.pem"), SslFiletype::PEM)
+            .unwrap();
+        tls_builder
+            .set_private_key_file(dir.join("ex_key.pem"), SslFiletype::PEM)
+            .unwrap();
+        tls_builder.set_ca_file(dir.join("ex_cert.pem")).unwrap();
+        tls_builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        let acceptor = OpenSSLAcceptor::new(OpenSSLConfig::try_from(tls_builder).unwrap());
+
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://{}/", tcp.local_addr().unwrap());
+
+        let app = Router::new().route("/", routing::any(|| async { "Hello" }));
+
+        let _jh = tokio::spawn(async {
+            axum_server::from_tcp(tcp)
+                .acceptor(acceptor)
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        });
+
+        let request = || {
+            RequestBuilder::new()
+                .method(Method::GET)
+                .uri_str(&url)
+                .unwrap()
+                .version(Version::HTTP_11)
+        };
+
+        let whitelist = Arc::new(vec![IpNet::new("127.0.0.1".parse().unwrap(), 0).unwrap()]);
+        // The server's certificate is issued for `localhost`, so skip verifying it -- this test is
+        // only concerned with the client side of the handshake.
+        let client = WebhookClient::new(Some(whitelist), None, true, None);
+
+        // Without a client certificate the server rejects the connection
+        assert!(client.execute(request().build().unwrap()).await.is_err());
+
+        let tls = ClientTlsConfig {
+            client_cert: Some(std::fs::read(dir.join("ex_cert.pem")).unwrap()),
+            client_key: Some(std::fs::read(dir.join("ex_key.pem")).unwrap()),
+            ca_bundle: None,
+        };
+        let res = client
+            .execute(request().client_tls(tls).build().unwrap())
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+    }
+
+    #[tokio::test]
+    async fn test_url_policy() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = tcp.local_addr().unwrap().port();
+
+        let app = Router::new().route("/", routing::any(|| async { "Hello" }));
+        let _jh = tokio::spawn(async {
+            axum::Server::from_tcp(tcp)
+                .unwrap()
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        });
+
+        let whitelist = Arc::new(vec![IpNet::new("127.0.0.1".parse().unwrap(), 0).unwrap()]);
+        let client = &WebhookClient::new(Some(whitelist), None, false, None);
+
+        let execute = move |host: &str, url_policy: Option<UrlPolicy>| {
+            let mut req = RequestBuilder::new()
+                .method(Method::GET)
+                .uri_str(&format!("http://{host}:{port}/"))
+                .unwrap()
+                .version(Version::HTTP_11);
+            if let Some(url_policy) = url_policy {
+                req = req.url_policy(url_policy);
+            }
+            client.execute(req.build().unwrap())
+        };
+
+        let loopback = "127.0.0.1".parse().unwrap();
+        let block_loopback = UrlPolicy {
+            blocked_cidrs: vec!["127.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        };
+
+        assert!(execute("127.0.0.1", None).await.is_ok());
+        assert!(execute("127.0.0.1", Some(UrlPolicy::default()))
+            .await
+            .is_ok());
+
+        // IP literals are checked before connecting
+        assert!(matches!(
+            execute("127.0.0.1", Some(block_loopback.clone())).await,
+            Err(Error::UrlPolicy(UrlPolicyViolation::Ip(ip))) if ip == loopback
+        ));
+
+        // Names are checked once resolved
+        assert!(execute("localhost", None).await.is_ok());
+        assert!(matches!(
+            execute("localhost", Some(block_loopback)).await,
+            Err(Error::UrlPolicy(UrlPolicyViolation::Ip(ip))) if ip == loopback
+        ));
+
+        let ports = UrlPolicy {
+            allowed_ports: vec![443],
+            ..Default::default()
+        };
+        assert!(matches!(
+            execute("127.0.0.1", Some(ports)).await,
+            Err(Error::UrlPolicy(UrlPolicyViolation::Port(p))) if p == port
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_broker_resolver() {
+        let loopback: IpAddr = "127.0.0.1".parse().unwrap();
+        let url = |host: &str| url::Url::parse(&format!("redis://{host}:6379")).unwrap();
+
+        // Private IPs are blocked unless whitelisted
+        let client = WebhookClient::new(None, None, false, None);
+        let resolver = client.broker_resolver(None);
+        assert!(matches!(
+            resolver.resolve(&url("127.0.0.1")).await,
+            Err(Error::BlockedIp)
+        ));
+        assert!(matches!(
+            resolver.resolve(&url("localhost")).await,
+            Err(Error::BlockedIp)
+        ));
+        assert!(!resolver.ip_allowed("localhost", loopback));
+
+        let whitelist = Arc::new(vec![IpNet::new(loopback, 8).unwrap()]);
+        let client = WebhookClient::new(Some(whitelist), None, false, None);
+        let resolver = client.broker_resolver(None);
+        // The address to connect to, with the port of the URL
+        assert_eq!(
+            resolver.resolve(&url("127.0.0.1")).await.unwrap(),
+            "127.0.0.1:6379".parse().unwrap()
+        );
+        assert!(resolver.check_host("localhost", 6379).is_ok());
+        assert!(resolver.ip_allowed("localhost", loopback));
+
+        let policy = UrlPolicy {
+            blocked_cidrs: vec!["127.0.0.0/8".parse().unwrap()],
+            allowed_ports: vec![6380],
+            ..Default::default()
+        };
+        let resolver = client.broker_resolver(Some(&policy));
+        assert!(matches!(
+            resolver.resolve(&url("localhost")).await,
+            Err(Error::UrlPolicy(UrlPolicyViolation::Port(6379)))
+        ));
+        assert!(matches!(
+            resolver.check_host("localhost", 6379),
+            Err(Error::UrlPolicy(UrlPolicyViolation::Port(6379)))
+        ));
+        assert!(!resolver.ip_allowed("localhost", loopback));
+    }
+
+    #[tokio::test]
+    async fn test_http2_and_connection_reuse() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", tcp.local_addr().unwrap());
+
+        // Serves both HTTP/1.1 and HTTP/2 with prior knowledge
+        let app = Router::new().route("/", routing::any(|| async { "Hello" }));
+        let _jh = tokio::spawn(async {
+            axum::Server::from_tcp(tcp)
+                .unwrap()
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        });
+
+        let whitelist = Arc::new(vec![IpNet::new("127.0.0.1".parse().unwrap(), 0).unwrap()]);
+        let request = || {
+            RequestBuilder::new()
+                .method(Method::GET)
+                .uri_str(&url)
+                .unwrap()
+                .version(Version::HTTP_11)
+                .build()
+                .unwrap()
+        };
+        let usage = |res: &http::Response<hyper::Body>| {
+            res.extensions().get::<ConnectionUsage>().unwrap().clone()
+        };
+
+        for (prior_knowledge_hosts, version) in [
+            (vec![], Version::HTTP_11),
+            (vec!["127.0.0.1".to_owned()], Version::HTTP_2),
+        ] {
+            let client = WebhookClient::with_http_config(
+                Some(whitelist.clone()),
+                None,
+                false,
+                None,
+                HttpClientConfig {
+                    http2_prior_knowledge_hosts: prior_knowledge_hosts,
+                    ..Default::default()
+                },
+            );
+
+            let res = client.execute(request()).await.unwrap();
+            assert_eq!(res.version(), version);
+            let first = usage(&res);
+            // Reading the body to the end releases the connection back to the pool
+            hyper::body::to_bytes(res.into_body()).await.unwrap();
+
+            let second = client.execute(request()).await.unwrap();
+            assert_eq!(second.version(), version);
+            assert!(Arc::ptr_eq(&first.0, &usage(&second).0));
+        }
+    }
 }
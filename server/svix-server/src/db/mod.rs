@@ -2,8 +2,8 @@
 // SPDX-License-Identifier: MIT
 
 use sea_orm::{
-    ColumnTrait, DatabaseConnection, DbBackend, DeleteResult, EntityTrait, QueryFilter,
-    SqlxPostgresConnector,
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, DbErr, DeleteResult, EntityTrait,
+    QueryFilter, SqlxPostgresConnector, Statement,
 };
 use sqlx::postgres::PgPoolOptions;
 
@@ -35,6 +35,27 @@ pub async fn run_migrations(cfg: &Configuration) {
     MIGRATIONS.run(&db).await.unwrap();
 }
 
+/// The version of the latest migration applied to the database, if any, and of the latest one
+/// known to this build.
+pub async fn migration_versions(db: &DatabaseConnection) -> Result<(Option<i64>, i64), DbErr> {
+    let applied = db
+        .query_one(Statement::from_string(
+            DbBackend::Postgres,
+            "SELECT max(version) AS version FROM _sqlx_migrations WHERE success",
+        ))
+        .await?
+        .map(|row| row.try_get::<Option<i64>>("", "version"))
+        .transpose()?
+        .flatten();
+    let latest = MIGRATIONS
+        .iter()
+        .map(|m| m.version)
+        .max()
+        .unwrap_or_default();
+
+    Ok((applied, latest))
+}
+
 /// Wipe an organization from existence in a way that ensures the operation can be tried again on
 /// failure.
 pub async fn wipe_org(cfg: &Configuration, org_id: OrganizationId) {
@@ -22,6 +22,11 @@ pub struct Model {
     pub name: String,
     pub rate_limit: Option<i32>,
     pub deleted: bool,
+    pub egress_proxy: Option<String>,
+    /// In days, overriding the organization's retention policy
+    pub retention_period: Option<i32>,
+    /// Deliveries to the application's endpoints are held while it's paused
+    pub paused: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -104,6 +109,7 @@ impl ActiveModel {
             created_at: Set(timestamp.into()),
             updated_at: Set(timestamp.into()),
             deleted: Set(false),
+            paused: Set(false),
             ..ActiveModelTrait::default()
         }
     }
@@ -0,0 +1,40 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use sea_orm::entity::prelude::*;
+
+use crate::core::types::{ApplicationId, OrganizationId};
+
+/// A change made to an organization's configuration through the API, as listed by the audit log
+/// API. Only ever inserted, and ordered by their ID.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "auditlog")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub created_at: DateTimeWithTimeZone,
+    pub org_id: OrganizationId,
+    /// The application the changed resource belongs to, if any
+    pub app_id: Option<ApplicationId>,
+    /// The subject of the token the change was made with
+    pub actor: String,
+    pub token_id: Option<String>,
+    pub action: String,
+    /// The path of the changed resource, relative to the API root
+    pub resource: String,
+    /// The fields of the resource that changed, before the change. Secrets are redacted.
+    pub before: Option<Json>,
+    /// The fields of the resource that changed, after the change. Secrets are redacted.
+    pub after: Option<Json>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
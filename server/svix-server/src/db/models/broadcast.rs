@@ -0,0 +1,74 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, ActiveValue::Set};
+
+use crate::core::{
+    broadcast::BroadcastSelector,
+    types::{ApplicationId, BaseId, BroadcastId, BroadcastStatus, OrganizationId},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "broadcast")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: BroadcastId,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    pub org_id: OrganizationId,
+    pub status: BroadcastStatus,
+    pub selector: BroadcastSelector,
+    /// The `MessageIn` as received, kept as text so the payload is sent as is
+    pub message_in: String,
+    /// The last application messages were created for
+    pub cursor: Option<ApplicationId>,
+    pub num_messages: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+#[axum::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        self.updated_at = Set(Utc::now().into());
+        Ok(self)
+    }
+}
+
+impl ActiveModel {
+    pub fn new(org_id: OrganizationId, selector: BroadcastSelector, message_in: String) -> Self {
+        let timestamp = Utc::now();
+        Self {
+            id: Set(BroadcastId::new(timestamp.into(), None)),
+            created_at: Set(timestamp.into()),
+            updated_at: Set(timestamp.into()),
+            org_id: Set(org_id),
+            status: Set(BroadcastStatus::Running),
+            selector: Set(selector),
+            message_in: Set(message_in),
+            cursor: Set(None),
+            num_messages: Set(0),
+        }
+    }
+}
+
+impl Entity {
+    pub fn secure_find(org_id: OrganizationId) -> Select<Entity> {
+        Self::find().filter(Column::OrgId.eq(org_id))
+    }
+
+    pub fn secure_find_by_id(org_id: OrganizationId, id: BroadcastId) -> Select<Entity> {
+        Self::secure_find(org_id).filter(Column::Id.eq(id))
+    }
+}
@@ -6,10 +6,15 @@ use sea_orm::{entity::prelude::*, ActiveValue::Set, Condition, IntoActiveModel};
 
 use super::endpointmetadata;
 use crate::{
-    core::types::{
-        ApplicationId, BaseId, EndpointHeaders, EndpointId, EndpointIdOrUid,
-        EndpointSecretInternal, EndpointUid, EventChannelSet, EventTypeNameSet,
-        ExpiringSigningKeys,
+    core::{
+        payload_filter::EndpointFilter,
+        sink::EndpointSink,
+        types::{
+            ApplicationId, BaseId, EndpointHeaders, EndpointId, EndpointIdOrUid,
+            EndpointOAuth2Config, EndpointSecretInternal, EndpointTlsConfig, EndpointUid,
+            EndpointVerificationStatus, EventChannelSet, EventTypeNameSet, ExpiringSigningKeys,
+            SchemaVersion,
+        },
     },
     error,
 };
@@ -35,6 +40,18 @@ pub struct Model {
     pub old_keys: Option<ExpiringSigningKeys>,
     pub channels: Option<EventChannelSet>,
     pub headers: Option<EndpointHeaders>,
+    pub tls: Option<EndpointTlsConfig>,
+    pub oauth2: Option<EndpointOAuth2Config>,
+    pub egress_proxy: Option<String>,
+    pub sink: Option<EndpointSink>,
+    pub filter: Option<EndpointFilter>,
+    pub schema_version: Option<SchemaVersion>,
+    /// Deliveries to the endpoint are held while it's paused
+    pub paused: bool,
+    /// Whether to send the trace context of messages in `traceparent` and `tracestate` headers
+    pub propagate_trace_context: bool,
+    /// Deliveries to endpoints which aren't verified are held while verification is enabled
+    pub verification_status: EndpointVerificationStatus,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -91,6 +108,9 @@ impl ActiveModel {
             created_at: Set(timestamp.into()),
             updated_at: Set(timestamp.into()),
             deleted: Set(false),
+            paused: Set(false),
+            propagate_trace_context: Set(false),
+            verification_status: Set(EndpointVerificationStatus::Verified),
             key: Set(key),
             ..ActiveModelTrait::default()
         }
@@ -10,8 +10,9 @@ use sea_orm::{entity::prelude::*, ActiveValue::Set};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    core::types::{
-        BaseId, EventTypeId, EventTypeName, FeatureFlag, FeatureFlagSet, OrganizationId,
+    core::{
+        schema_compat::breaking_changes,
+        types::{BaseId, EventTypeId, EventTypeName, FeatureFlag, FeatureFlagSet, OrganizationId},
     },
     json_wrapper,
 };
@@ -108,6 +109,45 @@ pub struct Schema(HashMap<String, Json>);
 json_wrapper!(Schema);
 
 impl Schema {
+    pub fn has_version(&self, version: &str) -> bool {
+        self.0.contains_key(version)
+    }
+
+    /// The versions from oldest to newest. Versions are compared numerically when they're
+    /// numbers, which they usually are.
+    fn versions(&self) -> Vec<&String> {
+        let mut versions: Vec<_> = self.0.keys().collect();
+        versions.sort_by_key(|version| (version.parse::<u64>().ok(), *version));
+        versions
+    }
+
+    /// The changes breaking backward compatibility made by this schema compared to `previous`,
+    /// the schema currently saved, by version.
+    ///
+    /// A changed version is checked against what it used to be, and an added version against
+    /// the version before it.
+    pub fn breaking_changes(&self, previous: Option<&Schema>) -> Vec<(String, String)> {
+        let versions = self.versions();
+        versions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, version)| {
+                let schema = &self.0[*version];
+                let baseline = match previous.and_then(|p| p.0.get(*version)) {
+                    Some(old) if old == schema => return None,
+                    Some(old) => old,
+                    None => &self.0[*versions.get(i.checked_sub(1)?)?],
+                };
+                Some(
+                    breaking_changes(baseline, schema)
+                        .into_iter()
+                        .map(|change| ((*version).clone(), change)),
+                )
+            })
+            .flatten()
+            .collect()
+    }
+
     pub fn example(&self) -> Option<&serde_json::Value> {
         self.0
             .get("1")
@@ -1,12 +1,14 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
+use std::collections::HashMap;
+
 use chrono::Utc;
-use sea_orm::{entity::prelude::*, ActiveValue::Set, Condition};
+use sea_orm::{entity::prelude::*, ActiveValue::Set, Condition, QuerySelect};
 
 use crate::core::types::{
-    ApplicationId, BaseId, EventChannelSet, EventTypeName, MessageId, MessageIdOrUid, MessageUid,
-    OrganizationId,
+    ApplicationId, BaseId, BroadcastId, EventChannelSet, EventTypeName, MessageId, MessageIdOrUid,
+    MessagePriority, MessageUid, OrganizationId, SchemaVersion, TraceContext,
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
@@ -23,6 +25,14 @@ pub struct Model {
     pub legacy_payload: Option<Json>,
     pub channels: Option<EventChannelSet>,
     pub expiration: DateTimeWithTimeZone,
+    pub deliver_at: Option<DateTimeWithTimeZone>,
+    pub cancelled_at: Option<DateTimeWithTimeZone>,
+    pub broadcast_id: Option<BroadcastId>,
+    pub schema_version: Option<SchemaVersion>,
+    /// The W3C trace context of the request which created the message
+    pub trace_context: Option<TraceContext>,
+    /// The priority of the queue the message's tasks are sent to, including retries and resends
+    pub priority: MessagePriority,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -89,4 +99,21 @@ impl Entity {
                 .add(Column::Uid.eq(id_or_uid)),
         )
     }
+
+    /// The priority of each of the messages, which the tasks rebuilt for them are queued with.
+    pub async fn priorities(
+        db: &impl ConnectionTrait,
+        ids: impl IntoIterator<Item = MessageId>,
+    ) -> Result<HashMap<MessageId, MessagePriority>, DbErr> {
+        Ok(Self::find()
+            .select_only()
+            .column(Column::Id)
+            .column(Column::Priority)
+            .filter(Column::Id.is_in(ids))
+            .into_tuple()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect())
+    }
 }
@@ -27,6 +27,8 @@ pub struct Model {
     pub trigger_type: MessageAttemptTriggerType,
     /// Response duration in milliseconds
     pub response_duration_ms: i64,
+    /// The key of the full response in the blob store, if it was too large for `response`
+    pub response_blob_key: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -14,6 +14,8 @@ pub struct Model {
     pub created_at: DateTimeWithTimeZone,
     pub payload: Vec<u8>,
     pub expiration: DateTimeWithTimeZone,
+    /// The key of the payload in the blob store, if it was offloaded there
+    pub blob_key: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -44,6 +46,14 @@ impl ActiveModel {
             ..ActiveModelTrait::default()
         }
     }
+
+    /// The content of a message whose payload was offloaded to the blob store.
+    pub fn new_offloaded(msg_id: MessageId, blob_key: String) -> Self {
+        Self {
+            blob_key: Set(Some(blob_key)),
+            ..Self::new(msg_id, vec![])
+        }
+    }
 }
 
 impl Entity {
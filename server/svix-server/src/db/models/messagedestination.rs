@@ -17,6 +17,9 @@ pub struct Model {
     pub endp_id: EndpointId,
     pub status: MessageStatus,
     pub next_attempt: Option<DateTimeWithTimeZone>,
+    /// Set while a worker processes the destination's task. A lease which expired means the
+    /// worker died before it was done, so the destination reaper queues the task again.
+    pub leased_until: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
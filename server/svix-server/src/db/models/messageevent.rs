@@ -0,0 +1,123 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, ActiveValue::Set, ConnectionTrait, Statement, TransactionTrait};
+
+use super::{message, messageattempt};
+use crate::core::types::{
+    ApplicationId, EndpointId, MessageAttemptId, MessageEventType, MessageId, MessageStatus,
+};
+
+/// A change in the state of a message, as listed by the events API. Only ever inserted, and
+/// ordered by the number they're given once committed.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "messageevent")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// Given once the event is committed, see [`Entity::sequence_committed`], and 0 until then
+    pub seq: i64,
+    pub created_at: DateTimeWithTimeZone,
+    pub app_id: ApplicationId,
+    pub msg_id: MessageId,
+    pub event_type: MessageEventType,
+    /// The endpoint of the attempt, for attempt events
+    pub endp_id: Option<EndpointId>,
+    pub attempt_id: Option<MessageAttemptId>,
+    /// The status of the attempt, for attempt events
+    pub status: Option<MessageStatus>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Arbitrary key of the advisory lock held while numbering events
+const SEQUENCE_LOCK_KEY: i64 = 0x6d73_6765_7665_6e74;
+
+impl Entity {
+    /// Gives up to `limit` of the committed events without a number the next ones, in the order
+    /// of their IDs. Returns how many were numbered.
+    ///
+    /// A single connection does so at a time, holding a lock until it commits, so events numbered
+    /// later always come after all the events numbered before: a reader which read up to some
+    /// number can't miss an event with a lower one, unlike with the IDs, which are taken before
+    /// the events are committed. Returns right away if another connection is already at it.
+    pub async fn sequence_committed(db: &DatabaseConnection, limit: u32) -> Result<u64, DbErr> {
+        let txn = db.begin().await?;
+        let backend = txn.get_database_backend();
+
+        let locked = txn
+            .query_one(Statement::from_sql_and_values(
+                backend,
+                "SELECT pg_try_advisory_xact_lock($1) AS locked",
+                [SEQUENCE_LOCK_KEY.into()],
+            ))
+            .await?
+            .map(|row| row.try_get::<bool>("", "locked"))
+            .transpose()?
+            .unwrap_or_default();
+        let mut numbered = 0;
+        if locked {
+            numbered = txn
+                .execute(Statement::from_sql_and_values(
+                    backend,
+                    r#"
+                UPDATE messageevent SET seq = numbered.seq
+                FROM (
+                    SELECT id, nextval('messageevent_seq') AS seq
+                    FROM (
+                        SELECT id FROM messageevent
+                        WHERE seq = 0
+                        ORDER BY id
+                        LIMIT $1
+                    ) AS unnumbered
+                ) AS numbered
+                WHERE messageevent.id = numbered.id
+            "#,
+                    [limit.into()],
+                ))
+                .await?
+                .rows_affected();
+        }
+
+        txn.commit().await?;
+        Ok(numbered)
+    }
+}
+
+impl ActiveModel {
+    pub fn created(msg: &message::Model) -> Self {
+        Self {
+            created_at: Set(Utc::now().into()),
+            app_id: Set(msg.app_id.clone()),
+            msg_id: Set(msg.id.clone()),
+            event_type: Set(MessageEventType::Created),
+            endp_id: Set(None),
+            attempt_id: Set(None),
+            status: Set(None),
+            ..Default::default()
+        }
+    }
+
+    pub fn attempted(app_id: ApplicationId, attempt: &messageattempt::Model) -> Self {
+        Self {
+            created_at: Set(Utc::now().into()),
+            app_id: Set(app_id),
+            msg_id: Set(attempt.msg_id.clone()),
+            event_type: Set(MessageEventType::Attempted),
+            endp_id: Set(Some(attempt.endp_id.clone())),
+            attempt_id: Set(Some(attempt.id.clone())),
+            status: Set(Some(attempt.status)),
+            ..Default::default()
+        }
+    }
+}
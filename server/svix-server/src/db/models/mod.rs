@@ -3,6 +3,8 @@
 
 pub mod application;
 pub mod applicationmetadata;
+pub mod auditlog;
+pub mod broadcast;
 pub mod endpoint;
 pub mod endpointmetadata;
 pub mod eventtype;
@@ -10,3 +12,6 @@ pub mod message;
 pub mod messageattempt;
 pub mod messagecontent;
 pub mod messagedestination;
+pub mod messageevent;
+pub mod orgretentionpolicy;
+pub mod orgurlpolicy;
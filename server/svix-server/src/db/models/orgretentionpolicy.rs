@@ -0,0 +1,60 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::Utc;
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, ActiveValue::Set};
+
+use crate::core::types::OrganizationId;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "orgretentionpolicy")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub org_id: OrganizationId,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    /// In days, used for the applications which don't set their own
+    pub retention_period: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+#[axum::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        self.updated_at = Set(Utc::now().into());
+        Ok(self)
+    }
+}
+
+impl ActiveModel {
+    pub fn new(org_id: OrganizationId, retention_period: i32) -> Self {
+        let timestamp = Utc::now();
+        Self {
+            org_id: Set(org_id),
+            created_at: Set(timestamp.into()),
+            updated_at: Set(timestamp.into()),
+            retention_period: Set(retention_period),
+        }
+    }
+}
+
+impl Entity {
+    pub fn upsert(am: ActiveModel) -> sea_orm::Insert<ActiveModel> {
+        Self::insert(am).on_conflict(
+            OnConflict::column(Column::OrgId)
+                .update_columns([Column::RetentionPeriod, Column::UpdatedAt])
+                .to_owned(),
+        )
+    }
+}
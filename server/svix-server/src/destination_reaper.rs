@@ -0,0 +1,294 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Queues again the tasks of message destinations lost with the worker processing them, e.g. when
+//! it crashed between updating a destination and queueing its next attempt. Workers lease the
+//! destinations of the tasks they consume, so only those whose lease expired are queued again,
+//! rather than every destination a backed up queue is still holding a task for.
+//!
+//! It also releases the destinations left held although their endpoint was resumed, e.g. when a
+//! worker held one from a stale cached copy of the endpoint after the release already ran.
+
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use chrono::{SubsecRound, Utc};
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, ConnectionTrait, DatabaseConnection, DbErr, Statement,
+};
+
+use crate::{
+    core::{
+        pause::release_held_destinations,
+        types::{
+            ApplicationId, EndpointId, EndpointVerificationStatus, MessageAttemptTriggerType,
+            MessageEndpointId, MessageId, MessagePriority, MessageStatus,
+        },
+    },
+    error::Result,
+    expired_message_cleaner::sleep_unless_shutting_down,
+    metrics::DestinationReaperMetrics,
+    queue::{MessageTask, QueueTask, TaskQueueProducer},
+    worker::release_destination_lease,
+};
+
+type DbResult<T> = std::result::Result<T, DbErr>;
+
+/// How long the destinations claimed by a run are kept from other runs while their tasks are
+/// queued
+const CLAIM_LEASE_SECS: i64 = 60;
+
+/// Claims up to `limit` of the destinations whose lease expired before `now`, i.e. whose worker
+/// died while processing their task, returning the tasks to queue for those still pending or
+/// sending. Their lease is moved forward to `claimed_until` as they're claimed, so another run
+/// doesn't queue them too, and only released once their task is queued: those left claimed when
+/// queueing fails are claimed again after it expires.
+async fn claim_lost_destinations(
+    pool: &DatabaseConnection,
+    now: DateTimeWithTimeZone,
+    claimed_until: DateTimeWithTimeZone,
+    limit: u32,
+) -> DbResult<Vec<(MessageEndpointId, Option<QueueTask>)>> {
+    let stmt = Statement::from_sql_and_values(
+        pool.get_database_backend(),
+        r#"
+        WITH lost AS (
+            SELECT id FROM messagedestination
+            WHERE leased_until < $1
+            ORDER BY leased_until
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE messagedestination AS dest
+        SET leased_until = $4, updated_at = now()
+        FROM lost, message
+        WHERE dest.id = lost.id AND message.id = dest.msg_id
+        RETURNING dest.id, dest.msg_id, dest.endp_id, dest.status, message.app_id,
+            message.priority, (
+                SELECT count(*) FROM messageattempt
+                WHERE messageattempt.msg_dest_id = dest.id AND messageattempt.trigger_type = $3
+            ) AS attempt_count
+    "#,
+        [
+            now.into(),
+            limit.into(),
+            i16::from(MessageAttemptTriggerType::Scheduled).into(),
+            claimed_until.into(),
+        ],
+    );
+
+    pool.query_all(stmt)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let dest_id = row.try_get::<MessageEndpointId>("", "id")?;
+
+            // The worker may have died after it was done with the destination
+            let status = row.try_get::<MessageStatus>("", "status")?;
+            if status != MessageStatus::Pending && status != MessageStatus::Sending {
+                return Ok((dest_id, None));
+            }
+
+            // Picks up the retry schedule where the previous attempts left it
+            let attempt_count = row.try_get::<i64>("", "attempt_count")?;
+            let task = QueueTask::MessageV1(MessageTask {
+                msg_id: row.try_get::<MessageId>("", "msg_id")?,
+                app_id: row.try_get::<ApplicationId>("", "app_id")?,
+                endpoint_id: row.try_get::<EndpointId>("", "endp_id")?,
+                trigger_type: MessageAttemptTriggerType::Scheduled,
+                attempt_count: attempt_count.try_into().unwrap_or(u16::MAX),
+                priority: row.try_get::<MessagePriority>("", "priority")?,
+            });
+            Ok((dest_id, Some(task)))
+        })
+        .collect()
+}
+
+/// Queues again the tasks of the destinations whose worker died while processing them, `limit`
+/// at a time. Returns how many were queued.
+pub async fn requeue_lost_destinations(
+    pool: &DatabaseConnection,
+    queue_tx: &TaskQueueProducer,
+    limit: u32,
+    metrics: &DestinationReaperMetrics,
+) -> Result<u64> {
+    let now = Utc::now();
+    // Truncated to the database's precision, so the claim can be matched when released
+    let claimed_until: DateTimeWithTimeZone = (now + chrono::Duration::seconds(CLAIM_LEASE_SECS))
+        .trunc_subsecs(6)
+        .into();
+    let mut total = 0;
+
+    loop {
+        let claimed = claim_lost_destinations(pool, now.into(), claimed_until, limit).await?;
+        let claimed_count = claimed.len() as u64;
+        let mut count = 0;
+        for (dest_id, task) in &claimed {
+            if let Some(task) = task {
+                if let Err(err) = queue_tx.send(task, None).await {
+                    metrics.record_requeued(count);
+                    return Err(err);
+                }
+                count += 1;
+            }
+            release_destination_lease(pool, dest_id, claimed_until).await?;
+        }
+        metrics.record_requeued(count);
+        total += count;
+
+        if claimed_count < limit as u64 || crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+
+    Ok(total)
+}
+
+/// Finds up to `limit` of the endpoints after `after` with held destinations, although neither
+/// they nor their application are paused, and they're verified when verification is enabled.
+async fn find_stranded_held_endpoints(
+    pool: &DatabaseConnection,
+    verification_enabled: bool,
+    after: &str,
+    limit: u32,
+) -> DbResult<Vec<(ApplicationId, EndpointId)>> {
+    let stmt = Statement::from_sql_and_values(
+        pool.get_database_backend(),
+        r#"
+        SELECT endpoint.app_id, endpoint.id
+        FROM endpoint
+        JOIN application ON application.id = endpoint.app_id
+        WHERE
+            endpoint.id > $1
+            AND NOT endpoint.deleted
+            AND NOT endpoint.paused
+            AND NOT application.deleted
+            AND NOT application.paused
+            AND (NOT $2 OR endpoint.sink IS NOT NULL OR endpoint.verification_status = $3)
+            AND EXISTS (
+                SELECT 1 FROM messagedestination
+                WHERE messagedestination.endp_id = endpoint.id AND messagedestination.status = $4
+            )
+        ORDER BY endpoint.id
+        LIMIT $5
+    "#,
+        [
+            after.into(),
+            verification_enabled.into(),
+            i16::from(EndpointVerificationStatus::Verified).into(),
+            i16::from(MessageStatus::Paused).into(),
+            limit.into(),
+        ],
+    );
+
+    pool.query_all(stmt)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok((
+                row.try_get::<ApplicationId>("", "app_id")?,
+                row.try_get::<EndpointId>("", "id")?,
+            ))
+        })
+        .collect()
+}
+
+/// Releases the destinations still held for endpoints which were resumed, `limit` endpoints at a
+/// time. Returns how many were queued.
+pub async fn release_stranded_held_destinations(
+    pool: &DatabaseConnection,
+    queue_tx: &TaskQueueProducer,
+    verification_enabled: bool,
+    limit: u32,
+) -> Result<u64> {
+    let mut after = String::new();
+    let mut total = 0;
+
+    loop {
+        let endpoints =
+            find_stranded_held_endpoints(pool, verification_enabled, &after, limit).await?;
+        let count = endpoints.len() as u32;
+        for (app_id, endp_id) in endpoints {
+            after = endp_id.0.clone();
+            total += release_held_destinations(pool, queue_tx, &app_id, vec![endp_id]).await?;
+        }
+
+        if count < limit || crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+
+    Ok(total)
+}
+
+/// Periodically queues again the tasks lost with their worker, and releases the destinations
+/// left held for resumed endpoints.
+pub async fn destination_reaper_loop(
+    pool: &DatabaseConnection,
+    queue_tx: &TaskQueueProducer,
+    verification_enabled: bool,
+) -> Result<()> {
+    const INTERVAL: Duration = Duration::from_secs(5 * 60);
+    const ON_ERROR: Duration = Duration::from_secs(10);
+    const BATCH_SIZE: u32 = 1_000;
+
+    let metrics = DestinationReaperMetrics::new(&opentelemetry::global::meter("svix.com"));
+    loop {
+        let start = Instant::now();
+        let requeued = match requeue_lost_destinations(pool, queue_tx, BATCH_SIZE, &metrics).await {
+            Err(err) => {
+                tracing::error!("{}", err);
+                false
+            }
+            Ok(count) => {
+                if count > 0 {
+                    tracing::warn!(
+                        elapsed =? start.elapsed(),
+                        "queued {} destinations lost with their worker again",
+                        count,
+                    );
+                }
+                true
+            }
+        };
+
+        let start = Instant::now();
+        let released = match release_stranded_held_destinations(
+            pool,
+            queue_tx,
+            verification_enabled,
+            BATCH_SIZE,
+        )
+        .await
+        {
+            Err(err) => {
+                tracing::error!("{}", err);
+                false
+            }
+            Ok(count) => {
+                if count > 0 {
+                    tracing::warn!(
+                        elapsed =? start.elapsed(),
+                        "released {} destinations left held for resumed endpoints",
+                        count,
+                    );
+                }
+                true
+            }
+        };
+
+        let sleep_time = if requeued && released {
+            INTERVAL
+        } else {
+            ON_ERROR
+        };
+
+        if !sleep_unless_shutting_down(sleep_time).await {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -11,7 +11,10 @@ use sea_orm::{
     TransactionTrait, UpdateResult,
 };
 
-use crate::error::{Error, Result};
+use crate::{
+    core::blob_store::BlobStore,
+    error::{Error, Result},
+};
 
 type DbResult<T> = std::result::Result<T, DbErr>;
 
@@ -41,12 +44,13 @@ async fn query_one_without_timeout(
     Ok(res)
 }
 
-/// Nullifies the payload column for expired messages,
-/// `limit` sets how many rows to update at a time.
+/// Nullifies the payload column for expired messages, and deletes the offloaded payloads from the
+/// blob store. `limit` sets how many rows to update at a time.
 pub async fn clean_expired_messages(
     pool: &DatabaseConnection,
     limit: u32,
     enable_legacy_message_cleaner: bool,
+    blob_store: &BlobStore,
 ) -> DbResult<UpdateResult> {
     // See the docs for [`has_message_payloads_pending_expiry`] for background on the legacy cleaner.
     let legacy_row_count = if enable_legacy_message_cleaner {
@@ -85,13 +89,20 @@ pub async fn clean_expired_messages(
                 FOR UPDATE SKIP LOCKED
             )
         )
+        RETURNING blob_key
     "#,
         [limit.into()],
     );
-    let res = pool.execute(stmt).await?;
+    let rows = pool.query_all(stmt).await?;
+
+    let blob_keys = rows
+        .iter()
+        .map(|row| row.try_get::<Option<String>>("", "blob_key"))
+        .collect::<DbResult<Vec<_>>>()?;
+    blob_store.delete_all(blob_keys.into_iter().flatten()).await;
 
     Ok(UpdateResult {
-        rows_affected: legacy_row_count + res.rows_affected(),
+        rows_affected: legacy_row_count + rows.len() as u64,
     })
 }
 
@@ -117,10 +128,34 @@ async fn has_message_payloads_pending_expiry(pool: &DatabaseConnection) -> Resul
     .map_err(|e| Error::generic(format!("failed to check for message payloads: {e}")))
 }
 
+/// Sleeps for `duration`, returning `false` early if the process is shutting down.
+pub(crate) async fn sleep_unless_shutting_down(duration: Duration) -> bool {
+    let sleep_start = Instant::now();
+    let mut interval = tokio::time::interval(Duration::from_secs(10));
+    interval.tick().await;
+    // Doing a plain sleep() was fine when the polling frequency was mere seconds, but since we're doing wider
+    // periods now (hours, not seconds), we need to be a little more careful about not preventing the process
+    // from shutting down.
+    // Using `interval()` so we can track how long we've been sleeping for, while still checking for the
+    // shutdown signal.
+    loop {
+        if crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+            return false;
+        }
+        interval.tick().await;
+        if sleep_start.elapsed() > duration {
+            return true;
+        }
+    }
+}
+
 /// Polls the database for expired messages to nullify payloads for.
 ///
 /// Uses a variable polling schedule, based on affected row counts each iteration of the loop.
-pub async fn expired_message_cleaner_loop(pool: &DatabaseConnection) -> Result<()> {
+pub async fn expired_message_cleaner_loop(
+    pool: &DatabaseConnection,
+    blob_store: &BlobStore,
+) -> Result<()> {
     let message_table_needs_cleaning = has_message_payloads_pending_expiry(pool).await?;
     if !message_table_needs_cleaning {
         tracing::info!("No payloads pending expiry found in `message` table. Skipping the cleaner for this table.");
@@ -133,27 +168,15 @@ pub async fn expired_message_cleaner_loop(pool: &DatabaseConnection) -> Result<(
     let mut sleep_time = None;
     loop {
         if let Some(duration) = sleep_time {
-            let sleep_start = Instant::now();
-            let mut interval = tokio::time::interval(Duration::from_secs(10));
-            interval.tick().await;
-            // Doing a plain sleep() was fine when the polling frequency was mere seconds, but since we're doing wider
-            // periods now (hours, not seconds), we need to be a little more careful about not preventing the process
-            // from shutting down.
-            // Using `interval()` so we can track how long we've been sleeping for, while still checking for the
-            // shutdown signal.
-            'inner: loop {
-                if crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
-                    return Ok(());
-                }
-                interval.tick().await;
-                if sleep_start.elapsed() > duration {
-                    break 'inner;
-                }
+            if !sleep_unless_shutting_down(duration).await {
+                return Ok(());
             }
         }
 
         let start = Instant::now();
-        match clean_expired_messages(pool, BATCH_SIZE, message_table_needs_cleaning).await {
+        match clean_expired_messages(pool, BATCH_SIZE, message_table_needs_cleaning, blob_store)
+            .await
+        {
             Err(err) => {
                 tracing::error!("{}", err);
                 sleep_time = Some(ON_ERROR);
@@ -16,13 +16,16 @@ use std::{
 use aide::axum::ApiRouter;
 use cfg::ConfigurationInner;
 use opentelemetry_otlp::WithExportConfig;
-use opentelemetry_sdk::{metrics::SdkMeterProvider, runtime::Tokio};
+use opentelemetry_sdk::{
+    metrics::{reader::DefaultAggregationSelector, PeriodicReader, SdkMeterProvider},
+    runtime::Tokio,
+};
 use queue::TaskQueueProducer;
 use redis::RedisManager;
 use sea_orm::DatabaseConnection;
 use sentry::integrations::tracing::EventFilter;
 use svix_ksuid::{KsuidLike, KsuidMs};
-use tokio::net::TcpListener;
+use tokio::{net::TcpListener, sync::Notify};
 use tower::layer::layer_fn;
 use tower_http::{
     cors::{AllowHeaders, Any, CorsLayer},
@@ -33,25 +36,35 @@ use tracing_subscriber::{layer::SubscriberExt as _, Layer as _};
 use crate::{
     cfg::{CacheBackend, Configuration},
     core::{
+        blob_store::BlobStore,
         cache,
         cache::Cache,
         idempotency::IdempotencyService,
         operational_webhooks::{OperationalWebhookSender, OperationalWebhookSenderInner},
     },
     db::init_db,
+    destination_reaper::destination_reaper_loop,
     expired_message_cleaner::expired_message_cleaner_loop,
+    message_event_sequencer::message_event_sequencer_loop,
+    metrics::DbPoolMetrics,
+    retention_cleaner::retention_cleaner_loop,
+    table_cleaner::table_cleaner_loop,
     worker::queue_handler,
 };
 
 pub mod cfg;
 pub mod core;
 pub mod db;
+pub mod destination_reaper;
 pub mod error;
 pub mod expired_message_cleaner;
+pub mod message_event_sequencer;
 pub mod metrics;
 pub mod openapi;
 pub mod queue;
 pub mod redis;
+pub mod retention_cleaner;
+pub mod table_cleaner;
 pub mod v1;
 pub mod worker;
 
@@ -59,6 +72,8 @@ const CRATE_NAME: &str = env!("CARGO_CRATE_NAME");
 
 pub static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
 
+static SHUTDOWN_NOTIFY: LazyLock<Notify> = LazyLock::new(Notify::new);
+
 pub static INSTANCE_ID: LazyLock<String> =
     LazyLock::new(|| hex::encode(KsuidMs::new(None, None).to_string()));
 
@@ -83,10 +98,31 @@ async fn graceful_shutdown_handler() {
     tokio::select! {
         _ = ctrl_c => {},
         _ = sigterm => {},
+        // Shutting down was started without a signal
+        _ = wait_for_shutdown() => return,
     }
 
     tracing::info!("Received shutdown signal. Shutting down gracefully...");
-    SHUTTING_DOWN.store(true, Ordering::SeqCst)
+    start_shutdown();
+}
+
+/// Shuts down gracefully, as on receiving a shutdown signal: the API stops accepting connections
+/// and the worker stops taking tasks, while those in flight are given until the shutdown timeout.
+pub fn start_shutdown() {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    SHUTDOWN_NOTIFY.notify_waiters();
+}
+
+/// Resolves once the shutdown signal has been received.
+pub async fn wait_for_shutdown() {
+    let notified = SHUTDOWN_NOTIFY.notified();
+    tokio::pin!(notified);
+    // Registers as a waiter before checking the flag, so the notification can't be missed
+    notified.as_mut().enable();
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        return;
+    }
+    notified.await;
 }
 
 pub async fn run(cfg: Configuration) {
@@ -101,6 +137,7 @@ pub struct AppState {
     cfg: Configuration,
     cache: Cache,
     op_webhooks: OperationalWebhookSender,
+    blob_store: BlobStore,
 }
 
 // Made public for the purpose of E2E testing in which a queue prefix is necessary to avoid tests
@@ -113,6 +150,7 @@ pub async fn run_with_prefix(
     tracing::debug!("DB: Initializing pool");
     let pool = init_db(&cfg).await;
     tracing::debug!("DB: Started");
+    let _db_pool_metrics = DbPoolMetrics::new(&opentelemetry::global::meter("svix.com"), &pool);
 
     tracing::debug!("Cache: Initializing {:?}", cfg.cache_type);
     let cache_backend = cfg.cache_backend();
@@ -128,8 +166,12 @@ pub async fn run_with_prefix(
     };
     tracing::debug!("Cache: Started");
 
+    tracing::debug!("Blob store: Initializing {:?}", cfg.blob_store_type);
+    let blob_store = BlobStore::new(&cfg).expect("Error initializing blob store");
+    tracing::debug!("Blob store: Started");
+
     tracing::debug!("Queue: Initializing {:?}", cfg.queue_type);
-    let (queue_tx, queue_rx) = queue::new_pair(&cfg, prefix.as_deref()).await;
+    let (queue_tx, queue_lanes) = queue::new_lanes(&cfg, prefix.as_deref()).await;
     tracing::debug!("Queue: Started");
 
     let op_webhook_sender = OperationalWebhookSenderInner::new(
@@ -150,6 +192,7 @@ pub async fn run_with_prefix(
         cfg: cfg.clone(),
         cache: cache.clone(),
         op_webhooks: op_webhook_sender.clone(),
+        blob_store: blob_store.clone(),
     };
     let v1_router = v1::router().with_state::<()>(app_state);
 
@@ -180,8 +223,26 @@ pub async fn run_with_prefix(
     let with_api = cfg.api_enabled;
     let with_worker = cfg.worker_enabled;
     let listen_address = cfg.listen_address;
-
-    let (server, worker_loop, expired_message_cleaner_loop) = tokio::join!(
+    let shutdown_timeout = Duration::from_secs(cfg.graceful_shutdown_timeout_secs);
+    let reaper_queue_tx = queue_tx.clone();
+    let endpoint_verification_enabled = cfg.endpoint_verification_enabled;
+    let message_event_retention = Duration::from_secs(cfg.message_event_retention_secs);
+    let audit_log_retention = Duration::from_secs(cfg.audit_log_retention_secs);
+    let metrics_listen_address = cfg
+        .prometheus_metrics_enabled
+        .then_some(cfg.prometheus_listen_address);
+
+    let (
+        server,
+        metrics_server,
+        worker_loop,
+        expired_message_cleaner_loop,
+        retention_cleaner_loop,
+        destination_reaper_loop,
+        message_event_sequencer_loop,
+        message_event_cleaner_loop,
+        audit_log_cleaner_loop,
+    ) = tokio::join!(
         async {
             if with_api {
                 let listener = match listener {
@@ -193,16 +254,42 @@ pub async fn run_with_prefix(
                 tracing::debug!("API: Listening on {}", listener.local_addr().unwrap());
 
                 let incoming = hyper::server::conn::AddrIncoming::from_listener(listener)?;
-                axum::Server::builder(incoming)
+                let server = axum::Server::builder(incoming)
                     .serve(svc)
-                    .with_graceful_shutdown(graceful_shutdown_handler())
-                    .await
+                    .with_graceful_shutdown(graceful_shutdown_handler());
+                // Open connections get until the shutdown timeout to complete their requests
+                tokio::select! {
+                    res = server => res,
+                    _ = async {
+                        wait_for_shutdown().await;
+                        tokio::time::sleep(shutdown_timeout).await;
+                    } => {
+                        tracing::warn!("API: Shutdown timeout reached, closing open connections");
+                        Ok(())
+                    }
+                }
             } else {
                 tracing::debug!("API: off");
                 graceful_shutdown_handler().await;
                 Ok(())
             }
         },
+        async {
+            if let Some(metrics_listen_address) = metrics_listen_address {
+                let listener = TcpListener::bind(metrics_listen_address)
+                    .await
+                    .expect("Error binding to prometheus_listen_address");
+                tracing::debug!("Metrics: Listening on {}", listener.local_addr().unwrap());
+
+                let incoming = hyper::server::conn::AddrIncoming::from_listener(listener)?;
+                axum::Server::builder(incoming)
+                    .serve(metrics::prometheus::router().into_make_service())
+                    .with_graceful_shutdown(graceful_shutdown_handler())
+                    .await
+            } else {
+                Ok(())
+            }
+        },
         async {
             if with_worker {
                 tracing::debug!("Worker: Started");
@@ -211,8 +298,9 @@ pub async fn run_with_prefix(
                     cache.clone(),
                     pool.clone(),
                     queue_tx,
-                    queue_rx,
+                    queue_lanes,
                     op_webhook_sender,
+                    blob_store.clone(),
                 )
                 .await
             } else {
@@ -223,17 +311,70 @@ pub async fn run_with_prefix(
         async {
             if with_worker {
                 tracing::debug!("Expired message cleaner: Started");
-                expired_message_cleaner_loop(&pool).await
+                expired_message_cleaner_loop(&pool, &blob_store).await
             } else {
                 tracing::debug!("Expired message cleaner: off");
                 Ok(())
             }
+        },
+        async {
+            if with_worker {
+                tracing::debug!("Retention cleaner: Started");
+                retention_cleaner_loop(&pool, &blob_store).await
+            } else {
+                tracing::debug!("Retention cleaner: off");
+                Ok(())
+            }
+        },
+        async {
+            if with_worker {
+                tracing::debug!("Destination reaper: Started");
+                destination_reaper_loop(&pool, &reaper_queue_tx, endpoint_verification_enabled)
+                    .await
+            } else {
+                tracing::debug!("Destination reaper: off");
+                Ok(())
+            }
+        },
+        async {
+            // Run by the API too, as it creates events and serves them
+            if with_api || with_worker {
+                tracing::debug!("Message event sequencer: Started");
+                message_event_sequencer_loop(&pool).await
+            } else {
+                tracing::debug!("Message event sequencer: off");
+                Ok(())
+            }
+        },
+        async {
+            if with_worker {
+                tracing::debug!("Message event cleaner: Started");
+                table_cleaner_loop(&pool, "messageevent", message_event_retention).await
+            } else {
+                tracing::debug!("Message event cleaner: off");
+                Ok(())
+            }
+        },
+        async {
+            if with_worker {
+                tracing::debug!("Audit log cleaner: Started");
+                table_cleaner_loop(&pool, "auditlog", audit_log_retention).await
+            } else {
+                tracing::debug!("Audit log cleaner: off");
+                Ok(())
+            }
         }
     );
 
     server.expect("Error initializing server");
+    metrics_server.expect("Error initializing metrics server");
     worker_loop.expect("Error initializing worker");
-    expired_message_cleaner_loop.expect("Error initializing expired message cleaner")
+    expired_message_cleaner_loop.expect("Error initializing expired message cleaner");
+    retention_cleaner_loop.expect("Error initializing retention cleaner");
+    destination_reaper_loop.expect("Error initializing destination reaper");
+    message_event_sequencer_loop.expect("Error initializing message event sequencer");
+    message_event_cleaner_loop.expect("Error initializing message event cleaner");
+    audit_log_cleaner_loop.expect("Error initializing audit log cleaner")
 }
 
 pub fn setup_tracing(
@@ -333,29 +474,41 @@ pub fn setup_tracing(
 }
 
 pub fn setup_metrics(cfg: &ConfigurationInner) -> Option<SdkMeterProvider> {
-    cfg.opentelemetry_address.as_ref().map(|addr| {
-        let exporter = opentelemetry_otlp::new_exporter()
-            .tonic()
-            .with_endpoint(addr);
+    if cfg.opentelemetry_address.is_none() && !cfg.prometheus_metrics_enabled {
+        return None;
+    }
 
-        opentelemetry_otlp::new_pipeline()
-            .metrics(Tokio)
-            .with_delta_temporality()
-            .with_exporter(exporter)
-            .with_resource(opentelemetry_sdk::Resource::new(vec![
-                opentelemetry::KeyValue::new(
-                    "service.name",
-                    cfg.opentelemetry_service_name.clone(),
-                ),
-                opentelemetry::KeyValue::new("instance_id", INSTANCE_ID.to_owned()),
-                opentelemetry::KeyValue::new(
-                    "service.version",
-                    option_env!("GITHUB_SHA").unwrap_or("unknown"),
-                ),
-            ]))
-            .build()
-            .unwrap()
-    })
+    let mut builder =
+        SdkMeterProvider::builder().with_resource(opentelemetry_sdk::Resource::new(vec![
+            opentelemetry::KeyValue::new("service.name", cfg.opentelemetry_service_name.clone()),
+            opentelemetry::KeyValue::new("instance_id", INSTANCE_ID.to_owned()),
+            opentelemetry::KeyValue::new(
+                "service.version",
+                option_env!("GITHUB_SHA").unwrap_or("unknown"),
+            ),
+        ]));
+
+    if let Some(addr) = &cfg.opentelemetry_address {
+        let exporter = opentelemetry_otlp::MetricsExporterBuilder::from(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(addr),
+        )
+        .build_metrics_exporter(
+            Box::new(metrics::DeltaTemporalitySelector),
+            Box::new(DefaultAggregationSelector::new()),
+        )
+        .unwrap();
+        builder = builder.with_reader(PeriodicReader::builder(exporter, Tokio).build());
+    }
+
+    if cfg.prometheus_metrics_enabled {
+        builder = builder.with_reader(metrics::prometheus::exporter());
+    }
+
+    let provider = builder.build();
+    opentelemetry::global::set_meter_provider(provider.clone());
+    Some(provider)
 }
 
 pub fn setup_tracing_for_tests() {
@@ -0,0 +1,33 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Numbers the message events once they're committed, in the order the events API lists them. Each
+//! server runs it, though only one at a time numbers events, the others skipping their turn.
+
+use std::{sync::atomic::Ordering, time::Duration};
+
+use sea_orm::DatabaseConnection;
+
+use crate::{db::models::messageevent, error::Result};
+
+/// Numbers the committed message events as they come, so the feeds of the events API stay up to
+/// date without their reads writing anything.
+pub async fn message_event_sequencer_loop(pool: &DatabaseConnection) -> Result<()> {
+    const INTERVAL: Duration = Duration::from_millis(250);
+    const ON_ERROR: Duration = Duration::from_secs(10);
+    const BATCH_SIZE: u32 = 1_000;
+
+    while !crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+        match messageevent::Entity::sequence_committed(pool, BATCH_SIZE).await {
+            Err(err) => {
+                tracing::error!("Failed to number message events: {err}");
+                tokio::time::sleep(ON_ERROR).await;
+            }
+            // More are waiting, so they're numbered right away
+            Ok(count) if count == BATCH_SIZE as u64 => {}
+            Ok(_) => tokio::time::sleep(INTERVAL).await,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+
+use super::init_metric;
+
+#[derive(Clone)]
+pub struct CacheMetrics {
+    lookups: Option<Counter<u64>>,
+}
+
+impl CacheMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        let lookups = init_metric(
+            meter
+                .u64_counter("svix.cache.lookups")
+                .with_description("Cache lookups, by whether the key was found")
+                .try_init(),
+        );
+
+        Self { lookups }
+    }
+
+    pub fn record_lookup(&self, hit: bool) {
+        if let Some(recorder) = &self.lookups {
+            recorder.add(
+                1,
+                &[KeyValue::new("result", if hit { "hit" } else { "miss" })],
+            );
+        }
+    }
+}
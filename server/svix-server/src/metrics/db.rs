@@ -0,0 +1,52 @@
+use opentelemetry::{
+    metrics::{Meter, ObservableGauge},
+    KeyValue,
+};
+use sea_orm::DatabaseConnection;
+
+use super::init_metric;
+
+/// Usage of the database connection pool, observed whenever metrics are collected. The gauges
+/// are only observed for as long as this is kept alive.
+pub struct DbPoolMetrics {
+    _connections: Option<ObservableGauge<u64>>,
+    _max_connections: Option<ObservableGauge<u64>>,
+}
+
+impl DbPoolMetrics {
+    pub fn new(meter: &Meter, db: &DatabaseConnection) -> Self {
+        let pool = db.get_postgres_connection_pool().clone();
+        let connections = init_metric(
+            meter
+                .u64_observable_gauge("svix.db.pool.connections")
+                .with_description("Open database connections, by whether they're in use or idle")
+                .with_callback(move |observer| {
+                    let idle = pool.num_idle() as u64;
+                    let open = u64::from(pool.size());
+                    observer.observe(
+                        open.saturating_sub(idle),
+                        &[KeyValue::new("state", "in_use")],
+                    );
+                    observer.observe(idle, &[KeyValue::new("state", "idle")]);
+                })
+                .try_init(),
+        );
+
+        let max = db
+            .get_postgres_connection_pool()
+            .options()
+            .get_max_connections();
+        let max_connections = init_metric(
+            meter
+                .u64_observable_gauge("svix.db.pool.max_connections")
+                .with_description("Maximum number of connections of the database pool")
+                .with_callback(move |observer| observer.observe(max.into(), &[]))
+                .try_init(),
+        );
+
+        Self {
+            _connections: connections,
+            _max_connections: max_connections,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use opentelemetry::{
+    metrics::{Counter, Histogram, Meter, Unit},
+    KeyValue,
+};
+
+use super::init_metric;
+
+#[derive(Clone)]
+pub struct DeliveryMetrics {
+    attempts: Option<Counter<u64>>,
+    duration: Option<Histogram<f64>>,
+}
+
+impl DeliveryMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        let attempts = init_metric(
+            meter
+                .u64_counter("svix.delivery.attempts")
+                .with_description(
+                    "Delivery attempts made to endpoints, by class of the response status, or \
+                     `error` when no response was received",
+                )
+                .try_init(),
+        );
+
+        let duration = init_metric(
+            meter
+                .f64_histogram("svix.delivery.duration")
+                .with_description("Time taken by endpoints to respond to delivery attempts")
+                .with_unit(Unit::new("ms"))
+                .try_init(),
+        );
+
+        Self { attempts, duration }
+    }
+
+    /// Records an HTTP delivery attempt, with a `status_code` of 0 when it didn't get a response.
+    pub fn record_attempt(&self, status_code: i16, duration_ms: i64) {
+        let status_class = match status_code {
+            100..=199 => "1xx",
+            200..=299 => "2xx",
+            300..=399 => "3xx",
+            400..=499 => "4xx",
+            500..=599 => "5xx",
+            _ => "error",
+        };
+        let attributes = [KeyValue::new("status_class", status_class)];
+
+        if let Some(recorder) = &self.attempts {
+            recorder.add(1, &attributes);
+        }
+        if let Some(recorder) = &self.duration {
+            recorder.record(duration_ms as f64, &attributes);
+        }
+    }
+}
@@ -1,4 +1,14 @@
+use opentelemetry_sdk::metrics::{data::Temporality, reader::TemporalitySelector, InstrumentKind};
+
+mod cache;
+mod db;
+mod delivery;
+pub mod prometheus;
+mod reaper;
 mod redis;
+mod retention;
+mod tenant;
+mod webhook_client;
 
 pub fn init_metric<T, E: std::fmt::Debug>(result: Result<T, E>) -> Option<T> {
     match result {
@@ -10,4 +20,29 @@ pub fn init_metric<T, E: std::fmt::Debug>(result: Result<T, E>) -> Option<T> {
     }
 }
 
-pub use self::redis::{RedisQueueMetrics, RedisQueueType};
+/// Exports the changes since the last export for counters and histograms, as expected by our OTLP
+/// collectors. Prometheus keeps scraping cumulative values regardless.
+#[derive(Debug)]
+pub struct DeltaTemporalitySelector;
+
+impl TemporalitySelector for DeltaTemporalitySelector {
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        match kind {
+            InstrumentKind::Counter
+            | InstrumentKind::ObservableCounter
+            | InstrumentKind::Histogram => Temporality::Delta,
+            _ => Temporality::Cumulative,
+        }
+    }
+}
+
+pub use self::{
+    cache::CacheMetrics,
+    db::DbPoolMetrics,
+    delivery::DeliveryMetrics,
+    reaper::DestinationReaperMetrics,
+    redis::{RedisQueueMetrics, RedisQueueType},
+    retention::RetentionMetrics,
+    tenant::TenantMetrics,
+    webhook_client::WebhookClientMetrics,
+};
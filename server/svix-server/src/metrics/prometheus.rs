@@ -0,0 +1,386 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Exposes the server's metrics in the Prometheus text format, next to (or instead of) pushing
+//! them over OTLP. Both are fed by the same instruments.
+//!
+//! Metrics are collected on every scrape by a [`ManualReader`] registered with the meter provider,
+//! and encoded in the [text exposition format]. The metrics of the same name from different meters
+//! are merged into one family, since each may only be exposed once.
+//!
+//! [text exposition format]: https://prometheus.io/docs/instrumenting/exposition_formats/
+
+use std::{
+    borrow::Cow,
+    fmt::{self, Write as _},
+    sync::{Arc, RwLock, Weak},
+};
+
+use axum::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use indexmap::IndexMap;
+use opentelemetry::{metrics::Result as MetricsResult, KeyValue};
+use opentelemetry_sdk::{
+    metrics::{
+        data::{Gauge, Histogram, Metric, ResourceMetrics, Sum, Temporality},
+        reader::{AggregationSelector, MetricReader, TemporalitySelector},
+        Aggregation, InstrumentKind, ManualReader, Pipeline,
+    },
+    Resource,
+};
+
+const CONTENT_TYPE_TEXT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// The reader of the current meter provider's Prometheus exporter.
+static READER: RwLock<Option<Arc<ManualReader>>> = RwLock::new(None);
+
+/// A reader to add to the meter provider, whose metrics are then served by [`metrics_handler`].
+#[derive(Debug)]
+pub struct PrometheusExporter {
+    reader: Arc<ManualReader>,
+}
+
+pub fn exporter() -> PrometheusExporter {
+    let reader = Arc::new(ManualReader::builder().build());
+    *READER.write().unwrap() = Some(reader.clone());
+    PrometheusExporter { reader }
+}
+
+impl TemporalitySelector for PrometheusExporter {
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.reader.temporality(kind)
+    }
+}
+
+impl AggregationSelector for PrometheusExporter {
+    fn aggregation(&self, kind: InstrumentKind) -> Aggregation {
+        self.reader.aggregation(kind)
+    }
+}
+
+impl MetricReader for PrometheusExporter {
+    fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+        self.reader.register_pipeline(pipeline)
+    }
+
+    fn collect(&self, rm: &mut ResourceMetrics) -> MetricsResult<()> {
+        self.reader.collect(rm)
+    }
+
+    fn force_flush(&self) -> MetricsResult<()> {
+        self.reader.force_flush()
+    }
+
+    fn shutdown(&self) -> MetricsResult<()> {
+        self.reader.shutdown()
+    }
+}
+
+pub async fn metrics_handler() -> Response {
+    let Some(reader) = READER.read().unwrap().clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mut rm = ResourceMetrics {
+        resource: Resource::empty(),
+        scope_metrics: Vec::new(),
+    };
+    if let Err(e) = reader.collect(&mut rm) {
+        tracing::error!(error = ?e, "Failed to collect metrics");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    ([(CONTENT_TYPE, CONTENT_TYPE_TEXT)], encode(&rm)).into_response()
+}
+
+/// The router of the listener dedicated to metrics.
+pub fn router() -> Router {
+    Router::new().route("/metrics", get(metrics_handler))
+}
+
+/// Prometheus metric names only allow `[a-zA-Z0-9_:]`, and the unit is part of the name.
+fn metric_name(metric: &Metric) -> String {
+    let unit = match metric.unit.as_ref() {
+        "" | "1" => None,
+        "ms" => Some("milliseconds"),
+        "s" => Some("seconds"),
+        "By" => Some("bytes"),
+        unit => Some(unit),
+    };
+
+    let mut name = sanitize(&metric.name, ':');
+    if let Some(unit) = unit {
+        let unit = sanitize(unit, '_');
+        if !name.ends_with(&format!("_{unit}")) {
+            name = format!("{name}_{unit}");
+        }
+    }
+    name
+}
+
+/// Replaces the characters Prometheus doesn't allow in names with underscores. `extra` is the
+/// only other character allowed besides `[a-zA-Z0-9_]`.
+fn sanitize(name: &str, extra: char) -> String {
+    let mut name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == extra {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn escape_label_value(value: &str) -> Cow<'_, str> {
+    if value.contains(['\\', '"', '\n']) {
+        value
+            .replace('\\', r"\\")
+            .replace('"', r#"\""#)
+            .replace('\n', r"\n")
+            .into()
+    } else {
+        value.into()
+    }
+}
+
+/// Formats the attributes as labels, with `extra` appended (e.g. a histogram bucket's `le`).
+fn labels(attributes: &[KeyValue], extra: Option<(&str, &str)>) -> String {
+    let labels: Vec<String> = attributes
+        .iter()
+        .map(|kv| (sanitize(kv.key.as_str(), '_'), kv.value.as_str()))
+        .map(|(key, value)| format!("{key}=\"{}\"", escape_label_value(&value)))
+        .chain(extra.map(|(key, value)| format!("{key}=\"{value}\"")))
+        .collect();
+
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+/// A metric family: the samples of every metric of the same name.
+struct Family {
+    typ: &'static str,
+    help: String,
+    samples: String,
+}
+
+/// The metric families, in the order their first metric was collected.
+#[derive(Default)]
+struct Families(IndexMap<String, Family>);
+
+impl Families {
+    /// The samples of the family to add the metric's to, or `None` if there's already a family of
+    /// the name with another type.
+    fn samples(&mut self, name: &str, metric: &Metric, typ: &'static str) -> Option<&mut String> {
+        let family = self.0.entry(name.to_owned()).or_insert_with(|| Family {
+            typ,
+            help: metric.description.to_string(),
+            samples: String::new(),
+        });
+        if family.typ != typ {
+            tracing::debug!(
+                "Metric {} conflicts with a {} of the same name",
+                metric.name,
+                family.typ
+            );
+            return None;
+        }
+        Some(&mut family.samples)
+    }
+
+    fn write(&self, buf: &mut String) -> fmt::Result {
+        for (name, family) in &self.0 {
+            if !family.help.is_empty() {
+                let help = family.help.replace('\\', r"\\").replace('\n', r"\n");
+                writeln!(buf, "# HELP {name} {help}")?;
+            }
+            writeln!(buf, "# TYPE {name} {}", family.typ)?;
+            buf.push_str(&family.samples);
+        }
+        Ok(())
+    }
+}
+
+/// Encodes the metrics of every scope in the text format.
+fn encode(rm: &ResourceMetrics) -> String {
+    let mut families = Families::default();
+    let mut buf = String::new();
+    // Writing to a string can't fail
+    for metric in rm.scope_metrics.iter().flat_map(|scope| &scope.metrics) {
+        let _ = encode_metric(&mut families, metric);
+    }
+    let _ = families.write(&mut buf);
+    buf
+}
+
+fn encode_sum<T: fmt::Display>(
+    families: &mut Families,
+    metric: &Metric,
+    name: &str,
+    sum: &Sum<T>,
+) -> fmt::Result {
+    let (typ, name) = if sum.is_monotonic {
+        ("counter", format!("{name}_total"))
+    } else {
+        ("gauge", name.to_owned())
+    };
+    let Some(buf) = families.samples(&name, metric, typ) else {
+        return Ok(());
+    };
+    for point in &sum.data_points {
+        writeln!(
+            buf,
+            "{name}{} {}",
+            labels(&point.attributes, None),
+            point.value
+        )?;
+    }
+    Ok(())
+}
+
+fn encode_gauge<T: fmt::Display>(
+    families: &mut Families,
+    metric: &Metric,
+    name: &str,
+    gauge: &Gauge<T>,
+) -> fmt::Result {
+    let Some(buf) = families.samples(name, metric, "gauge") else {
+        return Ok(());
+    };
+    for point in &gauge.data_points {
+        writeln!(
+            buf,
+            "{name}{} {}",
+            labels(&point.attributes, None),
+            point.value
+        )?;
+    }
+    Ok(())
+}
+
+fn encode_histogram<T: fmt::Display>(
+    families: &mut Families,
+    metric: &Metric,
+    name: &str,
+    histogram: &Histogram<T>,
+) -> fmt::Result {
+    let Some(buf) = families.samples(name, metric, "histogram") else {
+        return Ok(());
+    };
+    for point in &histogram.data_points {
+        let mut cumulative = 0;
+        for (i, count) in point.bucket_counts.iter().enumerate() {
+            cumulative += count;
+            let le = point
+                .bounds
+                .get(i)
+                .map_or_else(|| "+Inf".to_owned(), ToString::to_string);
+            writeln!(
+                buf,
+                "{name}_bucket{} {cumulative}",
+                labels(&point.attributes, Some(("le", &le)))
+            )?;
+        }
+        let point_labels = labels(&point.attributes, None);
+        writeln!(buf, "{name}_sum{point_labels} {}", point.sum)?;
+        writeln!(buf, "{name}_count{point_labels} {}", point.count)?;
+    }
+    Ok(())
+}
+
+fn encode_metric(families: &mut Families, metric: &Metric) -> fmt::Result {
+    let name = metric_name(metric);
+    let data = metric.data.as_any();
+
+    if let Some(sum) = data.downcast_ref::<Sum<u64>>() {
+        encode_sum(families, metric, &name, sum)
+    } else if let Some(sum) = data.downcast_ref::<Sum<i64>>() {
+        encode_sum(families, metric, &name, sum)
+    } else if let Some(sum) = data.downcast_ref::<Sum<f64>>() {
+        encode_sum(families, metric, &name, sum)
+    } else if let Some(gauge) = data.downcast_ref::<Gauge<u64>>() {
+        encode_gauge(families, metric, &name, gauge)
+    } else if let Some(gauge) = data.downcast_ref::<Gauge<i64>>() {
+        encode_gauge(families, metric, &name, gauge)
+    } else if let Some(gauge) = data.downcast_ref::<Gauge<f64>>() {
+        encode_gauge(families, metric, &name, gauge)
+    } else if let Some(histogram) = data.downcast_ref::<Histogram<u64>>() {
+        encode_histogram(families, metric, &name, histogram)
+    } else if let Some(histogram) = data.downcast_ref::<Histogram<i64>>() {
+        encode_histogram(families, metric, &name, histogram)
+    } else if let Some(histogram) = data.downcast_ref::<Histogram<f64>>() {
+        encode_histogram(families, metric, &name, histogram)
+    } else {
+        tracing::debug!("Unsupported aggregation of metric {}", metric.name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::{metrics::MeterProvider as _, KeyValue};
+    use opentelemetry_sdk::{
+        metrics::{data::ResourceMetrics, reader::MetricReader, SdkMeterProvider},
+        Resource,
+    };
+
+    use super::{encode, PrometheusExporter};
+
+    #[test]
+    fn test_text_format() {
+        let reader = PrometheusExporter {
+            reader: std::sync::Arc::new(
+                opentelemetry_sdk::metrics::ManualReader::builder().build(),
+            ),
+        };
+        let reader_handle = reader.reader.clone();
+        let provider = SdkMeterProvider::builder().with_reader(reader).build();
+        let meter = provider.meter("test");
+
+        meter
+            .u64_counter("svix.test.attempts")
+            .init()
+            .add(2, &[KeyValue::new("status", "a\"b")]);
+        meter
+            .f64_histogram("svix.test.duration")
+            .with_unit(opentelemetry::metrics::Unit::new("ms"))
+            .init()
+            .record(7.0, &[]);
+        provider
+            .meter("other")
+            .u64_counter("svix.test.attempts")
+            .init()
+            .add(3, &[KeyValue::new("status", "other")]);
+
+        let mut rm = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: Vec::new(),
+        };
+        reader_handle.collect(&mut rm).unwrap();
+        let buf = encode(&rm);
+
+        assert!(buf.contains("# TYPE svix_test_attempts_total counter\n"));
+        assert!(buf.contains("svix_test_attempts_total{status=\"a\\\"b\"} 2\n"));
+        assert!(buf.contains("# TYPE svix_test_duration_milliseconds histogram\n"));
+        assert!(buf.contains("svix_test_duration_milliseconds_bucket{le=\"10\"} 1\n"));
+        assert!(buf.contains("svix_test_duration_milliseconds_bucket{le=\"+Inf\"} 1\n"));
+        assert!(buf.contains("svix_test_duration_milliseconds_sum 7\n"));
+        assert!(buf.contains("svix_test_duration_milliseconds_count 1\n"));
+
+        // The metrics of the same name from different meters are one family
+        assert_eq!(buf.matches("# TYPE svix_test_attempts_total").count(), 1);
+        assert!(buf.contains("svix_test_attempts_total{status=\"other\"} 3\n"));
+    }
+}
@@ -0,0 +1,29 @@
+use opentelemetry::metrics::{Counter, Meter};
+
+use super::init_metric;
+
+#[derive(Clone)]
+pub struct DestinationReaperMetrics {
+    requeued: Option<Counter<u64>>,
+}
+
+impl DestinationReaperMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        let requeued = init_metric(
+            meter
+                .u64_counter("svix.destination_reaper.requeued")
+                .with_description(
+                    "Message destinations whose task was lost with its worker, and queued again",
+                )
+                .try_init(),
+        );
+
+        Self { requeued }
+    }
+
+    pub fn record_requeued(&self, count: u64) {
+        if let Some(recorder) = &self.requeued {
+            recorder.add(count, &[]);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+
+use super::init_metric;
+
+#[derive(Clone)]
+pub struct RetentionMetrics {
+    deleted_rows: Option<Counter<u64>>,
+}
+
+impl RetentionMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        let deleted_rows = init_metric(
+            meter
+                .u64_counter("svix.retention.deleted_rows")
+                .with_description(
+                    "Rows deleted for being older than their retention period, by table",
+                )
+                .try_init(),
+        );
+
+        Self { deleted_rows }
+    }
+
+    pub fn record_deleted(&self, table: &'static str, count: u64) {
+        if let Some(recorder) = &self.deleted_rows {
+            recorder.add(count, &[KeyValue::new("table", table)]);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use opentelemetry::{
+    metrics::{Counter, Meter, UpDownCounter},
+    KeyValue,
+};
+
+use super::init_metric;
+use crate::core::types::OrganizationId;
+
+#[derive(Clone)]
+pub struct TenantMetrics {
+    in_flight: Option<UpDownCounter<i64>>,
+    deferred: Option<Counter<u64>>,
+}
+
+impl TenantMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        let in_flight = init_metric(
+            meter
+                .i64_up_down_counter("svix.worker.tenant_in_flight")
+                .with_description("Deliveries being made by the worker, by organization")
+                .try_init(),
+        );
+
+        let deferred = init_metric(
+            meter
+                .u64_counter("svix.worker.tenant_deferred")
+                .with_description(
+                    "Tasks put back on the queue rather than waiting in the worker for their \
+                     organization's turn, by organization",
+                )
+                .try_init(),
+        );
+
+        Self {
+            in_flight,
+            deferred,
+        }
+    }
+
+    pub fn record_in_flight(&self, org_id: &OrganizationId, delta: i64) {
+        if let Some(recorder) = &self.in_flight {
+            recorder.add(delta, &[KeyValue::new("org_id", org_id.0.clone())]);
+        }
+    }
+
+    pub fn record_deferred(&self, org_id: &OrganizationId) {
+        if let Some(recorder) = &self.deferred {
+            recorder.add(1, &[KeyValue::new("org_id", org_id.0.clone())]);
+        }
+    }
+}
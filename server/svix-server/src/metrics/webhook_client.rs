@@ -0,0 +1,40 @@
+use http::Version;
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+
+use super::init_metric;
+
+#[derive(Clone)]
+pub struct WebhookClientMetrics {
+    requests: Option<Counter<u64>>,
+}
+
+impl WebhookClientMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        let requests = init_metric(
+            meter
+                .u64_counter("svix.webhook_client.requests")
+                .with_description(
+                    "Requests sent to endpoints, by HTTP version and whether they reused an \
+                     already open connection",
+                )
+                .try_init(),
+        );
+
+        Self { requests }
+    }
+
+    pub fn record_request(&self, version: Version, connection_reused: bool) {
+        if let Some(recorder) = &self.requests {
+            recorder.add(
+                1,
+                &[
+                    KeyValue::new("http_version", format!("{version:?}")),
+                    KeyValue::new("connection_reused", connection_reused),
+                ],
+            );
+        }
+    }
+}
@@ -19,7 +19,7 @@ pub fn initialize_openapi() -> OpenApi {
     let tag_groups = serde_json::json![[
         {
             "name": "General",
-            "tags": ["Application", "Event Type"]
+            "tags": ["Application", "Event Type", "URL Policy", "Retention Policy", "Broadcast", "Audit Log"]
         },
         {
             "name": "Application specific",
@@ -78,6 +78,22 @@ pub fn initialize_openapi() -> OpenApi {
                 name: "Event Type".to_owned(),
                 ..openapi::Tag::default()
             },
+            openapi::Tag {
+                name: "URL Policy".to_owned(),
+                ..openapi::Tag::default()
+            },
+            openapi::Tag {
+                name: "Retention Policy".to_owned(),
+                ..openapi::Tag::default()
+            },
+            openapi::Tag {
+                name: "Broadcast".to_owned(),
+                ..openapi::Tag::default()
+            },
+            openapi::Tag {
+                name: "Audit Log".to_owned(),
+                ..openapi::Tag::default()
+            },
             openapi::Tag {
                 name: "Authentication".to_owned(),
                 ..openapi::Tag::default()
@@ -254,7 +270,7 @@ mod webhooks {
     use schemars::JsonSchema;
 
     use crate::core::operational_webhooks::{
-        EndpointDisabledEventData, EndpointEvent, MessageAttemptEvent,
+        AuditLogEvent, EndpointDisabledEventData, EndpointEvent, MessageAttemptEvent,
     };
 
     /// Documents the webhook specified by the type `T`.
@@ -391,6 +407,20 @@ mod webhooks {
         common_: EndpointEvent,
     }
 
+    #[derive(JsonSchema)]
+    #[allow(unused)]
+    struct EndpointPausedEventData {
+        #[serde(flatten)]
+        common_: EndpointEvent,
+    }
+
+    #[derive(JsonSchema)]
+    #[allow(unused)]
+    struct EndpointResumedEventData {
+        #[serde(flatten)]
+        common_: EndpointEvent,
+    }
+
     #[derive(JsonSchema)]
     #[allow(unused)]
     struct MessageAttemptExhaustedEventData {
@@ -412,6 +442,19 @@ mod webhooks {
         common_: MessageAttemptEvent,
     }
 
+    #[derive(JsonSchema)]
+    #[allow(unused)]
+    struct AuditLogEntryCreatedEventData {
+        #[serde(flatten)]
+        common_: AuditLogEvent,
+    }
+
+    webhook_event!(
+        AuditLogEntryCreatedEvent,
+        AuditLogEntryCreatedEventData,
+        "audit_log.entry.created",
+        "Sent when a change to the configuration is recorded in the audit log, if `audit_log_operational_webhooks` is enabled."
+    );
     webhook_event!(
         EndpointCreatedEvent,
         EndpointCreatedEventData,
@@ -430,6 +473,18 @@ mod webhooks {
         "endpoint.updated",
         "Sent when an endpoint is updated."
     );
+    webhook_event!(
+        EndpointPausedEvent,
+        EndpointPausedEventData,
+        "endpoint.paused",
+        "Sent when an endpoint, or its application, is paused."
+    );
+    webhook_event!(
+        EndpointResumedEvent,
+        EndpointResumedEventData,
+        "endpoint.resumed",
+        "Sent when an endpoint, or its application, is resumed and its held messages are queued again."
+    );
     webhook_event!(
         EndpointDisabledEvent,
         EndpointDisabledEventData,
@@ -459,9 +514,12 @@ mod webhooks {
     /// format. For more info see https://redocly.com/docs/api-reference-docs/specification-extensions/x-webhooks/
     pub(super) fn webhooks() -> HashMap<String, openapi::PathItem> {
         HashMap::from([
+            document_webhook::<AuditLogEntryCreatedEvent>(),
             document_webhook::<EndpointCreatedEvent>(),
             document_webhook::<EndpointDeletedEvent>(),
             document_webhook::<EndpointDisabledEvent>(),
+            document_webhook::<EndpointPausedEvent>(),
+            document_webhook::<EndpointResumedEvent>(),
             document_webhook::<EndpointUpdatedEvent>(),
             document_webhook::<MessageAttemptExhaustedEvent>(),
             document_webhook::<MessageAttemptFailingEvent>(),
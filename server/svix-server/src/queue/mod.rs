@@ -9,7 +9,10 @@ use crate::{
     cfg::{Configuration, QueueBackend},
     core::{
         retry::{run_with_retries, Retry},
-        types::{ApplicationId, EndpointId, MessageAttemptTriggerType, MessageId},
+        types::{
+            ApplicationId, BroadcastId, EndpointId, MessageAttemptTriggerType, MessageId,
+            MessagePriority,
+        },
     },
     error::{Error, ErrorType, Result, Traceable},
 };
@@ -29,6 +32,40 @@ fn should_retry(err: &Error) -> bool {
     matches!(err.typ, ErrorType::Queue(_))
 }
 
+/// A queue consumed by the worker, with the priority of the tasks sent to it.
+pub struct QueueLane {
+    pub priority: MessagePriority,
+    pub consumer: TaskQueueConsumer,
+}
+
+/// Creates the producer and the consumers of the queues, with tasks sent to a queue per priority
+/// when priority lanes are enabled. Tasks of normal priority use the same queue as without priority
+/// lanes, and the high and low priority queues are consumed even while priority lanes are
+/// disabled, so enabling or disabling them doesn't strand any tasks.
+pub async fn new_lanes(
+    cfg: &Configuration,
+    prefix: Option<&str>,
+) -> (TaskQueueProducer, Vec<QueueLane>) {
+    let (mut producer, consumer) = new_pair(cfg, prefix).await;
+    let mut lanes = vec![QueueLane {
+        priority: MessagePriority::Normal,
+        consumer,
+    }];
+
+    for priority in [MessagePriority::High, MessagePriority::Low] {
+        let lane_prefix = format!("{}{}_", prefix.unwrap_or_default(), priority.as_str());
+        let (lane_producer, consumer) = new_pair(cfg, Some(&lane_prefix)).await;
+        if cfg.queue_priority_lanes_enabled {
+            producer.lanes.push((priority, lane_producer.normal));
+        }
+        lanes.push(QueueLane { priority, consumer });
+    }
+
+    (producer, lanes)
+}
+
+/// Creates the producer and consumer of a single queue, which every task is sent to regardless of
+/// its priority.
 pub async fn new_pair(
     cfg: &Configuration,
     prefix: Option<&str>,
@@ -68,6 +105,8 @@ pub struct MessageTask {
     pub endpoint_id: EndpointId,
     pub trigger_type: MessageAttemptTriggerType,
     pub attempt_count: u16,
+    #[serde(default, skip_serializing_if = "MessagePriority::is_normal")]
+    pub priority: MessagePriority,
 }
 
 impl MessageTask {
@@ -76,6 +115,7 @@ impl MessageTask {
         app_id: ApplicationId,
         endpoint_id: EndpointId,
         trigger_type: MessageAttemptTriggerType,
+        priority: MessagePriority,
     ) -> QueueTask {
         QueueTask::MessageV1(Self {
             msg_id,
@@ -83,6 +123,7 @@ impl MessageTask {
             endpoint_id,
             attempt_count: 0,
             trigger_type,
+            priority,
         })
     }
 }
@@ -94,6 +135,8 @@ pub struct MessageTaskBatch {
     pub app_id: ApplicationId,
     pub force_endpoint: Option<EndpointId>,
     pub trigger_type: MessageAttemptTriggerType,
+    #[serde(default, skip_serializing_if = "MessagePriority::is_normal")]
+    pub priority: MessagePriority,
 }
 
 impl MessageTaskBatch {
@@ -102,12 +145,65 @@ impl MessageTaskBatch {
         app_id: ApplicationId,
         force_endpoint: Option<EndpointId>,
         trigger_type: MessageAttemptTriggerType,
+        priority: MessagePriority,
     ) -> QueueTask {
         QueueTask::MessageBatch(Self {
             msg_id,
             app_id,
             force_endpoint,
             trigger_type,
+            priority,
+        })
+    }
+}
+
+/// Creates the messages of the next batch of applications a broadcast is sent to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastTask {
+    pub broadcast_id: BroadcastId,
+}
+
+impl BroadcastTask {
+    pub fn new_task(broadcast_id: BroadcastId) -> QueueTask {
+        QueueTask::Broadcast(Self { broadcast_id })
+    }
+}
+
+/// Sends the verification challenge to an endpoint pending verification.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointVerificationTask {
+    pub app_id: ApplicationId,
+    pub endpoint_id: EndpointId,
+    /// How many times the challenge was sent and failed already
+    #[serde(default)]
+    pub attempt_count: u16,
+}
+
+impl EndpointVerificationTask {
+    pub fn new_task(app_id: ApplicationId, endpoint_id: EndpointId) -> QueueTask {
+        QueueTask::EndpointVerification(Self {
+            app_id,
+            endpoint_id,
+            attempt_count: 0,
+        })
+    }
+}
+
+/// Queues again the destinations held for endpoints of an application once they're resumed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseHeldDestinationsTask {
+    pub app_id: ApplicationId,
+    pub endpoint_ids: Vec<EndpointId>,
+}
+
+impl ReleaseHeldDestinationsTask {
+    pub fn new_task(app_id: ApplicationId, endpoint_ids: Vec<EndpointId>) -> QueueTask {
+        QueueTask::ReleaseHeldDestinations(Self {
+            app_id,
+            endpoint_ids,
         })
     }
 }
@@ -119,6 +215,9 @@ pub enum QueueTask {
     HealthCheck,
     MessageV1(MessageTask),
     MessageBatch(MessageTaskBatch),
+    Broadcast(BroadcastTask),
+    EndpointVerification(EndpointVerificationTask),
+    ReleaseHeldDestinations(ReleaseHeldDestinationsTask),
 }
 
 impl QueueTask {
@@ -128,21 +227,86 @@ impl QueueTask {
             QueueTask::HealthCheck => "HealthCheck",
             QueueTask::MessageV1(_) => "MessageV1",
             QueueTask::MessageBatch(_) => "MessageBatch",
+            QueueTask::Broadcast(_) => "Broadcast",
+            QueueTask::EndpointVerification(_) => "EndpointVerification",
+            QueueTask::ReleaseHeldDestinations(_) => "ReleaseHeldDestinations",
         }
     }
 
     pub fn msg_id(&self) -> Option<&str> {
         match self {
-            QueueTask::HealthCheck => None,
+            QueueTask::HealthCheck
+            | QueueTask::Broadcast(_)
+            | QueueTask::EndpointVerification(_)
+            | QueueTask::ReleaseHeldDestinations(_) => None,
             QueueTask::MessageV1(v1) => Some(&v1.msg_id),
             QueueTask::MessageBatch(batch) => Some(&batch.msg_id),
         }
     }
+
+    /// The application the task is for, if it's for a single one.
+    pub fn app_id(&self) -> Option<&ApplicationId> {
+        match self {
+            QueueTask::HealthCheck | QueueTask::Broadcast(_) => None,
+            QueueTask::MessageV1(v1) => Some(&v1.app_id),
+            QueueTask::MessageBatch(batch) => Some(&batch.app_id),
+            QueueTask::EndpointVerification(task) => Some(&task.app_id),
+            QueueTask::ReleaseHeldDestinations(task) => Some(&task.app_id),
+        }
+    }
+
+    /// The priority of the queue the task is sent to.
+    pub fn priority(&self) -> MessagePriority {
+        match self {
+            QueueTask::HealthCheck
+            | QueueTask::Broadcast(_)
+            | QueueTask::EndpointVerification(_)
+            | QueueTask::ReleaseHeldDestinations(_) => MessagePriority::Normal,
+            QueueTask::MessageV1(v1) => v1.priority,
+            QueueTask::MessageBatch(batch) => batch.priority,
+        }
+    }
 }
 
-pub type TaskQueueProducer = SvixOmniProducer<QueueTask>;
 pub type TaskQueueConsumer = SvixOmniConsumer<QueueTask>;
 
+/// Sends tasks to the queue of their priority, or to a single queue when priority lanes are
+/// disabled.
+#[derive(Clone)]
+pub struct TaskQueueProducer {
+    normal: SvixOmniProducer<QueueTask>,
+    /// The queues of the other priorities, only set when priority lanes are enabled
+    lanes: Vec<(MessagePriority, SvixOmniProducer<QueueTask>)>,
+}
+
+impl TaskQueueProducer {
+    pub(super) fn new(inner: impl ScheduledQueueProducer + 'static) -> Self {
+        Self {
+            normal: SvixOmniProducer::new(inner),
+            lanes: Vec::new(),
+        }
+    }
+
+    fn lane(&self, priority: MessagePriority) -> &SvixOmniProducer<QueueTask> {
+        self.lanes
+            .iter()
+            .find(|(lane_priority, _)| *lane_priority == priority)
+            .map_or(&self.normal, |(_, producer)| producer)
+    }
+
+    pub async fn send(&self, task: &QueueTask, delay: Option<Duration>) -> Result<()> {
+        self.lane(task.priority()).send(task, delay).await
+    }
+
+    pub async fn redrive_dlq(&self) -> Result<()> {
+        self.normal.redrive_dlq().await?;
+        for (_, producer) in &self.lanes {
+            producer.redrive_dlq().await?;
+        }
+        Ok(())
+    }
+}
+
 pub struct SvixOmniProducer<T: OmniMessage> {
     inner: Arc<omniqueue::DynScheduledProducer>,
     _phantom: PhantomData<T>,
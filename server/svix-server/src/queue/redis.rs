@@ -522,6 +522,7 @@ pub mod tests {
             endpoint_id: EndpointId("test".to_owned()),
             trigger_type: MessageAttemptTriggerType::Manual,
             attempt_count: 0,
+            priority: Default::default(),
         });
         p.send(&mt, None).await.unwrap();
 
@@ -576,6 +577,7 @@ pub mod tests {
             endpoint_id: EndpointId("test2".to_owned()),
             trigger_type: MessageAttemptTriggerType::Manual,
             attempt_count: 0,
+            priority: Default::default(),
         });
         p.send(&mt, None).await.unwrap();
 
@@ -628,6 +630,7 @@ pub mod tests {
             endpoint_id: EndpointId("test".to_owned()),
             trigger_type: MessageAttemptTriggerType::Manual,
             attempt_count: 0,
+            priority: Default::default(),
         });
         p.send(&mt, None).await.unwrap();
 
@@ -671,6 +674,7 @@ pub mod tests {
             endpoint_id: EndpointId("test1".to_owned()),
             trigger_type: MessageAttemptTriggerType::Scheduled,
             attempt_count: 0,
+            priority: Default::default(),
         });
         let mt2 = QueueTask::MessageV1(MessageTask {
             msg_id: MessageId("test2".to_owned()),
@@ -678,6 +682,7 @@ pub mod tests {
             endpoint_id: EndpointId("test2".to_owned()),
             trigger_type: MessageAttemptTriggerType::Manual,
             attempt_count: 0,
+            priority: Default::default(),
         });
 
         p.send(&mt1, Some(Duration::from_millis(2000)))
@@ -764,6 +769,7 @@ pub mod tests {
                                 endpoint_id: EndpointId("TestEndpointID".to_owned()),
                                 trigger_type: MessageAttemptTriggerType::Manual,
                                 attempt_count: 0,
+                                priority: Default::default(),
                             }),
                         ),
                     )
@@ -783,6 +789,7 @@ pub mod tests {
                                 endpoint_id: EndpointId("TestEndpointID".to_owned()),
                                 trigger_type: MessageAttemptTriggerType::Manual,
                                 attempt_count: 0,
+                                priority: Default::default(),
                             }),
                         ),
                         Utc::now().timestamp() + 2,
@@ -856,6 +863,7 @@ pub mod tests {
                     endpoint_id: EndpointId("TestEndpointID".to_owned()),
                     trigger_type: MessageAttemptTriggerType::Manual,
                     attempt_count: 0,
+                    priority: Default::default(),
                 })
             );
             recv.ack().await.unwrap();
@@ -870,6 +878,7 @@ pub mod tests {
                     endpoint_id: EndpointId("TestEndpointID".to_owned()),
                     trigger_type: MessageAttemptTriggerType::Manual,
                     attempt_count: 0,
+                    priority: Default::default(),
                 })
             );
             recv.ack().await.unwrap();
@@ -884,6 +893,7 @@ pub mod tests {
                     endpoint_id: EndpointId("TestEndpointID".to_owned()),
                     trigger_type: MessageAttemptTriggerType::Manual,
                     attempt_count: 0,
+                    priority: Default::default(),
                 })
             );
             recv.ack().await.unwrap();
@@ -0,0 +1,242 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Deletes the messages older than their application's retention period, or their organization's
+//! default, along with their payloads, destinations, attempts and events, and their blobs.
+
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, Statement};
+
+use crate::{
+    core::{
+        blob_store::BlobStore,
+        types::{ApplicationId, BaseId, MessageId},
+    },
+    error::Result,
+    expired_message_cleaner::sleep_unless_shutting_down,
+    metrics::RetentionMetrics,
+};
+
+type DbResult<T> = std::result::Result<T, DbErr>;
+
+/// How many applications with a retention period are fetched at a time.
+const APP_BATCH_SIZE: u32 = 1_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionCleanerResult {
+    pub messages: u64,
+    pub destinations: u64,
+    pub attempts: u64,
+    pub events: u64,
+}
+
+impl std::ops::AddAssign for RetentionCleanerResult {
+    fn add_assign(&mut self, other: Self) {
+        self.messages += other.messages;
+        self.destinations += other.destinations;
+        self.attempts += other.attempts;
+        self.events += other.events;
+    }
+}
+
+/// Fetches the next applications with a retention period, with the retention period in effect.
+async fn fetch_apps_with_retention(
+    pool: &DatabaseConnection,
+    after: Option<&ApplicationId>,
+) -> DbResult<Vec<(ApplicationId, i32)>> {
+    let stmt = Statement::from_sql_and_values(
+        pool.get_database_backend(),
+        r#"
+        SELECT application.id, COALESCE(application.retention_period, orgretentionpolicy.retention_period) AS retention_period
+        FROM application
+        LEFT JOIN orgretentionpolicy ON orgretentionpolicy.org_id = application.org_id
+        WHERE
+            application.id > $1
+            AND COALESCE(application.retention_period, orgretentionpolicy.retention_period) IS NOT NULL
+        ORDER BY application.id
+        LIMIT $2
+    "#,
+        [
+            after.map(|id| id.0.clone()).unwrap_or_default().into(),
+            APP_BATCH_SIZE.into(),
+        ],
+    );
+
+    pool.query_all(stmt)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok((
+                row.try_get::<ApplicationId>("", "id")?,
+                row.try_get::<i32>("", "retention_period")?,
+            ))
+        })
+        .collect()
+}
+
+/// Deletes up to `limit` of the application's messages created before `cutoff`, and everything
+/// referencing them, in a single statement. Rows locked by the workers are skipped rather than
+/// waited on, they're picked up by a later pass.
+///
+/// Messages are picked by the time in their ID rather than `created_at`, so the application's
+/// messages are found through the `(app_id, id)` index.
+///
+/// Also returns the keys of the blobs of the deleted rows, which are left to the caller to delete.
+async fn delete_app_batch(
+    pool: &DatabaseConnection,
+    app_id: &ApplicationId,
+    cutoff: DateTime<Utc>,
+    limit: u32,
+) -> DbResult<(RetentionCleanerResult, Vec<String>)> {
+    let stmt = Statement::from_sql_and_values(
+        pool.get_database_backend(),
+        r#"
+        WITH batch AS (
+            SELECT id FROM message
+            WHERE
+                app_id = $1
+                AND id < $2
+                AND (deliver_at IS NULL OR deliver_at <= now())
+            ORDER BY id
+            LIMIT $3
+            FOR UPDATE SKIP LOCKED
+        ),
+        attempts AS (
+            DELETE FROM messageattempt WHERE msg_id IN (SELECT id FROM batch) RETURNING response_blob_key
+        ),
+        destinations AS (
+            DELETE FROM messagedestination WHERE msg_id IN (SELECT id FROM batch) RETURNING 1
+        ),
+        events AS (
+            DELETE FROM messageevent WHERE msg_id IN (SELECT id FROM batch) RETURNING 1
+        ),
+        contents AS (
+            DELETE FROM messagecontent WHERE id IN (SELECT id FROM batch) RETURNING blob_key
+        ),
+        messages AS (
+            DELETE FROM message WHERE id IN (SELECT id FROM batch) RETURNING 1
+        )
+        SELECT
+            (SELECT count(*) FROM messages) AS messages,
+            (SELECT count(*) FROM destinations) AS destinations,
+            (SELECT count(*) FROM attempts) AS attempts,
+            (SELECT count(*) FROM events) AS events,
+            (
+                SELECT string_agg(key, ',') FROM (
+                    SELECT blob_key AS key FROM contents
+                    UNION ALL
+                    SELECT response_blob_key AS key FROM attempts
+                ) AS keys
+            ) AS blob_keys
+    "#,
+        [
+            app_id.clone().into(),
+            MessageId::start_id(cutoff).into(),
+            limit.into(),
+        ],
+    );
+
+    let Some(row) = pool.query_one(stmt).await? else {
+        return Ok((RetentionCleanerResult::default(), vec![]));
+    };
+    let blob_keys = row
+        .try_get::<Option<String>>("", "blob_keys")?
+        .map(|keys| keys.split(',').map(ToOwned::to_owned).collect())
+        .unwrap_or_default();
+    Ok((
+        RetentionCleanerResult {
+            messages: row.try_get::<i64>("", "messages")? as u64,
+            destinations: row.try_get::<i64>("", "destinations")? as u64,
+            attempts: row.try_get::<i64>("", "attempts")? as u64,
+            events: row.try_get::<i64>("", "events")? as u64,
+        },
+        blob_keys,
+    ))
+}
+
+/// Makes a full pass over the applications with a retention period, deleting their messages
+/// older than it, `limit` messages at a time.
+pub async fn clean_retained_messages(
+    pool: &DatabaseConnection,
+    limit: u32,
+    metrics: &RetentionMetrics,
+    blob_store: &BlobStore,
+) -> DbResult<RetentionCleanerResult> {
+    let mut total = RetentionCleanerResult::default();
+    let mut cursor = None;
+
+    loop {
+        let apps = fetch_apps_with_retention(pool, cursor.as_ref()).await?;
+        let done = (apps.len() as u32) < APP_BATCH_SIZE;
+
+        for (app_id, retention_period) in &apps {
+            let cutoff = Utc::now() - chrono::Duration::days((*retention_period).into());
+            loop {
+                let (res, blob_keys) = delete_app_batch(pool, app_id, cutoff, limit).await?;
+                blob_store.delete_all(blob_keys).await;
+                metrics.record_deleted("message", res.messages);
+                metrics.record_deleted("messagedestination", res.destinations);
+                metrics.record_deleted("messageattempt", res.attempts);
+                metrics.record_deleted("messageevent", res.events);
+                total += res;
+
+                if res.messages < limit as u64 || crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+                    break;
+                }
+            }
+        }
+
+        cursor = apps.into_iter().last().map(|(app_id, _)| app_id);
+        if done || crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+
+    Ok(total)
+}
+
+/// Periodically deletes the messages older than their retention period.
+pub async fn retention_cleaner_loop(
+    pool: &DatabaseConnection,
+    blob_store: &BlobStore,
+) -> Result<()> {
+    const INTERVAL: Duration = Duration::from_secs(60 * 60);
+    const ON_ERROR: Duration = Duration::from_secs(10);
+    const BATCH_SIZE: u32 = 1_000;
+
+    let metrics = RetentionMetrics::new(&opentelemetry::global::meter("svix.com"));
+    loop {
+        let start = Instant::now();
+        let sleep_time = match clean_retained_messages(pool, BATCH_SIZE, &metrics, blob_store).await
+        {
+            Err(err) => {
+                tracing::error!("{}", err);
+                ON_ERROR
+            }
+            Ok(res) => {
+                if res.messages > 0 {
+                    tracing::debug!(
+                        elapsed =? start.elapsed(),
+                        "deleted {} messages, {} destinations, {} attempts and {} events past their retention period",
+                        res.messages,
+                        res.destinations,
+                        res.attempts,
+                        res.events,
+                    );
+                }
+                INTERVAL
+            }
+        };
+
+        if !sleep_unless_shutting_down(sleep_time).await {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! Deletes the rows of the tables only kept for a while, such as the message events and the audit
+//! log, once they're older than the configured retention.
+
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, ConnectionTrait, DatabaseConnection, DbErr, Statement,
+};
+
+use crate::{error::Result, expired_message_cleaner::sleep_unless_shutting_down};
+
+type DbResult<T> = std::result::Result<T, DbErr>;
+
+/// Deletes the rows of `table` created longer than `retention` ago, `limit` at a time. Returns how
+/// many were deleted. The table needs an `id` and a `created_at` column.
+pub async fn clean_table_older_than(
+    pool: &DatabaseConnection,
+    table: &'static str,
+    retention: Duration,
+    limit: u32,
+) -> DbResult<u64> {
+    let cutoff: DateTimeWithTimeZone = (Utc::now()
+        - chrono::Duration::from_std(retention).expect("Error parsing duration"))
+    .into();
+    let mut total = 0;
+
+    loop {
+        let stmt = Statement::from_sql_and_values(
+            pool.get_database_backend(),
+            format!(
+                r#"
+            DELETE FROM {table} WHERE id = any(
+                array(
+                    SELECT id FROM {table}
+                    WHERE created_at < $1
+                    LIMIT $2
+                )
+            )
+        "#
+            ),
+            [cutoff.into(), limit.into()],
+        );
+        let deleted = pool.execute(stmt).await?.rows_affected();
+        total += deleted;
+
+        if deleted < limit as u64 || crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+
+    Ok(total)
+}
+
+/// Periodically deletes the rows of `table` older than `retention`.
+pub async fn table_cleaner_loop(
+    pool: &DatabaseConnection,
+    table: &'static str,
+    retention: Duration,
+) -> Result<()> {
+    const INTERVAL: Duration = Duration::from_secs(60 * 60);
+    const ON_ERROR: Duration = Duration::from_secs(10);
+    const BATCH_SIZE: u32 = 5_000;
+
+    loop {
+        let start = Instant::now();
+        let sleep_time = match clean_table_older_than(pool, table, retention, BATCH_SIZE).await {
+            Err(err) => {
+                tracing::error!("{}", err);
+                ON_ERROR
+            }
+            Ok(count) => {
+                if count > 0 {
+                    tracing::debug!(elapsed =? start.elapsed(), "deleted {} rows from {}", count, table);
+                }
+                INTERVAL
+            }
+        };
+
+        if !sleep_unless_shutting_down(sleep_time).await {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -5,17 +5,29 @@ use aide::{
 use axum::extract::State;
 use svix_server_derive::aide_annotate;
 
-use crate::{core::permissions, error::Result, v1::utils::NoContent, AppState};
+use crate::{
+    core::{audit_log::Auditor, permissions},
+    error::Result,
+    v1::utils::NoContent,
+    AppState,
+};
 
 /// Redrive DLQ
 #[aide_annotate(op_id = "v1.admin.redrive-dlq")]
 pub async fn redrive_dlq(
-    State(AppState { queue_tx, .. }): State<AppState>,
+    State(AppState {
+        ref db, queue_tx, ..
+    }): State<AppState>,
     _: permissions::Organization,
+    auditor: Auditor,
 ) -> Result<NoContent> {
     if let Err(e) = queue_tx.redrive_dlq().await {
         tracing::warn!(error = ?e, "DLQ redrive failed");
     }
+
+    let entry = auditor.record(db, None, None, None).await?;
+    auditor.notify(entry).await;
+
     Ok(NoContent)
 }
 
@@ -10,20 +10,26 @@ use axum::{
     Json,
 };
 use chrono::{DateTime, Utc};
-use futures::FutureExt;
 use schemars::JsonSchema;
-use sea_orm::{ActiveModelTrait, ActiveValue::Set, TransactionTrait};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter,
+    TransactionTrait, TryIntoModel,
+};
 use serde::{Deserialize, Serialize};
 use svix_server_derive::{aide_annotate, ModelOut};
 use validator::{Validate, ValidationError};
 
 use crate::{
+    cfg::Configuration,
     core::{
+        audit_log::{self, Auditor},
+        pause::{invalidate_cached_app, send_pause_webhooks},
         permissions,
         types::{metadata::Metadata, ApplicationId, ApplicationUid},
     },
-    db::models::{application, applicationmetadata},
-    error::{http_error_on_conflict, HttpError, Result, Traceable},
+    db::models::{application, applicationmetadata, endpoint},
+    error::{http_error_on_conflict, HttpError, Result, Traceable, ValidationErrorItem},
+    queue::ReleaseHeldDestinationsTask,
     v1::utils::{
         apply_pagination, openapi_tag,
         patch::{
@@ -60,6 +66,12 @@ pub struct ApplicationIn {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uid: Option<ApplicationUid>,
 
+    /// The number of days after which the application's messages are deleted, along with their
+    /// attempts. Defaults to the organization's retention policy
+    #[validate(range(min = 1, message = "Retention periods must be at least one day"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_period: Option<u16>,
+
     #[serde(default)]
     pub metadata: Metadata,
 }
@@ -73,12 +85,14 @@ impl ModelIn for ApplicationIn {
             name,
             rate_limit,
             uid,
+            retention_period,
             metadata,
         } = self;
 
         app.name = Set(name);
         app.rate_limit = Set(rate_limit.map(|x| x.into()));
         app.uid = Set(uid);
+        app.retention_period = Set(retention_period.map(|x| x.into()));
         app_metadata.data = Set(metadata);
     }
 }
@@ -101,6 +115,10 @@ pub struct ApplicationPatch {
     #[validate]
     pub uid: UnrequiredNullableField<ApplicationUid>,
 
+    #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
+    #[validate(custom = "validate_retention_period_patch")]
+    pub retention_period: UnrequiredNullableField<u16>,
+
     #[serde(default, skip_serializing_if = "UnrequiredField::is_absent")]
     pub metadata: UnrequiredField<Metadata>,
 }
@@ -113,16 +131,18 @@ impl ModelIn for ApplicationPatch {
             name,
             rate_limit,
             uid,
+            retention_period,
             metadata,
         } = self;
 
-        // `model`'s version of `rate_limit` is an i32, while `self`'s is a u16.
-        let rate_limit_map = |x: u16| -> i32 { x.into() };
+        // `model`'s versions of `rate_limit` and `retention_period` are i32s, while `self`'s are u16s.
+        let u16_map = |x: u16| -> i32 { x.into() };
         let data = metadata;
 
         patch_field_non_nullable!(app, name);
-        patch_field_nullable!(app, rate_limit, rate_limit_map);
+        patch_field_nullable!(app, rate_limit, u16_map);
         patch_field_nullable!(app, uid);
+        patch_field_nullable!(app, retention_period, u16_map);
         patch_field_non_nullable!(app_metadata, data);
     }
 }
@@ -161,6 +181,18 @@ fn validate_rate_limit_patch(
     }
 }
 
+fn validate_retention_period_patch(
+    retention_period: &UnrequiredNullableField<u16>,
+) -> Result<(), ValidationError> {
+    match retention_period {
+        UnrequiredNullableField::Some(0) => Err(validation_error(
+            Some("range"),
+            Some("Retention periods must be at least one day"),
+        )),
+        _ => Ok(()),
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ModelOut, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ApplicationOut {
@@ -171,6 +203,14 @@ pub struct ApplicationOut {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rate_limit: Option<u16>,
+    /// The number of days after which the application's messages are deleted, if it overrides
+    /// the organization's retention policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_period: Option<u16>,
+    /// Messages to the endpoints of paused applications are held rather than sent, until the
+    /// application is resumed
+    #[serde(default)]
+    pub paused: bool,
 
     pub id: ApplicationId,
     pub created_at: DateTime<Utc>,
@@ -184,6 +224,8 @@ impl From<(application::Model, applicationmetadata::Model)> for ApplicationOut {
             uid: app.uid,
             name: app.name,
             rate_limit: app.rate_limit.map(|x| x as u16),
+            retention_period: app.retention_period.map(|x| x as u16),
+            paused: app.paused,
             id: app.id,
             created_at: app.created_at.into(),
             updated_at: app.updated_at.into(),
@@ -250,6 +292,7 @@ async fn create_application(
     State(AppState { ref db, .. }): State<AppState>,
     query: ValidatedQuery<CreateApplicationQueryParams>,
     permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<ApplicationIn>,
 ) -> Result<JsonStatusUpsert<ApplicationOut>> {
     if let Some(ref uid) = data.uid {
@@ -277,18 +320,17 @@ async fn create_application(
     data.update_model(&mut model);
     let (app, metadata) = model;
 
-    let (app, metadata) = db
-        .transaction(|txn| {
-            async move {
-                let app_result = app.insert(txn).await.map_err(http_error_on_conflict)?;
-                let metadata = metadata.upsert_or_delete(txn).await.trace()?;
-                Ok((app_result, metadata))
-            }
-            .boxed()
-        })
+    let txn = db.begin().await?;
+    let app = app.insert(&txn).await.map_err(http_error_on_conflict)?;
+    let metadata = metadata.upsert_or_delete(&txn).await.trace()?;
+    let out = ApplicationOut::from((app, metadata));
+    let entry = auditor
+        .record(&txn, Some(out.id.clone()), None, audit_log::state(&out))
         .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
 
-    Ok(JsonStatusUpsert::Created((app, metadata).into()))
+    Ok(JsonStatusUpsert::Created(out))
 }
 
 /// Get an application.
@@ -305,43 +347,45 @@ async fn update_application(
     State(AppState { ref db, .. }): State<AppState>,
     Path(ApplicationPath { app_id }): Path<ApplicationPath>,
     permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<ApplicationIn>,
 ) -> Result<JsonStatusUpsert<ApplicationOut>> {
-    let (app, metadata, create_models) = if let Some((app, metadata)) =
+    let (app, metadata, before) = if let Some((app, metadata)) =
         application::Model::fetch_with_metadata(db, org_id.clone(), app_id)
             .await
             .trace()?
     {
-        (app.into(), metadata.into(), false)
+        let before = audit_log::state(ApplicationOut::from((app.clone(), metadata.clone())));
+        (app.into(), metadata.into(), before)
     } else {
         let app = application::ActiveModel::new(org_id);
         let metadata = applicationmetadata::ActiveModel::new(app.id.clone().unwrap(), None);
-        (app, metadata, true)
+        (app, metadata, None)
     };
+    let create_models = before.is_none();
 
     let mut models = (app, metadata);
     data.update_model(&mut models);
     let (app, metadata) = models;
 
-    let (app, metadata) = db
-        .transaction(|txn| {
-            async move {
-                let app = if create_models {
-                    app.insert(txn).await.map_err(http_error_on_conflict)?
-                } else {
-                    app.update(txn).await.map_err(http_error_on_conflict)?
-                };
-                let metadata = metadata.upsert_or_delete(txn).await?;
-                Ok((app, metadata))
-            }
-            .boxed()
-        })
+    let txn = db.begin().await?;
+    let app = if create_models {
+        app.insert(&txn).await.map_err(http_error_on_conflict)?
+    } else {
+        app.update(&txn).await.map_err(http_error_on_conflict)?
+    };
+    let metadata = metadata.upsert_or_delete(&txn).await?;
+    let out = ApplicationOut::from((app, metadata));
+    let entry = auditor
+        .record(&txn, Some(out.id.clone()), before, audit_log::state(&out))
         .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
 
     if create_models {
-        Ok(JsonStatusUpsert::Created((app, metadata).into()))
+        Ok(JsonStatusUpsert::Created(out))
     } else {
-        Ok(JsonStatusUpsert::Updated((app, metadata).into()))
+        Ok(JsonStatusUpsert::Updated(out))
     }
 }
 
@@ -350,27 +394,31 @@ async fn update_application(
 async fn patch_application(
     State(AppState { ref db, .. }): State<AppState>,
     permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<ApplicationPatch>,
 ) -> Result<Json<ApplicationOut>> {
     let metadata = app.fetch_or_create_metadata(db).await.trace()?;
+    let before = audit_log::state(ApplicationOut::from((
+        app.clone(),
+        metadata.clone().try_into_model()?,
+    )));
     let app: application::ActiveModel = app.into();
 
     let mut model = (app, metadata);
     data.update_model(&mut model);
     let (app, metadata) = model;
 
-    let (app, metadata) = db
-        .transaction(|txn| {
-            async move {
-                let app = app.update(txn).await.map_err(http_error_on_conflict)?;
-                let metadata = metadata.upsert_or_delete(txn).await.trace()?;
-                Ok((app, metadata))
-            }
-            .boxed()
-        })
+    let txn = db.begin().await?;
+    let app = app.update(&txn).await.map_err(http_error_on_conflict)?;
+    let metadata = metadata.upsert_or_delete(&txn).await.trace()?;
+    let out = ApplicationOut::from((app, metadata));
+    let entry = auditor
+        .record(&txn, Some(out.id.clone()), before, audit_log::state(&out))
         .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
 
-    Ok(Json((app, metadata).into()))
+    Ok(Json(out))
 }
 
 /// Delete an application.
@@ -378,14 +426,230 @@ async fn patch_application(
 async fn delete_application(
     State(AppState { ref db, .. }): State<AppState>,
     permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    auditor: Auditor,
 ) -> Result<NoContent> {
+    let metadata = app.fetch_or_create_metadata(db).await?.try_into_model()?;
+    let app_id = app.id.clone();
+    let before = audit_log::state(ApplicationOut::from((app.clone(), metadata)));
+
     let mut app: application::ActiveModel = app.into();
     app.deleted = Set(true);
     app.uid = Set(None); // We don't want deleted UIDs to clash
-    app.update(db).await?;
+
+    let txn = db.begin().await?;
+    app.update(&txn).await?;
+    let entry = auditor.record(&txn, Some(app_id), before, None).await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
     Ok(NoContent)
 }
 
+/// Sets whether the application is paused, recording it in the audit log if it changed. Returns
+/// whether it did.
+async fn set_application_paused(
+    db: &sea_orm::DatabaseConnection,
+    auditor: &Auditor,
+    app: &application::Model,
+    paused: bool,
+) -> Result<bool> {
+    let metadata = app.fetch_or_create_metadata(db).await?.try_into_model()?;
+
+    let txn = db.begin().await?;
+    // Conditional, so concurrent calls don't both act on the change
+    let res = application::Entity::update_many()
+        .col_expr(application::Column::Paused, Expr::value(paused))
+        .filter(application::Column::Id.eq(app.id.clone()))
+        .filter(application::Column::Paused.eq(!paused))
+        .exec(&txn)
+        .await?;
+    if res.rows_affected == 0 {
+        return Ok(false);
+    }
+
+    let before = audit_log::state(ApplicationOut::from((app.clone(), metadata.clone())));
+    let after = application::Model {
+        paused,
+        ..app.clone()
+    };
+    let after = audit_log::state(ApplicationOut::from((after, metadata)));
+    let entry = auditor
+        .record(&txn, Some(app.id.clone()), before, after)
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    Ok(true)
+}
+
+/// The endpoints whose delivery pausing or resuming the application affects, i.e. the ones not
+/// paused themselves.
+async fn unpaused_endpoints(
+    db: &sea_orm::DatabaseConnection,
+    app: &application::Model,
+) -> Result<Vec<endpoint::Model>> {
+    Ok(endpoint::Entity::secure_find(app.id.clone())
+        .filter(endpoint::Column::Paused.eq(false))
+        .all(db)
+        .await?)
+}
+
+/// Pause the delivery of messages to all of the application's endpoints.
+///
+/// Messages sent while the application is paused are stored, and their attempts listed with the
+/// paused status, but they aren't sent until it's resumed.
+#[aide_annotate(op_id = "v1.application.pause")]
+async fn pause_application(
+    State(AppState {
+        ref db,
+        ref cache,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+) -> Result<NoContent> {
+    if set_application_paused(db, &auditor, &app, true).await? {
+        invalidate_cached_app(cache, &app).await;
+        let endpoints = unpaused_endpoints(db, &app).await?;
+        send_pause_webhooks(op_webhooks, &app, &endpoints, true).await?;
+    }
+
+    Ok(NoContent)
+}
+
+/// Resume the delivery of messages to a paused application.
+///
+/// The messages held while it was paused are sent in the order they were created, except for
+/// those of endpoints that are paused themselves.
+#[aide_annotate(op_id = "v1.application.resume")]
+async fn resume_application(
+    State(AppState {
+        ref db,
+        ref cache,
+        ref queue_tx,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+) -> Result<NoContent> {
+    if !set_application_paused(db, &auditor, &app, false).await? {
+        return Ok(NoContent);
+    }
+    invalidate_cached_app(cache, &app).await;
+
+    let endpoints = unpaused_endpoints(db, &app).await?;
+    if !endpoints.is_empty() {
+        let endp_ids = endpoints.iter().map(|e| e.id.clone()).collect();
+        queue_tx
+            .send(
+                &ReleaseHeldDestinationsTask::new_task(app.id.clone(), endp_ids),
+                None,
+            )
+            .await?;
+    }
+
+    send_pause_webhooks(op_webhooks, &app, &endpoints, false).await?;
+
+    Ok(NoContent)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Validate, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EgressIn {
+    /// The name of one of the egress proxies configured on the server, or `null` to use the
+    /// default route.
+    pub proxy: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EgressOut {
+    /// The name of the egress proxy set on this resource, if any.
+    pub proxy: Option<String>,
+    /// The IPs webhooks are sent from (in CIDR notation), for receivers that need to allowlist
+    /// them. Empty if unknown.
+    pub egress_ips: Vec<String>,
+}
+
+impl EgressOut {
+    pub(crate) fn new(cfg: &Configuration, proxy: Option<String>, effective: Option<&str>) -> Self {
+        Self {
+            egress_ips: cfg
+                .egress_ips(effective)
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            proxy,
+        }
+    }
+}
+
+/// Checks that the egress proxy exists in the server configuration.
+pub(crate) fn validate_egress_proxy(cfg: &Configuration, proxy: Option<&str>) -> Result<()> {
+    match proxy {
+        Some(name) if !cfg.egress_proxies.contains_key(name) => {
+            Err(HttpError::unprocessable_entity(vec![ValidationErrorItem {
+                loc: vec!["body".to_owned(), "proxy".to_owned()],
+                msg: format!("Unknown egress proxy: {name}"),
+                ty: "value_error".to_owned(),
+            }])
+            .into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Get the egress proxy of the application and the IPs its webhooks are sent from.
+#[aide_annotate(op_id = "v1.application.get-egress")]
+async fn get_application_egress(
+    State(AppState { cfg, .. }): State<AppState>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<EgressOut>> {
+    let effective = app.egress_proxy.as_deref();
+    Ok(Json(EgressOut::new(
+        &cfg,
+        app.egress_proxy.clone(),
+        effective,
+    )))
+}
+
+/// Set the egress proxy webhooks of the application are sent through.
+///
+/// Endpoints with their own egress proxy set aren't affected.
+#[aide_annotate(op_id = "v1.application.update-egress")]
+async fn update_application_egress(
+    State(AppState { ref db, cfg, .. }): State<AppState>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+    ValidatedJson(data): ValidatedJson<EgressIn>,
+) -> Result<Json<EgressOut>> {
+    validate_egress_proxy(&cfg, data.proxy.as_deref())?;
+
+    let before = audit_log::state(EgressOut::new(
+        &cfg,
+        app.egress_proxy.clone(),
+        app.egress_proxy.as_deref(),
+    ));
+    let app = application::ActiveModel {
+        egress_proxy: Set(data.proxy),
+        ..app.into()
+    };
+
+    let txn = db.begin().await?;
+    let app = app.update(&txn).await?;
+    let effective = app.egress_proxy.as_deref();
+    let out = EgressOut::new(&cfg, app.egress_proxy.clone(), effective);
+    let entry = auditor
+        .record(&txn, Some(app.id.clone()), before, audit_log::state(&out))
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    Ok(Json(out))
+}
+
 pub fn router() -> ApiRouter<AppState> {
     let tag = openapi_tag("Application");
     ApiRouter::new()
@@ -401,6 +665,24 @@ pub fn router() -> ApiRouter<AppState> {
                 .put_with(update_application, update_application_operation)
                 .patch_with(patch_application, patch_application_operation)
                 .delete_with(delete_application, delete_application_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/pause",
+            post_with(pause_application, pause_application_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/resume",
+            post_with(resume_application, resume_application_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/egress",
+            get_with(get_application_egress, get_application_egress_operation).put_with(
+                update_application_egress,
+                update_application_egress_operation,
+            ),
             tag,
         )
 }
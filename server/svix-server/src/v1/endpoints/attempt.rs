@@ -12,9 +12,10 @@ use axum::{
     Json,
 };
 use chrono::{DateTime, Utc};
+use futures::future::try_join_all;
 use hyper::StatusCode;
 use schemars::JsonSchema;
-use sea_orm::{entity::prelude::*, IntoActiveModel, QueryOrder, QuerySelect};
+use sea_orm::{entity::prelude::*, IntoActiveModel, QueryOrder, QuerySelect, TransactionTrait};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::value::RawValue;
 use svix_server_derive::{aide_annotate, ModelOut};
@@ -22,6 +23,8 @@ use validator::Validate;
 
 use crate::{
     core::{
+        audit_log::Auditor,
+        blob_store::BlobStore,
         permissions,
         types::{
             EndpointId, EndpointIdOrUid, EventChannel, EventTypeNameSet, MessageAttemptId,
@@ -116,21 +119,22 @@ impl ModelOut for EndpointMessageOut {
 }
 
 impl EndpointMessageOut {
-    pub fn from_dest_and_msg(
+    pub async fn from_dest_and_msg(
         dest: messagedestination::Model,
         msg: message::Model,
         with_content: bool,
-        msg_payload: Option<Vec<u8>>,
-    ) -> EndpointMessageOut {
-        EndpointMessageOut {
+        msg_content: Option<messagecontent::Model>,
+        blob_store: &BlobStore,
+    ) -> Result<EndpointMessageOut> {
+        Ok(EndpointMessageOut {
             msg: if with_content {
-                MessageOut::from_msg_and_payload(msg, msg_payload)
+                MessageOut::from_msg_and_payload(msg, msg_content, blob_store).await?
             } else {
                 MessageOut::without_payload(msg)
             },
             status: dest.status,
             next_attempt: dest.next_attempt,
-        }
+        })
     }
 }
 
@@ -179,7 +183,11 @@ fn default_true() -> bool {
 /// The `before` parameter lets you filter all items created before a certain date and is ignored if an iterator is passed.
 #[aide_annotate(op_id = "v1.message-attempt.list-attempted-messages")]
 async fn list_attempted_messages(
-    State(AppState { ref db, .. }): State<AppState>,
+    State(AppState {
+        ref db,
+        ref blob_store,
+        ..
+    }): State<AppState>,
     ValidatedQuery(pagination): ValidatedQuery<PaginationDescending<ReversibleIterator<MessageId>>>,
     ValidatedQuery(ListAttemptedMessagesQueryParams {
         channel,
@@ -258,25 +266,20 @@ async fn list_attempted_messages(
         .all(db)
         .await?
         .into_iter()
-        .map(|content| (content.id, content.payload))
-        .collect::<HashMap<MessageId, Vec<u8>>>();
+        .map(|content| (content.id.clone(), content))
+        .collect::<HashMap<MessageId, messagecontent::Model>>();
 
     let into = |(dest, msg): (messagedestination::Model, Option<message::Model>)| {
-        let msg =
-            msg.ok_or_else(|| Error::database("No associated message with messagedestination"))?;
-        let payload = msg_content_map.remove(&msg.id);
-        Ok(EndpointMessageOut::from_dest_and_msg(
-            dest,
-            msg,
-            with_content,
-            payload,
-        ))
+        let content = msg.as_ref().and_then(|msg| msg_content_map.remove(&msg.id));
+        async move {
+            let msg = msg
+                .ok_or_else(|| Error::database("No associated message with messagedestination"))?;
+            EndpointMessageOut::from_dest_and_msg(dest, msg, with_content, content, blob_store)
+                .await
+        }
     };
 
-    let out = dests_and_msgs
-        .into_iter()
-        .map(into)
-        .collect::<Result<_>>()?;
+    let out = try_join_all(dests_and_msgs.into_iter().map(into)).await?;
 
     Ok(Json(EndpointMessageOut::list_response(
         out,
@@ -778,7 +781,11 @@ async fn list_messageattempts(
 /// `msg_id`: Use a message id or a message `eventId`
 #[aide_annotate(op_id = "v1.message-attempt.get")]
 async fn get_messageattempt(
-    State(AppState { ref db, .. }): State<AppState>,
+    State(AppState {
+        ref db,
+        ref blob_store,
+        ..
+    }): State<AppState>,
     Path(ApplicationMsgAttemptPath {
         msg_id, attempt_id, ..
     }): Path<ApplicationMsgAttemptPath>,
@@ -794,14 +801,18 @@ async fn get_messageattempt(
         .one(db)
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
-    Ok(Json(attempt.into()))
+    // Only a single attempt comes with its full response, lists keep the truncated one
+    Ok(Json(blob_store.resolve_response(attempt).await?.into()))
 }
 
 /// Resend a message to the specified endpoint.
 #[aide_annotate(op_id = "v1.message-attempt.resend")]
 async fn resend_webhook(
     State(AppState {
-        ref db, queue_tx, ..
+        ref db,
+        queue_tx,
+        ref blob_store,
+        ..
     }): State<AppState>,
     Path(ApplicationMsgEndpointPath {
         msg_id,
@@ -809,6 +820,7 @@ async fn resend_webhook(
         ..
     }): Path<ApplicationMsgEndpointPath>,
     permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
 ) -> Result<NoContentWithCode<202>> {
     let (msg, msg_content) = message::Entity::secure_find_by_id_or_uid(app.id.clone(), msg_id)
         .find_also_related(messagecontent::Entity)
@@ -817,7 +829,10 @@ async fn resend_webhook(
         .ok_or_else(|| HttpError::not_found(None, None))?;
 
     let msg_content = match msg_content {
-        Some(m) => serde_json::from_slice(&m.payload).ok(),
+        Some(m) => blob_store
+            .resolve_payload(m)
+            .await?
+            .and_then(|payload| serde_json::from_slice::<serde_json::Value>(&payload).ok()),
         None => msg.legacy_payload,
     };
     if msg_content.is_none() {
@@ -844,39 +859,60 @@ async fn resend_webhook(
         .send(
             &MessageTask::new_task(
                 msg.id.clone(),
-                app.id,
+                app.id.clone(),
                 endp.id,
                 MessageAttemptTriggerType::Manual,
+                msg.priority,
             ),
             None,
         )
         .await?;
+
+    let entry = auditor.record(db, Some(app.id), None, None).await?;
+    auditor.notify(entry).await;
+
     Ok(NoContentWithCode)
 }
 
 /// Deletes the given attempt's response body. Useful when an endpoint accidentally returned sensitive content.
 #[aide_annotate(op_id = "v1.message-attempt.expunge-content")]
 async fn expunge_attempt_content(
-    State(AppState { ref db, .. }): State<AppState>,
+    State(AppState {
+        ref db,
+        ref blob_store,
+        ..
+    }): State<AppState>,
     Path(ApplicationMsgAttemptPath {
         msg_id, attempt_id, ..
     }): Path<ApplicationMsgAttemptPath>,
     permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    auditor: Auditor,
 ) -> Result<StatusCode> {
-    let msg = message::Entity::secure_find_by_id_or_uid(app.id, msg_id)
+    let msg = message::Entity::secure_find_by_id_or_uid(app.id.clone(), msg_id)
         .one(db)
         .await?
         .ok_or_else(|| HttpError::not_found(None, Some("Message not found".to_string())))?;
 
-    let mut attempt = messageattempt::Entity::secure_find_by_msg(msg.id)
+    let attempt = messageattempt::Entity::secure_find_by_msg(msg.id)
         .filter(messageattempt::Column::Id.eq(attempt_id))
         .one(db)
         .await?
-        .ok_or_else(|| HttpError::not_found(None, Some("Message attempt not found".to_string())))?
-        .into_active_model();
+        .ok_or_else(|| HttpError::not_found(None, Some("Message attempt not found".to_string())))?;
+    let blob_key = attempt.response_blob_key.clone();
 
+    let mut attempt = attempt.into_active_model();
     attempt.response = sea_orm::Set("EXPUNGED".to_string());
-    attempt.update(db).await?;
+    attempt.response_blob_key = sea_orm::Set(None);
+
+    let txn = db.begin().await?;
+    attempt.update(&txn).await?;
+    let entry = auditor.record(&txn, Some(app.id), None, None).await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    if let Some(key) = blob_key {
+        blob_store.delete(&key).await?;
+    }
 
     Ok(StatusCode::NO_CONTENT)
 }
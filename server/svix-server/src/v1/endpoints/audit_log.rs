@@ -0,0 +1,163 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use aide::axum::{routing::get_with, ApiRouter};
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+use svix_server_derive::aide_annotate;
+use validator::Validate;
+
+use crate::{
+    core::{permissions, types::ApplicationId},
+    db::models::auditlog,
+    error::{HttpError, Result, ValidationErrorItem},
+    v1::utils::{openapi_tag, ListResponse, PaginationLimit, ValidatedQuery},
+    AppState,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogEntryOut {
+    pub id: String,
+    /// The application the changed resource belongs to, if any
+    pub app_id: Option<ApplicationId>,
+    /// The subject of the token the change was made with: the application for application
+    /// tokens, and the organization otherwise
+    pub actor: String,
+    /// The ID (`jti`) of the token the change was made with, if it has one
+    pub token_id: Option<String>,
+    /// What the change was, e.g. `endpoint.updated` or `endpoint.secret.rotated`
+    pub action: String,
+    /// The path of the changed resource, relative to the API root
+    pub resource: String,
+    /// The fields of the resource that changed, before the change. Secrets are redacted.
+    pub before: Option<serde_json::Value>,
+    /// The fields of the resource that changed, after the change. Secrets are redacted.
+    pub after: Option<serde_json::Value>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<auditlog::Model> for AuditLogEntryOut {
+    fn from(model: auditlog::Model) -> Self {
+        Self {
+            id: model.id.to_string(),
+            app_id: model.app_id,
+            actor: model.actor,
+            token_id: model.token_id,
+            action: model.action,
+            resource: model.resource,
+            before: model.before,
+            after: model.after,
+            timestamp: model.created_at.into(),
+        }
+    }
+}
+
+fn default_audit_log_limit() -> PaginationLimit {
+    PaginationLimit(50)
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct ListAuditLogQueryParams {
+    /// Limit the number of returned items
+    #[validate]
+    #[serde(default = "default_audit_log_limit")]
+    limit: PaginationLimit,
+    /// The iterator returned from a prior invocation
+    iterator: Option<String>,
+    /// Only include the changes to the application's resources
+    app_id: Option<ApplicationId>,
+    /// Only include the changes of this kind, e.g. `endpoint.updated`
+    action: Option<String>,
+    /// Only include the changes made with tokens of this subject
+    actor: Option<String>,
+    /// Only include the changes to this resource, or the resources under it
+    resource: Option<String>,
+    /// Only include items created before a certain date
+    before: Option<DateTime<Utc>>,
+    /// Only include items created after a certain date
+    after: Option<DateTime<Utc>>,
+}
+
+/// List the changes made to the organization's configuration, newest first.
+///
+/// Every change to applications, endpoints and their settings, event types and the
+/// organization's policies made through the API is recorded, along with the token it was made
+/// with and the fields it changed. Secrets are redacted.
+#[aide_annotate(op_id = "v1.audit-log.list")]
+async fn list_audit_log(
+    State(AppState { ref db, .. }): State<AppState>,
+    ValidatedQuery(ListAuditLogQueryParams {
+        limit,
+        iterator,
+        app_id,
+        action,
+        actor,
+        resource,
+        before,
+        after,
+    }): ValidatedQuery<ListAuditLogQueryParams>,
+    permissions::Organization { org_id }: permissions::Organization,
+) -> Result<Json<ListResponse<AuditLogEntryOut>>> {
+    let mut query = auditlog::Entity::find()
+        .filter(auditlog::Column::OrgId.eq(org_id))
+        .order_by_desc(auditlog::Column::Id)
+        .limit(limit.0 + 1);
+
+    if let Some(iterator) = iterator {
+        let id: i64 = iterator.parse().map_err(|_| {
+            HttpError::unprocessable_entity(vec![ValidationErrorItem {
+                loc: vec!["query".to_owned(), "iterator".to_owned()],
+                msg: "Invalid iterator".to_owned(),
+                ty: "value_error".to_owned(),
+            }])
+        })?;
+        query = query.filter(auditlog::Column::Id.lt(id));
+    }
+    if let Some(app_id) = app_id {
+        query = query.filter(auditlog::Column::AppId.eq(app_id));
+    }
+    if let Some(action) = action {
+        query = query.filter(auditlog::Column::Action.eq(action));
+    }
+    if let Some(actor) = actor {
+        query = query.filter(auditlog::Column::Actor.eq(actor));
+    }
+    if let Some(resource) = resource {
+        let resource = resource.trim_end_matches('/');
+        query = query.filter(
+            Condition::any()
+                .add(auditlog::Column::Resource.eq(resource))
+                .add(auditlog::Column::Resource.starts_with(format!("{resource}/"))),
+        );
+    }
+    if let Some(before) = before {
+        query = query.filter(auditlog::Column::CreatedAt.lt(before));
+    }
+    if let Some(after) = after {
+        query = query.filter(auditlog::Column::CreatedAt.gt(after));
+    }
+
+    let mut entries = query.all(db).await?;
+    let done = entries.len() as u64 <= limit.0;
+    entries.truncate(limit.0 as usize);
+
+    Ok(Json(ListResponse {
+        iterator: entries.last().map(|e| e.id.to_string()),
+        prev_iterator: None,
+        data: entries.into_iter().map(Into::into).collect(),
+        done,
+    }))
+}
+
+pub fn router() -> ApiRouter<AppState> {
+    let tag = openapi_tag("Audit Log");
+    ApiRouter::new().api_route_with(
+        "/audit-log",
+        get_with(list_audit_log, list_audit_log_operation),
+        tag,
+    )
+}
@@ -12,7 +12,7 @@ use svix_server_derive::aide_annotate;
 use validator::Validate;
 
 use crate::{
-    core::{permissions, security::generate_app_token, types::FeatureFlagSet},
+    core::{audit_log::Auditor, permissions, security::generate_app_token, types::FeatureFlagSet},
     error::{HttpError, Result},
     v1::utils::{api_not_implemented, openapi_tag, ApplicationPath, ValidatedJson},
     AppState,
@@ -62,9 +62,10 @@ impl From<DashboardAccessOut> for AppPortalAccessOut {
 /// Use this function to get magic links (and authentication codes) for connecting your users to the Consumer Application Portal.
 #[aide_annotate(op_id = "v1.authentication.app-portal-access")]
 async fn app_portal_access(
-    State(AppState { cfg, .. }): State<AppState>,
+    State(AppState { ref db, cfg, .. }): State<AppState>,
     _: Path<ApplicationPath>,
     permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<AppPortalAccessIn>,
 ) -> Result<Json<AppPortalAccessOut>> {
     let token = generate_app_token(
@@ -74,6 +75,9 @@ async fn app_portal_access(
         data.feature_flags,
     )?;
 
+    let entry = auditor.record(db, Some(app.id.clone()), None, None).await?;
+    auditor.notify(entry).await;
+
     let login_key = serde_json::to_vec(&serde_json::json!({
         "appId": app.id,
         "token": token,
@@ -100,11 +104,13 @@ async fn dashboard_access(
     state: State<AppState>,
     path: Path<ApplicationPath>,
     permissions: permissions::OrganizationWithApplication,
+    auditor: Auditor,
 ) -> Result<Json<DashboardAccessOut>> {
     app_portal_access(
         state,
         path,
         permissions,
+        auditor,
         ValidatedJson(AppPortalAccessIn {
             feature_flags: FeatureFlagSet::default(),
         }),
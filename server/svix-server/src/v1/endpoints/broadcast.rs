@@ -0,0 +1,273 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashSet;
+
+use aide::axum::{
+    routing::{get_with, post_with},
+    ApiRouter,
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use sea_orm::{
+    entity::prelude::*, ActiveValue::Set, DatabaseConnection, IntoActiveModel, QueryOrder,
+    QuerySelect, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use svix_server_derive::aide_annotate;
+use validator::Validate;
+
+use super::message::{create_messages_bulk, deliver_at_error, schema_version_error, MessageIn};
+use crate::{
+    core::{
+        audit_log::Auditor,
+        blob_store::BlobStore,
+        broadcast::BroadcastSelector,
+        cache::Cache,
+        permissions,
+        types::{ApplicationId, BroadcastId, BroadcastStatus},
+    },
+    db::models::{application, broadcast, message},
+    error::{Error, HttpError, Result, ValidationErrorItem},
+    queue::{BroadcastTask, TaskQueueProducer},
+    v1::utils::{openapi_tag, JsonStatus, ValidatedJson},
+    AppState,
+};
+
+/// Number of applications messages are created for by each broadcast task.
+const BATCH_SIZE: u64 = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Validate, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastIn {
+    /// The message created for each of the selected applications
+    #[validate]
+    pub message: MessageIn,
+    #[validate]
+    pub selector: BroadcastSelector,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastOut {
+    pub id: BroadcastId,
+    pub status: BroadcastStatus,
+    pub selector: BroadcastSelector,
+    /// The number of messages created so far, one per application
+    pub num_messages: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<broadcast::Model> for BroadcastOut {
+    fn from(model: broadcast::Model) -> Self {
+        Self {
+            id: model.id,
+            status: model.status,
+            selector: model.selector,
+            num_messages: model.num_messages,
+            created_at: model.created_at.into(),
+            updated_at: model.updated_at.into(),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct BroadcastPath {
+    broadcast_id: BroadcastId,
+}
+
+/// Send a message to many of the organization's applications.
+///
+/// A message is created for each application matching the selector, in the background. Their
+/// `broadcastId` is set to the ID of the returned broadcast, which can be fetched to follow its
+/// progress.
+#[aide_annotate(op_id = "v1.broadcast.create")]
+async fn create_broadcast(
+    State(AppState {
+        ref db,
+        queue_tx,
+        cfg,
+        ..
+    }): State<AppState>,
+    permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
+    ValidatedJson(data): ValidatedJson<BroadcastIn>,
+) -> Result<JsonStatus<202, BroadcastOut>> {
+    if let Some(msg) = deliver_at_error(&cfg, data.message.deliver_at) {
+        return Err(HttpError::unprocessable_entity(vec![ValidationErrorItem {
+            loc: vec![
+                "body".to_owned(),
+                "message".to_owned(),
+                "deliverAt".to_owned(),
+            ],
+            msg,
+            ty: "value_error".to_owned(),
+        }])
+        .into());
+    }
+    if let Some(msg) = schema_version_error(db, org_id.clone(), &data.message).await? {
+        return Err(HttpError::unprocessable_entity(vec![ValidationErrorItem {
+            loc: vec![
+                "body".to_owned(),
+                "message".to_owned(),
+                "schemaVersion".to_owned(),
+            ],
+            msg,
+            ty: "value_error".to_owned(),
+        }])
+        .into());
+    }
+
+    let message_in = serde_json::to_string(&data.message).map_err(Error::generic)?;
+    let txn = db.begin().await?;
+    let broadcast = broadcast::ActiveModel::new(org_id, data.selector, message_in)
+        .insert(&txn)
+        .await?;
+    let entry = auditor.record(&txn, None, None, None).await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    queue_tx
+        .send(&BroadcastTask::new_task(broadcast.id.clone()), None)
+        .await?;
+
+    Ok(JsonStatus(broadcast.into()))
+}
+
+/// Get a broadcast, to follow its progress.
+#[aide_annotate(op_id = "v1.broadcast.get")]
+async fn get_broadcast(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(BroadcastPath { broadcast_id }): Path<BroadcastPath>,
+    permissions::Organization { org_id }: permissions::Organization,
+) -> Result<Json<BroadcastOut>> {
+    let broadcast = broadcast::Entity::secure_find_by_id(org_id, broadcast_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    Ok(Json(broadcast.into()))
+}
+
+/// Creates the messages of the next batch of applications, and queues the following batch if
+/// there are more.
+pub(crate) async fn process_broadcast_batch(
+    db: &DatabaseConnection,
+    queue_tx: &TaskQueueProducer,
+    cache: &Cache,
+    blob_store: &BlobStore,
+    BroadcastTask { broadcast_id }: BroadcastTask,
+) -> Result<()> {
+    let broadcast = broadcast::Entity::find_by_id(broadcast_id.clone())
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            Error::generic(format!(
+                "Unexpected: broadcast doesn't exist {broadcast_id}"
+            ))
+        })?;
+    if broadcast.status != BroadcastStatus::Running {
+        return Ok(());
+    }
+
+    let msg: MessageIn = match serde_json::from_str(&broadcast.message_in) {
+        Ok(msg) => msg,
+        Err(e) => {
+            tracing::error!("Invalid message for broadcast {broadcast_id}: {e}");
+            let mut broadcast = broadcast.into_active_model();
+            broadcast.status = Set(BroadcastStatus::Failed);
+            broadcast.update(db).await?;
+            return Ok(());
+        }
+    };
+
+    let mut query = application::Entity::secure_find(broadcast.org_id.clone())
+        .filter(broadcast.selector.condition())
+        .order_by_asc(application::Column::Id)
+        .limit(BATCH_SIZE);
+    if let Some(cursor) = &broadcast.cursor {
+        query = query.filter(application::Column::Id.gt(cursor.clone()));
+    }
+    let apps = query.all(db).await?;
+    let done = (apps.len() as u64) < BATCH_SIZE;
+    let cursor = apps
+        .last()
+        .map(|app| app.id.clone())
+        .or_else(|| broadcast.cursor.clone());
+
+    // The task may be retried after the messages of this batch were created, so they're only
+    // created for the applications which didn't get one yet.
+    let already_sent: HashSet<ApplicationId> = message::Entity::find()
+        .select_only()
+        .column(message::Column::AppId)
+        .filter(message::Column::BroadcastId.eq(broadcast_id.clone()))
+        .filter(message::Column::AppId.is_in(apps.iter().map(|app| app.id.clone())))
+        .into_tuple()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+
+    let items: Vec<_> = apps
+        .into_iter()
+        .filter(|app| !already_sent.contains(&app.id))
+        .map(|app| Ok((app, msg.clone())))
+        .collect();
+    let created = if items.is_empty() {
+        0
+    } else {
+        create_messages_bulk(
+            db,
+            queue_tx,
+            cache,
+            blob_store,
+            false,
+            items,
+            Some(broadcast_id.clone()),
+            // Created in the background, long after the request
+            None,
+        )
+        .await?
+        .data
+        .iter()
+        .filter(|result| result.message.is_some())
+        .count()
+    };
+
+    let num_messages = broadcast.num_messages + (created + already_sent.len()) as i32;
+    let mut broadcast = broadcast.into_active_model();
+    broadcast.cursor = Set(cursor);
+    broadcast.num_messages = Set(num_messages);
+    if done {
+        broadcast.status = Set(BroadcastStatus::Finished);
+    }
+    broadcast.update(db).await?;
+
+    if !done {
+        queue_tx
+            .send(&BroadcastTask::new_task(broadcast_id), None)
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub fn router() -> ApiRouter<AppState> {
+    let tag = openapi_tag("Broadcast");
+    ApiRouter::new()
+        .api_route_with(
+            "/broadcast",
+            post_with(create_broadcast, create_broadcast_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/broadcast/:broadcast_id",
+            get_with(get_broadcast, get_broadcast_operation),
+            tag,
+        )
+}
@@ -4,18 +4,23 @@ use axum::{
     extract::{Path, State},
     Json,
 };
-use sea_orm::{entity::prelude::*, ActiveValue::Set, QuerySelect, TransactionTrait};
+use sea_orm::{entity::prelude::*, ActiveValue::Set, QuerySelect, TransactionTrait, TryIntoModel};
+use serde_json::Value;
 use svix_server_derive::aide_annotate;
 use url::Url;
 
 use self::hack::EventTypeNameResult;
-use super::{EndpointIn, EndpointOut, EndpointPatch, EndpointUpdate};
+use super::{
+    verification::{require_verification, start_verification},
+    EndpointIn, EndpointOut, EndpointPatch, EndpointUpdate,
+};
 use crate::{
-    cfg::Configuration,
     core::{
-        operational_webhooks::{EndpointEvent, OperationalWebhook, OperationalWebhookSender},
+        audit_log::{self, Auditor},
+        operational_webhooks::{EndpointEvent, OperationalWebhook},
         permissions,
         types::{EndpointId, EventTypeName, EventTypeNameSet, OrganizationId},
+        url_policy::fetch_url_policy,
     },
     db::models::{application, endpoint, endpointmetadata, eventtype},
     error::{http_error_on_conflict, HttpError, Result, Traceable, ValidationErrorItem},
@@ -44,14 +49,14 @@ pub(super) async fn list_endpoints(
         .map_or(IteratorDirection::Normal, |iter| iter.direction());
 
     let query = apply_pagination(
-        endpoint::Entity::secure_find(app.id),
+        endpoint::Entity::secure_find(app.id.clone()),
         endpoint::Column::Id,
         limit,
         iterator,
         pagination.order.unwrap_or(Ordering::Descending),
     );
 
-    let results = query
+    let mut results: Vec<EndpointOut> = query
         .find_also_related(endpointmetadata::Entity)
         .all(db)
         .await?
@@ -61,6 +66,7 @@ pub(super) async fn list_endpoints(
             (endp, metadata).into()
         })
         .collect();
+    resolve_filter_types(db, &app.org_id, &mut results).await?;
 
     Ok(Json(EndpointOut::list_response(
         results,
@@ -70,27 +76,43 @@ pub(super) async fn list_endpoints(
 }
 
 async fn create_endp_from_data(
-    db: &DatabaseConnection,
-    cfg: &Configuration,
-    op_webhooks: &OperationalWebhookSender,
+    AppState {
+        db,
+        cfg,
+        cache,
+        queue_tx,
+        op_webhooks,
+        ..
+    }: &AppState,
+    auditor: &Auditor,
     app: application::Model,
     mut data: EndpointIn,
 ) -> Result<(endpoint::Model, endpointmetadata::Model)> {
     let key = data.key_take_or_generate(&cfg.encryption, &cfg.default_signature_type)?;
 
-    let mut endp = endpoint::ActiveModel::new(app.id, key);
+    let mut endp = endpoint::ActiveModel::new(app.id.clone(), key);
     let metadata =
         endpointmetadata::ActiveModel::new(endp.id.clone().unwrap(), mem::take(&mut data.metadata));
     data.update_model(&mut endp);
+    let verify = require_verification(cfg, &mut endp, None);
 
     let (endp, metadata) = {
         let txn = db.begin().await?;
         let endp = endp.insert(&txn).await.map_err(http_error_on_conflict)?;
         let metadata = metadata.upsert_or_delete(&txn).await.trace()?;
+        let after = audit_log::state(EndpointOut::from((endp.clone(), metadata.data.clone())));
+        let entry = auditor
+            .record(&txn, Some(app.id.clone()), None, after)
+            .await?;
         txn.commit().await?;
+        auditor.notify(entry).await;
         (endp, metadata)
     };
 
+    if verify {
+        start_verification(cache, queue_tx, &app, &endp).await?;
+    }
+
     op_webhooks
         .send_operational_webhook(
             &app.org_id,
@@ -106,26 +128,28 @@ async fn create_endp_from_data(
 /// When `secret` is `null` the secret is automatically generated (recommended)
 #[aide_annotate(op_id = "v1.endpoint.create")]
 pub(super) async fn create_endpoint(
-    State(AppState {
-        ref db,
-        ref cfg,
-        op_webhooks,
-        ..
-    }): State<AppState>,
+    State(state): State<AppState>,
     _: Path<ApplicationPath>,
     permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<EndpointIn>,
 ) -> Result<JsonStatus<201, EndpointOut>> {
+    let AppState { db, cfg, .. } = &state;
+
     if let Some(ref event_types_ids) = data.event_types_ids {
         validate_event_types(db, event_types_ids, &app.org_id).await?;
     }
     validate_endpoint_url(&data.url, cfg.endpoint_https_only)?;
+    validate_endpoint_url_policy(db, &app.org_id, &data.url).await?;
 
-    let (endp, metadata) = create_endp_from_data(db, cfg, &op_webhooks, app, data)
+    let org_id = app.org_id.clone();
+    let (endp, metadata) = create_endp_from_data(&state, &auditor, app, data)
         .await
         .trace()?;
 
-    Ok(JsonStatus((endp, metadata.data).into()))
+    let mut out: EndpointOut = (endp, metadata.data).into();
+    resolve_filter_types(db, &org_id, std::slice::from_mut(&mut out)).await?;
+    Ok(JsonStatus(out))
 }
 
 /// Get an endpoint.
@@ -143,24 +167,56 @@ pub(super) async fn get_endpoint(
 
     let metadata = metadata.map(|m| m.data).unwrap_or_default();
 
-    Ok(Json((endp, metadata).into()))
+    let mut out: EndpointOut = (endp, metadata).into();
+    resolve_filter_types(db, &app.org_id, std::slice::from_mut(&mut out)).await?;
+    Ok(Json(out))
+}
+
+/// The state of an endpoint, as fetched to be updated, recorded in the audit log.
+fn endpoint_state(
+    endp: &endpoint::ActiveModel,
+    metadata: &endpointmetadata::ActiveModel,
+) -> Result<Option<Value>> {
+    let endp = endp.clone().try_into_model()?;
+    let metadata = metadata.data.clone().take().unwrap_or_default();
+    Ok(audit_log::state(EndpointOut::from((endp, metadata))))
 }
 
 async fn update_endp_from_data(
-    db: &DatabaseConnection,
-    op_webhooks: &OperationalWebhookSender,
+    AppState {
+        db,
+        cfg,
+        cache,
+        queue_tx,
+        op_webhooks,
+        ..
+    }: &AppState,
+    auditor: &Auditor,
     app: application::Model,
-    endp: endpoint::ActiveModel,
+    before: Option<Value>,
+    old_url: &str,
+    mut endp: endpoint::ActiveModel,
     metadata: endpointmetadata::ActiveModel,
 ) -> Result<(endpoint::Model, endpointmetadata::Model)> {
+    let verify = require_verification(cfg, &mut endp, Some(old_url));
+
     let (endp, metadata) = {
         let txn = db.begin().await?;
         let endp = endp.update(&txn).await.map_err(http_error_on_conflict)?;
         let metadata = metadata.upsert_or_delete(&txn).await.trace()?;
+        let after = audit_log::state(EndpointOut::from((endp.clone(), metadata.data.clone())));
+        let entry = auditor
+            .record(&txn, Some(app.id.clone()), before, after)
+            .await?;
         txn.commit().await?;
+        auditor.notify(entry).await;
         (endp, metadata)
     };
 
+    if verify {
+        start_verification(cache, queue_tx, &app, &endp).await?;
+    }
+
     let app_uid = app.uid;
     op_webhooks
         .send_operational_webhook(
@@ -175,76 +231,88 @@ async fn update_endp_from_data(
 /// Update an endpoint.
 #[aide_annotate(op_id = "v1.endpoint.update")]
 pub(super) async fn update_endpoint(
-    State(AppState {
-        ref db,
-        ref cfg,
-        ref op_webhooks,
-        ..
-    }): State<AppState>,
+    State(state): State<AppState>,
     Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
     permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
     ValidatedJson(mut data): ValidatedJson<EndpointUpdate>,
 ) -> Result<JsonStatusUpsert<EndpointOut>> {
+    let AppState { db, cfg, .. } = &state;
+
     if let Some(ref event_types_ids) = data.event_types_ids {
         validate_event_types(db, event_types_ids, &app.org_id).await?;
     }
     validate_endpoint_url(&data.url, cfg.endpoint_https_only)?;
+    validate_endpoint_url_policy(db, &app.org_id, &data.url).await?;
 
     let models = endpoint::ActiveModel::fetch_with_metadata(db, app.id.clone(), endpoint_id)
         .await
         .trace()?;
 
+    let org_id = app.org_id.clone();
     if let Some((mut endp, mut metadata)) = models {
+        let before = endpoint_state(&endp, &metadata)?;
         metadata.data = Set(mem::take(&mut data.metadata));
+        let old_url = endp.url.clone().unwrap();
         data.update_model(&mut endp);
-        let (endp, metadata) = update_endp_from_data(db, op_webhooks, app, endp, metadata)
-            .await
-            .trace()?;
-        Ok(JsonStatusUpsert::Updated((endp, metadata.data).into()))
+        let (endp, metadata) =
+            update_endp_from_data(&state, &auditor, app, before, &old_url, endp, metadata)
+                .await
+                .trace()?;
+        let mut out: EndpointOut = (endp, metadata.data).into();
+        resolve_filter_types(db, &org_id, std::slice::from_mut(&mut out)).await?;
+        Ok(JsonStatusUpsert::Updated(out))
     } else {
         let data = data.into_in_with_default_key();
-        let (endp, metadata) = create_endp_from_data(db, cfg, op_webhooks, app, data)
+        let (endp, metadata) = create_endp_from_data(&state, &auditor, app, data)
             .await
             .trace()?;
-        Ok(JsonStatusUpsert::Created((endp, metadata.data).into()))
+        let mut out: EndpointOut = (endp, metadata.data).into();
+        resolve_filter_types(db, &org_id, std::slice::from_mut(&mut out)).await?;
+        Ok(JsonStatusUpsert::Created(out))
     }
 }
 
 /// Partially update an endpoint.
 #[aide_annotate]
 pub(super) async fn patch_endpoint(
-    State(AppState {
-        ref db,
-        cfg,
-        ref op_webhooks,
-        ..
-    }): State<AppState>,
+    State(state): State<AppState>,
     Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
     permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<EndpointPatch>,
 ) -> Result<Json<EndpointOut>> {
+    let AppState { db, cfg, .. } = &state;
+
     if let UnrequiredNullableField::Some(ref event_types_ids) = data.event_types_ids {
         validate_event_types(db, event_types_ids, &app.org_id).await?;
     }
     if let UnrequiredField::Some(url) = &data.url {
         validate_endpoint_url(url, cfg.endpoint_https_only)?;
+        validate_endpoint_url_policy(db, &app.org_id, url).await?;
     }
 
     let (mut endp, mut metadata) =
         endpoint::ActiveModel::fetch_with_metadata(db, app.id.clone(), endpoint_id)
             .await?
             .ok_or_else(|| HttpError::not_found(None, None))?;
+    let before = endpoint_state(&endp, &metadata)?;
 
     let mut patch_data = data; // need to alias so we can use data for `patch_field_non_nullable!`
 
     let data = mem::take(&mut patch_data.metadata);
     patch_field_non_nullable!(metadata, data);
+    let old_url = endp.url.clone().unwrap();
     patch_data.update_model(&mut endp);
-    let (endp, metadata) = update_endp_from_data(db, op_webhooks, app, endp, metadata)
-        .await
-        .trace()?;
+    let org_id = app.org_id.clone();
+    let (endp, metadata) =
+        update_endp_from_data(&state, &auditor, app, before, &old_url, endp, metadata)
+            .await
+            .trace()?;
 
-    Ok(Json((endp, metadata.data).into()))
+    let mut out: EndpointOut = (endp, metadata.data).into();
+    resolve_filter_types(db, &org_id, std::slice::from_mut(&mut out)).await?;
+    Ok(Json(out))
 }
 
 /// Delete an endpoint.
@@ -257,11 +325,15 @@ pub(super) async fn delete_endpoint(
     }): State<AppState>,
     Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
     permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
 ) -> Result<NoContent> {
-    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+    let (endp, metadata) = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .find_also_related(endpointmetadata::Entity)
         .one(db)
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
+    let metadata = metadata.map(|m| m.data).unwrap_or_default();
+    let before = audit_log::state(EndpointOut::from((endp.clone(), metadata)));
 
     // Cloning the ID/UID out of endp before it's consumed below
     let endpoint_id = endp.id.clone();
@@ -270,7 +342,14 @@ pub(super) async fn delete_endpoint(
     let mut endp: endpoint::ActiveModel = endp.into();
     endp.deleted = Set(true);
     endp.uid = Set(None); // We don't want deleted UIDs to clash
-    endp.update(db).await?;
+
+    let txn = db.begin().await?;
+    endp.update(&txn).await?;
+    let entry = auditor
+        .record(&txn, Some(app.id.clone()), before, None)
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
 
     op_webhooks
         .send_operational_webhook(
@@ -299,11 +378,10 @@ mod hack {
     }
 }
 
-async fn validate_event_types(
+async fn fetch_event_type_names(
     db: &DatabaseConnection,
-    event_types_ids: &EventTypeNameSet,
     org_id: &OrganizationId,
-) -> Result<()> {
+) -> Result<HashSet<EventTypeName>> {
     let event_types: Vec<EventTypeNameResult> = eventtype::Entity::secure_find(org_id.clone())
         .filter(eventtype::Column::Deleted.eq(false))
         .select_only()
@@ -311,12 +389,26 @@ async fn validate_event_types(
         .into_model::<EventTypeNameResult>()
         .all(db)
         .await?;
-    let event_types: HashSet<EventTypeName> =
-        HashSet::from_iter(event_types.into_iter().map(|x| x.name));
+    Ok(HashSet::from_iter(event_types.into_iter().map(|x| x.name)))
+}
+
+async fn validate_event_types(
+    db: &DatabaseConnection,
+    event_types_ids: &EventTypeNameSet,
+    org_id: &OrganizationId,
+) -> Result<()> {
+    let event_types = fetch_event_type_names(db, org_id).await?;
+    // Patterns have to match at least one event type, to catch typos
     let missing: Vec<&EventTypeName> = event_types_ids
         .0
         .iter()
-        .filter(|x| !event_types.contains(x))
+        .filter(|x| {
+            if x.is_pattern() {
+                !event_types.iter().any(|event_type| x.matches(event_type))
+            } else {
+                !event_types.contains(x)
+            }
+        })
         .collect();
 
     if missing.is_empty() {
@@ -336,6 +428,35 @@ async fn validate_event_types(
     }
 }
 
+/// Sets the event types currently matched by the endpoints' `filterTypes`.
+async fn resolve_filter_types(
+    db: &DatabaseConnection,
+    org_id: &OrganizationId,
+    endpoints: &mut [EndpointOut],
+) -> Result<()> {
+    if endpoints
+        .iter()
+        .all(|endp| endp.ep.event_types_ids.is_none())
+    {
+        return Ok(());
+    }
+
+    let event_types = fetch_event_type_names(db, org_id).await?;
+    for endp in endpoints {
+        endp.ep.resolved_filter_types = endp.ep.event_types_ids.as_ref().map(|patterns| {
+            EventTypeNameSet(
+                event_types
+                    .iter()
+                    .filter(|event_type| patterns.matches(event_type))
+                    .cloned()
+                    .collect(),
+            )
+        });
+    }
+
+    Ok(())
+}
+
 fn validate_endpoint_url(url: &Url, https_only: bool) -> Result<()> {
     if !https_only {
         return Ok(());
@@ -353,3 +474,24 @@ fn validate_endpoint_url(url: &Url, https_only: bool) -> Result<()> {
         .into())
     }
 }
+
+/// Checks the URL against the organization's URL policy. The IPs the host resolves to are only
+/// checked when sending webhooks, as they may change at any time.
+async fn validate_endpoint_url_policy(
+    db: &DatabaseConnection,
+    org_id: &OrganizationId,
+    url: &Url,
+) -> Result<()> {
+    let Some(policy) = fetch_url_policy(db, org_id).await? else {
+        return Ok(());
+    };
+
+    policy.check_url(url).map_err(|e| {
+        HttpError::unprocessable_entity(vec![ValidationErrorItem {
+            loc: vec!["body".to_owned(), "url".to_owned()],
+            msg: e.to_string(),
+            ty: "value_error".to_owned(),
+        }])
+        .into()
+    })
+}
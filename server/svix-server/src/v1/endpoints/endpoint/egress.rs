@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, TransactionTrait};
+use svix_server_derive::aide_annotate;
+
+use crate::{
+    core::{
+        audit_log::{self, Auditor},
+        operational_webhooks::{EndpointEvent, OperationalWebhook},
+        permissions,
+    },
+    db::models::endpoint,
+    error::{HttpError, Result},
+    v1::{
+        endpoints::application::{validate_egress_proxy, EgressIn, EgressOut},
+        utils::{ApplicationEndpointPath, ValidatedJson},
+    },
+    AppState,
+};
+
+/// Get the egress proxy of the endpoint and the IPs its webhooks are sent from.
+///
+/// Endpoints without an egress proxy of their own use the one of their application.
+#[aide_annotate(op_id = "v1.endpoint.get-egress")]
+pub(super) async fn get_endpoint_egress(
+    State(AppState { ref db, cfg, .. }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<EgressOut>> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let effective = endp.egress_proxy.as_deref().or(app.egress_proxy.as_deref());
+    Ok(Json(EgressOut::new(
+        &cfg,
+        endp.egress_proxy.clone(),
+        effective,
+    )))
+}
+
+/// Set the egress proxy webhooks to the endpoint are sent through.
+#[aide_annotate(op_id = "v1.endpoint.update-egress")]
+pub(super) async fn update_endpoint_egress(
+    State(AppState {
+        ref db,
+        cfg,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+    ValidatedJson(data): ValidatedJson<EgressIn>,
+) -> Result<Json<EgressOut>> {
+    validate_egress_proxy(&cfg, data.proxy.as_deref())?;
+
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let egress_out = |endp: &endpoint::Model| {
+        let effective = endp.egress_proxy.as_deref().or(app.egress_proxy.as_deref());
+        EgressOut::new(&cfg, endp.egress_proxy.clone(), effective)
+    };
+
+    let before = audit_log::state(egress_out(&endp));
+    let endp = endpoint::ActiveModel {
+        egress_proxy: Set(data.proxy),
+        ..endp.into()
+    };
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let out = egress_out(&endp);
+    let entry = auditor
+        .record(&txn, Some(app.id.clone()), before, audit_log::state(&out))
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    op_webhooks
+        .send_operational_webhook(
+            &app.org_id,
+            OperationalWebhook::EndpointUpdated(EndpointEvent::new(app.uid.as_ref(), &endp)),
+        )
+        .await?;
+
+    Ok(Json(out))
+}
@@ -2,18 +2,31 @@ use axum::{
     extract::{Path, State},
     Json,
 };
-use sea_orm::ActiveModelTrait;
+use sea_orm::{ActiveModelTrait, TransactionTrait};
+use serde_json::Value;
 use svix_server_derive::aide_annotate;
 
 use super::{EndpointHeadersIn, EndpointHeadersOut, EndpointHeadersPatchIn};
 use crate::{
-    core::permissions,
+    core::{
+        audit_log::{self, Auditor},
+        permissions,
+    },
     db::models::endpoint,
     error::{HttpError, Result},
     v1::utils::{ApplicationEndpointPath, ModelIn, NoContent, ValidatedJson},
     AppState,
 };
 
+fn headers_audit_state(endp: &endpoint::Model) -> Option<Value> {
+    audit_log::state(
+        endp.headers
+            .clone()
+            .map(EndpointHeadersOut::from)
+            .unwrap_or_default(),
+    )
+}
+
 /// Get the additional headers to be sent with the webhook
 #[aide_annotate(op_id = "v1.endpoint.get-headers")]
 pub(super) async fn get_endpoint_headers(
@@ -38,16 +51,30 @@ pub(super) async fn update_endpoint_headers(
     State(AppState { ref db, .. }): State<AppState>,
     Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
     permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<EndpointHeadersIn>,
 ) -> Result<NoContent> {
     let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
         .one(db)
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
+    let before = headers_audit_state(&endp);
 
     let mut endp: endpoint::ActiveModel = endp.into();
     data.update_model(&mut endp);
-    endp.update(db).await?;
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let entry = auditor
+        .record(
+            &txn,
+            Some(app.id.clone()),
+            before,
+            headers_audit_state(&endp),
+        )
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
 
     Ok(NoContent)
 }
@@ -58,16 +85,30 @@ pub(super) async fn patch_endpoint_headers(
     State(AppState { ref db, .. }): State<AppState>,
     Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
     permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<EndpointHeadersPatchIn>,
 ) -> Result<NoContent> {
     let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
         .one(db)
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
+    let before = headers_audit_state(&endp);
 
     let mut endp: endpoint::ActiveModel = endp.into();
     data.update_model(&mut endp);
-    endp.update(db).await?;
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let entry = auditor
+        .record(
+            &txn,
+            Some(app.id.clone()),
+            before,
+            headers_audit_state(&endp),
+        )
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
 
     Ok(NoContent)
 }
@@ -1,9 +1,15 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 mod crud;
+mod egress;
 mod headers;
+mod oauth2;
+mod pause;
 mod recovery;
 mod secrets;
+mod sink;
+mod tls;
+mod verification;
 
 use std::collections::{HashMap, HashSet};
 
@@ -19,22 +25,29 @@ use chrono::{DateTime, Duration, Utc};
 use schemars::JsonSchema;
 use sea_orm::{ActiveValue::Set, ColumnTrait, FromQueryResult, QueryFilter, QuerySelect};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use svix_server_derive::{aide_annotate, ModelIn, ModelOut};
 use url::Url;
-use validator::{Validate, ValidationError};
+use validator::{Validate, ValidationError, ValidationErrors};
 
 use self::secrets::generate_secret;
 use super::message::{create_message_inner, MessageIn, MessageOut, RawPayload};
 use crate::{
     cfg::DefaultSignatureType,
     core::{
+        audit_log,
         cryptography::Encryption,
+        otel_spans::RequestTraceContext,
+        payload_filter::EndpointFilter,
         permissions,
+        sink::EndpointSinkConfig,
         types::{
             metadata::Metadata, BaseId, EndpointHeaders, EndpointHeadersPatch, EndpointId,
-            EndpointSecret, EndpointSecretInternal, EndpointUid, EventChannelSet, EventTypeName,
-            EventTypeNameSet, MessageEndpointId, MessageStatus,
+            EndpointOAuth2Config, EndpointSecret, EndpointSecretInternal, EndpointTlsConfig,
+            EndpointUid, EndpointVerificationStatus, EventChannelSet, EventTypeName,
+            EventTypeNameSet, MessageEndpointId, MessageStatus, SchemaVersion,
         },
+        webhook_http_client::ClientTlsConfig,
     },
     db::models::{endpoint, eventtype, messagedestination},
     error::{self, HttpError},
@@ -56,6 +69,15 @@ pub fn validate_event_types_ids(event_types_ids: &EventTypeNameSet) -> Result<()
             Some("filterTypes"),
             Some("filterTypes can't be empty, it must have at least one item."),
         ))
+    } else if event_types_ids
+        .0
+        .iter()
+        .any(|x| x.validate_pattern().is_err())
+    {
+        Err(validation_error(
+            Some("filterTypes"),
+            Some("filterTypes must be event type names, or patterns with `*` as whole segments such as `invoice.*`."),
+        ))
     } else {
         Ok(())
     }
@@ -122,6 +144,15 @@ fn example_filter_types() -> Vec<&'static str> {
     vec!["user.signup", "user.deleted"]
 }
 
+fn example_endpoint_filter() -> serde_json::Value {
+    serde_json::json!({
+        "and": [
+            { ">": [{ "var": "amount" }, 1000] },
+            { "==": [{ "var": "currency" }, "EUR"] }
+        ]
+    })
+}
+
 fn endpoint_disabled_default() -> bool {
     false
 }
@@ -167,9 +198,10 @@ pub struct EndpointIn {
     #[serde(default)]
     #[schemars(example = "endpoint_disabled_default")]
     pub disabled: bool,
+    /// Event types this endpoint listens to (omit for all). `*` matches any segment of their
+    /// names, e.g. `invoice.*` or `*.created`.
     #[serde(rename = "filterTypes")]
     #[validate(custom = "validate_event_types_ids")]
-    #[validate]
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schemars(example = "example_filter_types", length(min = 1))]
     pub event_types_ids: Option<EventTypeNameSet>,
@@ -179,6 +211,19 @@ pub struct EndpointIn {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schemars(example = "example_channel_set", length(min = 1, max = 10))]
     pub channels: Option<EventChannelSet>,
+    /// A JsonLogic expression evaluated against the payload of each message, only messages it
+    /// holds true for are sent to the endpoint (omit for all)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = "example_endpoint_filter")]
+    pub filter: Option<EndpointFilter>,
+    /// The version of the event types' schemas the endpoint expects. Messages declaring another
+    /// version aren't sent to it (omit for all)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<SchemaVersion>,
+    /// Send the W3C trace context of the request which created each message in `traceparent`
+    /// and `tracestate` headers, so the endpoint can join the sender's distributed trace
+    #[serde(default)]
+    pub propagate_trace_context: bool,
 
     #[validate]
     #[serde(default)]
@@ -219,6 +264,9 @@ impl ModelIn for EndpointIn {
             disabled,
             event_types_ids,
             channels,
+            filter,
+            schema_version,
+            propagate_trace_context,
             key: _,
             metadata: _,
         } = self;
@@ -231,6 +279,9 @@ impl ModelIn for EndpointIn {
         model.disabled = Set(disabled);
         model.event_types_ids = Set(event_types_ids);
         model.channels = Set(channels);
+        model.filter = Set(filter);
+        model.schema_version = Set(schema_version);
+        model.propagate_trace_context = Set(propagate_trace_context);
     }
 }
 
@@ -265,9 +316,10 @@ struct EndpointUpdate {
     #[schemars(example = "endpoint_disabled_default")]
     pub disabled: bool,
 
+    /// Event types this endpoint listens to (omit for all). `*` matches any segment of their
+    /// names, e.g. `invoice.*` or `*.created`.
     #[serde(rename = "filterTypes")]
     #[validate(custom = "validate_event_types_ids")]
-    #[validate]
     #[schemars(example = "example_filter_types", length(min = 1))]
     pub event_types_ids: Option<EventTypeNameSet>,
 
@@ -277,6 +329,22 @@ struct EndpointUpdate {
     #[schemars(example = "example_channel_set", length(min = 1, max = 10))]
     pub channels: Option<EventChannelSet>,
 
+    /// A JsonLogic expression evaluated against the payload of each message, only messages it
+    /// holds true for are sent to the endpoint (omit for all)
+    #[serde(default)]
+    #[schemars(example = "example_endpoint_filter")]
+    pub filter: Option<EndpointFilter>,
+
+    /// The version of the event types' schemas the endpoint expects. Messages declaring another
+    /// version aren't sent to it (omit for all)
+    #[serde(default)]
+    pub schema_version: Option<SchemaVersion>,
+
+    /// Send the W3C trace context of the request which created each message in `traceparent`
+    /// and `tracestate` headers, so the endpoint can join the sender's distributed trace
+    #[serde(default)]
+    pub propagate_trace_context: bool,
+
     #[serde(default)]
     pub metadata: Metadata,
 }
@@ -295,6 +363,9 @@ impl ModelIn for EndpointUpdate {
             disabled,
             event_types_ids,
             channels,
+            filter,
+            schema_version,
+            propagate_trace_context,
             metadata: _,
         } = self;
 
@@ -306,6 +377,9 @@ impl ModelIn for EndpointUpdate {
         model.disabled = Set(disabled);
         model.event_types_ids = Set(event_types_ids);
         model.channels = Set(channels);
+        model.filter = Set(filter);
+        model.schema_version = Set(schema_version);
+        model.propagate_trace_context = Set(propagate_trace_context);
     }
 }
 
@@ -321,6 +395,9 @@ impl EndpointUpdate {
             disabled,
             event_types_ids,
             channels,
+            filter,
+            schema_version,
+            propagate_trace_context,
             metadata,
         } = self;
 
@@ -333,6 +410,9 @@ impl EndpointUpdate {
             disabled,
             event_types_ids,
             channels,
+            filter,
+            schema_version,
+            propagate_trace_context,
             metadata,
 
             key: None,
@@ -372,7 +452,6 @@ pub struct EndpointPatch {
 
     #[serde(default, rename = "filterTypes")]
     #[validate(custom = "validate_event_types_ids_unrequired_nullable")]
-    #[validate]
     #[serde(skip_serializing_if = "UnrequiredNullableField::is_absent")]
     pub event_types_ids: UnrequiredNullableField<EventTypeNameSet>,
 
@@ -381,6 +460,16 @@ pub struct EndpointPatch {
     #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
     pub channels: UnrequiredNullableField<EventChannelSet>,
 
+    #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
+    pub filter: UnrequiredNullableField<EndpointFilter>,
+
+    #[serde(default, skip_serializing_if = "UnrequiredNullableField::is_absent")]
+    pub schema_version: UnrequiredNullableField<SchemaVersion>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "UnrequiredField::is_absent")]
+    pub propagate_trace_context: UnrequiredField<bool>,
+
     #[validate]
     #[serde(default)]
     #[serde(rename = "secret")]
@@ -406,6 +495,9 @@ impl ModelIn for EndpointPatch {
             disabled,
             event_types_ids,
             channels,
+            filter,
+            schema_version,
+            propagate_trace_context,
             key: _,
             metadata: _,
         } = self;
@@ -421,6 +513,9 @@ impl ModelIn for EndpointPatch {
         patch_field_non_nullable!(model, disabled);
         patch_field_nullable!(model, event_types_ids);
         patch_field_nullable!(model, channels);
+        patch_field_nullable!(model, filter);
+        patch_field_nullable!(model, schema_version);
+        patch_field_non_nullable!(model, propagate_trace_context);
     }
 }
 
@@ -476,12 +571,33 @@ pub struct EndpointOutCommon {
         default = "endpoint_disabled_default"
     )]
     pub disabled: bool,
+    /// Messages to paused endpoints are held rather than sent, until the endpoint is resumed
+    #[serde(default)]
+    pub paused: bool,
     #[serde(rename = "filterTypes")]
     #[schemars(example = "example_filter_types", length(min = 1))]
     pub event_types_ids: Option<EventTypeNameSet>,
+    /// The existing event types matched by `filterTypes`, which may contain patterns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = "example_filter_types")]
+    pub resolved_filter_types: Option<EventTypeNameSet>,
     /// List of message channels this endpoint listens to (omit for all)
     #[schemars(example = "example_channel_set", length(min = 1, max = 10))]
     pub channels: Option<EventChannelSet>,
+    /// A JsonLogic expression evaluated against the payload of each message, only messages it
+    /// holds true for are sent to the endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = "example_endpoint_filter")]
+    pub filter: Option<EndpointFilter>,
+    /// The version of the event types' schemas the endpoint expects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<SchemaVersion>,
+    /// Whether the trace context of messages is sent in `traceparent` and `tracestate` headers
+    #[serde(default)]
+    pub propagate_trace_context: bool,
+    /// Messages aren't sent to endpoints pending verification, or which failed it, while the
+    /// server requires endpoints to echo the challenge sent to their URL
+    pub verification_status: EndpointVerificationStatus,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -496,8 +612,14 @@ impl From<endpoint::Model> for EndpointOutCommon {
             url: model.url,
             version: model.version as u16,
             disabled: model.disabled,
+            paused: model.paused,
             event_types_ids: model.event_types_ids,
+            resolved_filter_types: None,
             channels: model.channels,
+            filter: model.filter,
+            schema_version: model.schema_version,
+            propagate_trace_context: model.propagate_trace_context,
+            verification_status: model.verification_status,
             created_at: model.created_at.into(),
             updated_at: model.updated_at.into(),
         }
@@ -524,6 +646,12 @@ impl From<(endpoint::Model, Metadata)> for EndpointOut {
     }
 }
 
+/// The state of the endpoint recorded in the audit log by the changes leaving its metadata as is,
+/// which is left out of it.
+fn endpoint_audit_state(endp: &endpoint::Model) -> Option<Value> {
+    audit_log::state(EndpointOut::from((endp.clone(), Metadata::default())))
+}
+
 #[derive(Default, Clone, Debug, PartialEq, Eq, Validate, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EndpointSecretRotateIn {
@@ -647,6 +775,165 @@ impl ModelIn for EndpointHeadersPatchIn {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointTlsIn {
+    /// PEM encoded client certificate (optionally followed by its intermediates) presented to the
+    /// endpoint during the TLS handshake. Requires `clientKey`.
+    #[serde(default)]
+    pub client_cert: Option<String>,
+    /// PEM encoded private key of the client certificate. It is stored encrypted and is never
+    /// returned by the API.
+    #[serde(default)]
+    pub client_key: Option<String>,
+    /// PEM encoded CA certificates trusted (in addition to the system roots) when verifying the
+    /// endpoint's certificate.
+    #[serde(default)]
+    pub ca_bundle: Option<String>,
+}
+
+impl Validate for EndpointTlsIn {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+
+        if self.client_cert.is_some() != self.client_key.is_some() {
+            errors.add(
+                "clientKey",
+                validation_error(
+                    Some("tls"),
+                    Some("clientCert and clientKey must be set together."),
+                ),
+            );
+        } else if let Err(e) = ClientTlsConfig::from(self.clone()).validate() {
+            let mut err = validation_error(Some("tls"), None);
+            err.message = Some(e.to_string().into());
+            errors.add("__all__", err);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl From<EndpointTlsIn> for ClientTlsConfig {
+    fn from(tls: EndpointTlsIn) -> Self {
+        ClientTlsConfig {
+            client_cert: tls.client_cert.map(String::into_bytes),
+            client_key: tls.client_key.map(String::into_bytes),
+            ca_bundle: tls.ca_bundle.map(String::into_bytes),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointTlsOut {
+    pub client_cert: Option<String>,
+    /// Whether a private key is configured for the client certificate.
+    pub has_client_key: bool,
+    pub ca_bundle: Option<String>,
+}
+
+impl From<EndpointTlsConfig> for EndpointTlsOut {
+    fn from(tls: EndpointTlsConfig) -> Self {
+        Self {
+            has_client_key: tls.has_client_key(),
+            client_cert: tls.client_cert,
+            ca_bundle: tls.ca_bundle,
+        }
+    }
+}
+
+fn validate_oauth2_scopes(scopes: &[String]) -> Result<(), ValidationError> {
+    if scopes
+        .iter()
+        .any(|s| s.is_empty() || s.chars().any(|c| c.is_whitespace() || c.is_control()))
+    {
+        Err(validation_error(
+            Some("scopes"),
+            Some("Scopes must be non-empty and can't contain whitespace."),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Validate, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointOAuth2In {
+    /// The token endpoint of the authorization server.
+    #[validate(custom = "validate_url")]
+    pub token_url: Url,
+    #[validate(length(min = 1, message = "clientId can't be empty"))]
+    pub client_id: String,
+    /// The client secret. It is stored encrypted and is never returned by the API.
+    #[validate(length(min = 1, message = "clientSecret can't be empty"))]
+    pub client_secret: String,
+    #[validate(custom = "validate_oauth2_scopes")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointOAuth2Out {
+    pub token_url: String,
+    pub client_id: String,
+    pub scopes: Option<Vec<String>>,
+}
+
+impl From<EndpointOAuth2Config> for EndpointOAuth2Out {
+    fn from(oauth2: EndpointOAuth2Config) -> Self {
+        Self {
+            token_url: oauth2.token_url,
+            client_id: oauth2.client_id,
+            scopes: oauth2.scopes,
+        }
+    }
+}
+
+/// The sink of an endpoint, without any of the fields which may carry credentials.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum EndpointSinkOut {
+    Sqs,
+    #[serde(rename_all = "camelCase")]
+    Kafka {
+        topic: String,
+        tls: bool,
+    },
+    #[serde(rename = "rabbitMQ", rename_all = "camelCase")]
+    RabbitMq {
+        exchange: String,
+        routing_key: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Redis {
+        queue_key: String,
+    },
+}
+
+impl From<EndpointSinkConfig> for EndpointSinkOut {
+    fn from(sink: EndpointSinkConfig) -> Self {
+        match sink {
+            EndpointSinkConfig::Sqs { .. } => Self::Sqs,
+            EndpointSinkConfig::Kafka { topic, tls, .. } => Self::Kafka { topic, tls },
+            EndpointSinkConfig::RabbitMq {
+                exchange,
+                routing_key,
+                ..
+            } => Self::RabbitMq {
+                exchange,
+                routing_key,
+            },
+            EndpointSinkConfig::Redis { queue_key, .. } => Self::Redis { queue_key },
+        }
+    }
+}
+
 #[derive(Deserialize, JsonSchema)]
 struct EndpointStatsRange {
     since: Option<DateTime<Utc>>,
@@ -690,6 +977,10 @@ pub struct EndpointStatsOut {
     pub pending: i64,
     pub sending: i64,
     pub fail: i64,
+    /// Messages which weren't sent because of the endpoint's filter or pinned schema version
+    pub skipped: i64,
+    /// Messages held until the endpoint, or its application, is resumed
+    pub paused: i64,
 }
 
 #[derive(Debug, FromQueryResult)]
@@ -736,6 +1027,8 @@ async fn endpoint_stats(
         pending: query_out.remove(&MessageStatus::Pending).unwrap_or(0),
         fail: query_out.remove(&MessageStatus::Fail).unwrap_or(0),
         sending: query_out.remove(&MessageStatus::Sending).unwrap_or(0),
+        skipped: query_out.remove(&MessageStatus::Skipped).unwrap_or(0),
+        paused: query_out.remove(&MessageStatus::Paused).unwrap_or(0),
     }))
 }
 
@@ -757,12 +1050,14 @@ async fn send_example(
     state: State<AppState>,
     Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
     permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    RequestTraceContext(trace_context): RequestTraceContext,
     ValidatedJson(data): ValidatedJson<EventExampleIn>,
 ) -> error::Result<Json<MessageOut>> {
     let State(AppState {
         ref db,
         queue_tx,
         cache,
+        ref blob_store,
         ..
     }) = state;
 
@@ -799,11 +1094,24 @@ async fn send_example(
         payload: RawPayload::from_string(example).unwrap(),
         uid: None,
         payload_retention_period: 90,
+        deliver_at: None,
+        schema_version: None,
+        priority: Default::default(),
         extra_params: None,
     };
 
-    let create_message =
-        create_message_inner(db, queue_tx, cache, false, Some(endpoint.id), msg_in, app).await?;
+    let create_message = create_message_inner(
+        db,
+        queue_tx,
+        cache,
+        blob_store,
+        false,
+        Some(endpoint.id),
+        msg_in,
+        app,
+        trace_context,
+    )
+    .await?;
 
     Ok(Json(create_message))
 }
@@ -859,6 +1167,24 @@ pub fn router() -> ApiRouter<AppState> {
             ),
             &tag,
         )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/pause",
+            post_with(pause::pause_endpoint, pause::pause_endpoint_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/resume",
+            post_with(pause::resume_endpoint, pause::resume_endpoint_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/verify",
+            post_with(
+                verification::verify_endpoint,
+                verification::verify_endpoint_operation,
+            ),
+            &tag,
+        )
         .api_route_with(
             "/app/:app_id/endpoint/:endpoint_id/headers",
             get_with(
@@ -873,6 +1199,54 @@ pub fn router() -> ApiRouter<AppState> {
                 headers::update_endpoint_headers,
                 headers::update_endpoint_headers_operation,
             ),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/tls",
+            get_with(tls::get_endpoint_tls, tls::get_endpoint_tls_operation)
+                .put_with(tls::update_endpoint_tls, tls::update_endpoint_tls_operation)
+                .delete_with(tls::delete_endpoint_tls, tls::delete_endpoint_tls_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/oauth2",
+            get_with(
+                oauth2::get_endpoint_oauth2,
+                oauth2::get_endpoint_oauth2_operation,
+            )
+            .put_with(
+                oauth2::update_endpoint_oauth2,
+                oauth2::update_endpoint_oauth2_operation,
+            )
+            .delete_with(
+                oauth2::delete_endpoint_oauth2,
+                oauth2::delete_endpoint_oauth2_operation,
+            ),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/egress",
+            get_with(
+                egress::get_endpoint_egress,
+                egress::get_endpoint_egress_operation,
+            )
+            .put_with(
+                egress::update_endpoint_egress,
+                egress::update_endpoint_egress_operation,
+            ),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/endpoint/:endpoint_id/sink",
+            get_with(sink::get_endpoint_sink, sink::get_endpoint_sink_operation)
+                .put_with(
+                    sink::update_endpoint_sink,
+                    sink::update_endpoint_sink_operation,
+                )
+                .delete_with(
+                    sink::delete_endpoint_sink,
+                    sink::delete_endpoint_sink_operation,
+                ),
             tag,
         )
 }
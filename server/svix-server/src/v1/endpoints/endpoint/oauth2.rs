@@ -0,0 +1,191 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, DatabaseConnection, TransactionTrait};
+use serde_json::Value;
+use svix_server_derive::aide_annotate;
+use url::Url;
+
+use super::{EndpointOAuth2In, EndpointOAuth2Out};
+use crate::{
+    core::{
+        audit_log::{self, Auditor},
+        oauth2::invalidate_access_token,
+        operational_webhooks::{EndpointEvent, OperationalWebhook},
+        permissions,
+        types::{EndpointOAuth2Config, OrganizationId},
+        url_policy::fetch_url_policy,
+    },
+    db::models::endpoint,
+    error::{HttpError, Result, ValidationErrorItem},
+    v1::utils::{ApplicationEndpointPath, NoContent, ValidatedJson},
+    AppState,
+};
+
+fn oauth2_audit_state(endp: &endpoint::Model) -> Option<Value> {
+    let oauth2 = endp.oauth2.clone()?;
+    audit_log::state(EndpointOAuth2Out::from(oauth2))
+}
+
+/// Get the OAuth2 client credentials used to authenticate webhooks sent to the endpoint.
+///
+/// The client secret is never returned.
+#[aide_annotate(op_id = "v1.endpoint.get-oauth2")]
+pub(super) async fn get_endpoint_oauth2(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<EndpointOAuth2Out>> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id, endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let oauth2 = endp
+        .oauth2
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    Ok(Json(oauth2.into()))
+}
+
+/// Set the OAuth2 client credentials used to authenticate webhooks sent to the endpoint.
+///
+/// Webhooks are sent with an `Authorization: Bearer` header carrying an access token obtained
+/// from the token URL using the client credentials grant.
+#[aide_annotate(op_id = "v1.endpoint.update-oauth2")]
+pub(super) async fn update_endpoint_oauth2(
+    State(AppState {
+        ref db,
+        ref cache,
+        cfg,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+    ValidatedJson(data): ValidatedJson<EndpointOAuth2In>,
+) -> Result<NoContent> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let EndpointOAuth2In {
+        token_url,
+        client_id,
+        client_secret,
+        scopes,
+    } = data;
+    validate_token_url_policy(db, &app.org_id, &token_url).await?;
+    let oauth2 = EndpointOAuth2Config::new(
+        &cfg.encryption,
+        token_url.into(),
+        client_id,
+        &client_secret,
+        scopes,
+    )?;
+
+    let before = oauth2_audit_state(&endp);
+    let endp = endpoint::ActiveModel {
+        oauth2: Set(Some(oauth2)),
+        ..endp.into()
+    };
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let entry = auditor
+        .record(
+            &txn,
+            Some(app.id.clone()),
+            before,
+            oauth2_audit_state(&endp),
+        )
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    // Tokens issued for the previous credentials shouldn't be used anymore
+    invalidate_access_token(cache, &endp.id).await?;
+
+    op_webhooks
+        .send_operational_webhook(
+            &app.org_id,
+            OperationalWebhook::EndpointUpdated(EndpointEvent::new(app.uid.as_ref(), &endp)),
+        )
+        .await?;
+
+    Ok(NoContent)
+}
+
+/// Checks the token URL against the organization's URL policy, like endpoint URLs, as tokens are
+/// requested from the same network. The IPs its host resolves to are only checked when requesting
+/// tokens.
+async fn validate_token_url_policy(
+    db: &DatabaseConnection,
+    org_id: &OrganizationId,
+    token_url: &Url,
+) -> Result<()> {
+    let Some(policy) = fetch_url_policy(db, org_id).await? else {
+        return Ok(());
+    };
+
+    policy.check_url(token_url).map_err(|e| {
+        HttpError::unprocessable_entity(vec![ValidationErrorItem {
+            loc: vec!["body".to_owned(), "tokenUrl".to_owned()],
+            msg: e.to_string(),
+            ty: "value_error".to_owned(),
+        }])
+        .into()
+    })
+}
+
+/// Stop authenticating webhooks sent to the endpoint with OAuth2.
+#[aide_annotate(op_id = "v1.endpoint.delete-oauth2")]
+pub(super) async fn delete_endpoint_oauth2(
+    State(AppState {
+        ref db,
+        ref cache,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+) -> Result<NoContent> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let before = oauth2_audit_state(&endp);
+    let endp = endpoint::ActiveModel {
+        oauth2: Set(None),
+        ..endp.into()
+    };
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let entry = auditor
+        .record(
+            &txn,
+            Some(app.id.clone()),
+            before,
+            oauth2_audit_state(&endp),
+        )
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    invalidate_access_token(cache, &endp.id).await?;
+
+    op_webhooks
+        .send_operational_webhook(
+            &app.org_id,
+            OperationalWebhook::EndpointUpdated(EndpointEvent::new(app.uid.as_ref(), &endp)),
+        )
+        .await?;
+
+    Ok(NoContent)
+}
@@ -0,0 +1,126 @@
+use axum::extract::{Path, State};
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter, TransactionTrait};
+use svix_server_derive::aide_annotate;
+
+use super::endpoint_audit_state;
+use crate::{
+    core::{
+        audit_log::Auditor,
+        pause::{invalidate_cached_app, send_pause_webhooks},
+        permissions,
+    },
+    db::models::{application, endpoint},
+    error::{HttpError, Result},
+    queue::ReleaseHeldDestinationsTask,
+    v1::utils::{ApplicationEndpointPath, NoContent},
+    AppState,
+};
+
+/// Sets whether the endpoint is paused, recording it in the audit log if it changed. Returns
+/// whether it did.
+async fn set_endpoint_paused(
+    db: &sea_orm::DatabaseConnection,
+    auditor: &Auditor,
+    app: &application::Model,
+    endp: &endpoint::Model,
+    paused: bool,
+) -> Result<bool> {
+    let txn = db.begin().await?;
+    // Conditional, so concurrent calls don't both act on the change
+    let res = endpoint::Entity::update_many()
+        .col_expr(endpoint::Column::Paused, Expr::value(paused))
+        .filter(endpoint::Column::Id.eq(endp.id.clone()))
+        .filter(endpoint::Column::Paused.eq(!paused))
+        .exec(&txn)
+        .await?;
+    if res.rows_affected == 0 {
+        return Ok(false);
+    }
+
+    let after = endpoint::Model {
+        paused,
+        ..endp.clone()
+    };
+    let entry = auditor
+        .record(
+            &txn,
+            Some(app.id.clone()),
+            endpoint_audit_state(endp),
+            endpoint_audit_state(&after),
+        )
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    Ok(true)
+}
+
+/// Pause the delivery of messages to the endpoint.
+///
+/// Messages sent to the endpoint while it's paused are stored, and their attempts listed with the
+/// paused status, but they aren't sent until it's resumed.
+#[aide_annotate(op_id = "v1.endpoint.pause")]
+pub(super) async fn pause_endpoint(
+    State(AppState {
+        ref db,
+        ref cache,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+) -> Result<NoContent> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    if set_endpoint_paused(db, &auditor, &app, &endp, true).await? {
+        invalidate_cached_app(cache, &app).await;
+        send_pause_webhooks(op_webhooks, &app, &[endp], true).await?;
+    }
+
+    Ok(NoContent)
+}
+
+/// Resume the delivery of messages to a paused endpoint.
+///
+/// The messages held while it was paused are sent in the order they were created, unless its
+/// application is paused too.
+#[aide_annotate(op_id = "v1.endpoint.resume")]
+pub(super) async fn resume_endpoint(
+    State(AppState {
+        ref db,
+        ref cache,
+        ref queue_tx,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+) -> Result<NoContent> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    if !set_endpoint_paused(db, &auditor, &app, &endp, false).await? {
+        return Ok(NoContent);
+    }
+    invalidate_cached_app(cache, &app).await;
+
+    if !app.paused {
+        queue_tx
+            .send(
+                &ReleaseHeldDestinationsTask::new_task(app.id.clone(), vec![endp.id.clone()]),
+                None,
+            )
+            .await?;
+    }
+
+    send_pause_webhooks(op_webhooks, &app, &[endp], false).await?;
+
+    Ok(NoContent)
+}
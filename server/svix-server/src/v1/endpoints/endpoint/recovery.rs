@@ -8,13 +8,14 @@ use svix_server_derive::aide_annotate;
 use super::RecoverIn;
 use crate::{
     core::{
+        audit_log::Auditor,
         permissions,
         types::{
             BaseId, MessageAttemptTriggerType, MessageEndpointId, MessageStatus,
             QueueBackgroundTaskId,
         },
     },
-    db::models::{application, endpoint, messagedestination},
+    db::models::{application, endpoint, message, messagedestination},
     error::{HttpError, Result, ValidationErrorItem},
     queue::{MessageTask, TaskQueueProducer},
     v1::utils::{ApplicationEndpointPath, JsonStatus, ValidatedJson},
@@ -50,7 +51,13 @@ async fn bulk_recover_failed_messages(
         let cur_len = items.len() as u64;
         iterator = items.last().map(|x| x.id.clone());
 
+        let priorities =
+            message::Entity::priorities(&db, items.iter().map(|x| x.msg_id.clone())).await?;
         for msg_dest in items {
+            let priority = priorities
+                .get(&msg_dest.msg_id)
+                .copied()
+                .unwrap_or_default();
             queue_tx
                 .send(
                     &MessageTask::new_task(
@@ -58,6 +65,7 @@ async fn bulk_recover_failed_messages(
                         app.id.clone(),
                         msg_dest.endp_id,
                         MessageAttemptTriggerType::Manual,
+                        priority,
                     ),
                     None,
                 )
@@ -101,6 +109,7 @@ pub(super) async fn recover_failed_webhooks(
     }): State<AppState>,
     Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
     permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
     ValidatedJson(RecoverIn { since, until }): ValidatedJson<RecoverIn>,
 ) -> Result<JsonStatus<202, RecoverOut>> {
     let until = until.unwrap_or_else(Utc::now);
@@ -125,6 +134,9 @@ pub(super) async fn recover_failed_webhooks(
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
 
+    let entry = auditor.record(db, Some(app.id.clone()), None, None).await?;
+    auditor.notify(entry).await;
+
     let db = db.clone();
     let queue_tx = queue_tx.clone();
     tokio::spawn(async move {
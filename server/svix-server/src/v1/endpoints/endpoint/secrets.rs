@@ -5,13 +5,15 @@ use axum::{
     Json,
 };
 use chrono::{Duration, Utc};
-use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, TransactionTrait};
+use serde_json::Value;
 use svix_server_derive::aide_annotate;
 
 use super::{EndpointSecretOut, EndpointSecretRotateIn};
 use crate::{
     cfg::DefaultSignatureType,
     core::{
+        audit_log::{self, Auditor},
         cryptography::Encryption,
         operational_webhooks::{EndpointEvent, OperationalWebhook},
         permissions,
@@ -33,6 +35,12 @@ pub(super) fn generate_secret(
     }
 }
 
+fn secret_audit_state(encryption: &Encryption, endp: &endpoint::Model) -> Result<Option<Value>> {
+    Ok(audit_log::state(EndpointSecretOut {
+        key: endp.key.clone().into_endpoint_secret(encryption)?,
+    }))
+}
+
 /// Get the endpoint's signing secret.
 ///
 /// This is used to verify the authenticity of the webhook.
@@ -63,9 +71,10 @@ pub(super) async fn rotate_endpoint_secret(
     }): State<AppState>,
     Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
     permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<EndpointSecretRotateIn>,
 ) -> Result<NoContent> {
-    let mut endp = endpoint::Entity::secure_find_by_id_or_uid(app.id, endpoint_id)
+    let mut endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
         .one(db)
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
@@ -90,6 +99,7 @@ pub(super) async fn rotate_endpoint_secret(
         }
     }
 
+    let before = secret_audit_state(&cfg.encryption, &endp)?;
     let old_keys = endp.old_keys.take();
 
     let endp = endpoint::ActiveModel {
@@ -110,7 +120,19 @@ pub(super) async fn rotate_endpoint_secret(
         ))),
         ..endp.into()
     };
-    let endp = endp.update(db).await?;
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let entry = auditor
+        .record(
+            &txn,
+            Some(app.id.clone()),
+            before,
+            secret_audit_state(&cfg.encryption, &endp)?,
+        )
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
 
     op_webhooks
         .send_operational_webhook(
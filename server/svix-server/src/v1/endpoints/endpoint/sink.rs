@@ -0,0 +1,177 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, DatabaseConnection, TransactionTrait};
+use serde_json::Value;
+use svix_server_derive::aide_annotate;
+
+use super::EndpointSinkOut;
+use crate::{
+    cfg::Configuration,
+    core::{
+        audit_log::{self, Auditor},
+        operational_webhooks::{EndpointEvent, OperationalWebhook},
+        permissions,
+        sink::{EndpointSink, EndpointSinkConfig},
+        types::OrganizationId,
+        url_policy::fetch_url_policy,
+    },
+    db::models::endpoint,
+    error::{HttpError, Result, ValidationErrorItem},
+    v1::utils::{ApplicationEndpointPath, NoContent, ValidatedJson},
+    AppState,
+};
+
+fn sink_audit_state(cfg: &Configuration, endp: &endpoint::Model) -> Result<Option<Value>> {
+    let Some(sink) = &endp.sink else {
+        return Ok(None);
+    };
+    Ok(audit_log::state(EndpointSinkOut::from(
+        sink.config(&cfg.encryption)?,
+    )))
+}
+
+/// Get the sink messages to the endpoint are pushed to.
+///
+/// Connection strings and credentials are never returned.
+#[aide_annotate(op_id = "v1.endpoint.get-sink")]
+pub(super) async fn get_endpoint_sink(
+    State(AppState { ref db, cfg, .. }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<EndpointSinkOut>> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id, endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let sink = endp.sink.ok_or_else(|| HttpError::not_found(None, None))?;
+
+    Ok(Json(sink.config(&cfg.encryption)?.into()))
+}
+
+/// Push messages to the endpoint to an SQS queue, a Kafka topic, a RabbitMQ exchange or a Redis
+/// stream instead of sending them to its URL.
+///
+/// Attempts, retries and disabling of failing endpoints work the same as for HTTP delivery.
+#[aide_annotate(op_id = "v1.endpoint.update-sink")]
+pub(super) async fn update_endpoint_sink(
+    State(AppState {
+        ref db,
+        cfg,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+    ValidatedJson(data): ValidatedJson<EndpointSinkConfig>,
+) -> Result<NoContent> {
+    validate_sink_url_policy(db, &app.org_id, &data).await?;
+
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let before = sink_audit_state(&cfg, &endp)?;
+    let endp = endpoint::ActiveModel {
+        sink: Set(Some(EndpointSink::new(&cfg.encryption, &data)?)),
+        ..endp.into()
+    };
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let entry = auditor
+        .record(
+            &txn,
+            Some(app.id.clone()),
+            before,
+            sink_audit_state(&cfg, &endp)?,
+        )
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    op_webhooks
+        .send_operational_webhook(
+            &app.org_id,
+            OperationalWebhook::EndpointUpdated(EndpointEvent::new(app.uid.as_ref(), &endp)),
+        )
+        .await?;
+
+    Ok(NoContent)
+}
+
+/// Checks the brokers of the sink against the organization's URL policy, like endpoint URLs. The
+/// IPs their hosts resolve to are only checked when pushing messages.
+async fn validate_sink_url_policy(
+    db: &DatabaseConnection,
+    org_id: &OrganizationId,
+    sink: &EndpointSinkConfig,
+) -> Result<()> {
+    let invalid = |msg: String| {
+        HttpError::unprocessable_entity(vec![ValidationErrorItem {
+            loc: vec!["body".to_owned()],
+            msg,
+            ty: "value_error".to_owned(),
+        }])
+    };
+
+    let urls = sink.broker_urls().map_err(|e| invalid(e.to_string()))?;
+    let Some(policy) = fetch_url_policy(db, org_id).await? else {
+        return Ok(());
+    };
+    for url in urls {
+        policy.check_url(&url).map_err(|e| invalid(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Go back to sending messages to the endpoint's URL.
+#[aide_annotate(op_id = "v1.endpoint.delete-sink")]
+pub(super) async fn delete_endpoint_sink(
+    State(AppState {
+        ref db,
+        cfg,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+) -> Result<NoContent> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let before = sink_audit_state(&cfg, &endp)?;
+    let endp = endpoint::ActiveModel {
+        sink: Set(None),
+        ..endp.into()
+    };
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let entry = auditor
+        .record(
+            &txn,
+            Some(app.id.clone()),
+            before,
+            sink_audit_state(&cfg, &endp)?,
+        )
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    op_webhooks
+        .send_operational_webhook(
+            &app.org_id,
+            OperationalWebhook::EndpointUpdated(EndpointEvent::new(app.uid.as_ref(), &endp)),
+        )
+        .await?;
+
+    Ok(NoContent)
+}
@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, TransactionTrait};
+use serde_json::Value;
+use svix_server_derive::aide_annotate;
+
+use super::{EndpointTlsIn, EndpointTlsOut};
+use crate::{
+    core::{
+        audit_log::{self, Auditor},
+        operational_webhooks::{EndpointEvent, OperationalWebhook},
+        permissions,
+        types::EndpointTlsConfig,
+    },
+    db::models::endpoint,
+    error::{HttpError, Result},
+    v1::utils::{ApplicationEndpointPath, NoContent, ValidatedJson},
+    AppState,
+};
+
+fn tls_audit_state(endp: &endpoint::Model) -> Option<Value> {
+    audit_log::state(
+        endp.tls
+            .clone()
+            .map(EndpointTlsOut::from)
+            .unwrap_or_default(),
+    )
+}
+
+/// Get the TLS configuration used when sending webhooks to the endpoint.
+///
+/// The client certificate's private key is never returned.
+#[aide_annotate(op_id = "v1.endpoint.get-tls")]
+pub(super) async fn get_endpoint_tls(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<EndpointTlsOut>> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id, endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    match endp.tls {
+        Some(tls) => Ok(Json(tls.into())),
+        None => Ok(Json(EndpointTlsOut::default())),
+    }
+}
+
+/// Set the client certificate and CA bundle used when sending webhooks to the endpoint.
+#[aide_annotate(op_id = "v1.endpoint.update-tls")]
+pub(super) async fn update_endpoint_tls(
+    State(AppState {
+        ref db,
+        cfg,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+    ValidatedJson(data): ValidatedJson<EndpointTlsIn>,
+) -> Result<NoContent> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+    let before = tls_audit_state(&endp);
+
+    let EndpointTlsIn {
+        client_cert,
+        client_key,
+        ca_bundle,
+    } = data;
+    let tls = EndpointTlsConfig::new(
+        &cfg.encryption,
+        client_cert,
+        client_key.as_deref(),
+        ca_bundle,
+    )?;
+
+    let endp = endpoint::ActiveModel {
+        tls: Set(Some(tls)),
+        ..endp.into()
+    };
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let entry = auditor
+        .record(&txn, Some(app.id.clone()), before, tls_audit_state(&endp))
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    op_webhooks
+        .send_operational_webhook(
+            &app.org_id,
+            OperationalWebhook::EndpointUpdated(EndpointEvent::new(app.uid.as_ref(), &endp)),
+        )
+        .await?;
+
+    Ok(NoContent)
+}
+
+/// Remove the client certificate and CA bundle of the endpoint.
+#[aide_annotate(op_id = "v1.endpoint.delete-tls")]
+pub(super) async fn delete_endpoint_tls(
+    State(AppState {
+        ref db,
+        ref op_webhooks,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+) -> Result<NoContent> {
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+    let before = tls_audit_state(&endp);
+
+    let endp = endpoint::ActiveModel {
+        tls: Set(None),
+        ..endp.into()
+    };
+
+    let txn = db.begin().await?;
+    let endp = endp.update(&txn).await?;
+    let entry = auditor
+        .record(&txn, Some(app.id.clone()), before, tls_audit_state(&endp))
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    op_webhooks
+        .send_operational_webhook(
+            &app.org_id,
+            OperationalWebhook::EndpointUpdated(EndpointEvent::new(app.uid.as_ref(), &endp)),
+        )
+        .await?;
+
+    Ok(NoContent)
+}
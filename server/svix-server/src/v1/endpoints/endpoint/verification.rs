@@ -0,0 +1,117 @@
+use axum::extract::{Path, State};
+use sea_orm::{
+    sea_query::Expr, ActiveValue::Set, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter,
+    TransactionTrait,
+};
+use svix_server_derive::aide_annotate;
+
+use super::endpoint_audit_state;
+use crate::{
+    cfg::Configuration,
+    core::{
+        audit_log::Auditor, cache::Cache, pause::invalidate_cached_app, permissions,
+        types::EndpointVerificationStatus,
+    },
+    db::models::{application, endpoint},
+    error::{HttpError, Result},
+    queue::{EndpointVerificationTask, TaskQueueProducer},
+    v1::utils::{ApplicationEndpointPath, NoContentWithCode},
+    AppState,
+};
+
+/// Marks the endpoint as pending verification, if verification is enabled and it's new or its URL
+/// changed from `old_url`. Returns whether it was, in which case [`start_verification`] should be
+/// called once it's saved.
+pub(super) fn require_verification(
+    cfg: &Configuration,
+    endp: &mut endpoint::ActiveModel,
+    old_url: Option<&str>,
+) -> bool {
+    if !cfg.endpoint_verification_enabled || old_url == Some(endp.url.as_ref().as_str()) {
+        return false;
+    }
+
+    endp.verification_status = Set(EndpointVerificationStatus::PendingVerification);
+    true
+}
+
+/// Queues sending the challenge to the endpoint, and makes the worker hold its messages right
+/// away rather than once the cached copy of its application expires.
+pub(super) async fn start_verification(
+    cache: &Cache,
+    queue_tx: &TaskQueueProducer,
+    app: &application::Model,
+    endp: &endpoint::Model,
+) -> Result<()> {
+    invalidate_cached_app(cache, app).await;
+    queue_tx
+        .send(
+            &EndpointVerificationTask::new_task(app.id.clone(), endp.id.clone()),
+            None,
+        )
+        .await
+}
+
+async fn set_pending_verification(db: &impl ConnectionTrait, endp: &endpoint::Model) -> Result<()> {
+    endpoint::Entity::update_many()
+        .col_expr(
+            endpoint::Column::VerificationStatus,
+            Expr::value(EndpointVerificationStatus::PendingVerification),
+        )
+        .filter(endpoint::Column::Id.eq(endp.id.clone()))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Send the verification challenge to the endpoint's URL again.
+///
+/// The endpoint is pending verification until it responds with the challenge, and messages to it
+/// are held in the meantime. Only available when the server requires endpoints to be verified.
+#[aide_annotate(op_id = "v1.endpoint.verify")]
+pub(super) async fn verify_endpoint(
+    State(AppState {
+        ref db,
+        ref cfg,
+        ref cache,
+        ref queue_tx,
+        ..
+    }): State<AppState>,
+    Path(ApplicationEndpointPath { endpoint_id, .. }): Path<ApplicationEndpointPath>,
+    permissions::Application { app }: permissions::Application,
+    auditor: Auditor,
+) -> Result<NoContentWithCode<202>> {
+    if !cfg.endpoint_verification_enabled {
+        return Err(HttpError::bad_request(
+            Some("verification_disabled".to_owned()),
+            Some("Endpoint verification isn't enabled on this server".to_owned()),
+        )
+        .into());
+    }
+
+    let endp = endpoint::Entity::secure_find_by_id_or_uid(app.id.clone(), endpoint_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let txn = db.begin().await?;
+    set_pending_verification(&txn, &endp).await?;
+    let after = endpoint::Model {
+        verification_status: EndpointVerificationStatus::PendingVerification,
+        ..endp.clone()
+    };
+    let entry = auditor
+        .record(
+            &txn,
+            Some(app.id.clone()),
+            endpoint_audit_state(&endp),
+            endpoint_audit_state(&after),
+        )
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    start_verification(cache, queue_tx, &app, &endp).await?;
+
+    Ok(NoContentWithCode)
+}
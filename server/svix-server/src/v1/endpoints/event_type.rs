@@ -11,18 +11,19 @@ use axum::{
 };
 use chrono::{DateTime, Utc};
 use schemars::JsonSchema;
-use sea_orm::{entity::prelude::*, ActiveValue::Set};
+use sea_orm::{entity::prelude::*, ActiveValue::Set, TransactionTrait};
 use serde::{Deserialize, Serialize};
 use svix_server_derive::{aide_annotate, ModelIn, ModelOut};
 use validator::Validate;
 
 use crate::{
     core::{
+        audit_log::{self, Auditor},
         permissions,
         types::{EventTypeName, FeatureFlag},
     },
     db::models::eventtype,
-    error::{http_error_on_conflict, HttpError, Result},
+    error::{http_error_on_conflict, HttpError, Result, ValidationErrorItem},
     v1::utils::{
         api_not_implemented, apply_pagination, openapi_desc, openapi_tag,
         patch::{
@@ -230,6 +231,47 @@ pub struct ListFetchQueryParams {
     pub with_content: bool,
 }
 
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct SchemaCompatQueryParams {
+    /// When `true` schema versions which aren't backward compatible with the previous version
+    /// are accepted
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Rejects the schema versions breaking backward compatibility, unless forced to accept them.
+fn check_schema_compat(
+    schemas: Option<&eventtype::Schema>,
+    previous: Option<&eventtype::Schema>,
+    force: bool,
+) -> Result<()> {
+    let Some(schemas) = schemas else {
+        return Ok(());
+    };
+    if force {
+        return Ok(());
+    }
+
+    let changes = schemas.breaking_changes(previous);
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    Err(HttpError::unprocessable_entity(
+        changes
+            .into_iter()
+            .map(|(version, change)| ValidationErrorItem {
+                loc: vec!["body".to_owned(), "schemas".to_owned(), version],
+                msg: format!(
+                    "Breaking change from the previous version: {change}. Pass `force=true` to accept it."
+                ),
+                ty: "value_error".to_owned(),
+            })
+            .collect(),
+    )
+    .into())
+}
+
 /// Return the list of event types.
 #[aide_annotate(op_id = "v1.event-type.list")]
 async fn list_event_types(
@@ -289,22 +331,32 @@ async fn list_event_types(
 /// Unarchiving an event type will allow endpoints to filter on it and messages to be sent with it.
 /// Endpoints filtering on the event type before archival will continue to filter on it.
 /// This operation does not preserve the description and schemas.
+///
+/// Schema versions are checked for backward compatibility with the version before them.
 #[aide_annotate(op_id = "v1.event-type.create")]
 async fn create_event_type(
     State(AppState { ref db, .. }): State<AppState>,
+    ValidatedQuery(SchemaCompatQueryParams { force }): ValidatedQuery<SchemaCompatQueryParams>,
     permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<EventTypeIn>,
 ) -> Result<JsonStatus<201, EventTypeOut>> {
     let evtype = eventtype::Entity::secure_find_by_name(org_id.clone(), data.name.to_owned())
         .one(db)
         .await?;
+    check_schema_compat(
+        data.schemas.as_ref(),
+        evtype.as_ref().and_then(|e| e.schemas.as_ref()),
+        force,
+    )?;
+    let txn = db.begin().await?;
     let ret = match evtype {
         Some(evtype) => {
             if evtype.deleted {
                 let mut evtype: eventtype::ActiveModel = evtype.into();
                 evtype.deleted = Set(false);
                 data.update_model(&mut evtype);
-                evtype.update(db).await?
+                evtype.update(&txn).await?
             } else {
                 return Err(HttpError::conflict(
                     Some("event_type_exists".to_owned()),
@@ -318,10 +370,17 @@ async fn create_event_type(
                 org_id: Set(org_id),
                 ..data.into()
             };
-            evtype.insert(db).await.map_err(http_error_on_conflict)?
+            evtype.insert(&txn).await.map_err(http_error_on_conflict)?
         }
     };
-    Ok(JsonStatus(ret.into()))
+    let out = EventTypeOut::from(ret);
+    let entry = auditor
+        .record(&txn, None, None, audit_log::state(&out))
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    Ok(JsonStatus(out))
 }
 
 /// Get an event type.
@@ -348,24 +407,35 @@ async fn get_event_type(
 }
 
 /// Update an event type.
+///
+/// Changed or added schema versions are checked for backward compatibility with the previous
+/// version.
 #[aide_annotate(op_id = "v1.event-type.update")]
 async fn update_event_type(
     State(AppState { ref db, .. }): State<AppState>,
     Path(EventTypeNamePath { event_type_name }): Path<EventTypeNamePath>,
+    ValidatedQuery(SchemaCompatQueryParams { force }): ValidatedQuery<SchemaCompatQueryParams>,
     permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<EventTypeUpdate>,
 ) -> Result<JsonStatusUpsert<EventTypeOut>> {
     let evtype = eventtype::Entity::secure_find_by_name(org_id.clone(), event_type_name.clone())
         .one(db)
         .await?;
-
-    match evtype {
+    check_schema_compat(
+        data.schemas.as_ref(),
+        evtype.as_ref().and_then(|e| e.schemas.as_ref()),
+        force,
+    )?;
+
+    let txn = db.begin().await?;
+    let (before, ret) = match evtype {
         Some(evtype) => {
+            let before = audit_log::state(EventTypeOut::from(evtype.clone()));
             let mut evtype: eventtype::ActiveModel = evtype.into();
             data.update_model(&mut evtype);
-            let ret = evtype.update(db).await.map_err(http_error_on_conflict)?;
-
-            Ok(JsonStatusUpsert::Updated(ret.into()))
+            let ret = evtype.update(&txn).await.map_err(http_error_on_conflict)?;
+            (before, ret)
         }
         None => {
             let ret = eventtype::ActiveModel {
@@ -373,12 +443,24 @@ async fn update_event_type(
                 name: Set(event_type_name),
                 ..data.into()
             }
-            .insert(db)
+            .insert(&txn)
             .await
             .map_err(http_error_on_conflict)?;
-
-            Ok(JsonStatusUpsert::Created(ret.into()))
+            (None, ret)
         }
+    };
+    let created = before.is_none();
+    let out = EventTypeOut::from(ret);
+    let entry = auditor
+        .record(&txn, None, before, audit_log::state(&out))
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    if created {
+        Ok(JsonStatusUpsert::Created(out))
+    } else {
+        Ok(JsonStatusUpsert::Updated(out))
     }
 }
 
@@ -387,7 +469,9 @@ async fn update_event_type(
 async fn patch_event_type(
     State(AppState { ref db, .. }): State<AppState>,
     Path(EventTypeNamePath { event_type_name }): Path<EventTypeNamePath>,
+    ValidatedQuery(SchemaCompatQueryParams { force }): ValidatedQuery<SchemaCompatQueryParams>,
     permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
     ValidatedJson(data): ValidatedJson<EventTypePatch>,
 ) -> Result<Json<EventTypeOut>> {
     let evtype = eventtype::Entity::secure_find_by_name(org_id, event_type_name)
@@ -395,11 +479,24 @@ async fn patch_event_type(
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
 
+    if let UnrequiredNullableField::Some(schemas) = &data.schemas {
+        check_schema_compat(Some(schemas), evtype.schemas.as_ref(), force)?;
+    }
+
+    let before = audit_log::state(EventTypeOut::from(evtype.clone()));
     let mut evtype: eventtype::ActiveModel = evtype.into();
     data.update_model(&mut evtype);
 
-    let ret = evtype.update(db).await.map_err(http_error_on_conflict)?;
-    Ok(Json(ret.into()))
+    let txn = db.begin().await?;
+    let ret = evtype.update(&txn).await.map_err(http_error_on_conflict)?;
+    let out = EventTypeOut::from(ret);
+    let entry = auditor
+        .record(&txn, None, before, audit_log::state(&out))
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    Ok(Json(out))
 }
 
 #[derive(Debug, Deserialize, Serialize, Validate, JsonSchema)]
@@ -424,20 +521,27 @@ async fn delete_event_type(
         DeleteEventTypeQueryParams,
     >,
     permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
 ) -> Result<NoContent> {
     let evtype = eventtype::Entity::secure_find_by_name(org_id, event_type_name)
         .one(db)
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
 
+    let before = audit_log::state(EventTypeOut::from(evtype.clone()));
     let mut evtype: eventtype::ActiveModel = evtype.into();
 
+    let txn = db.begin().await?;
     if expunge {
-        evtype.delete(db).await?;
+        evtype.delete(&txn).await?;
     } else {
         evtype.deleted = Set(true);
-        evtype.update(db).await?;
+        evtype.update(&txn).await?;
     }
+    let entry = auditor.record(&txn, None, before, None).await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
     Ok(NoContent)
 }
 
@@ -476,7 +580,7 @@ mod tests {
 
     use serde_json::json;
 
-    use super::ListFetchQueryParams;
+    use super::{ListFetchQueryParams, SchemaCompatQueryParams};
 
     #[test]
     fn test_list_fetch_options_default() {
@@ -484,4 +588,10 @@ mod tests {
         assert!(!l.include_archived);
         assert!(!l.with_content);
     }
+
+    #[test]
+    fn test_schema_compat_query_params_default() {
+        let q: SchemaCompatQueryParams = serde_json::from_value(json!({})).unwrap();
+        assert!(!q.force);
+    }
 }
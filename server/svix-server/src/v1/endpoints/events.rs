@@ -0,0 +1,288 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+//! A feed of the state changes of an application's messages: their creation, and each of their
+//! attempts. It's read with a cursor, or streamed as Server-Sent Events.
+
+use std::{collections::VecDeque, convert::Infallible, sync::atomic::Ordering, time::Duration};
+
+use aide::axum::{routing::get_with, ApiRouter};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream};
+use schemars::JsonSchema;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+use svix_server_derive::aide_annotate;
+use validator::Validate;
+
+use crate::{
+    core::{
+        permissions,
+        types::{
+            ApplicationId, EndpointId, MessageAttemptId, MessageEventType, MessageId, MessageStatus,
+        },
+    },
+    db::models::messageevent,
+    error::{HttpError, Result, ValidationErrorItem},
+    v1::utils::{openapi_tag, PaginationLimit, ValidatedQuery},
+    AppState,
+};
+
+/// How often the stream checks for new events.
+const STREAM_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const STREAM_BATCH_SIZE: u64 = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageEventOut {
+    #[serde(rename = "type")]
+    pub event_type: MessageEventType,
+    pub msg_id: MessageId,
+    /// The endpoint the message was attempted to, for attempt events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_id: Option<EndpointId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempt_id: Option<MessageAttemptId>,
+    /// The status of the attempt, for attempt events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<MessageStatus>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<messageevent::Model> for MessageEventOut {
+    fn from(model: messageevent::Model) -> Self {
+        Self {
+            event_type: model.event_type,
+            msg_id: model.msg_id,
+            endpoint_id: model.endp_id,
+            attempt_id: model.attempt_id,
+            status: model.status,
+            timestamp: model.created_at.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageEventsOut {
+    pub data: Vec<MessageEventOut>,
+    /// Pass it as `iterator` to read the events after these
+    pub iterator: String,
+    /// Whether these are all of the events so far
+    pub done: bool,
+}
+
+fn default_events_limit() -> PaginationLimit {
+    PaginationLimit(50)
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct MessageEventsQueryParams {
+    /// Limit the number of returned items
+    #[validate]
+    #[serde(default = "default_events_limit")]
+    limit: PaginationLimit,
+    /// The iterator returned from a prior invocation
+    iterator: Option<String>,
+    /// Only include events after a certain date, when no `iterator` is given
+    after: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
+pub struct MessageEventsStreamQueryParams {
+    /// Stream the events after the one with this iterator. The `Last-Event-ID` header, sent by
+    /// clients reconnecting to the stream, takes precedence over it.
+    iterator: Option<String>,
+    /// Stream the events after a certain date, rather than only new ones, when no `iterator` is
+    /// given
+    after: Option<DateTime<Utc>>,
+}
+
+fn parse_iterator(iterator: &str) -> Result<i64> {
+    iterator.parse().map_err(|_| {
+        HttpError::unprocessable_entity(vec![ValidationErrorItem {
+            loc: vec!["query".to_owned(), "iterator".to_owned()],
+            msg: "Invalid iterator".to_owned(),
+            ty: "value_error".to_owned(),
+        }])
+        .into()
+    })
+}
+
+/// The application's events after the one numbered `after_seq`, oldest first. Only committed events
+/// are numbered, in the order they're committed by the [sequencer](crate::message_event_sequencer),
+/// so none are skipped by reading past them.
+async fn fetch_events(
+    db: &DatabaseConnection,
+    app_id: &ApplicationId,
+    after_seq: i64,
+    after: Option<DateTime<Utc>>,
+    limit: u64,
+) -> Result<Vec<messageevent::Model>> {
+    let mut query = messageevent::Entity::find()
+        .filter(messageevent::Column::AppId.eq(app_id.clone()))
+        .filter(messageevent::Column::Seq.gt(after_seq))
+        .order_by_asc(messageevent::Column::Seq)
+        .limit(limit);
+    if let Some(after) = after {
+        query = query.filter(messageevent::Column::CreatedAt.gt(after));
+    }
+
+    Ok(query.all(db).await?)
+}
+
+/// The number of the application's latest event, or 0 if it has none.
+async fn latest_event_seq(db: &DatabaseConnection, app_id: &ApplicationId) -> Result<i64> {
+    let latest: Option<Option<i64>> = messageevent::Entity::find()
+        .select_only()
+        .column_as(messageevent::Column::Seq.max(), "seq")
+        .filter(messageevent::Column::AppId.eq(app_id.clone()))
+        .into_tuple()
+        .one(db)
+        .await?;
+
+    Ok(latest.flatten().unwrap_or_default())
+}
+
+/// Reads the events of the application's messages: their creation, and each of their attempts.
+///
+/// Events are listed oldest first. Pass the returned `iterator` to the next call to read the
+/// events after these, e.g. when polling for new ones.
+#[aide_annotate(op_id = "v1.message.events")]
+async fn list_message_events(
+    State(AppState { ref db, .. }): State<AppState>,
+    ValidatedQuery(MessageEventsQueryParams {
+        limit,
+        iterator,
+        after,
+    }): ValidatedQuery<MessageEventsQueryParams>,
+    permissions::Application { app }: permissions::Application,
+) -> Result<Json<MessageEventsOut>> {
+    let (after_seq, after) = match iterator.as_deref() {
+        Some(iterator) => (parse_iterator(iterator)?, None),
+        None => (0, after),
+    };
+
+    let events = fetch_events(db, &app.id, after_seq, after, limit.0).await?;
+    let done = (events.len() as u64) < limit.0;
+
+    let iterator = match events.last() {
+        Some(last) => last.seq,
+        None if iterator.is_some() => after_seq,
+        // Events before `after` are skipped, so don't point the next call at them
+        None => latest_event_seq(db, &app.id).await?,
+    };
+
+    Ok(Json(MessageEventsOut {
+        data: events.into_iter().map(Into::into).collect(),
+        iterator: iterator.to_string(),
+        done,
+    }))
+}
+
+struct StreamState {
+    db: DatabaseConnection,
+    app_id: ApplicationId,
+    after_seq: i64,
+    after: Option<DateTime<Utc>>,
+    pending: VecDeque<messageevent::Model>,
+}
+
+impl StreamState {
+    /// The next event, waiting for one if need be. Ends the stream when the server is shutting
+    /// down, for the connection not to hold up the shutdown.
+    async fn next(mut self) -> Option<(Event, Self)> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                let sse_event = Event::default()
+                    .id(event.seq.to_string())
+                    .json_data(MessageEventOut::from(event))
+                    .ok()?;
+                return Some((sse_event, self));
+            }
+
+            if crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            match fetch_events(
+                &self.db,
+                &self.app_id,
+                self.after_seq,
+                self.after,
+                STREAM_BATCH_SIZE,
+            )
+            .await
+            {
+                Ok(events) if !events.is_empty() => {
+                    self.after_seq = events.last().map(|e| e.seq).unwrap_or(self.after_seq);
+                    self.pending.extend(events);
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to fetch message events for the stream: {e}");
+                }
+            }
+
+            tokio::time::sleep(STREAM_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Streams the events of the application's messages as Server-Sent Events, as they happen.
+///
+/// Each event's `id` is its iterator, so clients reconnecting with the `Last-Event-ID` header
+/// resume the stream where it left off.
+async fn stream_message_events(
+    State(AppState { db, .. }): State<AppState>,
+    ValidatedQuery(MessageEventsStreamQueryParams { iterator, after }): ValidatedQuery<
+        MessageEventsStreamQueryParams,
+    >,
+    permissions::Application { app }: permissions::Application,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .map(ToOwned::to_owned);
+
+    let (after_seq, after) = match last_event_id.or(iterator) {
+        Some(iterator) => (parse_iterator(&iterator)?, None),
+        None if after.is_some() => (0, after),
+        None => (latest_event_seq(&db, &app.id).await?, None),
+    };
+
+    let state = StreamState {
+        db,
+        app_id: app.id,
+        after_seq,
+        after,
+        pending: VecDeque::new(),
+    };
+    let events = stream::unfold(state, |state| async move {
+        state.next().await.map(|(event, state)| (Ok(event), state))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+pub fn router() -> ApiRouter<AppState> {
+    let tag = openapi_tag("Message");
+    ApiRouter::new()
+        .api_route_with(
+            "/app/:app_id/events",
+            get_with(list_message_events, list_message_events_operation),
+            tag,
+        )
+        // Left out of the OpenAPI spec, which can't describe event streams
+        .route("/app/:app_id/events/stream", get(stream_message_events))
+}
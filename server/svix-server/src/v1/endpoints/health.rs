@@ -1,24 +1,40 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
-use std::time::Duration;
+use std::{
+    fmt,
+    future::Future,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
 
 use aide::axum::{
     routing::{get, get_with},
     ApiRouter,
 };
 use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
 use sea_orm::{query::Statement, ConnectionTrait, DatabaseBackend};
 use serde::{Deserialize, Serialize};
 use svix_server_derive::aide_annotate;
 
 use crate::{
     core::cache::{kv_def, CacheBehavior, CacheKey, CacheValue},
+    db::migration_versions,
+    error::Error,
     queue::QueueTask,
-    v1::utils::{openapi_tag, NoContent},
+    v1::utils::{get_unix_timestamp, openapi_tag, NoContent},
+    worker::LAST_QUEUE_POLL,
     AppState,
 };
 
+/// How long a single check may take before it's reported as failing
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How much longer than its max poll time the worker may go without polling the queue before
+/// it's considered stuck
+const POLL_GRACE_PERIOD_SECS: u64 = 60;
+
 async fn ping() -> NoContent {
     NoContent
 }
@@ -30,22 +46,40 @@ pub enum HealthStatusVariant {
     Error,
 }
 
+/// Why a check failed. The details are only logged, since the health routes are unauthenticated.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthErrorCode {
+    Failed,
+    TimedOut,
+    WorkerNotPolling,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct HealthStatus {
     status: HealthStatusVariant,
-    // TODO: information field
+    /// How long the check took, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<HealthErrorCode>,
 }
 
 impl HealthStatus {
     pub fn new_ok() -> HealthStatus {
         HealthStatus {
             status: HealthStatusVariant::Ok,
+            latency_ms: None,
+            code: None,
         }
     }
 
-    pub fn new_error() -> HealthStatus {
+    pub fn new_error(code: HealthErrorCode) -> HealthStatus {
         HealthStatus {
             status: HealthStatusVariant::Error,
+            latency_ms: None,
+            code: Some(code),
         }
     }
 
@@ -58,75 +92,199 @@ impl HealthStatus {
             }
         )
     }
-}
-impl<O, E> From<Result<O, E>> for HealthStatus {
-    fn from(res: Result<O, E>) -> Self {
-        match res {
-            Ok(_) => HealthStatus::new_ok(),
-            Err(_) => HealthStatus::new_error(),
-        }
+
+    /// Runs the check of the component, timing it and failing it if it takes longer than
+    /// [`CHECK_TIMEOUT`].
+    async fn check<O, E: fmt::Display>(
+        component: &str,
+        check: impl Future<Output = Result<O, E>>,
+    ) -> Self {
+        let start = Instant::now();
+        let mut status = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+            Ok(Ok(_)) => HealthStatus::new_ok(),
+            Ok(Err(e)) => {
+                tracing::warn!("Health check of the {component} failed: {e}");
+                HealthStatus::new_error(HealthErrorCode::Failed)
+            }
+            Err(_) => {
+                tracing::warn!("Health check of the {component} timed out after {CHECK_TIMEOUT:?}");
+                HealthStatus::new_error(HealthErrorCode::TimedOut)
+            }
+        };
+        status.latency_ms = Some(start.elapsed().as_millis() as u64);
+        status
     }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueHealth {
+    #[serde(flatten)]
+    status: HealthStatus,
+    /// When this instance's worker last polled the queue. Only set on instances running a worker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_poll_at: Option<DateTime<Utc>>,
+    /// Seconds since this instance's worker last polled the queue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    consumer_lag_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationsHealth {
+    #[serde(flatten)]
+    status: HealthStatus,
+    /// The latest migration applied to the database
+    version: Option<i64>,
+    /// The latest migration known to this version of the server
+    latest_version: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct HealthReport {
     database: HealthStatus,
 
-    queue: HealthStatus,
+    queue: QueueHealth,
     cache: HealthStatus,
+    migrations: MigrationsHealth,
+
+    /// Only reported when operational webhooks are enabled, and not required to be healthy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    operational_webhooks: Option<HealthStatus>,
+}
+
+impl HealthReport {
+    pub fn is_ok(&self) -> bool {
+        self.database.is_ok()
+            && self.queue.status.is_ok()
+            && self.cache.is_ok()
+            && self.migrations.status.is_ok()
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
 struct HealthCheckCacheValue(());
 kv_def!(HealthCheckCacheKey, HealthCheckCacheValue);
 
-/// Verify the API server is up and running.
-#[aide_annotate(op_id = "v1.health.get")]
-async fn health(
-    State(AppState {
-        ref db,
+async fn health_report(
+    AppState {
+        db,
         queue_tx,
         cache,
+        cfg,
+        op_webhooks,
         ..
-    }): State<AppState>,
-) -> (StatusCode, Json<HealthReport>) {
-    // SELECT 1 FROM any table
-    let database: HealthStatus = db
-        .execute(Statement::from_string(
-            DatabaseBackend::Postgres,
-            "SELECT 1".to_owned(),
-        ))
-        .await
-        .into();
-
-    // Send a [`HealthCheck`] through the queue
-    let queue: HealthStatus = queue_tx.send(&QueueTask::HealthCheck, None).await.into();
-
-    // Set a cache value with an expiration to ensure it works
-    let cache: HealthStatus = cache
-        .set(
-            &HealthCheckCacheKey("health_check_value".to_owned()),
-            &HealthCheckCacheValue(()),
-            // Expires after this time, so it won't pollute the DB
-            Duration::from_millis(100),
-        )
-        .await
-        .into();
+    }: &AppState,
+) -> HealthReport {
+    let (database, queue, cache, migrations, operational_webhooks) = tokio::join!(
+        // SELECT 1 FROM any table
+        HealthStatus::check(
+            "database",
+            db.execute(Statement::from_string(
+                DatabaseBackend::Postgres,
+                "SELECT 1".to_owned(),
+            ))
+        ),
+        // Send a [`HealthCheck`] through the queue
+        HealthStatus::check("queue", queue_tx.send(&QueueTask::HealthCheck, None)),
+        // Set a cache value with an expiration to ensure it works
+        HealthStatus::check(
+            "cache",
+            cache.set(
+                &HealthCheckCacheKey("health_check_value".to_owned()),
+                &HealthCheckCacheValue(()),
+                // Expires after this time, so it won't pollute the DB
+                Duration::from_millis(100),
+            )
+        ),
+        async {
+            let mut versions = None;
+            let status = HealthStatus::check("migrations", async {
+                let (version, latest_version) = migration_versions(db).await?;
+                versions = Some((version, latest_version));
+                if version.unwrap_or_default() < latest_version {
+                    return Err(Error::database(format!(
+                        "pending migrations, the latest is {latest_version}"
+                    )));
+                }
+                Ok(())
+            })
+            .await;
+            let (version, latest_version) = versions.unwrap_or_default();
+            MigrationsHealth {
+                status,
+                version,
+                latest_version,
+            }
+        },
+        async {
+            if op_webhooks.is_enabled() {
+                Some(HealthStatus::check("operational webhooks", op_webhooks.health_check()).await)
+            } else {
+                None
+            }
+        },
+    );
+
+    let mut queue = QueueHealth {
+        status: queue,
+        last_poll_at: None,
+        consumer_lag_secs: None,
+    };
+    if cfg.worker_enabled {
+        let last_poll = LAST_QUEUE_POLL.load(Ordering::Relaxed);
+        let lag = get_unix_timestamp().saturating_sub(last_poll);
+        queue.last_poll_at = DateTime::from_timestamp(last_poll as i64, 0);
+        queue.consumer_lag_secs = Some(lag);
+
+        if queue.status.is_ok() && lag > u64::from(cfg.queue_max_poll_secs) + POLL_GRACE_PERIOD_SECS
+        {
+            tracing::warn!(
+                "Health check of the queue failed: the worker last polled it {lag}s ago"
+            );
+            queue.status = HealthStatus {
+                latency_ms: queue.status.latency_ms,
+                ..HealthStatus::new_error(HealthErrorCode::WorkerNotPolling)
+            };
+        }
+    }
+
+    HealthReport {
+        database,
+        queue,
+        cache,
+        migrations,
+        operational_webhooks,
+    }
+}
+
+/// Verify the API server is up and running.
+#[aide_annotate(op_id = "v1.health.get")]
+async fn health(State(state): State<AppState>) -> (StatusCode, Json<HealthReport>) {
+    let report = health_report(&state).await;
 
-    let status = if database.is_ok() && queue.is_ok() && cache.is_ok() {
+    let status = if report.is_ok() {
         StatusCode::OK
     } else {
         StatusCode::INTERNAL_SERVER_ERROR
     };
 
-    (
-        status,
-        Json(HealthReport {
-            database,
-            queue,
-            cache,
-        }),
-    )
+    (status, Json(report))
+}
+
+/// For readiness probes, fails while any required component is unhealthy or the server is
+/// shutting down.
+async fn ready(State(state): State<AppState>) -> (StatusCode, Json<HealthReport>) {
+    let report = health_report(&state).await;
+
+    let status = if report.is_ok() && !crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(report))
 }
 
 pub fn router() -> ApiRouter<AppState> {
@@ -134,6 +292,9 @@ pub fn router() -> ApiRouter<AppState> {
 
     ApiRouter::new()
         .api_route("/health/ping", get(ping).head(ping))
+        // For liveness probes, only checks the server responds
+        .api_route("/health/live", get(ping).head(ping))
+        .api_route("/health/ready", get(ready).head(ready))
         .api_route_with(
             "/health",
             get_with(health, |op| op.response::<204, ()>().with(health_operation))
@@ -1,6 +1,8 @@
 // SPDX-FileCopyrightText: © 2022 Svix Authors
 // SPDX-License-Identifier: MIT
 
+use std::collections::{HashMap, HashSet};
+
 use aide::axum::{
     routing::{delete_with, get_with, post_with},
     ApiRouter,
@@ -10,32 +12,40 @@ use axum::{
     Json,
 };
 use chrono::{DateTime, Duration, Utc};
-use futures::FutureExt;
+use futures::{future::try_join_all, FutureExt};
 use hyper::StatusCode;
 use schemars::JsonSchema;
-use sea_orm::{entity::prelude::*, ActiveValue::Set, IntoActiveModel, TransactionTrait};
+use sea_orm::{
+    entity::prelude::*, sea_query::OnConflict, ActiveValue::Set, Condition, IntoActiveModel,
+    TransactionTrait,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
 use svix_server_derive::{aide_annotate, ModelIn, ModelOut};
 use validator::{Validate, ValidationError};
 
 use crate::{
+    cfg::Configuration,
     core::{
+        audit_log::Auditor,
+        blob_store::BlobStore,
         cache::Cache,
         message_app::CreateMessageApp,
+        otel_spans::RequestTraceContext,
         permissions,
         types::{
-            EndpointId, EventChannel, EventChannelSet, EventTypeName, EventTypeNameSet,
-            MessageAttemptTriggerType, MessageId, MessageUid,
+            ApplicationId, ApplicationIdOrUid, BroadcastId, EndpointId, EventChannel,
+            EventChannelSet, EventTypeName, EventTypeNameSet, MessageAttemptTriggerType, MessageId,
+            MessagePriority, MessageUid, OrganizationId, SchemaVersion, TraceContext,
         },
     },
-    db::models::{application, message, messagecontent},
-    error::{http_error_on_conflict, Error, HttpError, Result},
+    db::models::{application, eventtype, message, messagecontent, messageevent},
+    error::{http_error_on_conflict, Error, HttpError, Result, ValidationErrorItem},
     queue::{MessageTaskBatch, TaskQueueProducer},
     v1::utils::{
-        filter_and_paginate_time_limited, openapi_tag, validation_error, ApplicationMsgPath,
-        EventTypesQueryParams, JsonStatus, ListResponse, ModelIn, ModelOut, PaginationDescending,
-        PaginationLimit, ReversibleIterator, ValidatedJson, ValidatedQuery,
+        filter_and_paginate_time_limited, openapi_tag, validation_error, validation_errors,
+        ApplicationMsgPath, EventTypesQueryParams, JsonStatus, ListResponse, ModelIn, ModelOut,
+        PaginationDescending, PaginationLimit, ReversibleIterator, ValidatedJson, ValidatedQuery,
     },
     AppState,
 };
@@ -101,10 +111,24 @@ pub fn validate_raw_payload_is_object(payload: &RawPayload) -> Result<(), Valida
     }
 }
 
+/// `bulk` can't be a message's UID, as the message couldn't be told apart from the `msg/bulk`
+/// route.
+pub fn validate_message_uid(uid: &MessageUid) -> Result<(), ValidationError> {
+    if uid.0 == "bulk" {
+        Err(validation_error(
+            Some("reserved"),
+            Some("bulk is reserved and can't be used as an eventId."),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Validate, ModelIn, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct MessageIn {
     /// Optional unique identifier for the message
+    #[validate(custom = "validate_message_uid")]
     #[validate]
     #[serde(rename = "eventId", skip_serializing_if = "Option::is_none")]
     pub uid: Option<MessageUid>,
@@ -124,6 +148,18 @@ pub struct MessageIn {
     #[serde(default = "default_90")]
     #[schemars(example = "default_90")]
     pub payload_retention_period: i64,
+    /// Deliver the message at this time instead of right away. Scheduled messages can be
+    /// cancelled until they're delivered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver_at: Option<DateTime<Utc>>,
+    /// The version of the event type's schema the payload conforms to. Endpoints pinned to
+    /// another version don't receive the message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<SchemaVersion>,
+    /// Messages of a higher priority are queued separately, so they aren't held up by messages of
+    /// a lower priority. Only has an effect when the server has priority lanes enabled.
+    #[serde(default, skip_serializing_if = "MessagePriority::is_normal")]
+    pub priority: MessagePriority,
     #[serde(rename = "transformationsParams")]
     #[schemars(skip)]
     pub extra_params: Option<MessageInExtraParams>,
@@ -168,15 +204,137 @@ impl ModelIn for MessageIn {
             event_type,
             channels,
             payload_retention_period,
+            deliver_at,
+            schema_version,
+            priority,
             ..
         } = self;
 
-        let expiration = Utc::now() + Duration::days(payload_retention_period);
+        // The payload has to be retained until the message is delivered
+        let expiration =
+            deliver_at.unwrap_or_else(Utc::now) + Duration::days(payload_retention_period);
 
         model.uid = Set(uid);
         model.event_type = Set(event_type);
         model.expiration = Set(expiration.with_timezone(&Utc).into());
         model.channels = Set(channels);
+        model.deliver_at = Set(deliver_at.map(Into::into));
+        model.schema_version = Set(schema_version);
+        model.priority = Set(priority);
+    }
+}
+
+/// Checks `deliverAt` against the server's configuration, which `Validate` has no access to.
+pub(crate) fn deliver_at_error(
+    cfg: &Configuration,
+    deliver_at: Option<DateTime<Utc>>,
+) -> Option<String> {
+    let deliver_at = deliver_at?;
+
+    let now = Utc::now();
+    let max_delay = Duration::seconds(cfg.max_message_delivery_delay as _);
+    if deliver_at <= now {
+        Some("deliverAt must be in the future.".to_owned())
+    } else if deliver_at > now + max_delay {
+        Some(format!(
+            "deliverAt can be at most {} seconds in the future.",
+            cfg.max_message_delivery_delay
+        ))
+    } else {
+        None
+    }
+}
+
+fn validate_deliver_at(cfg: &Configuration, deliver_at: Option<DateTime<Utc>>) -> Result<()> {
+    match deliver_at_error(cfg, deliver_at) {
+        Some(msg) => Err(HttpError::unprocessable_entity(vec![ValidationErrorItem {
+            loc: vec!["body".to_owned(), "deliverAt".to_owned()],
+            msg,
+            ty: "value_error".to_owned(),
+        }])
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Checks the declared `schemaVersion` is one of the event type's schema versions, which
+/// `Validate` has no access to.
+pub(crate) async fn schema_version_error(
+    db: &DatabaseConnection,
+    org_id: OrganizationId,
+    msg: &MessageIn,
+) -> Result<Option<String>> {
+    let Some(version) = &msg.schema_version else {
+        return Ok(None);
+    };
+
+    let evtype = eventtype::Entity::secure_find_by_name(org_id, msg.event_type.clone())
+        .one(db)
+        .await?;
+    if evtype
+        .and_then(|evtype| evtype.schemas)
+        .is_some_and(|schemas| schemas.has_version(version))
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(format!(
+        "The event type has no schema version {version}."
+    )))
+}
+
+async fn validate_schema_version(
+    db: &DatabaseConnection,
+    org_id: OrganizationId,
+    msg: &MessageIn,
+) -> Result<()> {
+    match schema_version_error(db, org_id, msg).await? {
+        Some(msg) => Err(HttpError::unprocessable_entity(vec![ValidationErrorItem {
+            loc: vec!["body".to_owned(), "schemaVersion".to_owned()],
+            msg,
+            ty: "value_error".to_owned(),
+        }])
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Where a message scheduled with `deliverAt` is at. Not set for messages delivered right away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageScheduleStatus {
+    /// The message will be delivered at `deliverAt`
+    Scheduled,
+    /// Delivery was cancelled before `deliverAt`
+    Cancelled,
+    /// `deliverAt` has passed and the message was sent on to its endpoints
+    Dispatched,
+}
+
+impl MessageScheduleStatus {
+    fn of(model: &message::Model) -> Option<Self> {
+        let deliver_at = model.deliver_at?;
+        Some(if model.cancelled_at.is_some() {
+            Self::Cancelled
+        } else if deliver_at > Utc::now() {
+            Self::Scheduled
+        } else {
+            Self::Dispatched
+        })
+    }
+
+    fn condition(self) -> Condition {
+        let now = Utc::now();
+        let not_cancelled = message::Column::CancelledAt.is_null();
+        match self {
+            Self::Scheduled => Condition::all()
+                .add(message::Column::DeliverAt.gt(now))
+                .add(not_cancelled),
+            Self::Cancelled => Condition::all().add(message::Column::CancelledAt.is_not_null()),
+            Self::Dispatched => Condition::all()
+                .add(message::Column::DeliverAt.lte(now))
+                .add(not_cancelled),
+        }
     }
 }
 
@@ -195,10 +353,33 @@ pub struct MessageOut {
     pub id: MessageId,
     #[serde(rename = "timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_status: Option<MessageScheduleStatus>,
+    /// The broadcast the message was created by, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub broadcast_id: Option<BroadcastId>,
+    /// The version of the event type's schema the payload conforms to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<SchemaVersion>,
 }
 
 impl MessageOut {
-    pub fn from_msg_and_payload(model: message::Model, content: Option<Vec<u8>>) -> Self {
+    /// The message with its payload, which is fetched from the blob store if it was offloaded.
+    pub async fn from_msg_and_payload(
+        model: message::Model,
+        content: Option<messagecontent::Model>,
+        blob_store: &BlobStore,
+    ) -> Result<Self> {
+        let payload = match content {
+            Some(content) => blob_store.resolve_payload(content).await?,
+            None => None,
+        };
+        Ok(Self::from_msg_and_raw_payload(model, payload))
+    }
+
+    pub fn from_msg_and_raw_payload(model: message::Model, content: Option<Vec<u8>>) -> Self {
         let payload = content
             .and_then(|p| match serde_json::from_slice(&p) {
                 Ok(v) => Some(v),
@@ -215,23 +396,31 @@ impl MessageOut {
         .expect("Can never fail");
 
         Self {
+            schedule_status: MessageScheduleStatus::of(&model),
             uid: model.uid,
             event_type: model.event_type,
             payload,
             channels: model.channels,
             id: model.id,
             created_at: model.created_at.into(),
+            deliver_at: model.deliver_at.map(Into::into),
+            broadcast_id: model.broadcast_id,
+            schema_version: model.schema_version,
         }
     }
 
     pub fn without_payload(model: message::Model) -> Self {
         Self {
+            schedule_status: MessageScheduleStatus::of(&model),
             uid: model.uid,
             event_type: model.event_type,
             payload: RawPayload::from_string("{}".to_string()).expect("Can never fail"),
             channels: model.channels,
             id: model.id,
             created_at: model.created_at.into(),
+            deliver_at: model.deliver_at.map(Into::into),
+            broadcast_id: model.broadcast_id,
+            schema_version: model.schema_version,
         }
     }
 }
@@ -256,6 +445,8 @@ pub struct ListMessagesQueryParams {
     /// When `true` message payloads are included in the response
     #[serde(default = "default_true")]
     with_content: bool,
+    /// Only include messages scheduled with `deliverAt` which are in this state
+    schedule_status: Option<MessageScheduleStatus>,
 }
 
 /// List all of the application's messages.
@@ -265,13 +456,18 @@ pub struct ListMessagesQueryParams {
 /// `before` and `after` cannot be used simultaneously.
 #[aide_annotate(op_id = "v1.message.list")]
 async fn list_messages(
-    State(AppState { ref db, .. }): State<AppState>,
+    State(AppState {
+        ref db,
+        ref blob_store,
+        ..
+    }): State<AppState>,
     ValidatedQuery(pagination): ValidatedQuery<PaginationDescending<ReversibleIterator<MessageId>>>,
     ValidatedQuery(ListMessagesQueryParams {
         channel,
         with_content,
         before,
         after,
+        schedule_status,
     }): ValidatedQuery<ListMessagesQueryParams>,
     EventTypesQueryParams(event_types): EventTypesQueryParams,
     permissions::Application { app }: permissions::Application,
@@ -288,6 +484,10 @@ async fn list_messages(
         query = query.filter(Expr::cust_with_values("channels @> $1", [channel.jsonb()]));
     }
 
+    if let Some(schedule_status) = schedule_status {
+        query = query.filter(schedule_status.condition());
+    }
+
     let (query, iter_direction) = filter_and_paginate_time_limited(
         query,
         message::Column::Id,
@@ -300,16 +500,16 @@ async fn list_messages(
 
     let msgs_and_content: Vec<(message::Model, Option<messagecontent::Model>)> =
         query.all(db).await?.into_iter().collect();
-    let into = |(msg, content): (message::Model, Option<messagecontent::Model>)| {
+    let into = |(msg, content): (message::Model, Option<messagecontent::Model>)| async move {
         if with_content {
-            MessageOut::from_msg_and_payload(msg, content.map(|c| c.payload))
+            MessageOut::from_msg_and_payload(msg, content, blob_store).await
         } else {
-            MessageOut::without_payload(msg)
+            Ok(MessageOut::without_payload(msg))
         }
     };
 
     Ok(Json(MessageOut::list_response(
-        msgs_and_content.into_iter().map(into).collect(),
+        try_join_all(msgs_and_content.into_iter().map(into)).await?,
         limit as usize,
         iter_direction,
     )))
@@ -331,33 +531,72 @@ pub struct CreateMessageQueryParams {
 /// Messages can also have `channels`, which similar to event types let endpoints filter by them. Unlike event types, messages can have multiple channels, and channels don't imply a specific message content or schema.
 ///
 /// The `payload` property is the webhook's body (the actual webhook message). Svix supports payload sizes of up to ~350kb, though it's generally a good idea to keep webhook payloads small, probably no larger than 40kb.
+///
+/// Messages with a `deliverAt` timestamp are stored right away but only dispatched at that time, and can be cancelled until then.
+///
+/// The `schemaVersion` declares which version of the event type's schema the payload conforms to. Endpoints pinned to a different version don't receive the message.
 #[aide_annotate(op_id = "v1.message.create")]
 async fn create_message(
     State(AppState {
         ref db,
         queue_tx,
         cache,
+        cfg,
+        ref blob_store,
         ..
     }): State<AppState>,
     ValidatedQuery(CreateMessageQueryParams { with_content }): ValidatedQuery<
         CreateMessageQueryParams,
     >,
     permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    RequestTraceContext(trace_context): RequestTraceContext,
     ValidatedJson(data): ValidatedJson<MessageIn>,
 ) -> Result<JsonStatus<202, MessageOut>> {
+    validate_deliver_at(&cfg, data.deliver_at)?;
+    validate_schema_version(db, app.org_id.clone(), &data).await?;
+
     Ok(JsonStatus(
-        create_message_inner(db, queue_tx, cache, with_content, None, data, app).await?,
+        create_message_inner(
+            db,
+            queue_tx,
+            cache,
+            blob_store,
+            with_content,
+            None,
+            data,
+            app,
+            trace_context,
+        )
+        .await?,
     ))
 }
 
+/// The content of a new message, with its payload offloaded to the blob store if it's large.
+async fn new_message_content(
+    blob_store: &BlobStore,
+    msg_id: MessageId,
+    payload: Vec<u8>,
+) -> Result<messagecontent::ActiveModel> {
+    if blob_store.should_offload_payload(payload.len()) {
+        let key = BlobStore::payload_key(&msg_id);
+        blob_store.put(&key, payload).await?;
+        Ok(messagecontent::ActiveModel::new_offloaded(msg_id, key))
+    } else {
+        Ok(messagecontent::ActiveModel::new(msg_id, payload))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn create_message_inner(
     db: &DatabaseConnection,
     queue_tx: TaskQueueProducer,
     cache: Cache,
+    blob_store: &BlobStore,
     with_content: bool,
     force_endpoint: Option<EndpointId>,
     data: MessageIn,
     app: application::Model,
+    trace_context: Option<TraceContext>,
 ) -> Result<MessageOut> {
     let create_message_app = CreateMessageApp::layered_fetch(
         &cache,
@@ -375,46 +614,376 @@ pub(crate) async fn create_message_inner(
     let msg = message::ActiveModel {
         app_id: Set(app.id.clone()),
         org_id: Set(app.org_id),
+        trace_context: Set(trace_context),
         ..data.into()
     };
+    let msg_id = msg.id.clone().unwrap();
+    let offloaded = blob_store.should_offload_payload(payload.len());
+    let msg_content = new_message_content(blob_store, msg_id.clone(), payload.clone()).await?;
 
-    let (msg, msg_content) = db
+    let res = db
         .transaction(|txn| {
             async move {
                 let msg = msg.insert(txn).await.map_err(http_error_on_conflict)?;
-                let msg_content = messagecontent::ActiveModel::new(msg.id.clone(), payload);
-                let msg_content = msg_content.insert(txn).await?;
-                Ok((msg, msg_content))
+                msg_content.insert(txn).await?;
+                messageevent::ActiveModel::created(&msg).insert(txn).await?;
+                Ok(msg)
             }
             .boxed()
         })
-        .await?;
+        .await;
+    if res.is_err() && offloaded {
+        blob_store
+            .delete_all([BlobStore::payload_key(&msg_id)])
+            .await;
+    }
+    let msg = res?;
 
+    enqueue_message(&queue_tx, &create_message_app, &msg, force_endpoint).await?;
+
+    let msg_out = if with_content {
+        MessageOut::from_msg_and_raw_payload(msg, Some(payload))
+    } else {
+        MessageOut::without_payload(msg)
+    };
+
+    Ok(msg_out)
+}
+
+/// Queues the dispatch of a newly created message, unless no endpoint would receive it.
+async fn enqueue_message(
+    queue_tx: &TaskQueueProducer,
+    create_message_app: &CreateMessageApp,
+    msg: &message::Model,
+    force_endpoint: Option<EndpointId>,
+) -> Result<()> {
+    // Endpoints may well be added before a scheduled message is delivered, so it's always queued
+    let delay = msg.deliver_at.map(|deliver_at| {
+        (DateTime::<Utc>::from(deliver_at) - Utc::now())
+            .to_std()
+            .unwrap_or_default()
+    });
     let trigger_type = MessageAttemptTriggerType::Scheduled;
-    if !create_message_app
-        .filtered_endpoints(trigger_type, &msg.event_type, msg.channels.as_ref())
-        .is_empty()
+    if delay.is_some()
+        || !create_message_app
+            .filtered_endpoints(trigger_type, &msg.event_type, msg.channels.as_ref())
+            .is_empty()
     {
         queue_tx
             .send(
                 &MessageTaskBatch::new_task(
                     msg.id.clone(),
-                    app.id.clone(),
+                    msg.app_id.clone(),
                     force_endpoint,
-                    MessageAttemptTriggerType::Scheduled,
+                    trigger_type,
+                    msg.priority,
                 ),
-                None,
+                delay,
             )
             .await?;
     }
 
-    let msg_out = if with_content {
-        MessageOut::from_msg_and_payload(msg, Some(msg_content.payload))
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Validate, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkMessageIn {
+    /// The messages are validated one by one, so invalid messages don't prevent the others from
+    /// being created.
+    #[validate(length(min = 1, max = 500))]
+    pub messages: Vec<MessageIn>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Validate, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkAppMessageIn {
+    /// The ID or UID of the application to create the message for
+    #[validate]
+    pub app: ApplicationIdOrUid,
+    #[validate]
+    pub message: MessageIn,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Validate, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkAppMessagesIn {
+    /// The messages are validated one by one, so invalid messages don't prevent the others from
+    /// being created.
+    #[validate(length(min = 1, max = 500))]
+    pub messages: Vec<BulkAppMessageIn>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkMessageError {
+    pub code: String,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkMessageResultOut {
+    /// The status the message would have been answered with if created on its own: 202 when it
+    /// was created, 404 when its application doesn't exist, 409 when its `eventId` was already
+    /// used and 422 when it's invalid.
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<MessageOut>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<BulkMessageError>,
+}
+
+impl BulkMessageResultOut {
+    fn created(msg: MessageOut) -> Self {
+        Self {
+            status: StatusCode::ACCEPTED.as_u16(),
+            message: Some(msg),
+            error: None,
+        }
+    }
+
+    fn error(status: StatusCode, code: &str, detail: String) -> Self {
+        Self {
+            status: status.as_u16(),
+            message: None,
+            error: Some(BulkMessageError {
+                code: code.to_owned(),
+                detail,
+            }),
+        }
+    }
+
+    fn invalid(cfg: &Configuration, data: &impl Validate, msg: &MessageIn) -> Option<Self> {
+        let mut errors: Vec<String> = match data.validate() {
+            Ok(()) => vec![],
+            Err(e) => validation_errors(vec![], e)
+                .into_iter()
+                .map(|item| format!("{}: {}", item.loc.join("."), item.msg))
+                .collect(),
+        };
+        if let Some(e) = deliver_at_error(cfg, msg.deliver_at) {
+            errors.push(format!("deliverAt: {e}"));
+        }
+
+        (!errors.is_empty()).then(|| {
+            Self::error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "validation",
+                errors.join("; "),
+            )
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkMessageOut {
+    /// The result for each message, in the order of the request
+    pub data: Vec<BulkMessageResultOut>,
+}
+
+/// Reports the messages declaring a `schemaVersion` their event type doesn't have as invalid. Each
+/// event type is only looked up once.
+async fn check_schema_versions(
+    db: &DatabaseConnection,
+    items: Vec<Result<(application::Model, MessageIn), BulkMessageResultOut>>,
+) -> Result<Vec<Result<(application::Model, MessageIn), BulkMessageResultOut>>> {
+    let mut errors: HashMap<(OrganizationId, EventTypeName, SchemaVersion), Option<String>> =
+        HashMap::new();
+    let mut checked = Vec::with_capacity(items.len());
+
+    for item in items {
+        let (app, msg) = match item {
+            Ok((app, msg)) => (app, msg),
+            Err(result) => {
+                checked.push(Err(result));
+                continue;
+            }
+        };
+        let Some(version) = msg.schema_version.clone() else {
+            checked.push(Ok((app, msg)));
+            continue;
+        };
+
+        let key = (app.org_id.clone(), msg.event_type.clone(), version);
+        let error = match errors.get(&key) {
+            Some(error) => error.clone(),
+            None => {
+                let error = schema_version_error(db, app.org_id.clone(), &msg).await?;
+                errors.insert(key, error.clone());
+                error
+            }
+        };
+        checked.push(match error {
+            Some(e) => Err(BulkMessageResultOut::error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "validation",
+                format!("schemaVersion: {e}"),
+            )),
+            None => Ok((app, msg)),
+        });
+    }
+
+    Ok(checked)
+}
+
+/// Creates the valid messages in a single transaction. Messages whose `eventId` was already used
+/// are skipped rather than failing the others.
+pub(crate) async fn create_messages_bulk(
+    db: &DatabaseConnection,
+    queue_tx: &TaskQueueProducer,
+    cache: &Cache,
+    blob_store: &BlobStore,
+    with_content: bool,
+    items: Vec<Result<(application::Model, MessageIn), BulkMessageResultOut>>,
+    broadcast_id: Option<BroadcastId>,
+    trace_context: Option<TraceContext>,
+) -> Result<BulkMessageOut> {
+    let items = check_schema_versions(db, items).await?;
+
+    let mut apps = HashMap::new();
+    let mut msg_ids = Vec::with_capacity(items.len());
+    let mut msgs = Vec::new();
+    let mut payloads = HashMap::new();
+    let mut results = Vec::with_capacity(items.len());
+
+    for item in items {
+        match item {
+            Ok((app, data)) => {
+                let payload = data.payload();
+                let msg = message::ActiveModel {
+                    app_id: Set(app.id.clone()),
+                    org_id: Set(app.org_id.clone()),
+                    broadcast_id: Set(broadcast_id.clone()),
+                    trace_context: Set(trace_context.clone()),
+                    ..data.into()
+                };
+                let msg_id = msg.id.clone().unwrap();
+
+                payloads.insert(msg_id.clone(), payload);
+                msg_ids.push(Some(msg_id));
+                msgs.push(msg);
+                apps.entry(app.id.clone()).or_insert(app);
+                results.push(None);
+            }
+            Err(result) => {
+                msg_ids.push(None);
+                results.push(Some(result));
+            }
+        }
+    }
+
+    let created: Vec<message::Model> = if msgs.is_empty() {
+        vec![]
     } else {
-        MessageOut::without_payload(msg)
+        let mut offloaded = Vec::new();
+        let mut contents = Vec::with_capacity(payloads.len());
+        for (msg_id, payload) in &payloads {
+            if blob_store.should_offload_payload(payload.len()) {
+                offloaded.push(msg_id.clone());
+            }
+            let content = new_message_content(blob_store, msg_id.clone(), payload.clone()).await?;
+            contents.push((msg_id.clone(), content));
+        }
+        let res = db
+            .transaction(|txn| {
+                async move {
+                    // Duplicate `eventId`s are left out rather than aborting the whole transaction
+                    let created = message::Entity::insert_many(msgs)
+                        .on_conflict(OnConflict::new().do_nothing().to_owned())
+                        .exec_with_returning_many(txn)
+                        .await?;
+
+                    let created_ids: HashSet<_> = created.iter().map(|m| &m.id).collect();
+                    let contents: Vec<_> = contents
+                        .into_iter()
+                        .filter(|(msg_id, _)| created_ids.contains(msg_id))
+                        .map(|(_, content)| content)
+                        .collect();
+                    if !contents.is_empty() {
+                        messagecontent::Entity::insert_many(contents)
+                            .exec(txn)
+                            .await?;
+                    }
+                    if !created.is_empty() {
+                        messageevent::Entity::insert_many(
+                            created.iter().map(messageevent::ActiveModel::created),
+                        )
+                        .exec(txn)
+                        .await?;
+                    }
+
+                    Ok(created)
+                }
+                .boxed()
+            })
+            .await;
+
+        // The payloads of messages which weren't created aren't referenced by anything
+        let created_ids: HashSet<_> = match &res {
+            Ok(created) => created.iter().map(|m| m.id.clone()).collect(),
+            Err(_) => HashSet::new(),
+        };
+        blob_store
+            .delete_all(
+                offloaded
+                    .iter()
+                    .filter(|msg_id| !created_ids.contains(*msg_id))
+                    .map(BlobStore::payload_key),
+            )
+            .await;
+
+        res?
     };
 
-    Ok(msg_out)
+    let mut create_message_apps: HashMap<ApplicationId, CreateMessageApp> = HashMap::new();
+    let mut created: HashMap<MessageId, message::Model> =
+        created.into_iter().map(|m| (m.id.clone(), m)).collect();
+
+    for (msg_id, result) in msg_ids.into_iter().zip(results.iter_mut()) {
+        let Some(msg_id) = msg_id else {
+            continue;
+        };
+        let Some(msg) = created.remove(&msg_id) else {
+            *result = Some(BulkMessageResultOut::error(
+                StatusCode::CONFLICT,
+                "conflict",
+                "A message with this eventId already exists".to_owned(),
+            ));
+            continue;
+        };
+
+        if !create_message_apps.contains_key(&msg.app_id) {
+            let app = &apps[&msg.app_id];
+            let create_message_app = CreateMessageApp::layered_fetch(
+                cache,
+                db,
+                Some(app.clone()),
+                app.org_id.clone(),
+                app.id.clone(),
+                std::time::Duration::from_secs(30),
+            )
+            .await?
+            .ok_or_else(|| Error::generic(format!("Application doesn't exist: {}", app.id)))?;
+            create_message_apps.insert(app.id.clone(), create_message_app);
+        }
+        enqueue_message(queue_tx, &create_message_apps[&msg.app_id], &msg, None).await?;
+
+        let payload = payloads.remove(&msg_id);
+        *result = Some(BulkMessageResultOut::created(if with_content {
+            MessageOut::from_msg_and_raw_payload(msg, payload)
+        } else {
+            MessageOut::without_payload(msg)
+        }));
+    }
+
+    Ok(BulkMessageOut {
+        data: results
+            .into_iter()
+            .map(|r| r.expect("every message has a result"))
+            .collect(),
+    })
 }
 
 #[derive(Debug, Deserialize, Validate, JsonSchema)]
@@ -427,7 +996,11 @@ pub struct GetMessageQueryParams {
 /// Get a message by its ID or eventID.
 #[aide_annotate(op_id = "v1.message.get")]
 async fn get_message(
-    State(AppState { ref db, .. }): State<AppState>,
+    State(AppState {
+        ref db,
+        ref blob_store,
+        ..
+    }): State<AppState>,
     Path(ApplicationMsgPath { msg_id, .. }): Path<ApplicationMsgPath>,
     ValidatedQuery(GetMessageQueryParams { with_content }): ValidatedQuery<GetMessageQueryParams>,
     permissions::Application { app }: permissions::Application,
@@ -438,7 +1011,7 @@ async fn get_message(
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
     let msg_out = if with_content {
-        MessageOut::from_msg_and_payload(msg, msg_content.map(|c| c.payload))
+        MessageOut::from_msg_and_payload(msg, msg_content, blob_store).await?
     } else {
         MessageOut::without_payload(msg)
     };
@@ -450,11 +1023,16 @@ async fn get_message(
 /// The message can't be replayed or resent once its payload has been deleted or expired.
 #[aide_annotate(op_id = "v1.message.expunge-content")]
 async fn expunge_message_content(
-    State(AppState { ref db, .. }): State<AppState>,
+    State(AppState {
+        ref db,
+        ref blob_store,
+        ..
+    }): State<AppState>,
     Path(ApplicationMsgPath { msg_id, .. }): Path<ApplicationMsgPath>,
     permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    auditor: Auditor,
 ) -> Result<StatusCode> {
-    let msg = message::Entity::secure_find_by_id_or_uid(app.id, msg_id)
+    let msg = message::Entity::secure_find_by_id_or_uid(app.id.clone(), msg_id)
         .one(db)
         .await?
         .ok_or_else(|| HttpError::not_found(None, None))?;
@@ -462,11 +1040,181 @@ async fn expunge_message_content(
     let mut msg = msg.into_active_model();
 
     msg.legacy_payload = Set(None);
-    msg.update(db).await?;
 
-    messagecontent::Entity::delete_by_id(msg_id)
-        .exec(db)
+    let txn = db.begin().await?;
+    msg.update(&txn).await?;
+    let mut blob_key = None;
+    if let Some(content) = messagecontent::Entity::find_by_id(msg_id).one(&txn).await? {
+        blob_key = content.blob_key.clone();
+        content.delete(&txn).await?;
+    }
+    let entry = auditor.record(&txn, Some(app.id), None, None).await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    if let Some(key) = blob_key {
+        blob_store.delete(&key).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Creates many messages for the application at once.
+///
+/// Each message is validated and created independently of the others, and its result is reported
+/// in the same position as in the request. Messages whose `eventId` was already used are reported
+/// as conflicts rather than failing the whole request.
+#[aide_annotate(op_id = "v1.message.create-bulk")]
+async fn create_messages_bulk_for_app(
+    State(AppState {
+        ref db,
+        ref queue_tx,
+        ref cache,
+        ref blob_store,
+        cfg,
+        ..
+    }): State<AppState>,
+    ValidatedQuery(CreateMessageQueryParams { with_content }): ValidatedQuery<
+        CreateMessageQueryParams,
+    >,
+    permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    RequestTraceContext(trace_context): RequestTraceContext,
+    ValidatedJson(data): ValidatedJson<BulkMessageIn>,
+) -> Result<Json<BulkMessageOut>> {
+    let items = data
+        .messages
+        .into_iter()
+        .map(
+            |msg| match BulkMessageResultOut::invalid(&cfg, &msg, &msg) {
+                Some(invalid) => Err(invalid),
+                None => Ok((app.clone(), msg)),
+            },
+        )
+        .collect();
+
+    Ok(Json(
+        create_messages_bulk(
+            db,
+            queue_tx,
+            cache,
+            blob_store,
+            with_content,
+            items,
+            None,
+            trace_context,
+        )
+        .await?,
+    ))
+}
+
+/// Creates many messages at once, for any of the organization's applications.
+///
+/// Each message is validated and created independently of the others, and its result is reported
+/// in the same position as in the request.
+#[aide_annotate(op_id = "v1.message.create-bulk-multi-app")]
+async fn create_messages_bulk_multi_app(
+    State(AppState {
+        ref db,
+        ref queue_tx,
+        ref cache,
+        ref blob_store,
+        cfg,
+        ..
+    }): State<AppState>,
+    ValidatedQuery(CreateMessageQueryParams { with_content }): ValidatedQuery<
+        CreateMessageQueryParams,
+    >,
+    permissions::Organization { org_id }: permissions::Organization,
+    RequestTraceContext(trace_context): RequestTraceContext,
+    ValidatedJson(data): ValidatedJson<BulkAppMessagesIn>,
+) -> Result<Json<BulkMessageOut>> {
+    let app_keys: HashSet<&str> = data.messages.iter().map(|m| m.app.0.as_str()).collect();
+    let app_keys: Vec<String> = app_keys.into_iter().map(ToOwned::to_owned).collect();
+
+    let mut apps: HashMap<String, application::Model> = HashMap::new();
+    for app in application::Entity::secure_find(org_id)
+        .filter(
+            Condition::any()
+                .add(application::Column::Id.is_in(app_keys.clone()))
+                .add(application::Column::Uid.is_in(app_keys)),
+        )
+        .all(db)
+        .await?
+    {
+        if let Some(uid) = &app.uid {
+            apps.insert(uid.0.clone(), app.clone());
+        }
+        apps.insert(app.id.0.clone(), app);
+    }
+
+    let items = data
+        .messages
+        .into_iter()
+        .map(|item| {
+            if let Some(invalid) = BulkMessageResultOut::invalid(&cfg, &item, &item.message) {
+                return Err(invalid);
+            }
+            match apps.get(&item.app.0) {
+                Some(app) => Ok((app.clone(), item.message)),
+                None => Err(BulkMessageResultOut::error(
+                    StatusCode::NOT_FOUND,
+                    "not_found",
+                    format!("Application not found: {}", item.app.0),
+                )),
+            }
+        })
+        .collect();
+
+    Ok(Json(
+        create_messages_bulk(
+            db,
+            queue_tx,
+            cache,
+            blob_store,
+            with_content,
+            items,
+            None,
+            trace_context,
+        )
+        .await?,
+    ))
+}
+
+/// Cancel the delivery of a message scheduled with `deliverAt`.
+///
+/// Fails with a 409 if the message wasn't scheduled, or was already dispatched or cancelled.
+#[aide_annotate(op_id = "v1.message.cancel-scheduled")]
+async fn cancel_scheduled_message(
+    State(AppState { ref db, .. }): State<AppState>,
+    Path(ApplicationMsgPath { msg_id, .. }): Path<ApplicationMsgPath>,
+    permissions::OrganizationWithApplication { app }: permissions::OrganizationWithApplication,
+    auditor: Auditor,
+) -> Result<StatusCode> {
+    let msg = message::Entity::secure_find_by_id_or_uid(app.id.clone(), msg_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    let txn = db.begin().await?;
+    // Conditional so it can't race with the message being dispatched
+    let res = message::Entity::update_many()
+        .col_expr(message::Column::CancelledAt, Expr::value(Utc::now()))
+        .filter(message::Column::Id.eq(msg.id))
+        .filter(MessageScheduleStatus::Scheduled.condition())
+        .exec(&txn)
         .await?;
+
+    if res.rows_affected == 0 {
+        return Err(HttpError::conflict(
+            Some("not_scheduled".to_owned()),
+            Some("The message isn't scheduled for later delivery".to_owned()),
+        )
+        .into());
+    }
+
+    let entry = auditor.record(&txn, Some(app.id), None, None).await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -479,6 +1227,22 @@ pub fn router() -> ApiRouter<AppState> {
                 .get_with(list_messages, list_messages_operation),
             &tag,
         )
+        .api_route_with(
+            "/app/:app_id/msg/bulk",
+            post_with(
+                create_messages_bulk_for_app,
+                create_messages_bulk_for_app_operation,
+            ),
+            &tag,
+        )
+        .api_route_with(
+            "/msg/bulk",
+            post_with(
+                create_messages_bulk_multi_app,
+                create_messages_bulk_multi_app_operation,
+            ),
+            &tag,
+        )
         .api_route_with(
             "/app/:app_id/msg/:msg_id",
             get_with(get_message, get_message_operation),
@@ -487,6 +1251,11 @@ pub fn router() -> ApiRouter<AppState> {
         .api_route_with(
             "/app/:app_id/msg/:msg_id/content",
             delete_with(expunge_message_content, expunge_message_content_operation),
+            &tag,
+        )
+        .api_route_with(
+            "/app/:app_id/msg/:msg_id/cancel",
+            post_with(cancel_scheduled_message, cancel_scheduled_message_operation),
             tag,
         )
 }
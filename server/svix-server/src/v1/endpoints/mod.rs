@@ -4,8 +4,13 @@
 pub mod admin;
 pub mod application;
 pub mod attempt;
+pub mod audit_log;
 pub mod auth;
+pub mod broadcast;
 pub mod endpoint;
 pub mod event_type;
+pub mod events;
 pub mod health;
 pub mod message;
+pub mod retention_policy;
+pub mod url_policy;
@@ -0,0 +1,122 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use aide::axum::{routing::get_with, ApiRouter};
+use axum::{extract::State, Json};
+use schemars::JsonSchema;
+use sea_orm::{EntityTrait, ModelTrait, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use svix_server_derive::aide_annotate;
+use validator::Validate;
+
+use crate::{
+    core::{
+        audit_log::{self, Auditor},
+        permissions,
+    },
+    db::models::orgretentionpolicy,
+    error::{HttpError, Result},
+    v1::utils::{openapi_tag, NoContent, ValidatedJson},
+    AppState,
+};
+
+fn example_retention_period() -> u16 {
+    30
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Validate, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// The number of days after which messages are deleted, along with their attempts
+    #[validate(range(min = 1, message = "Retention periods must be at least one day"))]
+    #[schemars(example = "example_retention_period")]
+    pub retention_period: u16,
+}
+
+impl From<orgretentionpolicy::Model> for RetentionPolicy {
+    fn from(model: orgretentionpolicy::Model) -> Self {
+        Self {
+            retention_period: model.retention_period as u16,
+        }
+    }
+}
+
+/// Get the organization's default retention policy.
+#[aide_annotate(op_id = "v1.retention-policy.get")]
+async fn get_retention_policy(
+    State(AppState { ref db, .. }): State<AppState>,
+    permissions::Organization { org_id }: permissions::Organization,
+) -> Result<Json<RetentionPolicy>> {
+    let policy = orgretentionpolicy::Entity::find_by_id(org_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| HttpError::not_found(None, None))?;
+
+    Ok(Json(policy.into()))
+}
+
+/// Set the organization's default retention policy.
+///
+/// Messages older than the retention period are deleted in the background, along with their
+/// attempts and the stored responses. Applications setting their own `retentionPeriod` override
+/// it. Without any retention period, messages are kept forever.
+#[aide_annotate(op_id = "v1.retention-policy.update")]
+async fn update_retention_policy(
+    State(AppState { ref db, .. }): State<AppState>,
+    permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
+    ValidatedJson(data): ValidatedJson<RetentionPolicy>,
+) -> Result<Json<RetentionPolicy>> {
+    let txn = db.begin().await?;
+    let before = orgretentionpolicy::Entity::find_by_id(org_id.clone())
+        .one(&txn)
+        .await?
+        .and_then(|model| audit_log::state(RetentionPolicy::from(model)));
+    let policy: RetentionPolicy = orgretentionpolicy::Entity::upsert(
+        orgretentionpolicy::ActiveModel::new(org_id, data.retention_period.into()),
+    )
+    .exec_with_returning(&txn)
+    .await?
+    .into();
+    let entry = auditor
+        .record(&txn, None, before, audit_log::state(&policy))
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    Ok(Json(policy))
+}
+
+/// Remove the organization's default retention policy.
+#[aide_annotate(op_id = "v1.retention-policy.delete")]
+async fn delete_retention_policy(
+    State(AppState { ref db, .. }): State<AppState>,
+    permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
+) -> Result<NoContent> {
+    let txn = db.begin().await?;
+    let mut before = None;
+    if let Some(model) = orgretentionpolicy::Entity::find_by_id(org_id)
+        .one(&txn)
+        .await?
+    {
+        before = audit_log::state(RetentionPolicy::from(model.clone()));
+        model.delete(&txn).await?;
+    }
+    let entry = auditor.record(&txn, None, before, None).await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    Ok(NoContent)
+}
+
+pub fn router() -> ApiRouter<AppState> {
+    let tag = openapi_tag("Retention Policy");
+    ApiRouter::new().api_route_with(
+        "/retention-policy",
+        get_with(get_retention_policy, get_retention_policy_operation)
+            .put_with(update_retention_policy, update_retention_policy_operation)
+            .delete_with(delete_retention_policy, delete_retention_policy_operation),
+        tag,
+    )
+}
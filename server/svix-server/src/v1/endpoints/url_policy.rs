@@ -0,0 +1,104 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use aide::axum::{routing::get_with, ApiRouter};
+use axum::{extract::State, Json};
+use sea_orm::{EntityTrait, ModelTrait, TransactionTrait};
+use svix_server_derive::aide_annotate;
+
+use crate::{
+    core::{
+        audit_log::{self, Auditor},
+        permissions,
+        url_policy::{fetch_url_policy, invalidate_url_policy, UrlPolicy},
+    },
+    db::models::orgurlpolicy,
+    error::Result,
+    v1::utils::{openapi_tag, NoContent, ValidatedJson},
+    AppState,
+};
+
+/// Get the organization's URL policy.
+///
+/// An empty policy is returned if none is set.
+#[aide_annotate(op_id = "v1.url-policy.get")]
+async fn get_url_policy(
+    State(AppState { ref db, .. }): State<AppState>,
+    permissions::Organization { org_id }: permissions::Organization,
+) -> Result<Json<UrlPolicy>> {
+    Ok(Json(
+        fetch_url_policy(db, &org_id).await?.unwrap_or_default(),
+    ))
+}
+
+/// Set the organization's URL policy.
+///
+/// The policy restricts the hostnames, IPs and ports endpoints may use. It's enforced when
+/// endpoints are created or updated, and whenever webhooks are sent, but doesn't modify existing
+/// endpoints. It can't be used to reach private IPs blocked by the server configuration.
+#[aide_annotate(op_id = "v1.url-policy.update")]
+async fn update_url_policy(
+    State(AppState {
+        ref db, ref cache, ..
+    }): State<AppState>,
+    permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
+    ValidatedJson(data): ValidatedJson<UrlPolicy>,
+) -> Result<Json<UrlPolicy>> {
+    let txn = db.begin().await?;
+    let before = orgurlpolicy::Entity::find_by_id(org_id.clone())
+        .one(&txn)
+        .await?
+        .and_then(|model| audit_log::state(model.policy));
+    let policy = orgurlpolicy::Entity::upsert(orgurlpolicy::ActiveModel::new(org_id.clone(), data))
+        .exec_with_returning(&txn)
+        .await?
+        .policy;
+    let entry = auditor
+        .record(&txn, None, before, audit_log::state(&policy))
+        .await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    invalidate_url_policy(cache, &org_id).await?;
+
+    Ok(Json(policy))
+}
+
+/// Remove the organization's URL policy.
+#[aide_annotate(op_id = "v1.url-policy.delete")]
+async fn delete_url_policy(
+    State(AppState {
+        ref db, ref cache, ..
+    }): State<AppState>,
+    permissions::Organization { org_id }: permissions::Organization,
+    auditor: Auditor,
+) -> Result<NoContent> {
+    let txn = db.begin().await?;
+    let mut before = None;
+    if let Some(model) = orgurlpolicy::Entity::find_by_id(org_id.clone())
+        .one(&txn)
+        .await?
+    {
+        before = audit_log::state(&model.policy);
+        model.delete(&txn).await?;
+    }
+    let entry = auditor.record(&txn, None, before, None).await?;
+    txn.commit().await?;
+    auditor.notify(entry).await;
+
+    invalidate_url_policy(cache, &org_id).await?;
+
+    Ok(NoContent)
+}
+
+pub fn router() -> ApiRouter<AppState> {
+    let tag = openapi_tag("URL Policy");
+    ApiRouter::new().api_route_with(
+        "/url-policy",
+        get_with(get_url_policy, get_url_policy_operation)
+            .put_with(update_url_policy, update_url_policy_operation)
+            .delete_with(delete_url_policy, delete_url_policy_operation),
+        tag,
+    )
+}
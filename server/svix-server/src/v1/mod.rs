@@ -20,8 +20,13 @@ pub fn router() -> ApiRouter<AppState> {
         .merge(endpoints::endpoint::router())
         .merge(endpoints::event_type::router())
         .merge(endpoints::message::router())
+        .merge(endpoints::events::router())
+        .merge(endpoints::broadcast::router())
         .merge(endpoints::attempt::router())
         .merge(endpoints::admin::router())
+        .merge(endpoints::url_policy::router())
+        .merge(endpoints::retention_policy::router())
+        .merge(endpoints::audit_log::router())
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(AxumOtelSpanCreator)
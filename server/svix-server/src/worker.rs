@@ -2,48 +2,72 @@
 // SPDX-Licensepub(crate) -Identifier: MIT
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{
         atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc, LazyLock,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use axum::body::HttpBody as _;
-use chrono::Utc;
-use futures::future;
+use bytes::Bytes;
+use chrono::{SubsecRound, Utc};
+use futures::{future, FutureExt};
 use http::{HeaderValue, StatusCode, Version};
 use rand::Rng;
 use sea_orm::{
-    prelude::DateTimeUtc, ActiveModelBehavior, ActiveModelTrait, ColumnTrait, DatabaseConnection,
-    EntityTrait, QueryFilter, Set, TryIntoModel,
+    prelude::{DateTimeUtc, DateTimeWithTimeZone},
+    sea_query::Expr,
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait,
+    QueryFilter, QuerySelect, Set, TransactionTrait, TryIntoModel,
 };
 use serde::{Deserialize, Serialize};
 use tokio::time::sleep;
 use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{
-    cfg::Configuration,
+    cfg::{Configuration, ProxyConfig},
     core::{
+        blob_store::BlobStore,
         cache::{kv_def, Cache, CacheBehavior, CacheKey, CacheValue},
+        concurrency_limiter::{ConcurrencyLimiter, ConcurrencyPermit},
         cryptography::Encryption,
+        endpoint_verification::{set_verification_result, VerificationChallenge},
+        fair_queue::FairQueue,
         message_app::{CreateMessageApp, CreateMessageEndpoint},
+        oauth2,
         operational_webhooks::{
             EndpointDisabledEventData, MessageAttemptEvent, OperationalWebhook,
             OperationalWebhookSender,
         },
+        otel_spans::stored_span_context,
+        pause::{invalidate_cached_app, process_release_task},
+        sink::{SinkClient, SinkMessage},
         types::{
             ApplicationId, ApplicationUid, BaseId, EndpointHeaders, EndpointId,
-            EndpointSecretInternal, EndpointSecretType, MessageAttemptId,
-            MessageAttemptTriggerType, MessageId, MessageStatus, MessageUid, OrganizationId,
+            EndpointSecretInternal, EndpointSecretType, EndpointVerificationStatus, EventTypeName,
+            MessageAttemptId, MessageAttemptTriggerType, MessageEndpointId, MessageId,
+            MessagePriority, MessageStatus, MessageUid, OrganizationId, TraceContext,
         },
-        webhook_http_client::{Error as WebhookClientError, RequestBuilder, WebhookClient},
+        url_policy::{self, UrlPolicy},
+        webhook_http_client::{
+            read_body_bounded, ClientTlsConfig, Error as WebhookClientError, HttpClientConfig,
+            RequestBuilder, WebhookClient,
+        },
+    },
+    db::models::{
+        application, endpoint, message, messageattempt, messagecontent, messagedestination,
+        messageevent,
     },
-    db::models::{endpoint, message, messageattempt, messagecontent, messagedestination},
     error::{Error, ErrorType, HttpError, Result},
-    queue::{MessageTask, QueueTask, TaskQueueConsumer, TaskQueueProducer},
-    v1::utils::get_unix_timestamp,
+    metrics::{DeliveryMetrics, TenantMetrics},
+    queue::{
+        EndpointVerificationTask, MessageTask, QueueLane, QueueTask, ReleaseHeldDestinationsTask,
+        TaskQueueDelivery, TaskQueueProducer,
+    },
+    v1::{endpoints::broadcast, utils::get_unix_timestamp},
 };
 
 pub type CaseSensitiveHeaderMap = HashMap<String, HeaderValue>;
@@ -60,6 +84,17 @@ const OP_WEBHOOKS_SEND_FAILING_EVENT_AFTER: usize = 4;
 
 const RESPONSE_MAX_SIZE: usize = 20000;
 
+/// How long tasks which can't wait in the worker for their organization's turn are put back on
+/// the queue for, before jitter
+const TENANT_DEFER_DELAY: Duration = Duration::from_secs(5);
+
+/// How long a task waits in the worker for its turn before it's put back on the queue instead,
+/// comfortably below the time after which the queue redelivers it
+const TENANT_MAX_WAIT: Duration = Duration::from_secs(15);
+
+/// How many tasks a lane holds waiting for their turn, per task it runs at once
+const TENANT_QUEUED_PER_TASK: usize = 2;
+
 /// A simple struct noting the context of the wrapped [`DateTimeUtc`]. This struct is returned when
 /// you are to disable disable an endpoint. This is optionally returned by [`process_failure_cache`]
 /// which is to be called after all retry events are exhausted.
@@ -190,6 +225,7 @@ fn generate_msg_headers(
     signatures: String,
     whitelabel_headers: bool,
     configured_headers: Option<&EndpointHeaders>,
+    access_token: Option<&str>,
     _endpoint_url: &str,
 ) -> Result<CaseSensitiveHeaderMap> {
     let mut headers = CaseSensitiveHeaderMap::new();
@@ -233,6 +269,17 @@ fn generate_msg_headers(
             }
         }
     }
+    // Set last, so a configured `Authorization` header doesn't clobber the access token. Header
+    // names are case-insensitive, so it's removed whatever its case, rather than sent twice.
+    if let Some(access_token) = access_token {
+        headers.retain(|name, _| !name.eq_ignore_ascii_case("authorization"));
+        headers.insert(
+            "authorization".to_owned(),
+            format!("Bearer {access_token}")
+                .parse()
+                .map_err(|e| Error::generic(format!("Error parsing access token: {e:?}")))?,
+        );
+    }
 
     Ok(headers)
 }
@@ -245,6 +292,150 @@ struct WorkerContext<'a> {
     queue_tx: &'a TaskQueueProducer,
     op_webhook_sender: &'a OperationalWebhookSender,
     webhook_client: &'a WebhookClient,
+    sinks: &'a SinkClient,
+    blob_store: &'a BlobStore,
+    delivery_metrics: &'a DeliveryMetrics,
+    tenant_limits: &'a TenantLimits,
+}
+
+/// Caps the deliveries the worker makes at once per organization and per endpoint, so a single
+/// tenant with a large backlog or a slow endpoint can't take up every worker slot.
+struct TenantLimits {
+    orgs: ConcurrencyLimiter<OrganizationId>,
+    endpoints: ConcurrencyLimiter<EndpointId>,
+    metrics: TenantMetrics,
+}
+
+impl TenantLimits {
+    fn new(cfg: &Configuration) -> Self {
+        Self {
+            orgs: ConcurrencyLimiter::new(
+                cfg.worker_max_tasks_per_org,
+                cfg.worker_max_tasks_per_org_overrides.clone(),
+            ),
+            endpoints: ConcurrencyLimiter::new(cfg.worker_max_tasks_per_endpoint, HashMap::new()),
+            metrics: TenantMetrics::new(&opentelemetry::global::meter("svix.com")),
+        }
+    }
+
+    fn try_acquire(&self, org_id: &OrganizationId, endp_id: &EndpointId) -> Option<TenantPermit> {
+        let org = self.orgs.try_acquire(org_id)?;
+        let endpoint = self.endpoints.try_acquire(endp_id)?;
+        self.metrics.record_in_flight(org_id, 1);
+
+        Some(TenantPermit {
+            _org: org,
+            _endpoint: endpoint,
+            org_id: org_id.clone(),
+            metrics: self.metrics.clone(),
+        })
+    }
+}
+
+struct TenantPermit {
+    _org: ConcurrencyPermit<OrganizationId>,
+    _endpoint: ConcurrencyPermit<EndpointId>,
+    org_id: OrganizationId,
+    metrics: TenantMetrics,
+}
+
+impl Drop for TenantPermit {
+    fn drop(&mut self) {
+        self.metrics.record_in_flight(&self.org_id, -1);
+    }
+}
+
+/// The organizations of the applications tasks are for, which the worker's fair queues are keyed
+/// by. An application never moves to another organization, so they're remembered.
+#[derive(Default)]
+struct TenantResolver {
+    orgs: std::sync::Mutex<HashMap<ApplicationId, OrganizationId>>,
+}
+
+/// How many applications' organizations [`TenantResolver`] remembers, at most
+const TENANT_RESOLVER_MAX_APPS: usize = 100_000;
+
+impl TenantResolver {
+    /// The organization of each task's application, or `None` for tasks not for an application or
+    /// for one which doesn't exist (anymore).
+    async fn org_ids(
+        &self,
+        db: &DatabaseConnection,
+        tasks: &[TaskQueueDelivery],
+    ) -> Vec<Option<OrganizationId>> {
+        let missing: HashSet<ApplicationId> = {
+            let orgs = self.orgs.lock().unwrap();
+            tasks
+                .iter()
+                .filter_map(|delivery| delivery.task.app_id())
+                .filter(|app_id| !orgs.contains_key(*app_id))
+                .cloned()
+                .collect()
+        };
+
+        if !missing.is_empty() {
+            let found = application::Entity::find()
+                .select_only()
+                .column(application::Column::Id)
+                .column(application::Column::OrgId)
+                .filter(application::Column::Id.is_in(missing))
+                .into_tuple::<(ApplicationId, OrganizationId)>()
+                .all(db)
+                .await;
+            match found {
+                Ok(found) => {
+                    let mut orgs = self.orgs.lock().unwrap();
+                    if orgs.len() + found.len() > TENANT_RESOLVER_MAX_APPS {
+                        orgs.clear();
+                    }
+                    orgs.extend(found);
+                }
+                // The tasks are still run, only not in their organization's turn
+                Err(err) => tracing::warn!("Error looking up the organizations of tasks: {err}"),
+            }
+        }
+
+        let orgs = self.orgs.lock().unwrap();
+        tasks
+            .iter()
+            .map(|delivery| {
+                delivery
+                    .task
+                    .app_id()
+                    .and_then(|app_id| orgs.get(app_id).cloned())
+            })
+            .collect()
+    }
+}
+
+/// A task received from the queue, waiting in a lane's [`FairQueue`] for its turn.
+struct WaitingTask {
+    delivery: TaskQueueDelivery,
+    org_id: Option<OrganizationId>,
+    since: Instant,
+}
+
+/// Puts a task back on the queue for a few seconds, rather than having it wait in the worker.
+async fn defer_delivery(
+    queue_tx: &TaskQueueProducer,
+    delivery: TaskQueueDelivery,
+    org_id: Option<&OrganizationId>,
+    tenant_limits: &TenantLimits,
+) {
+    if let Some(org_id) = org_id {
+        tenant_limits.metrics.record_deferred(org_id);
+    }
+    let delay = TENANT_DEFER_DELAY.mul_f32(rand::thread_rng().gen_range(1.0..=2.0));
+    let res = match queue_tx.send(&delivery.task, Some(delay)).await {
+        Ok(()) => delivery.ack().await,
+        Err(err) => {
+            tracing::error!("Error deferring task: {err}");
+            delivery.nack().await
+        }
+    };
+    if let Err(err) = res {
+        tracing::error!("Error acknowledging deferred task: {err}");
+    }
 }
 
 struct FailedDispatch(messageattempt::ActiveModel, Error);
@@ -253,7 +444,6 @@ struct SuccessfulDispatch(messageattempt::ActiveModel);
 #[allow(clippy::large_enum_variant)]
 enum IncompleteDispatch {
     Pending(PendingDispatch),
-    #[allow(dead_code)]
     Failed(FailedDispatch),
 }
 
@@ -264,6 +454,9 @@ struct PendingDispatch {
     payload: String,
     request_timeout: u64,
     created_at: DateTimeUtc,
+    tls: Option<ClientTlsConfig>,
+    proxy: Option<ProxyConfig>,
+    url_policy: Option<UrlPolicy>,
 }
 
 // Clippy fails to compute the first variant's size, stating it as
@@ -274,19 +467,139 @@ enum CompletedDispatch {
     Successful(SuccessfulDispatch),
 }
 
+/// The fields of a message attempt common to all attempts of the message destination. These are
+/// completed with the result of the attempt.
+fn new_attempt(
+    msg_task: &MessageTask,
+    endp: &CreateMessageEndpoint,
+    msg_dest: &messagedestination::Model,
+    created_at: DateTimeUtc,
+) -> messageattempt::ActiveModel {
+    messageattempt::ActiveModel {
+        // Set both ID and created_at to the same timestamp
+        id: Set(MessageAttemptId::new(created_at.into(), None)),
+        created_at: Set(created_at.into()),
+        msg_id: Set(msg_task.msg_id.clone()),
+        endp_id: Set(endp.id.clone()),
+        msg_dest_id: Set(msg_dest.id.clone()),
+        url: Set(endp.url.clone()),
+        ended_at: Set(Some(Utc::now().into())),
+        trigger_type: Set(msg_task.trigger_type),
+        response_duration_ms: Set(0), // Default to 0, will be updated after the request
+        ..Default::default()
+    }
+}
+
+/// How requests to the endpoint are made, other than their payload and signature.
+struct RequestRoute {
+    proxy: Option<ProxyConfig>,
+    url_policy: Option<UrlPolicy>,
+    access_token: Option<String>,
+}
+
+async fn request_route(
+    WorkerContext {
+        cfg,
+        cache,
+        db,
+        webhook_client,
+        ..
+    }: &WorkerContext<'_>,
+    endp: &CreateMessageEndpoint,
+    org_id: &OrganizationId,
+    egress_proxy: Option<&str>,
+) -> Result<RequestRoute> {
+    let proxy = match egress_proxy {
+        Some(name) => match cfg.egress_proxies.get(name) {
+            Some(egress_proxy) => Some(egress_proxy.proxy.clone()),
+            // Never fall back to the default route, the receiver may only accept the proxy's IPs
+            None => return Err(Error::generic(format!("Unknown egress proxy: {name}"))),
+        },
+        None => None,
+    };
+
+    let url_policy = url_policy::cached_url_policy(cache, db, org_id)
+        .await?
+        .filter(|p| !p.is_empty());
+
+    let access_token = match &endp.oauth2 {
+        Some(config) => Some(
+            oauth2::get_access_token(
+                cache,
+                webhook_client,
+                &cfg.encryption,
+                &endp.id,
+                config,
+                proxy.as_ref(),
+                url_policy.as_ref(),
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    Ok(RequestRoute {
+        proxy,
+        url_policy,
+        access_token,
+    })
+}
+
+/// The client certificate and CA bundle the endpoint is configured with, if any.
+fn client_tls_config(
+    cfg: &Configuration,
+    endp: &CreateMessageEndpoint,
+) -> Result<Option<ClientTlsConfig>> {
+    endp.tls
+        .as_ref()
+        .map(|tls| -> Result<_> {
+            Ok(ClientTlsConfig {
+                client_cert: tls.client_cert.clone().map(String::into_bytes),
+                client_key: tls.client_key(&cfg.encryption)?.map(String::into_bytes),
+                ca_bundle: tls.ca_bundle.clone().map(String::into_bytes),
+            })
+        })
+        .transpose()
+}
+
 #[tracing::instrument(skip_all)]
 async fn prepare_dispatch(
-    WorkerContext { cfg, .. }: &WorkerContext<'_>,
+    worker_context: &WorkerContext<'_>,
     DispatchContext {
         msg_task,
         payload,
         endp,
+        org_id,
+        egress_proxy,
+        trace_context,
         ..
     }: DispatchContext<'_>,
+    msg_dest: &messagedestination::Model,
 ) -> Result<IncompleteDispatch> {
+    let cfg = worker_context.cfg;
     let attempt_created_at = Utc::now();
 
-    let headers = {
+    // Errors here fail the attempt just like the endpoint not responding
+    let failed = |err: Error| -> Result<IncompleteDispatch> {
+        let attempt = messageattempt::ActiveModel {
+            response_status_code: Set(0),
+            response: Set(err.to_string()),
+            status: Set(MessageStatus::Fail),
+            ..new_attempt(msg_task, endp, msg_dest, attempt_created_at)
+        };
+        Ok(IncompleteDispatch::Failed(FailedDispatch(attempt, err)))
+    };
+
+    let RequestRoute {
+        proxy,
+        url_policy,
+        access_token,
+    } = match request_route(worker_context, endp, org_id, egress_proxy).await {
+        Ok(route) => route,
+        Err(err) => return failed(err),
+    };
+
+    let mut headers = {
         let keys = endp.valid_signing_keys();
 
         let signatures = sign_msg(
@@ -303,10 +616,25 @@ async fn prepare_dispatch(
             signatures,
             cfg.whitelabel_headers,
             endp.headers.as_ref(),
+            access_token.as_deref(),
             &endp.url,
         )?
     };
 
+    // Lets the receiver join the trace of the request which created the message
+    if let (true, Some(trace_context)) = (endp.propagate_trace_context, trace_context) {
+        for (k, v) in &trace_context.0 {
+            if v.is_empty() {
+                continue;
+            }
+            if let Ok(v) = v.parse() {
+                headers.insert(k.clone(), v);
+            }
+        }
+    }
+
+    let tls = client_tls_config(cfg, endp)?;
+
     Ok(IncompleteDispatch::Pending(PendingDispatch {
         method: http::Method::POST,
         url: endp.url.clone(),
@@ -314,10 +642,12 @@ async fn prepare_dispatch(
         payload: payload.to_owned(),
         request_timeout: cfg.worker_request_timeout as _,
         created_at: attempt_created_at,
+        tls,
+        proxy,
+        url_policy,
     }))
 }
 
-#[tracing::instrument(skip_all)]
 async fn make_http_call(
     DispatchContext { msg_task, endp, .. }: DispatchContext<'_>,
     PendingDispatch {
@@ -327,34 +657,35 @@ async fn make_http_call(
         payload,
         request_timeout,
         created_at,
+        tls,
+        proxy,
+        url_policy,
     }: PendingDispatch,
     msg_dest: &messagedestination::Model,
     client: &WebhookClient,
+    blob_store: &BlobStore,
+    metrics: &DeliveryMetrics,
 ) -> Result<CompletedDispatch> {
-    let req = RequestBuilder::new()
+    let mut req = RequestBuilder::new()
         .method(method)
         .uri_str(&url)
         .map_err(|e| Error::validation(format!("URL is invalid: {e:?}")))?
         .headers(headers)
         .body(payload.into(), HeaderValue::from_static("application/json"))
         .version(Version::HTTP_11)
-        .timeout(Duration::from_secs(request_timeout))
-        .build()
-        .map_err(Error::generic)?;
+        .timeout(Duration::from_secs(request_timeout));
+    if let Some(tls) = tls {
+        req = req.client_tls(tls);
+    }
+    if let Some(proxy) = proxy {
+        req = req.proxy(proxy);
+    }
+    if let Some(url_policy) = url_policy {
+        req = req.url_policy(url_policy);
+    }
+    let req = req.build().map_err(Error::generic)?;
 
-    let attempt = messageattempt::ActiveModel {
-        // Set both ID and created_at to the same timestamp
-        id: Set(MessageAttemptId::new(created_at.into(), None)),
-        created_at: Set(created_at.into()),
-        msg_id: Set(msg_task.msg_id.clone()),
-        endp_id: Set(endp.id.clone()),
-        msg_dest_id: Set(msg_dest.id.clone()),
-        url: Set(endp.url.clone()),
-        ended_at: Set(Some(Utc::now().into())),
-        trigger_type: Set(msg_task.trigger_type),
-        response_duration_ms: Set(0), // Default to 0, will be updated after the request
-        ..Default::default()
-    };
+    let attempt = new_attempt(msg_task, endp, msg_dest, created_at);
 
     match client.execute(req).await {
         Ok(res) => {
@@ -362,6 +693,7 @@ async fn make_http_call(
             let duration_ms = (Utc::now() - created_at).num_milliseconds();
 
             let status_code = res.status().as_u16() as i16;
+            metrics.record_attempt(status_code, duration_ms);
             let status = if res.status().is_success() {
                 MessageStatus::Success
             } else {
@@ -374,10 +706,23 @@ async fn make_http_call(
                 None
             };
 
-            let body = match res.into_body().collect().await {
-                Ok(collected) => {
-                    let bytes = collected.to_bytes();
+            let mut response_blob_key = None;
+            let read_limit = blob_store.response_read_limit(RESPONSE_MAX_SIZE);
+            let body = match read_body_bounded(res.into_body(), read_limit).await {
+                Ok(bytes) => {
                     if bytes.len() > RESPONSE_MAX_SIZE {
+                        // The full response (up to the read limit) is kept in the blob store, if
+                        // there's one
+                        if blob_store.is_enabled() {
+                            let key = BlobStore::response_key(attempt.id.as_ref());
+                            match blob_store
+                                .put(&key, bytes_to_string(bytes.clone()).into_bytes())
+                                .await
+                            {
+                                Ok(()) => response_blob_key = Some(key),
+                                Err(err) => tracing::error!("Failed storing response: {err}"),
+                            }
+                        }
                         bytes_to_string(bytes.slice(..RESPONSE_MAX_SIZE))
                     } else {
                         bytes_to_string(bytes)
@@ -389,6 +734,7 @@ async fn make_http_call(
             let attempt = messageattempt::ActiveModel {
                 response_status_code: Set(status_code),
                 response: Set(body),
+                response_blob_key: Set(response_blob_key),
                 status: Set(status),
                 response_duration_ms: Set(duration_ms),
                 ..attempt
@@ -405,6 +751,7 @@ async fn make_http_call(
         Err(err) => {
             // For errors, we still calculate the duration
             let duration_ms = (Utc::now() - created_at).num_milliseconds();
+            metrics.record_attempt(0, duration_ms);
 
             Ok(CompletedDispatch::Failed(FailedDispatch(
                 messageattempt::ActiveModel {
@@ -420,6 +767,104 @@ async fn make_http_call(
     }
 }
 
+/// Delivers the message to the endpoint's sink rather than its URL. The attempt is recorded the
+/// same way as for HTTP, without a status code.
+#[tracing::instrument(skip_all)]
+async fn push_to_sink(
+    WorkerContext {
+        cfg,
+        cache,
+        db,
+        sinks,
+        webhook_client,
+        ..
+    }: &WorkerContext<'_>,
+    DispatchContext {
+        msg_task,
+        payload,
+        endp,
+        org_id,
+        event_type,
+        ..
+    }: DispatchContext<'_>,
+    msg_dest: &messagedestination::Model,
+) -> Result<CompletedDispatch> {
+    let created_at = Utc::now();
+
+    let res = async {
+        let config = endp
+            .sink
+            .as_ref()
+            .expect("only called for endpoints with a sink")
+            .config(&cfg.encryption)?;
+
+        let url_policy = url_policy::cached_url_policy(cache, db, org_id)
+            .await?
+            .filter(|p| !p.is_empty());
+        let resolver = webhook_client.broker_resolver(url_policy.as_ref());
+
+        let msg = SinkMessage {
+            id: &msg_task.msg_id,
+            event_type,
+            timestamp: created_at.timestamp(),
+            payload: serde_json::from_str(payload).map_err(Error::generic)?,
+        };
+
+        tokio::time::timeout(
+            Duration::from_secs(cfg.worker_request_timeout.into()),
+            sinks.send(&config, &resolver, msg),
+        )
+        .await
+        .map_err(|_| Error::generic("Timed out pushing to sink"))?
+    }
+    .await;
+
+    let duration_ms = (Utc::now() - created_at).num_milliseconds();
+    let attempt = messageattempt::ActiveModel {
+        response_status_code: Set(0),
+        response_duration_ms: Set(duration_ms),
+        ..new_attempt(msg_task, endp, msg_dest, created_at)
+    };
+
+    Ok(match res {
+        Ok(()) => CompletedDispatch::Successful(SuccessfulDispatch(messageattempt::ActiveModel {
+            response: Set(String::new()),
+            status: Set(MessageStatus::Success),
+            ..attempt
+        })),
+        Err(err) => CompletedDispatch::Failed(FailedDispatch(
+            messageattempt::ActiveModel {
+                response: Set(err.to_string()),
+                status: Set(MessageStatus::Fail),
+                ..attempt
+            },
+            err,
+        )),
+    })
+}
+
+/// Inserts the attempt along with its event, for the events API.
+async fn insert_attempt(
+    db: &DatabaseConnection,
+    app_id: &ApplicationId,
+    attempt: messageattempt::ActiveModel,
+) -> Result<messageattempt::Model> {
+    let app_id = app_id.clone();
+    let attempt = db
+        .transaction(|txn| {
+            async move {
+                let attempt = attempt.insert(txn).await?;
+                messageevent::ActiveModel::attempted(app_id, &attempt)
+                    .insert(txn)
+                    .await?;
+                Ok::<_, Error>(attempt)
+            }
+            .boxed()
+        })
+        .await?;
+    Ok(attempt)
+}
+
 #[tracing::instrument(skip_all, fields(response_code, msg_dest_id = msg_dest.id.0))]
 async fn handle_successful_dispatch(
     WorkerContext {
@@ -441,7 +886,7 @@ async fn handle_successful_dispatch(
     msg_dest: messagedestination::Model,
 ) -> Result<()> {
     attempt.ended_at = Set(Some(Utc::now().into()));
-    let attempt = attempt.insert(*db).await?;
+    let attempt = insert_attempt(db, app_id, attempt).await?;
 
     let msg_dest = messagedestination::ActiveModel {
         status: Set(MessageStatus::Success),
@@ -516,7 +961,7 @@ async fn handle_failed_dispatch(
     msg_dest: messagedestination::Model,
 ) -> Result<()> {
     attempt.ended_at = Set(Some(Utc::now().into()));
-    let attempt = attempt.insert(*db).await?;
+    let attempt = insert_attempt(db, app_id, attempt).await?;
 
     tracing::Span::current().record("response_code", attempt.response_status_code);
     tracing::info!("Webhook failure.");
@@ -667,6 +1112,36 @@ struct DispatchContext<'a> {
     app_id: &'a ApplicationId,
     app_uid: Option<&'a ApplicationUid>,
     msg_uid: Option<&'a MessageUid>,
+    event_type: &'a EventTypeName,
+    /// The named egress proxy of the endpoint, or else of its application
+    egress_proxy: Option<&'a str>,
+    /// The trace context of the request which created the message
+    trace_context: Option<&'a TraceContext>,
+}
+
+async fn dispatch(
+    worker_context: &WorkerContext<'_>,
+    dispatch_context: DispatchContext<'_>,
+    msg_dest: &messagedestination::Model,
+) -> Result<CompletedDispatch> {
+    if dispatch_context.endp.sink.is_some() {
+        return push_to_sink(worker_context, dispatch_context, msg_dest).await;
+    }
+
+    match prepare_dispatch(worker_context, dispatch_context.clone(), msg_dest).await? {
+        IncompleteDispatch::Pending(pending) => {
+            make_http_call(
+                dispatch_context,
+                pending,
+                msg_dest,
+                worker_context.webhook_client,
+                worker_context.blob_store,
+                worker_context.delivery_metrics,
+            )
+            .await
+        }
+        IncompleteDispatch::Failed(failed) => Ok(CompletedDispatch::Failed(failed)),
+    }
 }
 
 /// Dispatches one webhook
@@ -686,10 +1161,14 @@ async fn dispatch_message_task(
     endp: CreateMessageEndpoint,
     msg_dest: messagedestination::Model,
 ) -> Result<()> {
-    let WorkerContext { webhook_client, .. } = worker_context;
+    let WorkerContext { cfg, cache, db, .. } = worker_context;
 
     tracing::trace!("Dispatch start");
 
+    if let Some(trace_context) = &msg.trace_context {
+        tracing::Span::current().add_link(stored_span_context(trace_context));
+    }
+
     if (msg_dest.status != MessageStatus::Pending && msg_dest.status != MessageStatus::Sending)
         && (msg_task.trigger_type != MessageAttemptTriggerType::Manual)
     {
@@ -702,6 +1181,22 @@ async fn dispatch_message_task(
         return Ok(());
     }
 
+    // Held until the endpoint or its application is resumed, or the endpoint verified, which
+    // queues it again
+    let unverified = cfg.endpoint_verification_enabled
+        && endp.sink.is_none()
+        && endp.verification_status != EndpointVerificationStatus::Verified;
+    if endp.paused || app.paused || unverified {
+        tracing::debug!("Endpoint or application paused, or endpoint unverified, holding delivery");
+        let msg_dest = messagedestination::ActiveModel {
+            status: Set(MessageStatus::Paused),
+            next_attempt: Set(None),
+            ..msg_dest.into()
+        };
+        msg_dest.update(*db).await?;
+        return Ok(());
+    }
+
     let dispatch_context = DispatchContext {
         msg_task: &msg_task,
         payload,
@@ -710,15 +1205,27 @@ async fn dispatch_message_task(
         app_id: &app.id,
         app_uid: app.uid.as_ref(),
         msg_uid: msg.uid.as_ref(),
+        event_type: &msg.event_type,
+        egress_proxy: endp.egress_proxy.as_deref().or(app.egress_proxy.as_deref()),
+        trace_context: msg.trace_context.as_ref(),
     };
 
-    let dispatch = prepare_dispatch(worker_context, dispatch_context.clone()).await?;
-    let completed = match dispatch {
-        IncompleteDispatch::Pending(pending) => {
-            make_http_call(dispatch_context.clone(), pending, &msg_dest, webhook_client).await?
+    let mut completed = dispatch(worker_context, dispatch_context.clone(), &msg_dest).await?;
+
+    // The access token may have been revoked before its expiry, so retry once with a fresh one.
+    // The rejected attempt is still recorded, like any other.
+    if let (Some(_), CompletedDispatch::Failed(FailedDispatch(attempt, _))) =
+        (&endp.oauth2, &completed)
+    {
+        if attempt.response_status_code == Set(StatusCode::UNAUTHORIZED.as_u16() as i16) {
+            let mut attempt = attempt.clone();
+            attempt.ended_at = Set(Some(Utc::now().into()));
+            insert_attempt(db, &app.id, attempt).await?;
+
+            oauth2::invalidate_access_token(cache, &endp.id).await?;
+            completed = dispatch(worker_context, dispatch_context.clone(), &msg_dest).await?;
         }
-        IncompleteDispatch::Failed(failed) => CompletedDispatch::Failed(failed),
-    };
+    }
 
     match completed {
         CompletedDispatch::Successful(success) => {
@@ -730,13 +1237,225 @@ async fn dispatch_message_task(
     }
 }
 
-fn bytes_to_string(bytes: bytes::Bytes) -> String {
+/// Sends a signed challenge to the endpoint's URL, returning whether the response echoed it.
+async fn send_verification_challenge(
+    worker_context: &WorkerContext<'_>,
+    endp: &CreateMessageEndpoint,
+    app: &application::Model,
+) -> Result<bool> {
+    let WorkerContext {
+        cfg,
+        webhook_client,
+        ..
+    } = worker_context;
+
+    let RequestRoute {
+        proxy,
+        url_policy,
+        access_token,
+    } = request_route(
+        worker_context,
+        endp,
+        &app.org_id,
+        endp.egress_proxy.as_deref().or(app.egress_proxy.as_deref()),
+    )
+    .await?;
+
+    let challenge = VerificationChallenge::new();
+    let payload = serde_json::to_string(&challenge).map_err(Error::generic)?;
+    let msg_id = MessageId::new(None, None);
+    let timestamp = Utc::now().timestamp();
+    let signatures = sign_msg(
+        &cfg.encryption,
+        timestamp,
+        &payload,
+        &msg_id,
+        &endp.valid_signing_keys(),
+    );
+    let headers = generate_msg_headers(
+        timestamp,
+        &msg_id,
+        signatures,
+        cfg.whitelabel_headers,
+        endp.headers.as_ref(),
+        access_token.as_deref(),
+        &endp.url,
+    )?;
+
+    let mut req = RequestBuilder::new()
+        .method(http::Method::POST)
+        .uri_str(&endp.url)
+        .map_err(|e| Error::validation(format!("URL is invalid: {e:?}")))?
+        .headers(headers)
+        .body(payload.into(), HeaderValue::from_static("application/json"))
+        .version(Version::HTTP_11)
+        .timeout(Duration::from_secs(cfg.worker_request_timeout as _));
+    if let Some(tls) = client_tls_config(cfg, endp)? {
+        req = req.client_tls(tls);
+    }
+    if let Some(proxy) = proxy {
+        req = req.proxy(proxy);
+    }
+    if let Some(url_policy) = url_policy {
+        req = req.url_policy(url_policy);
+    }
+    let req = req.build().map_err(Error::generic)?;
+
+    let res = webhook_client.execute(req).await?;
+    if !res.status().is_success() {
+        tracing::debug!(
+            "Endpoint responded to the verification challenge with {}",
+            res.status()
+        );
+        return Ok(false);
+    }
+    let body = res
+        .into_body()
+        .collect()
+        .await
+        .map_err(Error::generic)?
+        .to_bytes();
+
+    Ok(challenge.is_echoed_by(&body))
+}
+
+/// Verifies an endpoint pending verification, and has the messages held for it queued once
+/// verified. A failed challenge is sent again following the verification retry schedule, and the
+/// endpoint only fails verification once none is left.
+#[tracing::instrument(
+    skip_all,
+    level = "error",
+    fields(
+        app_id = task.app_id.0.as_str(),
+        endp_id = task.endpoint_id.0.as_str(),
+    )
+)]
+async fn process_endpoint_verification(
+    worker_context: &WorkerContext<'_>,
+    task: EndpointVerificationTask,
+) -> Result<()> {
+    let WorkerContext {
+        cfg,
+        cache,
+        db,
+        queue_tx,
+        ..
+    } = worker_context;
+
+    let Some((endp, Some(app))) =
+        endpoint::Entity::secure_find_by_id(task.app_id.clone(), task.endpoint_id.clone())
+            .find_also_related(application::Entity)
+            .one(*db)
+            .await?
+    else {
+        tracing::debug!("Endpoint to verify no longer exists");
+        return Ok(());
+    };
+    if endp.verification_status != EndpointVerificationStatus::PendingVerification {
+        return Ok(());
+    }
+
+    // Messages to sinks don't go to the endpoint's URL
+    let verified = if endp.sink.is_some() {
+        true
+    } else {
+        let cme = CreateMessageEndpoint::try_from(endp.clone())?;
+        match send_verification_challenge(worker_context, &cme, &app).await {
+            Ok(verified) => verified,
+            Err(err) => {
+                tracing::debug!("Failed sending the verification challenge: {err}");
+                false
+            }
+        }
+    };
+
+    if !verified {
+        if let Some(delay) = cfg
+            .endpoint_verification_retry_schedule
+            .get(task.attempt_count as usize)
+        {
+            tracing::debug!("Verification challenge failed, sending it again in {delay:?}");
+            let retry = EndpointVerificationTask {
+                attempt_count: task.attempt_count + 1,
+                ..task
+            };
+            queue_tx
+                .send(&QueueTask::EndpointVerification(retry), Some(*delay))
+                .await?;
+            return Ok(());
+        }
+    }
+
+    if !set_verification_result(db, &endp.id, &endp.url, verified).await? {
+        return Ok(());
+    }
+    invalidate_cached_app(cache, &app).await;
+
+    // Queued rather than released right away, so the held messages are still released if this
+    // worker dies before it's done
+    if verified && !endp.paused && !app.paused {
+        queue_tx
+            .send(
+                &ReleaseHeldDestinationsTask::new_task(app.id.clone(), vec![endp.id]),
+                None,
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn bytes_to_string(bytes: Bytes) -> String {
     match std::str::from_utf8(&bytes) {
         Ok(v) => v.to_owned(),
         Err(_) => base64::encode(&bytes),
     }
 }
 
+/// Leases the destination to this worker while it processes its task, until the reaper threshold
+/// has passed. Returns when the lease expires.
+async fn lease_destination(
+    db: &DatabaseConnection,
+    cfg: &Configuration,
+    dest_id: &MessageEndpointId,
+) -> Result<DateTimeWithTimeZone> {
+    let threshold = chrono::Duration::seconds(
+        cfg.destination_reaper_threshold_secs
+            .try_into()
+            .unwrap_or(i64::MAX),
+    );
+    // Truncated to the database's precision, so the lease can be matched when released
+    let leased_until: DateTimeWithTimeZone = (Utc::now() + threshold).trunc_subsecs(6).into();
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::LeasedUntil,
+            Expr::value(leased_until),
+        )
+        .filter(messagedestination::Column::Id.eq(dest_id.clone()))
+        .exec(db)
+        .await?;
+    Ok(leased_until)
+}
+
+/// Releases a lease on the destination, e.g. the one taken by [`lease_destination`], unless it was
+/// taken over since.
+pub(crate) async fn release_destination_lease(
+    db: &DatabaseConnection,
+    dest_id: &MessageEndpointId,
+    leased_until: DateTimeWithTimeZone,
+) -> Result<()> {
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::LeasedUntil,
+            Expr::value(Option::<DateTimeWithTimeZone>::None),
+        )
+        .filter(messagedestination::Column::Id.eq(dest_id.clone()))
+        .filter(messagedestination::Column::LeasedUntil.eq(leased_until))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
 /// Manages preparation and execution of a QueueTask type
 #[tracing::instrument(
     skip_all,
@@ -760,12 +1479,32 @@ async fn process_queue_task_inner(
     worker_context: WorkerContext<'_>,
     queue_task: QueueTask,
 ) -> Result<()> {
-    let WorkerContext { db, cache, .. }: WorkerContext<'_> = worker_context;
+    let WorkerContext {
+        cfg,
+        db,
+        cache,
+        queue_tx,
+        blob_store,
+        tenant_limits,
+        ..
+    }: WorkerContext<'_> = worker_context;
     let span = tracing::Span::current();
+    // Retries stay on the queue of the message's priority
+    let priority = queue_task.priority();
 
     let (mut msg, msg_content, force_endpoint, destination, trigger_type, attempt_count) =
         match queue_task {
             QueueTask::HealthCheck => return Ok(()),
+            QueueTask::EndpointVerification(task) => {
+                return process_endpoint_verification(&worker_context, task).await;
+            }
+            QueueTask::ReleaseHeldDestinations(task) => {
+                return process_release_task(db, queue_tx, task).await;
+            }
+            QueueTask::Broadcast(task) => {
+                return broadcast::process_broadcast_batch(db, queue_tx, cache, blob_store, task)
+                    .await;
+            }
             QueueTask::MessageV1(task) => {
                 let (msg, msg_content) = message::Entity::find_by_id(task.msg_id.clone())
                     .find_also_related(messagecontent::Entity)
@@ -804,6 +1543,12 @@ async fn process_queue_task_inner(
                     .ok_or_else(|| {
                         Error::generic(format!("Unexpected: message doesn't exist {}", task.msg_id))
                     })?;
+
+                if msg.cancelled_at.is_some() {
+                    tracing::debug!("Scheduled message was cancelled. Returning");
+                    return Ok(());
+                }
+
                 (
                     msg,
                     msg_content,
@@ -819,8 +1564,12 @@ async fn process_queue_task_inner(
     span.record("app_id", &msg.app_id.0);
     span.record("org_id", &msg.org_id.0);
 
+    let msg_content = match msg_content {
+        Some(content) => blob_store.resolve_payload(content).await?,
+        None => None,
+    };
     let payload = msg_content
-        .and_then(|m| String::from_utf8(m.payload).ok())
+        .and_then(|payload| String::from_utf8(payload).ok())
         .or_else(|| {
             msg.legacy_payload
                 .take()
@@ -856,6 +1605,33 @@ async fn process_queue_task_inner(
         .cloned()
         .collect();
 
+    // Endpoints filtering the message out by its payload, or pinned to another version of its
+    // schema, still get a destination, so it's listed as skipped for them. Like event types and
+    // channels, filters don't apply to manual attempts.
+    let (endpoints, skipped): (Vec<_>, Vec<_>) =
+        if destination.is_none() && trigger_type != MessageAttemptTriggerType::Manual {
+            let payload_value = endpoints
+                .iter()
+                .any(|endpoint| endpoint.filter.is_some())
+                .then(|| serde_json::from_str(&payload).unwrap_or(serde_json::Value::Null));
+            endpoints.into_iter().partition(|endpoint| {
+                let schema_version_matches = match (&endpoint.schema_version, &msg.schema_version) {
+                    (Some(expected), Some(declared)) => expected == declared,
+                    _ => true,
+                };
+                let filter_matches = match (&endpoint.filter, &payload_value) {
+                    (Some(filter), Some(payload_value)) => filter.matches(payload_value),
+                    _ => true,
+                };
+                schema_version_matches && filter_matches
+            })
+        } else {
+            (endpoints, vec![])
+        };
+
+    // A message task's delivery holds its organization's and endpoint's slots from when the task
+    // got its turn, while those fanned out from a batch take theirs here
+    let from_batch = destination.is_none();
     let destinations = match destination {
         Some(d) => vec![d],
         None => {
@@ -869,15 +1645,32 @@ async fn process_queue_task_inner(
                     ..messagedestination::ActiveModel::new()
                 })
                 .collect();
+            let skipped_destinations =
+                skipped
+                    .iter()
+                    .map(|endpoint| messagedestination::ActiveModel {
+                        msg_id: Set(msg.id.clone()),
+                        endp_id: Set(endpoint.id.clone()),
+                        next_attempt: Set(None),
+                        status: Set(MessageStatus::Skipped),
+                        ..messagedestination::ActiveModel::new()
+                    });
 
-            if destinations.is_empty() {
+            if destinations.is_empty() && skipped.is_empty() {
                 tracing::debug!("No destinations for message. Returning");
                 return Ok(());
             }
 
-            messagedestination::Entity::insert_many(destinations.clone())
-                .exec(db)
-                .await?;
+            messagedestination::Entity::insert_many(
+                destinations.iter().cloned().chain(skipped_destinations),
+            )
+            .exec(db)
+            .await?;
+
+            if destinations.is_empty() {
+                tracing::debug!("Message filtered out by all endpoints. Returning");
+                return Ok(());
+            }
 
             let dests: Result<_, _> = destinations
                 .into_iter()
@@ -897,17 +1690,47 @@ async fn process_queue_task_inner(
                 endpoint_id: endpoint.id.clone(),
                 attempt_count,
                 trigger_type,
+                priority,
             };
+            let worker_context = &worker_context;
+            let msg = &msg;
+            let create_message_app = &create_message_app;
+            let payload = &payload;
+
+            async move {
+                let _permit = if from_batch {
+                    let Some(permit) =
+                        tenant_limits.try_acquire(&create_message_app.org_id, &endpoint.id)
+                    else {
+                        // Waits for its turn in the organization's queue, like any other delivery
+                        tracing::debug!(
+                            "Organization or endpoint at its concurrency limit, queueing delivery"
+                        );
+                        tenant_limits
+                            .metrics
+                            .record_deferred(&create_message_app.org_id);
+                        return queue_tx.send(&QueueTask::MessageV1(task), None).await;
+                    };
+                    Some(permit)
+                } else {
+                    None
+                };
 
-            dispatch_message_task(
-                &worker_context,
-                &msg,
-                &create_message_app,
-                task,
-                &payload,
-                endpoint,
-                destination,
-            )
+                let dest_id = destination.id.clone();
+                let leased_until = lease_destination(db, cfg, &dest_id).await?;
+                let res = dispatch_message_task(
+                    worker_context,
+                    msg,
+                    create_message_app,
+                    task,
+                    payload,
+                    endpoint,
+                    destination,
+                )
+                .await;
+                release_destination_lease(db, &dest_id, leased_until).await?;
+                res
+            }
         });
 
     let join = future::join_all(futures).await;
@@ -935,10 +1758,12 @@ pub async fn queue_handler(
     cache: Cache,
     db: DatabaseConnection,
     queue_tx: TaskQueueProducer,
-    mut queue_rx: TaskQueueConsumer,
+    lanes: Vec<QueueLane>,
     op_webhook_sender: OperationalWebhookSender,
+    blob_store: BlobStore,
 ) -> Result<()> {
     let recv_deadline = Duration::from_secs(cfg.queue_max_poll_secs.into());
+    let shutdown_timeout = Duration::from_secs(cfg.graceful_shutdown_timeout_secs);
 
     static NUM_WORKERS: AtomicUsize = AtomicUsize::new(0);
 
@@ -948,14 +1773,38 @@ pub async fn queue_handler(
     } else {
         tracing::info!("Worker concurrent task limit: {}", task_limit);
     }
+    if cfg.queue_priority_lanes_enabled && task_limit > 0 {
+        for priority in [
+            MessagePriority::High,
+            MessagePriority::Normal,
+            MessagePriority::Low,
+        ] {
+            tracing::info!(
+                "Worker concurrent task limit of {} priority tasks: {}",
+                priority.as_str(),
+                cfg.worker_priority_shares
+                    .max_tasks(priority, cfg.worker_max_tasks)
+            );
+        }
+    }
 
-    let webhook_client = WebhookClient::new(
+    let webhook_client = WebhookClient::with_http_config(
         cfg.whitelist_subnets.clone(),
         Some(Arc::new(vec!["backend".to_owned()])),
         cfg.dangerous_disable_tls_verification,
         cfg.proxy_config.as_ref(),
+        HttpClientConfig {
+            http2_alpn: cfg.worker_http2_alpn,
+            http2_prior_knowledge_hosts: cfg.worker_http2_prior_knowledge_hosts.clone(),
+            pool_max_idle_per_host: cfg.worker_pool_max_idle_per_host,
+            pool_idle_timeout: Some(Duration::from_secs(cfg.worker_pool_idle_timeout)),
+        },
     );
 
+    let sinks = SinkClient::new();
+    let delivery_metrics = DeliveryMetrics::new(&opentelemetry::global::meter("svix.com"));
+    let tenant_limits = Arc::new(TenantLimits::new(cfg));
+
     tokio::spawn(
         async move {
             let mut interval = tokio::time::interval(Duration::from_millis(500));
@@ -973,38 +1822,143 @@ pub async fn queue_handler(
         )),
     );
 
-    loop {
-        if task_limit > 0 {
-            let num_workers = NUM_WORKERS.load(Ordering::Relaxed);
-            if num_workers > task_limit.into() {
-                tokio::time::sleep(Duration::from_millis(100)).await;
-                continue;
-            }
-        }
+    let tenant_resolver = TenantResolver::default();
+    let org_weights: HashMap<Option<OrganizationId>, u16> = cfg
+        .worker_org_weights
+        .iter()
+        .map(|(org_id, weight)| (Some(org_id.clone()), *weight))
+        .collect();
 
-        if crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
-            tokio::join!(async move {
-                let mut interval = tokio::time::interval(Duration::from_millis(500));
+    // Each lane is polled on its own, with its own share of the tasks, so a backlog of tasks of
+    // one priority doesn't hold up the others
+    let lane_loops = lanes.into_iter().map(|QueueLane { priority, mut consumer }| {
+        // Without priority lanes, the high and low priority queues only hold what was queued on
+        // them while they were enabled, and are drained with their share
+        let task_limit = if cfg.queue_priority_lanes_enabled || priority != MessagePriority::Normal {
+            cfg.worker_priority_shares
+                .max_tasks(priority, cfg.worker_max_tasks)
+        } else {
+            cfg.worker_max_tasks
+        };
+        let max_queued = if task_limit > 0 {
+            usize::from(task_limit) * TENANT_QUEUED_PER_TASK
+        } else {
+            usize::MAX
+        };
+        let lane_workers = Arc::new(AtomicUsize::new(0));
+        // Received tasks wait here in a queue per organization, and are run as the organizations
+        // take turns, so one with a large backlog doesn't hold up the others
+        let fair_queue = Arc::new(FairQueue::new(
+            org_weights.clone(),
+            cfg.worker_max_queued_per_org.into(),
+        ));
+        let (cache, db, queue_tx, op_webhook_sender, blob_store) =
+            (&cache, &db, &queue_tx, &op_webhook_sender, &blob_store);
+        let (webhook_client, sinks, delivery_metrics, tenant_limits, tenant_resolver) = (
+            &webhook_client,
+            &sinks,
+            &delivery_metrics,
+            &tenant_limits,
+            &tenant_resolver,
+        );
+
+        let receive = {
+            let fair_queue = fair_queue.clone();
+            async move {
                 loop {
-                    interval.tick().await;
-                    let num_workers = NUM_WORKERS.load(Ordering::Relaxed);
-                    if num_workers > 0 {
-                        tracing::info!(
-                            "{} active workers, waiting to shut down worker.",
-                            num_workers
-                        );
-                    } else {
-                        tracing::info!("No active workers, shutting down worker.");
+                    // Put back on the queue before it redelivers them
+                    for waiting in
+                        fair_queue.take_where(|waiting| waiting.since.elapsed() > TENANT_MAX_WAIT)
+                    {
+                        defer_delivery(
+                            queue_tx,
+                            waiting.delivery,
+                            waiting.org_id.as_ref(),
+                            tenant_limits,
+                        )
+                        .await;
+                    }
+
+                    if crate::SHUTTING_DOWN.load(Ordering::SeqCst) {
                         break;
                     }
+
+                    if fair_queue.len() >= max_queued {
+                        // The worker is busy rather than stuck, so it must still count as polling
+                        // for the health checks
+                        update_last_poll_time().await;
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+
+                    let batch = tokio::select! {
+                        res = consumer.receive_all(recv_deadline) => res,
+                        // Stops waiting on new tasks as soon as shutting down
+                        _ = crate::wait_for_shutdown() => break,
+                    };
+
+                    match batch {
+                        Ok(batch) => {
+                            let org_ids = tenant_resolver.org_ids(db, &batch).await;
+                            for (delivery, org_id) in batch.into_iter().zip(org_ids) {
+                                let waiting = WaitingTask {
+                                    delivery,
+                                    org_id: org_id.clone(),
+                                    since: Instant::now(),
+                                };
+                                // The organization already has as many tasks waiting as it may,
+                                // so this one is put back on the queue, letting the worker get to
+                                // the other organizations' tasks behind it
+                                if let Err(waiting) = fair_queue.push(org_id, waiting) {
+                                    defer_delivery(
+                                        queue_tx,
+                                        waiting.delivery,
+                                        waiting.org_id.as_ref(),
+                                        tenant_limits,
+                                    )
+                                    .await;
+                                }
+                            }
+                        }
+                        Err(err) => {
+                            tracing::error!("Error receiving task: {:?}", err);
+                            sleep(tokio::time::Duration::from_millis(10)).await;
+                        }
+                    }
+
+                    update_last_poll_time().await;
                 }
-            });
-            break;
-        }
+            }
+        };
+
+        let dispatch = {
+            let fair_queue = fair_queue.clone();
+            let lane_workers = lane_workers.clone();
+            async move {
+                loop {
+                    if task_limit > 0 {
+                        let num_workers = lane_workers.load(Ordering::Relaxed);
+                        if num_workers > task_limit.into() {
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            continue;
+                        }
+                    }
 
-        match queue_rx.receive_all(recv_deadline).await {
-            Ok(batch) => {
-                for delivery in batch {
+                    // Deliveries wait for a free slot of their organization and endpoint
+                    let next = fair_queue.pop(|org_id, waiting| {
+                        match (org_id, waiting.delivery.task.as_ref()) {
+                            (Some(org_id), QueueTask::MessageV1(task)) => tenant_limits
+                                .try_acquire(org_id, &task.endpoint_id)
+                                .map(Some),
+                            _ => Some(None),
+                        }
+                    });
+                    let (waiting, permit) = tokio::select! {
+                        next = next => next,
+                        _ = crate::wait_for_shutdown() => break,
+                    };
+
+                    let delivery = waiting.delivery;
                     let cfg = cfg.clone();
                     let cache = cache.clone();
                     let db = db.clone();
@@ -1012,9 +1966,16 @@ pub async fn queue_handler(
                     let queue_task = delivery.task.clone();
                     let op_webhook_sender = op_webhook_sender.clone();
                     let webhook_client = webhook_client.clone();
+                    let sinks = sinks.clone();
+                    let blob_store = blob_store.clone();
+                    let delivery_metrics = delivery_metrics.clone();
+                    let tenant_limits = tenant_limits.clone();
+                    let lane_workers = lane_workers.clone();
+                    let fair_queue = fair_queue.clone();
 
                     tokio::spawn(async move {
                         NUM_WORKERS.fetch_add(1, Ordering::Relaxed);
+                        lane_workers.fetch_add(1, Ordering::Relaxed);
                         let worker_context = WorkerContext {
                             cfg: &cfg,
                             db: &db,
@@ -1022,14 +1983,28 @@ pub async fn queue_handler(
                             op_webhook_sender: &op_webhook_sender,
                             queue_tx: &queue_tx,
                             webhook_client: &webhook_client,
+                            sinks: &sinks,
+                            blob_store: &blob_store,
+                            delivery_metrics: &delivery_metrics,
+                            tenant_limits: &tenant_limits,
                         };
 
                         let queue_task =
                             Arc::try_unwrap(queue_task).unwrap_or_else(|arc| (*arc).clone());
-                        if process_queue_task(worker_context, queue_task)
-                            .await
-                            .is_err()
-                        {
+                        // Tasks still running once the shutdown timeout is reached are put back
+                        // on the queue right away, rather than after their ack deadline
+                        let shutdown_deadline = async {
+                            crate::wait_for_shutdown().await;
+                            sleep(shutdown_timeout).await;
+                        };
+                        let succeeded = tokio::select! {
+                            res = process_queue_task(worker_context, queue_task) => res.is_ok(),
+                            _ = shutdown_deadline => {
+                                tracing::warn!("Shutdown timeout reached, returning in-flight task to the queue");
+                                false
+                            }
+                        };
+                        if !succeeded {
                             if let Err(err) = delivery.nack().await {
                                 tracing::error!(
                                     "Error sending 'nack' to Redis after task execution error: {}",
@@ -1043,18 +2018,45 @@ pub async fn queue_handler(
                             );
                         }
 
+                        // Frees the organization's and endpoint's slots for the tasks waiting on
+                        // them
+                        drop(permit);
+                        fair_queue.wake();
                         NUM_WORKERS.fetch_sub(1, Ordering::Relaxed);
+                        lane_workers.fetch_sub(1, Ordering::Relaxed);
                     });
                 }
             }
-            Err(err) => {
-                tracing::error!("Error receiving task: {:?}", err);
-                sleep(tokio::time::Duration::from_millis(10)).await;
+        };
+
+        async move {
+            tokio::join!(receive, dispatch);
+
+            // Tasks which never got their turn go straight back to the queue
+            for waiting in fair_queue.take_where(|_| true) {
+                if let Err(err) = waiting.delivery.nack().await {
+                    tracing::error!("Error sending 'nack' to Redis for unstarted task: {}", err);
+                }
             }
-        }
 
-        update_last_poll_time().await;
-    }
+            let mut interval = tokio::time::interval(Duration::from_millis(500));
+            loop {
+                interval.tick().await;
+                let num_workers = lane_workers.load(Ordering::Relaxed);
+                if num_workers > 0 {
+                    tracing::info!(
+                        priority = priority.as_str(),
+                        "{} active workers, waiting to shut down worker.",
+                        num_workers
+                    );
+                } else {
+                    tracing::info!("No active workers, shutting down worker.");
+                    break;
+                }
+            }
+        }
+    });
+    future::join_all(lane_loops).await;
 
     Ok(())
 }
@@ -1099,6 +2101,7 @@ mod tests {
                 signatures,
                 WHITELABEL_HEADERS,
                 None,
+                None,
                 ENDPOINT_URL,
             )
             .unwrap(),
@@ -1130,6 +2133,7 @@ mod tests {
             signatures,
             WHITELABEL_HEADERS,
             Some(&EndpointHeaders(headers)),
+            None,
             ENDPOINT_URL,
         )
         .unwrap();
@@ -1137,6 +2141,36 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_generate_msg_headers_with_access_token() {
+        let mut headers = HashMap::new();
+        headers.insert("Authorization".to_owned(), "Basic Zm9vOmJhcg==".to_owned());
+
+        let (_, id) = mock_headers();
+        let signatures = sign_msg(
+            &Encryption::new_noop(),
+            TIMESTAMP,
+            BODY,
+            &id,
+            ENDPOINT_SIGNING_KEYS,
+        );
+
+        let actual = generate_msg_headers(
+            TIMESTAMP,
+            &id,
+            signatures,
+            WHITELABEL_HEADERS,
+            Some(&EndpointHeaders(headers)),
+            Some("token"),
+            ENDPOINT_URL,
+        )
+        .unwrap();
+
+        assert_eq!(actual.get("authorization").unwrap(), "Bearer token");
+        // Not sent alongside the access token under another case
+        assert!(actual.get("Authorization").is_none());
+    }
+
     // Tests endpoint signing keys -- expected values are fetched from the Svix documentation for a
     // direct comparison to the current implementation.
     #[test]
@@ -1166,6 +2200,7 @@ mod tests {
             signatures,
             WHITELABEL_HEADERS,
             None,
+            None,
             ENDPOINT_URL,
         )
         .unwrap();
@@ -0,0 +1,226 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use reqwest::StatusCode;
+use serde::de::IgnoredAny;
+use serde_json::json;
+use svix_server::{
+    core::types::OrganizationId,
+    v1::{endpoints::audit_log::AuditLogEntryOut, utils::ListResponse},
+};
+
+use crate::utils::{
+    common_calls::{create_test_app, create_test_endpoint},
+    get_default_test_config, start_svix_server_with_cfg_and_org_id, TestClient,
+};
+
+async fn list_audit_log(client: &TestClient, query: &str) -> ListResponse<AuditLogEntryOut> {
+    client
+        .get(&format!("api/v1/audit-log/?{query}"), StatusCode::OK)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_audit_log() {
+    let cfg = get_default_test_config();
+    let org_id = OrganizationId::new(None, None);
+    let (client, _jh) = start_svix_server_with_cfg_and_org_id(&cfg, org_id.clone()).await;
+
+    let app_id = create_test_app(&client, "auditLogApp").await.unwrap().id;
+    let endp_id = create_test_endpoint(&client, &app_id, "https://a.example.com/")
+        .await
+        .unwrap()
+        .id;
+
+    let _: IgnoredAny = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/"),
+            json!({ "url": "https://b.example.com/" }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/secret/rotate/"),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    client
+        .delete(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/"),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+
+    // Reads and failed changes aren't recorded
+    let _: IgnoredAny = client
+        .get(&format!("api/v1/app/{app_id}/"), StatusCode::OK)
+        .await
+        .unwrap();
+    client
+        .delete(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/"),
+            StatusCode::NOT_FOUND,
+        )
+        .await
+        .unwrap();
+
+    let list = list_audit_log(&client, &format!("app_id={app_id}")).await;
+    assert!(list.done);
+    assert_eq!(
+        list.data
+            .iter()
+            .map(|e| e.action.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "endpoint.deleted",
+            "endpoint.secret.rotated",
+            "endpoint.updated",
+            "endpoint.created",
+            "application.created",
+        ]
+    );
+    for entry in &list.data {
+        assert_eq!(entry.actor, org_id.to_string());
+        assert_eq!(entry.app_id.as_ref(), Some(&app_id));
+    }
+
+    let [deleted, rotated, updated, created, app_created] = &list.data[..] else {
+        panic!("unexpected entries: {:?}", list.data);
+    };
+    let endp_resource = format!("/app/{app_id}/endpoint/{endp_id}");
+
+    assert_eq!(app_created.resource, format!("/app/{app_id}"));
+    assert_eq!(app_created.before, None);
+
+    assert_eq!(created.resource, endp_resource);
+    assert_eq!(
+        created.after.as_ref().unwrap()["url"],
+        "https://a.example.com/"
+    );
+
+    // Only the changed fields are kept
+    assert_eq!(updated.resource, endp_resource);
+    let before = updated.before.as_ref().unwrap();
+    let after = updated.after.as_ref().unwrap();
+    assert_eq!(before["url"], "https://a.example.com/");
+    assert_eq!(after["url"], "https://b.example.com/");
+    assert!(before.get("description").is_none());
+    assert!(after.get("description").is_none());
+
+    // Secrets are redacted, though the change is still recorded
+    assert_eq!(rotated.resource, format!("{endp_resource}/secret"));
+    assert_eq!(
+        rotated.before,
+        Some(json!({ "key": "[REDACTED]" })),
+        "{rotated:?}"
+    );
+    assert_eq!(rotated.after, Some(json!({ "key": "[REDACTED]" })));
+
+    assert_eq!(deleted.resource, endp_resource);
+    assert_eq!(
+        deleted.before.as_ref().unwrap()["url"],
+        "https://b.example.com/"
+    );
+    assert_eq!(deleted.after, None);
+
+    // Filters
+    let list = list_audit_log(&client, &format!("resource={endp_resource}")).await;
+    assert_eq!(list.data.len(), 4);
+    let list = list_audit_log(&client, "action=endpoint.updated").await;
+    assert_eq!(list.data, vec![updated.clone()]);
+    let list = list_audit_log(&client, "actor=org_unknown").await;
+    assert!(list.data.is_empty());
+
+    // Pagination
+    let page = list_audit_log(&client, "limit=2").await;
+    assert!(!page.done);
+    assert_eq!(page.data, list_audit_log(&client, "").await.data[..2]);
+    let rest = list_audit_log(
+        &client,
+        &format!("limit=10&iterator={}", page.iterator.unwrap()),
+    )
+    .await;
+    assert!(rest.done);
+    assert_eq!(rest.data.len(), 3);
+    assert_eq!(rest.data[0], *rotated);
+}
+
+#[tokio::test]
+async fn test_audit_log_concurrent_changes() {
+    let cfg = get_default_test_config();
+    let (client, _jh) =
+        start_svix_server_with_cfg_and_org_id(&cfg, OrganizationId::new(None, None)).await;
+
+    let app_id = create_test_app(&client, "name-0").await.unwrap().id;
+
+    // Concurrent changes don't wait on each other, and are all recorded
+    let changes = (1..=5).map(|i| {
+        let client = client.clone();
+        let app_id = app_id.clone();
+        async move {
+            let _: IgnoredAny = client
+                .patch(
+                    &format!("api/v1/app/{app_id}/"),
+                    json!({ "name": format!("name-{i}") }),
+                    StatusCode::OK,
+                )
+                .await
+                .unwrap();
+        }
+    });
+    futures::future::join_all(changes).await;
+
+    let list = list_audit_log(
+        &client,
+        &format!("app_id={app_id}&action=application.updated"),
+    )
+    .await;
+    let mut names: Vec<_> = list
+        .data
+        .iter()
+        .map(|entry| entry.after.as_ref().unwrap()["name"].clone())
+        .collect();
+    names.sort_by_key(ToString::to_string);
+    assert_eq!(
+        names,
+        (1..=5)
+            .map(|i| json!(format!("name-{i}")))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn test_audit_log_operations() {
+    let cfg = get_default_test_config();
+    let (client, _jh) =
+        start_svix_server_with_cfg_and_org_id(&cfg, OrganizationId::new(None, None)).await;
+
+    let app_id = create_test_app(&client, "auditLogOperationsApp")
+        .await
+        .unwrap()
+        .id;
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/auth/app-portal-access/{app_id}/"),
+            json!({}),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+
+    // Only that the token was issued is recorded, not the token itself
+    let list = list_audit_log(&client, "action=auth.app_portal_access.created").await;
+    let [entry] = &list.data[..] else {
+        panic!("unexpected entries: {:?}", list.data);
+    };
+    assert_eq!(entry.resource, format!("/auth/app-portal-access/{app_id}"));
+    assert_eq!(entry.app_id.as_ref(), Some(&app_id));
+    assert_eq!(entry.before, None);
+    assert_eq!(entry.after, None);
+}
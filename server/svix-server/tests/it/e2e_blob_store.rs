@@ -0,0 +1,173 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use reqwest::StatusCode;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use svix_server::{
+    cfg::{BlobStoreType, S3Config},
+    core::{
+        blob_store::BlobStore,
+        types::{BaseId, MessageId},
+    },
+    db::models::{messageattempt, messagecontent},
+    v1::{
+        endpoints::{attempt::MessageAttemptOut, message::MessageOut},
+        utils::ListResponse,
+    },
+};
+
+use crate::utils::{
+    common_calls::{create_test_app, create_test_endpoint, create_test_message},
+    get_default_test_config, run_with_retries, start_svix_server_with_cfg, TestReceiver,
+};
+
+#[tokio::test]
+async fn test_blob_store_offloading() {
+    let blob_dir = std::env::temp_dir().join(format!("svix-blobs-{}", MessageId::new(None, None)));
+    let mut cfg = get_default_test_config();
+    cfg.blob_store_type = BlobStoreType::Filesystem;
+    cfg.blob_store_path = Some(blob_dir.to_string_lossy().into_owned());
+    cfg.blob_payload_threshold = 100;
+    cfg.blob_response_max_size = 25_000;
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let pool = svix_server::db::init_db(&std::sync::Arc::new(cfg)).await;
+
+    // Larger than the 20 KB kept in the database
+    let response_body = "r".repeat(30_000);
+    let mut receiver = TestReceiver::start_with_body(StatusCode::OK, response_body.clone());
+
+    let app_id = create_test_app(&client, "blobApp").await.unwrap().id;
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let large_payload = json!({ "data": "x".repeat(200) });
+    let large: MessageOut = create_test_message(&client, &app_id, large_payload.clone())
+        .await
+        .unwrap();
+    let small: MessageOut = create_test_message(&client, &app_id, json!({ "small": true }))
+        .await
+        .unwrap();
+
+    // Only the large payload is offloaded, and it's still sent in full
+    assert_eq!(receiver.data_recv.recv().await.unwrap(), large_payload);
+    receiver.data_recv.recv().await.unwrap();
+
+    let large_content = messagecontent::Entity::find_by_id(large.id.clone())
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+    let blob_key = large_content.blob_key.unwrap();
+    assert!(large_content.payload.is_empty());
+    assert!(blob_dir.join(&blob_key).exists());
+    let small_content = messagecontent::Entity::find_by_id(small.id.clone())
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(small_content.blob_key, None);
+
+    let msg: MessageOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/msg/{}/", large.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(msg.payload.0.get()).unwrap(),
+        large_payload
+    );
+
+    // The database keeps the truncated response for lists, fetching the attempt returns the full
+    // one, cut off at the configured maximum
+    let attempts: ListResponse<MessageAttemptOut> = run_with_retries(|| async {
+        let list: ListResponse<MessageAttemptOut> = client
+            .get(
+                &format!("api/v1/app/{app_id}/attempt/msg/{}/", large.id),
+                StatusCode::OK,
+            )
+            .await?;
+        if list.data.is_empty() {
+            anyhow::bail!("attempt not recorded yet");
+        }
+        Ok(list)
+    })
+    .await
+    .unwrap();
+    assert_eq!(attempts.data[0].response, response_body[..20_000]);
+
+    let full: MessageAttemptOut = client
+        .get(
+            &format!(
+                "api/v1/app/{app_id}/msg/{}/attempt/{}/",
+                large.id, attempts.data[0].id
+            ),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(full.response, response_body[..25_000]);
+
+    let attempt = messageattempt::Entity::find()
+        .filter(messageattempt::Column::MsgId.eq(large.id.clone()))
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(attempt.response.len(), 20_000);
+    let response_blob_key = attempt.response_blob_key.unwrap();
+    assert!(blob_dir.join(&response_blob_key).exists());
+
+    // Expunging the content deletes the blobs
+    client
+        .delete(
+            &format!("api/v1/app/{app_id}/msg/{}/content/", large.id),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    assert!(!blob_dir.join(&blob_key).exists());
+
+    client
+        .delete(
+            &format!(
+                "api/v1/app/{app_id}/msg/{}/attempt/{}/content/",
+                large.id, attempt.id
+            ),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    assert!(!blob_dir.join(&response_blob_key).exists());
+
+    let _ = std::fs::remove_dir_all(blob_dir);
+}
+
+// Run with `cargo test -- --ignored minio` only when MinIO is up with a `svix-test` bucket
+#[tokio::test]
+#[ignore]
+async fn test_blob_store_s3_minio() {
+    let mut cfg = get_default_test_config();
+    cfg.blob_store_type = BlobStoreType::S3;
+    cfg.blob_store_s3 = Some(S3Config {
+        endpoint: "http://localhost:9000".parse().unwrap(),
+        bucket: "svix-test".to_owned(),
+        region: "us-east-1".to_owned(),
+        access_key_id: "minioadmin".to_owned(),
+        secret_access_key: "minioadmin".to_owned(),
+    });
+    let store = BlobStore::new(&cfg).unwrap();
+
+    let key = BlobStore::payload_key(&MessageId::new(None, None));
+    assert_eq!(store.get(&key).await.unwrap(), None);
+
+    store.put(&key, b"payload".to_vec()).await.unwrap();
+    assert_eq!(store.get(&key).await.unwrap(), Some(b"payload".to_vec()));
+
+    store.delete(&key).await.unwrap();
+    assert_eq!(store.get(&key).await.unwrap(), None);
+}
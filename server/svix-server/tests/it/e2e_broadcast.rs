@@ -0,0 +1,176 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use reqwest::StatusCode;
+use serde::de::IgnoredAny;
+use serde_json::json;
+use svix_server::{
+    core::types::BroadcastStatus,
+    v1::{
+        endpoints::{application::ApplicationOut, broadcast::BroadcastOut, message::MessageOut},
+        utils::ListResponse,
+    },
+};
+
+use crate::utils::{run_with_retries, start_svix_server};
+
+#[tokio::test]
+async fn test_broadcast() {
+    let (client, _jh) = start_svix_server().await;
+
+    let mut apps = vec![];
+    for (name, metadata) in [
+        (
+            "broadcastApp1",
+            json!({ "tier": "enterprise", "region": "eu" }),
+        ),
+        ("broadcastApp2", json!({ "tier": "enterprise" })),
+        ("broadcastApp3", json!({ "tier": "free" })),
+        ("broadcastApp4", json!({})),
+    ] {
+        let app: ApplicationOut = client
+            .post(
+                "api/v1/app/",
+                json!({ "name": name, "metadata": metadata }),
+                StatusCode::CREATED,
+            )
+            .await
+            .unwrap();
+        apps.push(app.id);
+    }
+
+    let broadcast: BroadcastOut = client
+        .post(
+            "api/v1/broadcast/",
+            json!({
+                "message": { "eventType": "maintenance.scheduled", "payload": { "at": "soon" } },
+                "selector": { "type": "metadata", "metadata": { "tier": "enterprise" } },
+            }),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(broadcast.status, BroadcastStatus::Running);
+
+    let broadcast = run_with_retries(|| async {
+        let out: BroadcastOut = client
+            .get(
+                &format!("api/v1/broadcast/{}/", broadcast.id),
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+        if out.status != BroadcastStatus::Finished {
+            anyhow::bail!("broadcast not finished yet");
+        }
+        Ok(out)
+    })
+    .await
+    .unwrap();
+    assert_eq!(broadcast.num_messages, 2);
+
+    for (app_id, expected) in apps.iter().zip([1, 1, 0, 0]) {
+        let list: ListResponse<MessageOut> = client
+            .get(&format!("api/v1/app/{app_id}/msg/"), StatusCode::OK)
+            .await
+            .unwrap();
+        assert_eq!(list.data.len(), expected);
+        for msg in list.data {
+            assert_eq!(msg.broadcast_id.as_ref(), Some(&broadcast.id));
+            assert_eq!(msg.payload.0.get(), r#"{"at":"soon"}"#);
+        }
+    }
+
+    // All applications
+    let broadcast: BroadcastOut = client
+        .post(
+            "api/v1/broadcast/",
+            json!({
+                "message": { "eventType": "maintenance.done", "payload": {} },
+                "selector": { "type": "all" },
+            }),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    let broadcast = run_with_retries(|| async {
+        let out: BroadcastOut = client
+            .get(
+                &format!("api/v1/broadcast/{}/", broadcast.id),
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+        if out.status != BroadcastStatus::Finished {
+            anyhow::bail!("broadcast not finished yet");
+        }
+        Ok(out)
+    })
+    .await
+    .unwrap();
+    assert_eq!(broadcast.num_messages, 4);
+
+    // An empty metadata selector must be explicit about targeting all applications
+    let _: IgnoredAny = client
+        .post(
+            "api/v1/broadcast/",
+            json!({
+                "message": { "eventType": "maintenance.done", "payload": {} },
+                "selector": { "type": "metadata", "metadata": {} },
+            }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    let _: IgnoredAny = client
+        .get(
+            "api/v1/broadcast/brdcst_2Fn5XbWlPB5pQxJwm2YSqsmYKNB/",
+            StatusCode::NOT_FOUND,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_broadcast_schema_version() {
+    let (client, _jh) = start_svix_server().await;
+
+    let _: IgnoredAny = client
+        .post(
+            "api/v1/event-type/",
+            json!({
+                "name": "maintenance.scheduled",
+                "description": "Maintenance was scheduled",
+                "schemas": { "1": { "type": "object" } },
+            }),
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    // The message can only declare a version the event type has
+    let _: IgnoredAny = client
+        .post(
+            "api/v1/broadcast/",
+            json!({
+                "message": { "eventType": "maintenance.scheduled", "payload": {}, "schemaVersion": "2" },
+                "selector": { "type": "all" },
+            }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    let _: BroadcastOut = client
+        .post(
+            "api/v1/broadcast/",
+            json!({
+                "message": { "eventType": "maintenance.scheduled", "payload": {}, "schemaVersion": "1" },
+                "selector": { "type": "all" },
+            }),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+}
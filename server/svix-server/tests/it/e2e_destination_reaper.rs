@@ -0,0 +1,142 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use std::time::Duration;
+
+use chrono::Utc;
+use reqwest::StatusCode;
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use svix_ksuid::KsuidLike;
+use svix_server::{
+    core::types::{MessageAttemptTriggerType, MessageId, MessageStatus},
+    db::models::messagedestination,
+    destination_reaper::requeue_lost_destinations,
+    metrics::DestinationReaperMetrics,
+    queue::{new_pair, MessageTask, QueueTask, TaskQueueConsumer},
+    v1::{endpoints::attempt::MessageAttemptOut, utils::ListResponse},
+};
+
+use crate::utils::{
+    common_calls::{create_test_app, create_test_endpoint, create_test_message},
+    get_default_test_config, run_with_retries, start_svix_server, TestReceiver,
+};
+
+#[tokio::test]
+async fn test_lost_destinations_are_requeued() {
+    let (client, _jh) = start_svix_server().await;
+    let cfg = std::sync::Arc::new(get_default_test_config());
+    let pool = svix_server::db::init_db(&cfg).await;
+    let metrics = DestinationReaperMetrics::new(&opentelemetry::global::meter("svix.com"));
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let app_id = create_test_app(&client, "reaperApp").await.unwrap().id;
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+    let msg = create_test_message(&client, &app_id, json!({ "test": "value" }))
+        .await
+        .unwrap();
+    receiver.data_recv.recv().await.unwrap();
+    run_with_retries(|| async {
+        let list: ListResponse<MessageAttemptOut> = client
+            .get(
+                &format!("api/v1/app/{app_id}/attempt/msg/{}/", msg.id),
+                StatusCode::OK,
+            )
+            .await?;
+        if list.data.is_empty() {
+            anyhow::bail!("attempt not recorded yet");
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    // Overdue but not leased, as while its task waits on a backed up queue
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::Status,
+            Expr::value(i16::from(MessageStatus::Sending)),
+        )
+        .col_expr(
+            messagedestination::Column::NextAttempt,
+            Expr::value(Utc::now() - chrono::Duration::hours(2)),
+        )
+        .filter(messagedestination::Column::MsgId.eq(msg.id.clone()))
+        .exec(&pool)
+        .await
+        .unwrap();
+
+    // Nothing consumes this queue, so the task can be checked
+    let prefix = svix_ksuid::Ksuid::new(None, None).to_string();
+    let (queue_tx, mut queue_rx) = new_pair(&cfg, Some(&prefix)).await;
+
+    requeue_lost_destinations(&pool, &queue_tx, 100, &metrics)
+        .await
+        .unwrap();
+    assert!(find_task(&mut queue_rx, &msg.id).await.is_none());
+
+    // As if the worker crashed after the attempt, before queueing the next one
+    messagedestination::Entity::update_many()
+        .col_expr(
+            messagedestination::Column::LeasedUntil,
+            Expr::value(Utc::now() - chrono::Duration::minutes(1)),
+        )
+        .filter(messagedestination::Column::MsgId.eq(msg.id.clone()))
+        .exec(&pool)
+        .await
+        .unwrap();
+
+    let requeued = requeue_lost_destinations(&pool, &queue_tx, 100, &metrics)
+        .await
+        .unwrap();
+    assert!(requeued >= 1);
+
+    let task = find_task(&mut queue_rx, &msg.id)
+        .await
+        .expect("lost destination wasn't queued");
+    assert_eq!(task.app_id, app_id);
+    assert_eq!(task.endpoint_id, endp_id);
+    assert_eq!(task.trigger_type, MessageAttemptTriggerType::Scheduled);
+    // Resumes the retry schedule after the attempt already made
+    assert_eq!(task.attempt_count, 1);
+
+    // The lease is released once the task is queued, so it's only queued again once
+    let dest = messagedestination::Entity::find()
+        .filter(messagedestination::Column::MsgId.eq(msg.id.clone()))
+        .one(&pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(dest.leased_until.is_none());
+
+    requeue_lost_destinations(&pool, &queue_tx, 100, &metrics)
+        .await
+        .unwrap();
+    assert!(find_task(&mut queue_rx, &msg.id).await.is_none());
+}
+
+/// Acks everything on the queue, returning the task queued for the message if any.
+async fn find_task(queue_rx: &mut TaskQueueConsumer, msg_id: &MessageId) -> Option<MessageTask> {
+    let mut found = None;
+    loop {
+        let batch = queue_rx
+            .receive_all(Duration::from_millis(500))
+            .await
+            .unwrap();
+        if batch.is_empty() {
+            return found;
+        }
+        for delivery in batch {
+            if let QueueTask::MessageV1(task) = &*delivery.task {
+                if &task.msg_id == msg_id {
+                    assert!(found.is_none(), "task queued more than once");
+                    found = Some(task.clone());
+                }
+            }
+            delivery.ack().await.unwrap();
+        }
+    }
+}
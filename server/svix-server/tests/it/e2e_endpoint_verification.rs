@@ -0,0 +1,377 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::extract::State;
+use reqwest::StatusCode;
+use serde::de::IgnoredAny;
+use serde_json::json;
+use svix_server::{
+    core::types::{ApplicationId, EndpointId, EndpointVerificationStatus},
+    v1::endpoints::endpoint::{EndpointOut, EndpointStatsOut},
+};
+use tokio::sync::mpsc;
+
+use crate::utils::{
+    common_calls::{create_test_app, create_test_endpoint, create_test_message},
+    get_default_test_config, run_with_retries, start_svix_server, start_svix_server_with_cfg,
+    TestClient, TestReceiver,
+};
+
+type ChallengeReceiverState = (
+    Arc<AtomicBool>,
+    Arc<AtomicUsize>,
+    mpsc::Sender<serde_json::Value>,
+);
+
+async fn challenge_receiver_route(
+    State((echo, challenges, tx)): State<ChallengeReceiverState>,
+    axum::Json(body): axum::Json<serde_json::Value>,
+) -> String {
+    if body["type"] == "endpoint.verification" {
+        challenges.fetch_add(1, Ordering::SeqCst);
+        if echo.load(Ordering::SeqCst) {
+            return body["challenge"].as_str().unwrap().to_owned();
+        }
+        return "ok".to_owned();
+    }
+    tx.send(body).await.unwrap();
+    String::new()
+}
+
+/// Receives messages, and answers verification challenges with the challenge while `echo` is set.
+struct ChallengeReceiver {
+    endpoint: String,
+    echo: Arc<AtomicBool>,
+    /// How many challenges were received
+    challenges: Arc<AtomicUsize>,
+    data_recv: mpsc::Receiver<serde_json::Value>,
+    _jh: tokio::task::JoinHandle<()>,
+}
+
+impl ChallengeReceiver {
+    fn start(echo: bool) -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let echo = Arc::new(AtomicBool::new(echo));
+        let challenges = Arc::new(AtomicUsize::new(0));
+        let (tx, data_recv) = mpsc::channel(32);
+
+        let routes = axum::Router::new()
+            .route("/", axum::routing::post(challenge_receiver_route))
+            .with_state((echo.clone(), challenges.clone(), tx));
+
+        let _jh = tokio::spawn(async move {
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(routes.into_make_service())
+                .await
+                .unwrap();
+        });
+
+        Self {
+            endpoint,
+            echo,
+            challenges,
+            data_recv,
+            _jh,
+        }
+    }
+}
+
+async fn wait_for_status(
+    client: &TestClient,
+    app_id: &ApplicationId,
+    endp_id: &EndpointId,
+    expected: EndpointVerificationStatus,
+) {
+    run_with_retries(|| async {
+        let endp: EndpointOut = client
+            .get(
+                &format!("api/v1/app/{app_id}/endpoint/{endp_id}/"),
+                StatusCode::OK,
+            )
+            .await?;
+        if endp.ep.verification_status != expected {
+            anyhow::bail!("{:?}, expected {expected:?}", endp.ep.verification_status);
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+async fn wait_for_held(
+    client: &TestClient,
+    app_id: &ApplicationId,
+    endp_id: &EndpointId,
+    expected: i64,
+) {
+    run_with_retries(|| async {
+        let stats: EndpointStatsOut = client
+            .get(
+                &format!("api/v1/app/{app_id}/endpoint/{endp_id}/stats/"),
+                StatusCode::OK,
+            )
+            .await?;
+        if stats.paused != expected {
+            anyhow::bail!("{} held messages, expected {expected}", stats.paused);
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_endpoint_verification() {
+    let mut cfg = get_default_test_config();
+    cfg.endpoint_verification_enabled = true;
+    // Failing on the first failed challenge
+    cfg.endpoint_verification_retry_schedule = vec![];
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "verificationApp")
+        .await
+        .unwrap()
+        .id;
+
+    let mut receiver = ChallengeReceiver::start(true);
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+    assert_eq!(
+        endp.ep.verification_status,
+        EndpointVerificationStatus::PendingVerification
+    );
+    wait_for_status(
+        &client,
+        &app_id,
+        &endp.id,
+        EndpointVerificationStatus::Verified,
+    )
+    .await;
+
+    create_test_message(&client, &app_id, json!({ "i": 0 }))
+        .await
+        .unwrap();
+    assert_eq!(receiver.data_recv.recv().await.unwrap(), json!({ "i": 0 }));
+
+    // Changing the URL requires verifying it again, and messages are held until it is
+    let mut other = ChallengeReceiver::start(false);
+    let _: IgnoredAny = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", endp.id),
+            json!({ "url": other.endpoint }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    wait_for_status(
+        &client,
+        &app_id,
+        &endp.id,
+        EndpointVerificationStatus::Failed,
+    )
+    .await;
+
+    for i in 1..3 {
+        create_test_message(&client, &app_id, json!({ "i": i }))
+            .await
+            .unwrap();
+    }
+    wait_for_held(&client, &app_id, &endp.id, 2).await;
+    assert!(other.data_recv.try_recv().is_err());
+
+    // Updating the endpoint without changing its URL doesn't
+    let _: IgnoredAny = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", endp.id),
+            json!({ "description": "updated" }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    wait_for_status(
+        &client,
+        &app_id,
+        &endp.id,
+        EndpointVerificationStatus::Failed,
+    )
+    .await;
+
+    // Once verified, the held messages are sent
+    other.echo.store(true, Ordering::SeqCst);
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{}/verify/", endp.id),
+            json!({}),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    wait_for_status(
+        &client,
+        &app_id,
+        &endp.id,
+        EndpointVerificationStatus::Verified,
+    )
+    .await;
+
+    let mut received = vec![
+        other.data_recv.recv().await.unwrap(),
+        other.data_recv.recv().await.unwrap(),
+    ];
+    received.sort_by_key(|v| v["i"].as_i64());
+    assert_eq!(received, [json!({ "i": 1 }), json!({ "i": 2 })]);
+    wait_for_held(&client, &app_id, &endp.id, 0).await;
+    assert!(receiver.data_recv.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_endpoint_verification_requires_echo() {
+    let mut cfg = get_default_test_config();
+    cfg.endpoint_verification_enabled = true;
+    // Failing on the first failed challenge
+    cfg.endpoint_verification_retry_schedule = vec![];
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "verificationApp")
+        .await
+        .unwrap()
+        .id;
+
+    // Responding successfully isn't enough
+    let receiver = TestReceiver::start(StatusCode::OK);
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+    wait_for_status(
+        &client,
+        &app_id,
+        &endp.id,
+        EndpointVerificationStatus::Failed,
+    )
+    .await;
+
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/ep_unknown/verify/"),
+            json!({}),
+            StatusCode::NOT_FOUND,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_endpoint_verification_retries() {
+    let mut cfg = get_default_test_config();
+    cfg.endpoint_verification_enabled = true;
+    cfg.endpoint_verification_retry_schedule =
+        vec![Duration::from_millis(500), Duration::from_millis(100)];
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "verificationApp")
+        .await
+        .unwrap()
+        .id;
+
+    // A failed challenge is sent again rather than failing the endpoint right away
+    let mut receiver = ChallengeReceiver::start(false);
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+    run_with_retries(|| async {
+        if receiver.challenges.load(Ordering::SeqCst) == 0 {
+            anyhow::bail!("no challenge received yet");
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+    create_test_message(&client, &app_id, json!({ "i": 0 }))
+        .await
+        .unwrap();
+    let endp_out: EndpointOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", endp.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        endp_out.ep.verification_status,
+        EndpointVerificationStatus::PendingVerification
+    );
+
+    receiver.echo.store(true, Ordering::SeqCst);
+    wait_for_status(
+        &client,
+        &app_id,
+        &endp.id,
+        EndpointVerificationStatus::Verified,
+    )
+    .await;
+    assert_eq!(receiver.data_recv.recv().await.unwrap(), json!({ "i": 0 }));
+    wait_for_held(&client, &app_id, &endp.id, 0).await;
+
+    // The endpoint fails once the last retry failed too
+    let other = ChallengeReceiver::start(false);
+    let _: IgnoredAny = client
+        .patch(
+            &format!("api/v1/app/{app_id}/endpoint/{}/", endp.id),
+            json!({ "url": other.endpoint }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    wait_for_status(
+        &client,
+        &app_id,
+        &endp.id,
+        EndpointVerificationStatus::Failed,
+    )
+    .await;
+    assert_eq!(other.challenges.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_endpoint_verification_disabled() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "verificationApp")
+        .await
+        .unwrap()
+        .id;
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+    assert_eq!(
+        endp.ep.verification_status,
+        EndpointVerificationStatus::Verified
+    );
+
+    create_test_message(&client, &app_id, json!({ "i": 0 }))
+        .await
+        .unwrap();
+    assert_eq!(receiver.data_recv.recv().await.unwrap(), json!({ "i": 0 }));
+
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{}/verify/", endp.id),
+            json!({}),
+            StatusCode::BAD_REQUEST,
+        )
+        .await
+        .unwrap();
+}
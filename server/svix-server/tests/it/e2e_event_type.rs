@@ -320,3 +320,105 @@ async fn test_schema() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn test_schema_versions_compat() {
+    let (client, _jh) = start_svix_server().await;
+
+    let v1 = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "invoiceId": { "type": "string" },
+            "amount": { "type": "number" },
+        },
+        "required": ["invoiceId", "amount"],
+    });
+    let v2_compatible = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "invoiceId": { "type": "string" },
+            "amount": { "type": "number" },
+            "currency": { "type": "string" },
+        },
+        "required": ["invoiceId", "amount", "currency"],
+    });
+    let v2_breaking = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "invoiceId": { "type": "string" },
+            "amount": { "type": "integer" },
+        },
+        "required": ["invoiceId"],
+    });
+
+    let _: EventTypeOut = client
+        .post(
+            "api/v1/event-type/",
+            serde_json::json!({
+                "name": "invoice.paid",
+                "description": "An invoice was paid",
+                "schemas": { "1": v1 },
+            }),
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    // Adding a breaking version is rejected
+    let _: IgnoredAny = client
+        .patch(
+            "api/v1/event-type/invoice.paid/",
+            serde_json::json!({ "schemas": { "1": v1, "2": v2_breaking } }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    // So is changing an existing version in a breaking way
+    let _: IgnoredAny = client
+        .put(
+            "api/v1/event-type/invoice.paid/",
+            serde_json::json!({
+                "description": "An invoice was paid",
+                "schemas": { "1": v2_breaking },
+            }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    let et: EventTypeOut = client
+        .patch(
+            "api/v1/event-type/invoice.paid/",
+            serde_json::json!({ "schemas": { "1": v1, "2": v2_compatible } }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert!(et.schemas.unwrap().has_version("2"));
+
+    // Unless forced
+    let et: EventTypeOut = client
+        .patch(
+            "api/v1/event-type/invoice.paid/?force=true",
+            serde_json::json!({ "schemas": { "1": v1, "2": v2_compatible, "3": v2_breaking } }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert!(et.schemas.unwrap().has_version("3"));
+
+    // New event types are checked too
+    let _: IgnoredAny = client
+        .post(
+            "api/v1/event-type/",
+            serde_json::json!({
+                "name": "invoice.refunded",
+                "description": "An invoice was refunded",
+                "schemas": { "1": v1, "2": v2_breaking },
+            }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+}
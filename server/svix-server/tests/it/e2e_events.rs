@@ -0,0 +1,236 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use reqwest::StatusCode;
+use sea_orm::{ActiveModelTrait, Set, TransactionTrait};
+use serde::de::IgnoredAny;
+use serde_json::json;
+use svix_server::{
+    core::types::{ApplicationId, BaseId, MessageEventType, MessageId, MessageStatus},
+    db::models::messageevent,
+    v1::endpoints::events::{MessageEventOut, MessageEventsOut},
+};
+
+use crate::utils::{
+    common_calls::{create_test_app, create_test_endpoint, create_test_message},
+    get_default_test_config, run_with_retries, start_svix_server, start_svix_server_with_cfg,
+    TestReceiver,
+};
+
+#[tokio::test]
+async fn test_message_events() {
+    let (client, _jh) = start_svix_server().await;
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let app_id = create_test_app(&client, "messageEventsApp")
+        .await
+        .unwrap()
+        .id;
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+
+    let msg = create_test_message(&client, &app_id, json!({ "test": "events" }))
+        .await
+        .unwrap();
+    receiver.data_recv.recv().await.unwrap();
+
+    let events = run_with_retries(|| async {
+        let events: MessageEventsOut = client
+            .get(&format!("api/v1/app/{app_id}/events/"), StatusCode::OK)
+            .await?;
+        if events.data.len() < 2 {
+            anyhow::bail!("{} events, expected 2", events.data.len());
+        }
+        Ok(events)
+    })
+    .await
+    .unwrap();
+
+    assert!(events.done);
+    assert_eq!(events.data.len(), 2);
+    assert_eq!(events.data[0].event_type, MessageEventType::Created);
+    assert_eq!(events.data[0].msg_id, msg.id);
+    assert_eq!(events.data[1].event_type, MessageEventType::Attempted);
+    assert_eq!(events.data[1].msg_id, msg.id);
+    assert_eq!(events.data[1].endpoint_id.as_ref(), Some(&endp_id));
+    assert_eq!(events.data[1].status, Some(MessageStatus::Success));
+
+    // Nothing new after the iterator
+    let next: MessageEventsOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/events/?iterator={}", events.iterator),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert!(next.data.is_empty());
+    assert!(next.done);
+    assert_eq!(next.iterator, events.iterator);
+
+    // Paginated with the limit
+    let first: MessageEventsOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/events/?limit=1"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(first.data, events.data[..1]);
+    assert!(!first.done);
+    let second: MessageEventsOut = client
+        .get(
+            &format!(
+                "api/v1/app/{app_id}/events/?limit=1&iterator={}",
+                first.iterator
+            ),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(second.data, events.data[1..]);
+
+    let _: IgnoredAny = client
+        .get(
+            &format!("api/v1/app/{app_id}/events/?iterator=invalid"),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    // Events of other applications aren't listed
+    let other_app_id = create_test_app(&client, "messageEventsOtherApp")
+        .await
+        .unwrap()
+        .id;
+    let other: MessageEventsOut = client
+        .get(
+            &format!("api/v1/app/{other_app_id}/events/"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert!(other.data.is_empty());
+}
+
+#[tokio::test]
+async fn test_message_events_stream() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "messageEventsStreamApp")
+        .await
+        .unwrap()
+        .id;
+
+    let mut resp = client
+        .get_response(
+            &format!("api/v1/app/{app_id}/events/stream"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], "text/event-stream");
+
+    // Only events after the stream started are sent
+    let msg = create_test_message(&client, &app_id, json!({ "test": "stream" }))
+        .await
+        .unwrap();
+
+    let mut body = String::new();
+    let event = tokio::time::timeout(Duration::from_secs(15), async {
+        loop {
+            let chunk = resp.chunk().await.unwrap().unwrap();
+            body.push_str(std::str::from_utf8(&chunk).unwrap());
+            // Events end with an empty line
+            if !body.contains("\n\n") {
+                continue;
+            }
+            if let Some(data) = body.lines().find_map(|l| l.strip_prefix("data:")) {
+                break serde_json::from_str::<MessageEventOut>(data.trim()).unwrap();
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(event.event_type, MessageEventType::Created);
+    assert_eq!(event.msg_id, msg.id);
+    assert!(body.lines().any(|l| l.starts_with("id:")));
+}
+
+fn created_event(app_id: &ApplicationId, msg_id: &MessageId) -> messageevent::ActiveModel {
+    messageevent::ActiveModel {
+        created_at: Set(Utc::now().into()),
+        app_id: Set(app_id.clone()),
+        msg_id: Set(msg_id.clone()),
+        event_type: Set(MessageEventType::Created),
+        endp_id: Set(None),
+        attempt_id: Set(None),
+        status: Set(None),
+        ..Default::default()
+    }
+}
+
+/// An event committed after the iterator moved past its ID is still read after it.
+#[tokio::test]
+async fn test_message_events_commit_order() {
+    let cfg = get_default_test_config();
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let db = svix_server::db::init_db(&Arc::new(cfg)).await;
+
+    let app_id = create_test_app(&client, "messageEventsCommitOrderApp")
+        .await
+        .unwrap()
+        .id;
+    let slow_msg_id = MessageId::new(None, None);
+    let fast_msg_id = MessageId::new(None, None);
+
+    // Takes its ID first, but is committed last
+    let slow_txn = db.begin().await.unwrap();
+    let slow = created_event(&app_id, &slow_msg_id)
+        .insert(&slow_txn)
+        .await
+        .unwrap();
+    let fast = created_event(&app_id, &fast_msg_id)
+        .insert(&db)
+        .await
+        .unwrap();
+    assert!(slow.id < fast.id);
+
+    let first = run_with_retries(|| async {
+        let events: MessageEventsOut = client
+            .get(&format!("api/v1/app/{app_id}/events/"), StatusCode::OK)
+            .await?;
+        if events.data.is_empty() {
+            anyhow::bail!("committed event not listed yet");
+        }
+        Ok(events)
+    })
+    .await
+    .unwrap();
+    assert_eq!(first.data.len(), 1);
+    assert_eq!(first.data[0].msg_id, fast_msg_id);
+
+    slow_txn.commit().await.unwrap();
+
+    let next = run_with_retries(|| async {
+        let events: MessageEventsOut = client
+            .get(
+                &format!("api/v1/app/{app_id}/events/?iterator={}", first.iterator),
+                StatusCode::OK,
+            )
+            .await?;
+        if events.data.is_empty() {
+            anyhow::bail!("late event not listed yet");
+        }
+        Ok(events)
+    })
+    .await
+    .unwrap();
+    assert_eq!(next.data.len(), 1);
+    assert_eq!(next.data[0].msg_id, slow_msg_id);
+}
@@ -24,3 +24,38 @@ async fn ping_without_trailing_slash() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn health_report_details() {
+    let (client, _jh) = start_svix_server().await;
+
+    let report: serde_json::Value = client.get("api/v1/health/", StatusCode::OK).await.unwrap();
+
+    for component in ["database", "queue", "cache", "migrations"] {
+        assert_eq!(report[component]["status"], "ok", "{report}");
+        assert!(report[component]["latencyMs"].is_u64(), "{report}");
+        assert!(report[component].get("code").is_none(), "{report}");
+    }
+    assert_eq!(
+        report["migrations"]["version"],
+        report["migrations"]["latestVersion"]
+    );
+    // Operational webhooks aren't enabled in tests
+    assert!(report.get("operationalWebhooks").is_none());
+}
+
+#[tokio::test]
+async fn liveness_and_readiness() {
+    let (client, _jh) = start_svix_server().await;
+
+    client
+        .get_without_response("api/v1/health/live/", StatusCode::NO_CONTENT)
+        .await
+        .unwrap();
+
+    let report: serde_json::Value = client
+        .get("api/v1/health/ready/", StatusCode::OK)
+        .await
+        .unwrap();
+    assert_eq!(report["database"]["status"], "ok");
+}
@@ -7,20 +7,26 @@ use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
 use serde::de::IgnoredAny;
 use serde_json::json;
 use svix_server::{
-    db::models::messagecontent,
+    core::{blob_store::BlobStore, types::MessagePriority},
+    db::models::{message, messagecontent},
     expired_message_cleaner,
     v1::{
         endpoints::{
             attempt::MessageAttemptOut,
-            message::{MessageOut, RawPayload},
+            endpoint::EndpointIn,
+            message::{BulkMessageOut, MessageOut, MessageScheduleStatus, RawPayload},
         },
         utils::ListResponse,
     },
 };
 
 use crate::utils::{
-    common_calls::{create_test_app, create_test_endpoint, create_test_msg_with, message_in},
-    run_with_retries, start_svix_server, TestReceiver,
+    common_calls::{
+        create_test_app, create_test_endpoint, create_test_msg_with, endpoint_in, message_in,
+        post_endpoint,
+    },
+    get_default_test_config, run_with_retries, start_svix_server, start_svix_server_with_cfg,
+    TestReceiver,
 };
 
 #[tokio::test]
@@ -412,7 +418,7 @@ async fn test_payload_retention_period() {
         .unwrap();
     assert_eq!(1, res.rows_affected);
 
-    expired_message_cleaner::clean_expired_messages(&pool, 5000, false)
+    expired_message_cleaner::clean_expired_messages(&pool, 5000, false, &BlobStore::default())
         .await
         .unwrap();
 
@@ -564,3 +570,434 @@ async fn test_raw_payload() {
     let rec_body = receiver.data_recv.recv().await;
     assert_eq!(msg_payload.to_string(), rec_body.unwrap().to_string());
 }
+
+#[tokio::test]
+async fn test_message_priority_lanes() {
+    let mut cfg = get_default_test_config();
+    cfg.queue_priority_lanes_enabled = true;
+    cfg.worker_max_tasks = 10;
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    let app_id = create_test_app(&client, "testPriorityLanes")
+        .await
+        .unwrap()
+        .id;
+
+    let mut receiver = TestReceiver::start(axum::http::StatusCode::OK);
+
+    let endp = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    // Every lane is consumed, whatever the priority
+    let mut msg_ids = Vec::new();
+    for priority in ["high", "normal", "low"] {
+        let msg: MessageOut = client
+            .post(
+                &format!("api/v1/app/{app_id}/msg/"),
+                json!({
+                    "eventType": "priority.test",
+                    "payload": { "priority": priority },
+                    "priority": priority,
+                }),
+                StatusCode::ACCEPTED,
+            )
+            .await
+            .unwrap();
+
+        let rec_body = receiver.data_recv.recv().await.unwrap();
+        assert_eq!(rec_body, json!({ "priority": priority }));
+        msg_ids.push(msg.id);
+    }
+
+    // The priority is stored with the message, so retries and resends keep it
+    let pool = svix_server::db::init_db(&std::sync::Arc::new(get_default_test_config())).await;
+    let priorities = message::Entity::priorities(&pool, msg_ids.clone())
+        .await
+        .unwrap();
+    assert_eq!(priorities[&msg_ids[0]], MessagePriority::High);
+    assert_eq!(priorities[&msg_ids[1]], MessagePriority::Normal);
+    assert_eq!(priorities[&msg_ids[2]], MessagePriority::Low);
+
+    let endp_id = endp.id;
+    client
+        .post_without_response(
+            &format!(
+                "api/v1/app/{app_id}/msg/{}/endpoint/{endp_id}/resend/",
+                msg_ids[0]
+            ),
+            json!({}),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        receiver.data_recv.recv().await.unwrap(),
+        json!({ "priority": "high" })
+    );
+
+    // Unknown priorities are rejected
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            json!({
+                "eventType": "priority.test",
+                "payload": {},
+                "priority": "urgent",
+            }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_message_trace_context_propagation() {
+    const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "testTraceContext")
+        .await
+        .unwrap()
+        .id;
+
+    let mut propagating = TestReceiver::start(axum::http::StatusCode::OK);
+    let mut other = TestReceiver::start(axum::http::StatusCode::OK);
+
+    post_endpoint(
+        &client,
+        &app_id,
+        EndpointIn {
+            propagate_trace_context: true,
+            ..endpoint_in(&propagating.endpoint)
+        },
+    )
+    .await
+    .unwrap();
+    create_test_endpoint(&client, &app_id, &other.endpoint)
+        .await
+        .unwrap();
+
+    let _: IgnoredAny = client
+        .post_with_headers(
+            &format!("api/v1/app/{app_id}/msg/"),
+            &[("traceparent", TRACEPARENT), ("tracestate", "vendor=value")],
+            message_in("trace.test", json!({ "test": "trace" })).unwrap(),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+
+    propagating.data_recv.recv().await.unwrap();
+    let headers = propagating.header_recv.recv().await.unwrap();
+    assert_eq!(headers["traceparent"], TRACEPARENT);
+    assert_eq!(headers["tracestate"], "vendor=value");
+
+    // Only sent to endpoints which opted in
+    other.data_recv.recv().await.unwrap();
+    let headers = other.header_recv.recv().await.unwrap();
+    assert!(!headers.contains_key("traceparent"));
+}
+
+#[tokio::test]
+async fn test_scheduled_message() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "scheduledMessageApp")
+        .await
+        .unwrap()
+        .id;
+
+    let mut receiver = TestReceiver::start(axum::http::StatusCode::OK);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let msg_url = format!("api/v1/app/{app_id}/msg/");
+    let scheduled_msg = |deliver_at: chrono::DateTime<Utc>| {
+        json!({
+            "eventType": "event.type",
+            "payload": { "test": "value" },
+            "deliverAt": deliver_at,
+        })
+    };
+
+    for deliver_at in [
+        Utc::now() - Duration::minutes(1),
+        Utc::now() + Duration::days(365),
+    ] {
+        let _: IgnoredAny = client
+            .post(
+                &msg_url,
+                scheduled_msg(deliver_at),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            )
+            .await
+            .unwrap();
+    }
+
+    // Delivered once `deliverAt` has passed
+    let deliver_at = Utc::now() + Duration::seconds(2);
+    let msg: MessageOut = client
+        .post(&msg_url, scheduled_msg(deliver_at), StatusCode::ACCEPTED)
+        .await
+        .unwrap();
+    assert_eq!(msg.schedule_status, Some(MessageScheduleStatus::Scheduled));
+
+    let payload = receiver.data_recv.recv().await.unwrap();
+    assert!(Utc::now() >= deliver_at);
+    assert_eq!(payload, json!({ "test": "value" }));
+
+    let msg: MessageOut = client
+        .get(&format!("{msg_url}{}/", msg.id), StatusCode::OK)
+        .await
+        .unwrap();
+    assert_eq!(msg.schedule_status, Some(MessageScheduleStatus::Dispatched));
+    let _: IgnoredAny = client
+        .post(
+            &format!("{msg_url}{}/cancel/", msg.id),
+            json!({}),
+            StatusCode::CONFLICT,
+        )
+        .await
+        .unwrap();
+
+    // Cancelled before `deliverAt`, so never delivered
+    let msg: MessageOut = client
+        .post(
+            &msg_url,
+            scheduled_msg(Utc::now() + Duration::hours(1)),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+
+    let list: ListResponse<MessageOut> = client
+        .get(
+            &format!("{msg_url}?schedule_status=scheduled"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(list.data.len(), 1);
+    assert_eq!(list.data[0].id, msg.id);
+
+    client
+        .post_without_response(
+            &format!("{msg_url}{}/cancel/", msg.id),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+
+    let msg: MessageOut = client
+        .get(&format!("{msg_url}{}/", msg.id), StatusCode::OK)
+        .await
+        .unwrap();
+    assert_eq!(msg.schedule_status, Some(MessageScheduleStatus::Cancelled));
+
+    let list: ListResponse<MessageOut> = client
+        .get(
+            &format!("{msg_url}?schedule_status=scheduled"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert!(list.data.is_empty());
+}
+
+#[tokio::test]
+async fn test_bulk_message_create() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "bulkMessageApp").await.unwrap().id;
+    let mut receiver = TestReceiver::start(axum::http::StatusCode::OK);
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+
+    let existing: MessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            json!({ "eventType": "event.type", "payload": {}, "eventId": "existing" }),
+            StatusCode::ACCEPTED,
+        )
+        .await
+        .unwrap();
+    receiver.data_recv.recv().await.unwrap();
+
+    let res: BulkMessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/bulk/"),
+            json!({
+                "messages": [
+                    { "eventType": "event.type", "payload": { "n": 1 }, "eventId": "new" },
+                    { "eventType": "$$invalid", "payload": { "n": 2 } },
+                    { "eventType": "event.type", "payload": { "n": 3 }, "eventId": "existing" },
+                    { "eventType": "event.type", "payload": { "n": 4 }, "eventId": "new" },
+                    { "eventType": "event.type", "payload": { "n": 5 } },
+                ],
+            }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+
+    let statuses: Vec<u16> = res.data.iter().map(|r| r.status).collect();
+    assert_eq!(statuses, vec![202, 422, 409, 409, 202]);
+    assert_eq!(
+        res.data[0]
+            .message
+            .as_ref()
+            .unwrap()
+            .uid
+            .as_ref()
+            .unwrap()
+            .0,
+        "new"
+    );
+    assert!(res.data[1].message.is_none());
+    assert!(res.data[1]
+        .error
+        .as_ref()
+        .unwrap()
+        .detail
+        .contains("eventType"));
+
+    // Both new messages are delivered, and nothing else
+    let mut delivered = vec![
+        receiver.data_recv.recv().await.unwrap(),
+        receiver.data_recv.recv().await.unwrap(),
+    ];
+    delivered.sort_by_key(|p| p["n"].as_i64());
+    assert_eq!(delivered, vec![json!({ "n": 1 }), json!({ "n": 5 })]);
+
+    let msg: MessageOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/msg/{}/", existing.id),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(msg.payload.0.get(), "{}");
+
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/bulk/"),
+            json!({ "messages": [] }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+
+    // The route's name can't be a message's eventId
+    let _: IgnoredAny = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/"),
+            json!({ "eventType": "event.type", "payload": {}, "eventId": "bulk" }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+    let res: BulkMessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/bulk/"),
+            json!({ "messages": [{ "eventType": "event.type", "payload": {}, "eventId": "bulk" }] }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.data[0].status, 422);
+
+    // Across applications, by ID or UID
+    let _: IgnoredAny = client
+        .post(
+            "api/v1/app/",
+            json!({ "name": "bulkMessageApp2", "uid": "bulk-app-2" }),
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+    let res: BulkMessageOut = client
+        .post(
+            "api/v1/msg/bulk/",
+            json!({
+                "messages": [
+                    { "app": app_id, "message": { "eventType": "event.type", "payload": { "n": 6 } } },
+                    { "app": "bulk-app-2", "message": { "eventType": "event.type", "payload": { "n": 7 } } },
+                    { "app": "missing-app", "message": { "eventType": "event.type", "payload": {} } },
+                ],
+            }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let statuses: Vec<u16> = res.data.iter().map(|r| r.status).collect();
+    assert_eq!(statuses, vec![202, 202, 404]);
+}
+
+#[tokio::test]
+async fn test_message_bulk_schema_version() {
+    let (client, _jh) = start_svix_server().await;
+
+    let app_id = create_test_app(&client, "bulkSchemaVersionApp")
+        .await
+        .unwrap()
+        .id;
+    let _: IgnoredAny = client
+        .post(
+            "api/v1/event-type/",
+            json!({
+                "name": "invoice.paid",
+                "description": "An invoice was paid",
+                "schemas": { "1": { "type": "object" } },
+            }),
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+
+    // Messages can only declare versions their event type has, in bulk too
+    let res: BulkMessageOut = client
+        .post(
+            &format!("api/v1/app/{app_id}/msg/bulk/"),
+            json!({
+                "messages": [
+                    { "eventType": "invoice.paid", "payload": {}, "schemaVersion": "1" },
+                    { "eventType": "invoice.paid", "payload": {}, "schemaVersion": "2" },
+                    { "eventType": "invoice.unknown", "payload": {}, "schemaVersion": "1" },
+                    { "eventType": "invoice.paid", "payload": {} },
+                ],
+            }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let statuses: Vec<u16> = res.data.iter().map(|r| r.status).collect();
+    assert_eq!(statuses, vec![202, 422, 422, 202]);
+    assert!(res.data[1]
+        .error
+        .as_ref()
+        .unwrap()
+        .detail
+        .contains("schemaVersion"));
+
+    let res: BulkMessageOut = client
+        .post(
+            "api/v1/msg/bulk/",
+            json!({
+                "messages": [
+                    { "app": app_id, "message": { "eventType": "invoice.paid", "payload": {}, "schemaVersion": "2" } },
+                    { "app": app_id, "message": { "eventType": "invoice.paid", "payload": {}, "schemaVersion": "1" } },
+                ],
+            }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let statuses: Vec<u16> = res.data.iter().map(|r| r.status).collect();
+    assert_eq!(statuses, vec![422, 202]);
+}
@@ -0,0 +1,80 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::utils::{
+    common_calls::{create_test_app, create_test_endpoint, create_test_message},
+    get_default_test_config, run_with_retries, start_svix_server_with_cfg, TestReceiver,
+};
+
+/// An address to serve metrics on, which nothing is listening on.
+fn free_address() -> std::net::SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+async fn get_metrics(addr: std::net::SocketAddr) -> anyhow::Result<String> {
+    let res = reqwest::get(format!("http://{addr}/metrics")).await?;
+    if res.status() != StatusCode::OK {
+        anyhow::bail!("unexpected status {}", res.status());
+    }
+    Ok(res.text().await?)
+}
+
+#[tokio::test]
+async fn test_prometheus_metrics() {
+    let mut cfg = get_default_test_config();
+    cfg.prometheus_metrics_enabled = true;
+    cfg.prometheus_listen_address = free_address();
+    // Leaves the global meter provider in place for the other tests, which is harmless
+    let _provider = svix_server::setup_metrics(&cfg);
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+
+    let app_id = create_test_app(&client, "metricsApp").await.unwrap().id;
+    create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+    create_test_message(&client, &app_id, json!({ "test": true }))
+        .await
+        .unwrap();
+    receiver.data_recv.recv().await.unwrap();
+
+    let body = run_with_retries(|| async {
+        let body = get_metrics(cfg.prometheus_listen_address).await?;
+        if !body.contains("status_class=\"2xx\"") {
+            anyhow::bail!("attempt not recorded yet");
+        }
+        Ok(body)
+    })
+    .await
+    .unwrap();
+
+    assert!(body.contains("svix_delivery_attempts_total"));
+    assert!(body.contains("svix_delivery_duration_milliseconds_bucket"));
+    assert!(body.contains("svix_db_pool_connections"));
+
+    // Never on the public API port
+    client
+        .get_text("metrics", StatusCode::NOT_FOUND)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_prometheus_metrics_disabled() {
+    let mut cfg = get_default_test_config();
+    cfg.prometheus_listen_address = free_address();
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+
+    client
+        .get_text("metrics", StatusCode::NOT_FOUND)
+        .await
+        .unwrap();
+    assert!(get_metrics(cfg.prometheus_listen_address).await.is_err());
+}
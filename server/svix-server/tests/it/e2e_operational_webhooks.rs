@@ -152,6 +152,7 @@ async fn test_endpoint_create_update_and_delete() {
                 name: "TestOperationalWebhookApplication".to_owned(),
                 rate_limit: None,
                 uid: Some(ApplicationUid(org_id.to_string())),
+                retention_period: None,
                 metadata: Metadata::default(),
             },
             StatusCode::CREATED,
@@ -313,6 +314,7 @@ async fn test_message_attempt_operational_webhooks() {
                 name: "TestOperationalWebhookApplication".to_owned(),
                 rate_limit: None,
                 uid: Some(ApplicationUid(org_id.to_string())),
+                retention_period: None,
                 metadata: Metadata::default(),
             },
             StatusCode::CREATED,
@@ -0,0 +1,284 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use std::{collections::HashSet, time::Duration};
+
+use reqwest::StatusCode;
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use svix_ksuid::KsuidLike;
+use svix_server::{
+    core::types::{ApplicationId, EndpointId, MessageStatus},
+    db::models::{endpoint, messagedestination},
+    destination_reaper::release_stranded_held_destinations,
+    queue::{new_pair, QueueTask},
+    v1::endpoints::{
+        application::ApplicationOut,
+        endpoint::{EndpointOut, EndpointStatsOut},
+    },
+};
+
+use crate::utils::{
+    common_calls::{create_test_app, create_test_endpoint, create_test_message},
+    get_default_test_config, run_with_retries, start_svix_server, TestClient, TestReceiver,
+};
+
+async fn wait_for_paused(
+    client: &TestClient,
+    app_id: &ApplicationId,
+    endp_id: &EndpointId,
+    expected: i64,
+) {
+    run_with_retries(|| async {
+        let stats: EndpointStatsOut = client
+            .get(
+                &format!("api/v1/app/{app_id}/endpoint/{endp_id}/stats/"),
+                StatusCode::OK,
+            )
+            .await?;
+        if stats.paused != expected {
+            anyhow::bail!("{} paused messages, expected {expected}", stats.paused);
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
+async fn is_endpoint_paused(
+    client: &TestClient,
+    app_id: &ApplicationId,
+    endp_id: &EndpointId,
+) -> bool {
+    let endp: EndpointOut = client
+        .get(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/"),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    endp.ep.paused
+}
+
+async fn receive_all(receiver: &mut TestReceiver, count: usize) -> HashSet<serde_json::Value> {
+    let mut received = HashSet::new();
+    for _ in 0..count {
+        received.insert(receiver.data_recv.recv().await.unwrap());
+    }
+    received
+}
+
+#[tokio::test]
+async fn test_pause_endpoint() {
+    let (client, _jh) = start_svix_server().await;
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let app_id = create_test_app(&client, "pauseEndpointApp")
+        .await
+        .unwrap()
+        .id;
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/pause/"),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    assert!(is_endpoint_paused(&client, &app_id, &endp_id).await);
+
+    for i in 0..3 {
+        create_test_message(&client, &app_id, json!({ "i": i }))
+            .await
+            .unwrap();
+    }
+    wait_for_paused(&client, &app_id, &endp_id, 3).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(receiver.data_recv.try_recv().is_err());
+
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/resume/"),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    assert!(!is_endpoint_paused(&client, &app_id, &endp_id).await);
+
+    let received = receive_all(&mut receiver, 3).await;
+    assert_eq!(
+        received,
+        (0..3).map(|i| json!({ "i": i })).collect::<HashSet<_>>()
+    );
+    wait_for_paused(&client, &app_id, &endp_id, 0).await;
+
+    // Resuming an endpoint that isn't paused is a no-op
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/resume/"),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_pause_application() {
+    let (client, _jh) = start_svix_server().await;
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let app_id = create_test_app(&client, "pauseApplicationApp")
+        .await
+        .unwrap()
+        .id;
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/pause/"),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    let app: ApplicationOut = client
+        .get(&format!("api/v1/app/{app_id}/"), StatusCode::OK)
+        .await
+        .unwrap();
+    assert!(app.paused);
+
+    create_test_message(&client, &app_id, json!({ "test": "app" }))
+        .await
+        .unwrap();
+    wait_for_paused(&client, &app_id, &endp_id, 1).await;
+
+    // Still held while the endpoint itself is paused, even once the application is resumed
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/pause/"),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/resume/"),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(receiver.data_recv.try_recv().is_err());
+    wait_for_paused(&client, &app_id, &endp_id, 1).await;
+
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/resume/"),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        receiver.data_recv.recv().await.unwrap(),
+        json!({ "test": "app" })
+    );
+}
+
+#[tokio::test]
+async fn test_stranded_held_destinations_are_released() {
+    let (client, _jh) = start_svix_server().await;
+    let cfg = std::sync::Arc::new(get_default_test_config());
+    let pool = svix_server::db::init_db(&cfg).await;
+
+    let receiver = TestReceiver::start(StatusCode::OK);
+    let app_id = create_test_app(&client, "strandedApp").await.unwrap().id;
+    let endp_id = create_test_endpoint(&client, &app_id, &receiver.endpoint)
+        .await
+        .unwrap()
+        .id;
+
+    client
+        .post_without_response(
+            &format!("api/v1/app/{app_id}/endpoint/{endp_id}/pause/"),
+            json!({}),
+            StatusCode::NO_CONTENT,
+        )
+        .await
+        .unwrap();
+    let mut msg_ids = HashSet::new();
+    for i in 0..2 {
+        let msg = create_test_message(&client, &app_id, json!({ "i": i }))
+            .await
+            .unwrap();
+        msg_ids.insert(msg.id);
+    }
+    wait_for_paused(&client, &app_id, &endp_id, 2).await;
+
+    // As if the server stopped after resuming the endpoint, before releasing its deliveries
+    endpoint::Entity::update_many()
+        .col_expr(endpoint::Column::Paused, Expr::value(false))
+        .filter(endpoint::Column::Id.eq(endp_id.clone()))
+        .exec(&pool)
+        .await
+        .unwrap();
+
+    // Nothing consumes this queue, so the tasks can be checked
+    let prefix = svix_ksuid::Ksuid::new(None, None).to_string();
+    let (queue_tx, mut queue_rx) = new_pair(&cfg, Some(&prefix)).await;
+
+    let released = release_stranded_held_destinations(&pool, &queue_tx, false, 100)
+        .await
+        .unwrap();
+    assert!(released >= 2);
+
+    let mut queued = HashSet::new();
+    while queued.len() < msg_ids.len() {
+        let batch = queue_rx.receive_all(Duration::from_secs(5)).await.unwrap();
+        assert!(!batch.is_empty(), "held destinations weren't queued");
+        for delivery in batch {
+            if let QueueTask::MessageV1(task) = &*delivery.task {
+                if task.endpoint_id == endp_id {
+                    queued.insert(task.msg_id.clone());
+                }
+            }
+            delivery.ack().await.unwrap();
+        }
+    }
+    assert_eq!(queued, msg_ids);
+
+    let held = messagedestination::Entity::find()
+        .filter(messagedestination::Column::EndpId.eq(endp_id.clone()))
+        .filter(messagedestination::Column::Status.eq(MessageStatus::Paused))
+        .all(&pool)
+        .await
+        .unwrap();
+    assert!(held.is_empty());
+
+    // Releasing them again doesn't queue them twice
+    release_stranded_held_destinations(&pool, &queue_tx, false, 100)
+        .await
+        .unwrap();
+    let batch = queue_rx
+        .receive_all(Duration::from_millis(500))
+        .await
+        .unwrap();
+    for delivery in batch {
+        if let QueueTask::MessageV1(task) = &*delivery.task {
+            assert_ne!(task.endpoint_id, endp_id);
+        }
+        delivery.ack().await.unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+use chrono::{Duration, Utc};
+use reqwest::StatusCode;
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection,
+    EntityTrait, PaginatorTrait, QueryFilter,
+};
+use serde::de::IgnoredAny;
+use serde_json::json;
+use svix_server::{
+    core::{
+        blob_store::BlobStore,
+        types::{
+            ApplicationId, BaseId, EndpointId, EventTypeName, MessageAttemptId,
+            MessageAttemptTriggerType, MessageEndpointId, MessageId, MessageStatus, OrganizationId,
+        },
+    },
+    db::models::{message, messageattempt, messagecontent, messagedestination, messageevent},
+    metrics::RetentionMetrics,
+    retention_cleaner::clean_retained_messages,
+    v1::endpoints::{
+        application::ApplicationOut, message::MessageOut, retention_policy::RetentionPolicy,
+    },
+};
+
+use crate::utils::{common_calls::create_test_endpoint, start_svix_server};
+
+/// Inserts a message created `age` ago, as if it had been delivered to the endpoint. The retention
+/// cleaner goes by the time in the message's ID, so it can't just be made to look older.
+async fn insert_delivered_message(
+    pool: &DatabaseConnection,
+    app_id: &ApplicationId,
+    endp_id: &EndpointId,
+    age: Duration,
+) -> MessageId {
+    let created_at = Utc::now() - age;
+
+    let msg = message::ActiveModel {
+        id: Set(MessageId::new(created_at.into(), None)),
+        app_id: Set(app_id.clone()),
+        org_id: Set(OrganizationId::new(None, None)),
+        event_type: Set(EventTypeName("test.event".to_owned())),
+        created_at: Set(created_at.into()),
+        expiration: Set((created_at + Duration::days(90)).into()),
+        ..message::ActiveModel::new()
+    }
+    .insert(pool)
+    .await
+    .unwrap();
+    messagecontent::ActiveModel::new(msg.id.clone(), br#"{"test":"value"}"#.to_vec())
+        .insert(pool)
+        .await
+        .unwrap();
+
+    let dest = messagedestination::ActiveModel {
+        id: Set(MessageEndpointId::new(created_at.into(), None)),
+        created_at: Set(created_at.into()),
+        updated_at: Set(created_at.into()),
+        msg_id: Set(msg.id.clone()),
+        endp_id: Set(endp_id.clone()),
+        status: Set(MessageStatus::Success),
+        ..ActiveModelTrait::default()
+    }
+    .insert(pool)
+    .await
+    .unwrap();
+    let attempt = messageattempt::ActiveModel {
+        id: Set(MessageAttemptId::new(created_at.into(), None)),
+        created_at: Set(created_at.into()),
+        msg_id: Set(msg.id.clone()),
+        msg_dest_id: Set(dest.id),
+        endp_id: Set(endp_id.clone()),
+        url: Set("https://example.com/".to_owned()),
+        status: Set(MessageStatus::Success),
+        response_status_code: Set(200),
+        response: Set(String::new()),
+        ended_at: Set(Some(created_at.into())),
+        trigger_type: Set(MessageAttemptTriggerType::Scheduled),
+        response_duration_ms: Set(0),
+        response_blob_key: Set(None),
+    }
+    .insert(pool)
+    .await
+    .unwrap();
+
+    messageevent::Entity::insert_many([
+        messageevent::ActiveModel::created(&msg),
+        messageevent::ActiveModel::attempted(app_id.clone(), &attempt),
+    ])
+    .exec(pool)
+    .await
+    .unwrap();
+
+    msg.id
+}
+
+#[tokio::test]
+async fn test_retention_policy() {
+    let (client, _jh) = start_svix_server().await;
+    dotenvy::dotenv().ok();
+    let cfg = svix_server::cfg::load().expect("Error loading configuration");
+    let pool = svix_server::db::init_db(&cfg).await;
+    let metrics = RetentionMetrics::new(&opentelemetry::global::meter("svix.com"));
+
+    let _: IgnoredAny = client
+        .get("api/v1/retention-policy/", StatusCode::NOT_FOUND)
+        .await
+        .unwrap();
+    let _: IgnoredAny = client
+        .put(
+            "api/v1/retention-policy/",
+            json!({ "retentionPeriod": 0 }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )
+        .await
+        .unwrap();
+    let policy: RetentionPolicy = client
+        .put(
+            "api/v1/retention-policy/",
+            json!({ "retentionPeriod": 30 }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    assert_eq!(policy.retention_period, 30);
+
+    let short_app: ApplicationOut = client
+        .post(
+            "api/v1/app/",
+            json!({ "name": "shortRetention", "retentionPeriod": 7 }),
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(short_app.retention_period, Some(7));
+    let default_app: ApplicationOut = client
+        .post(
+            "api/v1/app/",
+            json!({ "name": "defaultRetention" }),
+            StatusCode::CREATED,
+        )
+        .await
+        .unwrap();
+    assert_eq!(default_app.retention_period, None);
+
+    let mut msg_ids = vec![];
+    for app in [&short_app, &default_app] {
+        let endp_id = create_test_endpoint(&client, &app.id, "https://example.com/")
+            .await
+            .unwrap()
+            .id;
+        msg_ids.push(insert_delivered_message(&pool, &app.id, &endp_id, Duration::days(10)).await);
+    }
+
+    // Only the application with a shorter retention period than their age loses its message
+    let res = clean_retained_messages(&pool, 1000, &metrics, &BlobStore::default())
+        .await
+        .unwrap();
+    assert!(res.messages >= 1 && res.destinations >= 1 && res.attempts >= 1 && res.events >= 2);
+
+    let _: IgnoredAny = client
+        .get(
+            &format!("api/v1/app/{}/msg/{}/", short_app.id, msg_ids[0]),
+            StatusCode::NOT_FOUND,
+        )
+        .await
+        .unwrap();
+    let _: MessageOut = client
+        .get(
+            &format!("api/v1/app/{}/msg/{}/", default_app.id, msg_ids[1]),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    let attempts = messageattempt::Entity::find()
+        .filter(messageattempt::Column::MsgId.is_in(msg_ids.clone()))
+        .count(&pool)
+        .await
+        .unwrap();
+    assert_eq!(attempts, 1);
+    let events = messageevent::Entity::find()
+        .filter(messageevent::Column::MsgId.is_in(msg_ids.clone()))
+        .count(&pool)
+        .await
+        .unwrap();
+    assert_eq!(events, 2);
+
+    // Lowering the organization's default applies to the other application
+    let _: RetentionPolicy = client
+        .put(
+            "api/v1/retention-policy/",
+            json!({ "retentionPeriod": 5 }),
+            StatusCode::OK,
+        )
+        .await
+        .unwrap();
+    clean_retained_messages(&pool, 1000, &metrics, &BlobStore::default())
+        .await
+        .unwrap();
+
+    let _: IgnoredAny = client
+        .get(
+            &format!("api/v1/app/{}/msg/{}/", default_app.id, msg_ids[1]),
+            StatusCode::NOT_FOUND,
+        )
+        .await
+        .unwrap();
+    let attempts = messageattempt::Entity::find()
+        .filter(messageattempt::Column::MsgId.is_in(msg_ids))
+        .count(&pool)
+        .await
+        .unwrap();
+    assert_eq!(attempts, 0);
+
+    client
+        .delete("api/v1/retention-policy/", StatusCode::NO_CONTENT)
+        .await
+        .unwrap();
+    let _: IgnoredAny = client
+        .get("api/v1/retention-policy/", StatusCode::NOT_FOUND)
+        .await
+        .unwrap();
+}
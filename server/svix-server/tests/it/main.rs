@@ -1,13 +1,22 @@
 mod db;
 mod e2e_application;
 mod e2e_attempt;
+mod e2e_audit_log;
 mod e2e_auth;
+mod e2e_blob_store;
+mod e2e_broadcast;
+mod e2e_destination_reaper;
 mod e2e_endpoint;
+mod e2e_endpoint_verification;
 mod e2e_event_type;
+mod e2e_events;
 mod e2e_health;
 mod e2e_message;
+mod e2e_metrics;
 mod e2e_operational_webhooks;
+mod e2e_pause;
 mod e2e_proxy;
+mod e2e_retention;
 mod integ_webhook_http_client;
 mod message_app;
 mod redis_queue;
@@ -35,6 +35,9 @@ async fn get_pool(cfg: &Configuration) -> RedisManager {
 fn task_queue_delivery_to_u16(tqd: &TaskQueueDelivery) -> u16 {
     match &*tqd.task {
         QueueTask::HealthCheck => panic!("Health check in test"),
+        QueueTask::Broadcast(_) => panic!("Broadcast in test"),
+        QueueTask::EndpointVerification(_) => panic!("Endpoint verification in test"),
+        QueueTask::ReleaseHeldDestinations(_) => panic!("Release of held destinations in test"),
         QueueTask::MessageBatch(batch) => u16::from_str(batch.msg_id.as_str()).unwrap(),
         QueueTask::MessageV1(task) => u16::from_str(task.msg_id.as_str()).unwrap(),
     }
@@ -75,6 +78,7 @@ async fn test_many_queue_consumers_inner(prefix: &str, delay: Option<Duration>)
                     endpoint_id: EndpointId("TestEndpointId".to_owned()),
                     trigger_type: MessageAttemptTriggerType::Manual,
                     attempt_count: 0,
+                    priority: Default::default(),
                 }),
                 delay,
             )
@@ -65,6 +65,9 @@ pub fn default_test_endpoint() -> EndpointIn {
         disabled: Default::default(),
         event_types_ids: Default::default(),
         channels: Default::default(),
+        filter: Default::default(),
+        schema_version: Default::default(),
+        propagate_trace_context: Default::default(),
         key: Default::default(),
         metadata: Default::default(),
     }
@@ -123,6 +126,9 @@ pub fn message_in<T: Serialize>(event_type: &str, payload: T) -> Result<MessageI
         payload_retention_period: 5,
         channels: None,
         uid: None,
+        deliver_at: None,
+        schema_version: None,
+        priority: Default::default(),
         extra_params: None,
     })
 }
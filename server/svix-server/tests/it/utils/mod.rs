@@ -79,6 +79,45 @@ impl TestClient {
             .context("error receiving/parsing response")
     }
 
+    /// Sends the request without reading the response, e.g. to read a streamed response.
+    pub async fn get_response(
+        &self,
+        endpoint: &str,
+        expected_code: StatusCode,
+    ) -> Result<reqwest::Response> {
+        let mut req = self.client.get(self.build_uri(endpoint));
+        req = self.add_headers(req);
+
+        let resp = req.send().await.context("error sending request")?;
+
+        if resp.status() != expected_code {
+            anyhow::bail!(
+                "assertion failed: expected status {}, actual status {}",
+                expected_code,
+                resp.status()
+            );
+        }
+
+        Ok(resp)
+    }
+
+    pub async fn get_text(&self, endpoint: &str, expected_code: StatusCode) -> Result<String> {
+        let mut req = self.client.get(self.build_uri(endpoint));
+        req = self.add_headers(req);
+
+        let resp = req.send().await.context("error sending request")?;
+
+        if resp.status() != expected_code {
+            anyhow::bail!(
+                "assertion failed: expected status {}, actual status {}",
+                expected_code,
+                resp.status()
+            );
+        }
+
+        resp.text().await.context("error receiving response")
+    }
+
     pub async fn get_without_response(
         &self,
         endpoint: &str,
@@ -189,6 +228,35 @@ impl TestClient {
         resp.json().await.context("error receiving/paring response")
     }
 
+    pub async fn post_with_headers<I: Serialize, O: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        headers: &[(&str, &str)],
+        input: I,
+        expected_code: StatusCode,
+    ) -> Result<O> {
+        let mut req = self.add_headers(self.client.post(self.build_uri(endpoint)));
+        for (k, v) in headers {
+            req = req.header(*k, *v);
+        }
+
+        let resp = req
+            .json(&input)
+            .send()
+            .await
+            .context("error sending request")?;
+
+        if resp.status() != expected_code {
+            anyhow::bail!(
+                "assertion failed: expected status {}, actual status {}",
+                expected_code,
+                resp.status()
+            );
+        }
+
+        resp.json().await.context("error receiving/paring response")
+    }
+
     pub async fn put<I: Serialize, O: DeserializeOwned>(
         &self,
         endpoint: &str,
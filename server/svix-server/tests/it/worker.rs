@@ -1,18 +1,32 @@
 //! Test module for worker functionality that depends on external networking and test utilities.
 //! As such they are included with integration tests for organizational purposes.
-use std::{net::TcpListener, sync::Arc, time::Duration};
+use std::{
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use axum::extract::State;
 use http::StatusCode;
-use svix_server::v1::{
-    endpoints::{attempt::MessageAttemptOut, endpoint::EndpointOut},
-    utils::ListResponse,
+use svix_server::{
+    core::{
+        security::generate_org_token,
+        types::{BaseId, OrganizationId},
+    },
+    v1::{
+        endpoints::{attempt::MessageAttemptOut, endpoint::EndpointOut},
+        utils::ListResponse,
+    },
 };
 use tokio::sync::Mutex;
 
 use crate::utils::{
     common_calls::{create_test_app, create_test_endpoint, create_test_message},
     get_default_test_config, run_with_retries, start_svix_server, start_svix_server_with_cfg,
+    TestReceiver,
 };
 
 /// Runs a full Axum server with two endpoints. The first endpoint redirects to the second endpoint
@@ -343,3 +357,115 @@ async fn test_endpoint_disable_on_sporadic_failure() {
         receiver.jh.abort();
     }
 }
+
+/// Runs a full Axum server taking a while to respond, recording how many requests it was handling
+/// at once at most.
+struct SlowReceiver {
+    pub endpoint: String,
+    pub jh: tokio::task::JoinHandle<()>,
+    pub max_in_flight: Arc<AtomicUsize>,
+}
+
+#[derive(Clone)]
+struct SlowReceiverState {
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Arc<AtomicUsize>,
+    delay: Duration,
+}
+
+impl SlowReceiver {
+    pub fn start(delay: Duration) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let routes = axum::Router::new()
+            .route("/", axum::routing::post(slow_receiver_route))
+            .with_state(SlowReceiverState {
+                in_flight: Arc::new(AtomicUsize::new(0)),
+                max_in_flight: max_in_flight.clone(),
+                delay,
+            })
+            .into_make_service();
+
+        let jh = tokio::spawn(async move {
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(routes)
+                .await
+                .unwrap();
+        });
+
+        SlowReceiver {
+            endpoint,
+            jh,
+            max_in_flight,
+        }
+    }
+}
+
+async fn slow_receiver_route(
+    State(SlowReceiverState {
+        in_flight,
+        max_in_flight,
+        delay,
+    }): State<SlowReceiverState>,
+) -> StatusCode {
+    let now_in_flight = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+    max_in_flight.fetch_max(now_in_flight, Ordering::SeqCst);
+    tokio::time::sleep(delay).await;
+    in_flight.fetch_sub(1, Ordering::SeqCst);
+    StatusCode::OK
+}
+
+/// A tenant with a backlog of deliveries to a slow endpoint only gets its share of the worker,
+/// while another tenant's messages are delivered right away.
+#[tokio::test]
+async fn test_noisy_tenant_does_not_starve_others() {
+    let mut cfg = get_default_test_config();
+    cfg.worker_max_tasks_per_org = 2;
+
+    let (client, _jh) = start_svix_server_with_cfg(&cfg).await;
+    // Another organization on the same server
+    let mut quiet_client = client.clone();
+    quiet_client.set_auth_header(
+        generate_org_token(&cfg.jwt_signing_config, OrganizationId::new(None, None)).unwrap(),
+    );
+
+    let slow_receiver = SlowReceiver::start(Duration::from_secs(1));
+    let noisy_app_id = create_test_app(&client, "noisyApp").await.unwrap().id;
+    create_test_endpoint(&client, &noisy_app_id, &slow_receiver.endpoint)
+        .await
+        .unwrap();
+    for _ in 0..20 {
+        create_test_message(&client, &noisy_app_id, serde_json::json!({}))
+            .await
+            .unwrap();
+    }
+
+    let mut receiver = TestReceiver::start(StatusCode::OK);
+    let quiet_app_id = create_test_app(&quiet_client, "quietApp").await.unwrap().id;
+    create_test_endpoint(&quiet_client, &quiet_app_id, &receiver.endpoint)
+        .await
+        .unwrap();
+    create_test_message(
+        &quiet_client,
+        &quiet_app_id,
+        serde_json::json!({ "quiet": true }),
+    )
+    .await
+    .unwrap();
+
+    // The noisy tenant's backlog takes ten seconds at its limit, which the quiet tenant's message
+    // doesn't wait on
+    let payload = tokio::time::timeout(Duration::from_secs(3), receiver.data_recv.recv())
+        .await
+        .expect("The quiet tenant's message wasn't delivered in time")
+        .unwrap();
+    assert_eq!(payload, serde_json::json!({ "quiet": true }));
+    assert!(slow_receiver.max_in_flight.load(Ordering::SeqCst) <= 2);
+
+    slow_receiver.jh.abort();
+    receiver.jh.abort();
+}
@@ -0,0 +1,136 @@
+// SPDX-FileCopyrightText: © 2022 Svix Authors
+// SPDX-License-Identifier: MIT
+
+// Using a single test in its own integration test binary, as shutting down is process-wide and
+// would stop the servers of every other test.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use reqwest::StatusCode;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use svix_ksuid::KsuidLike;
+use svix_server::{
+    core::types::{MessageAttemptTriggerType, MessagePriority, MessageStatus},
+    db::models::messageattempt,
+    queue::{new_pair, MessageTask, QueueTask},
+};
+
+#[allow(dead_code)]
+#[path = "it/utils/mod.rs"]
+mod utils;
+
+use utils::{
+    common_calls::{create_test_app, create_test_endpoint, create_test_message},
+    get_default_test_config, run_with_retries, start_svix_server_with_cfg_and_org_id_and_prefix,
+};
+
+/// Answers each request after `delay`, counting the requests it received.
+fn start_slow_receiver(delay: Duration) -> (String, Arc<AtomicUsize>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+    let received = Arc::new(AtomicUsize::new(0));
+
+    let routes = axum::Router::new().route(
+        "/",
+        axum::routing::post({
+            let received = received.clone();
+            move || async move {
+                received.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(delay).await;
+                StatusCode::OK
+            }
+        }),
+    );
+    tokio::spawn(async move {
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(routes.into_make_service())
+            .await
+            .unwrap();
+    });
+
+    (endpoint, received)
+}
+
+#[tokio::test]
+async fn test_shutdown_drains_in_flight_deliveries() {
+    svix_server::setup_tracing_for_tests();
+
+    let mut cfg = get_default_test_config();
+    cfg.graceful_shutdown_timeout_secs = 10;
+    let prefix = svix_ksuid::Ksuid::new(None, None).to_string();
+    let (client, jh) = start_svix_server_with_cfg_and_org_id_and_prefix(
+        &cfg,
+        svix_server::core::types::OrganizationId::new(None, None),
+        prefix.clone(),
+    )
+    .await;
+    let cfg = Arc::new(cfg);
+    let pool = svix_server::db::init_db(&cfg).await;
+
+    let (endpoint, received) = start_slow_receiver(Duration::from_secs(2));
+    let app_id = create_test_app(&client, "shutdownApp").await.unwrap().id;
+    let endp_id = create_test_endpoint(&client, &app_id, &endpoint)
+        .await
+        .unwrap()
+        .id;
+    let msg = create_test_message(&client, &app_id, json!({ "test": "value" }))
+        .await
+        .unwrap();
+
+    // Shut down while the delivery is in flight
+    run_with_retries(|| async {
+        if received.load(Ordering::SeqCst) == 0 {
+            anyhow::bail!("delivery not started yet");
+        }
+        Ok(())
+    })
+    .await
+    .unwrap();
+    svix_server::start_shutdown();
+
+    // Queued after the worker stopped taking tasks, so it's left on the queue
+    let (queue_tx, mut queue_rx) = new_pair(&cfg, Some(&prefix)).await;
+    queue_tx
+        .send(
+            &MessageTask::new_task(
+                msg.id.clone(),
+                app_id.clone(),
+                endp_id.clone(),
+                MessageAttemptTriggerType::Manual,
+                MessagePriority::Normal,
+            ),
+            None,
+        )
+        .await
+        .unwrap();
+
+    // The server stops once the in-flight delivery is done, and its attempt is recorded
+    tokio::time::timeout(Duration::from_secs(15), jh)
+        .await
+        .expect("server didn't stop")
+        .unwrap();
+    let attempts = messageattempt::Entity::find()
+        .filter(messageattempt::Column::MsgId.eq(msg.id.clone()))
+        .all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(attempts.len(), 1);
+    assert_eq!(attempts[0].status, MessageStatus::Success);
+    assert_eq!(received.load(Ordering::SeqCst), 1);
+
+    let batch = queue_rx.receive_all(Duration::from_secs(1)).await.unwrap();
+    assert_eq!(batch.len(), 1);
+    assert!(
+        matches!(&*batch[0].task, QueueTask::MessageV1(task) if task.msg_id == msg.id),
+        "{:?}",
+        batch[0].task
+    );
+}